@@ -1,14 +1,28 @@
 use anyhow::anyhow;
 use log::info;
-use neuron_engine::app::feature_request::{ExtensionRequest, QueueRequest, RequestHelper};
+use neuron_engine::app::extension_features::ExtendedDynamicState3Features;
+use neuron_engine::app::feature_request::{
+    DeviceFeatureRequest, ExtensionRequest, QueueRequest, RequestHelper,
+};
 use neuron_engine::app::{Application, run};
 use neuron_engine::ash::vk::{CommandBufferResetFlags, QueueFamilyProperties};
 use neuron_engine::ash::{ext, vk};
+use neuron_engine::errors::SubmitError;
+use neuron_engine::render::barrier::{Access, ImageBarrier, full_subresource_range};
+use neuron_engine::render::command_recorder::{
+    AutoSubmitInfo, CommandBufferExt, CommandBufferSyncInfo, SemaphoreInfo,
+};
 use neuron_engine::render::context::VulkanContext;
 use neuron_engine::render::context::command_pool::CommandPool;
+use neuron_engine::render::context::device::LazyQueue;
 use neuron_engine::render::context::instance::Instance;
-use neuron_engine::render::context::queues::QueueLabel;
+use neuron_engine::render::context::physical_device::PhysicalDeviceInfo;
+use neuron_engine::render::context::queue::Queue;
+use neuron_engine::render::context::queues::{QueueFamily, QueueLabel};
+use neuron_engine::render::context::version::{API_VERSION_1_4, ApiVersionRequest};
 use neuron_engine::render::frame_set::FrameSet;
+use neuron_engine::render::handoff::OwnershipHandoff;
+use neuron_engine::render::image_state::{ImageStateTracker, ImageUsage};
 use neuron_engine::winit::event_loop::ActiveEventLoop;
 use neuron_engine::winit::window::{Window, WindowId};
 use neuron_engine::{Engine, EngineCallbackHandler};
@@ -21,8 +35,11 @@ struct State {
     vulkan_context: Arc<VulkanContext>,
     command_pool: CommandPool,
     command_buffers: FrameSet<vk::CommandBuffer>,
-    graphics_queue: vk::Queue,
+    graphics_queue: Arc<Queue>,
     graphics_queue_family: u32,
+    /// Command buffers acquiring the rendered images on the presentation queue, when it is not the graphics queue.
+    present_commands: Option<(CommandPool, FrameSet<vk::CommandBuffer>)>,
+    image_states: ImageStateTracker,
 }
 
 impl State {
@@ -36,19 +53,36 @@ impl State {
         };
 
         let command_pool = CommandPool::new(engine.vulkan(), queue_ref.family, true)?;
-        let command_buffers = command_pool.allocate_command_buffer_set()?;
+        let command_buffers =
+            command_pool.allocate_command_buffer_set(engine.frames_in_flight())?;
         let graphics_queue = engine
             .vulkan()
             .device()
-            .get_queue(queue_ref.clone())
+            .get_queue(queue_ref)
             .expect("Invalid graphics queue ref returned by engine");
 
+        let present_commands = match engine
+            .vulkan()
+            .device()
+            .get_labeled_queue_ref(QueueLabel::Presentation)
+        {
+            Some(present_ref) if present_ref != queue_ref => {
+                let pool = CommandPool::new(engine.vulkan(), present_ref.family, true)?;
+                let command_buffers =
+                    pool.allocate_command_buffer_set(engine.frames_in_flight())?;
+                Some((pool, command_buffers))
+            }
+            _ => None,
+        };
+
         Ok(State {
             vulkan_context: engine.vulkan(),
             command_pool,
             command_buffers,
             graphics_queue,
             graphics_queue_family: queue_ref.family,
+            present_commands,
+            image_states: ImageStateTracker::new(),
         })
     }
 }
@@ -62,15 +96,25 @@ impl EngineCallbackHandler for MyApp {
         NAME
     }
 
+    fn api_version(&self) -> ApiVersionRequest {
+        ApiVersionRequest::new(vk::API_VERSION_1_3, API_VERSION_1_4)
+    }
+
     fn on_request_device_extensions(&mut self, requests: &mut Vec<ExtensionRequest>) {
         requests
             .required(ext::extended_dynamic_state3::NAME)
             .optional(ext::image_2d_view_of_3d::NAME);
     }
 
-    fn on_physical_device(&mut self, physical_device: vk::PhysicalDevice, instance: &Instance) {
-        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
-        info!("Physical Device Type: {:?}", properties.device_type);
+    fn on_request_features(&mut self, requests: &mut Vec<DeviceFeatureRequest>) {
+        requests
+            .required(ExtendedDynamicState3Features::POLYGON_MODE)
+            .optional(ExtendedDynamicState3Features::COLOR_BLEND_ENABLE)
+            .optional(ExtendedDynamicState3Features::COLOR_WRITE_MASK);
+    }
+
+    fn on_physical_device(&mut self, physical_device: &PhysicalDeviceInfo, _instance: &Instance) {
+        info!("Physical Device Type: {:?}", physical_device.device_type());
     }
 
     fn on_queue_selection(
@@ -79,9 +123,18 @@ impl EngineCallbackHandler for MyApp {
         _families: Vec<QueueFamilyProperties>,
     ) -> anyhow::Result<Vec<QueueRequest>> {
         Ok(vec![
-            QueueRequest::flexible_labeled(1, 2, QueueLabel::Transfer),
-            QueueRequest::flexible_labeled_custom(0, 22, "Graphics Extras"),
-            QueueRequest::strict_labeled_custom(0, 1, "Graphics Exclusive"),
+            QueueRequest::flexible(QueueFamily::TRANSFER, 2, Some(QueueLabel::Transfer)),
+            QueueRequest::flexible(
+                QueueFamily::GRAPHICS,
+                22,
+                Some(QueueLabel::Custom("Graphics Extras")),
+            )
+            .with_priority(0.5),
+            QueueRequest::strict(
+                QueueFamily::GRAPHICS,
+                1,
+                Some(QueueLabel::Custom("Graphics Exclusive")),
+            ),
         ])
     }
 
@@ -106,7 +159,7 @@ impl Application for MyApp {
         window_id: WindowId,
         engine: &mut Engine,
     ) {
-        let Some(state) = self.state.as_ref() else {
+        let Some(state) = self.state.as_mut() else {
             return;
         };
 
@@ -114,114 +167,131 @@ impl Application for MyApp {
             window
                 .borrow_mut()
                 .render_frame(|_window, image| {
-                    let command_buffer = state.command_buffers[image.current_frame()].clone();
+                    let command_buffer = state.command_buffers[image.current_frame()];
                     let vulkan = engine.vulkan();
-                    let device = vulkan.device();
 
                     unsafe {
-                        device.reset_command_buffer(
+                        vulkan.device().reset_command_buffer(
                             command_buffer,
                             CommandBufferResetFlags::empty(),
                         )?;
-                        device.begin_command_buffer(
-                            command_buffer,
-                            &vk::CommandBufferBeginInfo::default()
-                                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
-                        )?;
+                    }
 
-                        let image_barrier1 = vk::ImageMemoryBarrier::default()
-                            .image(image.image())
-                            .src_access_mask(vk::AccessFlags::empty())
-                            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-                            .src_queue_family_index(image.present_queue_family())
-                            .dst_queue_family_index(state.graphics_queue_family)
-                            .old_layout(vk::ImageLayout::UNDEFINED)
-                            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-                            .subresource_range(
-                                vk::ImageSubresourceRange::default()
-                                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                    .base_array_layer(0)
-                                    .layer_count(1)
-                                    .base_mip_level(0)
-                                    .level_count(1),
-                            );
-
-                        let image_barrier2 = vk::ImageMemoryBarrier::default()
-                            .image(image.image())
-                            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-                            .dst_access_mask(vk::AccessFlags::empty())
-                            .src_queue_family_index(state.graphics_queue_family)
-                            .dst_queue_family_index(image.present_queue_family())
-                            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-                            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-                            .subresource_range(
-                                vk::ImageSubresourceRange::default()
-                                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                    .base_array_layer(0)
-                                    .layer_count(1)
-                                    .base_mip_level(0)
-                                    .level_count(1),
-                            );
-
-                        device.cmd_pipeline_barrier(
-                            command_buffer,
-                            vk::PipelineStageFlags::TOP_OF_PIPE,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::DependencyFlags::empty(),
-                            &[],
-                            &[],
-                            &[image_barrier1],
-                        );
-
-                        let mut color = vk::ClearColorValue::default();
-                        color.float32 = [1.0f32, 0.0f32, 0.0f32, 1.0f32];
-
-                        device.cmd_clear_color_image(
-                            command_buffer,
+                    // the image moves to the presentation queue, which may belong to another family
+                    let handoff = OwnershipHandoff::between(
+                        state.graphics_queue.queue_ref(),
+                        image.present_queue(),
+                    )
+                    .image(
+                        ImageBarrier::new(
                             image.image(),
                             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                            &color,
-                            &[vk::ImageSubresourceRange::default()
-                                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                .base_array_layer(0)
-                                .layer_count(1)
-                                .base_mip_level(0)
-                                .level_count(1)],
-                        );
-
-                        device.cmd_pipeline_barrier(
-                            command_buffer,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                            vk::DependencyFlags::empty(),
-                            &[],
-                            &[],
-                            &[image_barrier2],
-                        );
-
-                        device.end_command_buffer(command_buffer)?;
-
-                        let cmds = [command_buffer];
-                        let waits = [image.image_available_semaphore()];
-                        let signals = [image.render_finished_semaphore()];
-                        let wait_stages = [vk::PipelineStageFlags::TOP_OF_PIPE];
-
-                        let submit_info = vk::SubmitInfo::default()
-                            .command_buffers(&cmds)
-                            .wait_semaphores(&waits)
-                            .wait_dst_stage_mask(&wait_stages)
-                            .signal_semaphores(&signals);
-
-                        device.queue_submit(state.graphics_queue, &[submit_info], image.in_flight_fence())?;
+                            vk::ImageLayout::PRESENT_SRC_KHR,
+                        )
+                        .src(Access::TRANSFER_WRITE)
+                        .subresource_range(full_subresource_range(vk::ImageAspectFlags::COLOR)),
+                        vk::SharingMode::EXCLUSIVE,
+                    );
+
+                    let acquire_command_buffer = if handoff.is_same_queue() {
+                        None
+                    } else {
+                        let (_, command_buffers) = state
+                            .present_commands
+                            .as_ref()
+                            .expect("the presentation queue is not the graphics queue");
+                        Some(command_buffers[image.current_frame()])
+                    };
+
+                    let render_finished = SemaphoreInfo::binary(
+                        image.render_finished_semaphore(),
+                        vk::PipelineStageFlags2::ALL_COMMANDS,
+                    );
+
+                    let mut sync_info = CommandBufferSyncInfo::new().wait(SemaphoreInfo::binary(
+                        image.image_available_semaphore(),
+                        vk::PipelineStageFlags2::TRANSFER,
+                    ));
+                    if acquire_command_buffer.is_none() {
+                        sync_info = sync_info
+                            .signal(render_finished)
+                            .fence(image.in_flight_fence());
                     }
 
-                    Ok(())
+                    let mut recorder = command_buffer.begin_auto_submit(
+                        vulkan.clone(),
+                        true,
+                        AutoSubmitInfo::new(
+                            LazyQueue::Ref(state.graphics_queue.queue_ref()),
+                            sync_info,
+                        ),
+                    )?;
+
+                    state
+                        .image_states
+                        .acquire_swapchain_image(image.image(), vk::PipelineStageFlags2::TRANSFER);
+
+                    recorder
+                        .transition_image(
+                            &mut state.image_states,
+                            image.image(),
+                            ImageUsage::TRANSFER_DST,
+                            state.graphics_queue_family,
+                        )
+                        .expect("acquire_swapchain_image registers the image")
+                        .clear_color_image(
+                            image.image(),
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            vk::ClearColorValue {
+                                float32: [1.0, 0.0, 0.0, 1.0],
+                            },
+                            &[full_subresource_range(vk::ImageAspectFlags::COLOR)],
+                        )
+                        .release(&handoff);
+
+                    let rendered = recorder.finish().map_err(submit_error)?;
+
+                    let Some(acquire_command_buffer) = acquire_command_buffer else {
+                        return Ok(());
+                    };
+
+                    unsafe {
+                        vulkan.device().reset_command_buffer(
+                            acquire_command_buffer,
+                            CommandBufferResetFlags::empty(),
+                        )?;
+                    }
+
+                    let mut sync_info = CommandBufferSyncInfo::new()
+                        .signal(render_finished)
+                        .fence(image.in_flight_fence());
+                    if let Some(rendered) = rendered {
+                        sync_info = sync_info
+                            .wait_submission(rendered, vk::PipelineStageFlags2::ALL_COMMANDS);
+                    }
+
+                    let mut recorder = acquire_command_buffer.begin_auto_submit(
+                        vulkan,
+                        true,
+                        AutoSubmitInfo::new(LazyQueue::Ref(image.present_queue()), sync_info),
+                    )?;
+                    recorder.acquire(&handoff);
+                    recorder.finish().map(|_| ()).map_err(submit_error)
                 })
                 .expect("Failed to render frame");
         }
     }
 }
 
+fn submit_error(error: SubmitError) -> vk::Result {
+    match error {
+        SubmitError::VulkanError(result) => result,
+        SubmitError::QueueNotFound(_) => {
+            unreachable!("the graphics and presentation queues are owned by the device")
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     env_logger::init();
     let app = MyApp { state: None };
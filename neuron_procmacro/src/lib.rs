@@ -1,10 +1,12 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
-use quote::__private::ext::RepToTokensExt;
 use quote::quote;
 use syn::parse::Parser;
-use syn::{parse_macro_input, ItemImpl, ItemTrait, Path, PathSegment, TraitBound, TraitBoundModifier, TypeParamBound};
+use syn::{
+    parse_macro_input, ImplItem, ItemImpl, ItemTrait, Path, PathSegment, TraitBound, TraitBoundModifier, Type,
+    TypeParamBound,
+};
 
 #[proc_macro_attribute]
 pub fn sealed(args: TokenStream, input: TokenStream) -> TokenStream {
@@ -58,6 +60,8 @@ pub fn sealed(args: TokenStream, input: TokenStream) -> TokenStream {
 
 
 // use this like you are making an impl block for the target type, it'll rework that correctly.
+// `#[extend_type(vk::CommandBuffer)] impl CommandBufferExt { ... }` declares a `CommandBufferExt` trait sealed to the
+// target type and implements it with the block's items.
 #[proc_macro_attribute]
 pub fn extend_type(args: TokenStream, input: TokenStream) -> TokenStream {
     let args_parsed = syn::punctuated::Punctuated::<Path, syn::Token![,]>::parse_terminated
@@ -72,35 +76,51 @@ pub fn extend_type(args: TokenStream, input: TokenStream) -> TokenStream {
         panic!("Cannot extend type with a trait impl block")
     }
 
-    let ty = input.self_ty.clone();
-
+    let Type::Path(trait_type) = input.self_ty.as_ref() else {
+        panic!("The impl block must name the extension trait")
+    };
+    let Some(trait_ident) = trait_type.path.get_ident().cloned() else {
+        panic!("The extension trait name must be a single identifier")
+    };
 
+    let target = &args_parsed[0];
+    let seal_ident = Ident::new(&format!("__{}Seal", trait_ident), Span::call_site());
+    let seal_mod = Ident::new(&format!("__{}_seal", trait_ident.to_string().to_lowercase()), Span::call_site());
 
-    let seal_ident = Ident::new(&format!("__{}_Ext{}_Seal", ), Span::call_site());
+    let signatures = input.items.iter().map(|item| match item {
+        ImplItem::Fn(f) => {
+            let attrs = &f.attrs;
+            let sig = &f.sig;
+            quote! { #(#attrs)* #sig; }
+        }
+        _ => panic!("Only functions can extend a type"),
+    });
+    let items = input.items.iter().map(|item| match item {
+        ImplItem::Fn(f) => {
+            let sig = &f.sig;
+            let block = &f.block;
+            quote! { #sig #block }
+        }
+        _ => unreachable!(),
+    });
+    let generics = &input.generics;
 
-    let mut seal_impls = quote! {};
-    for type_path in args_parsed {
-        seal_impls.extend(quote! {
-            impl #seal_ident for #type_path {}
-        })
-    }
+    let tokens = quote! {
+        pub trait #trait_ident: #seal_mod::#seal_ident {
+            #(#signatures)*
+        }
 
-    let seal_def = quote! {
-        mod __seal {
+        mod #seal_mod {
             use super::*;
 
             pub(super) trait #seal_ident {}
 
-            #seal_impls
+            impl #seal_ident for #target {}
         }
-    };
-
-    let mut seal_trait_path = Path::from(Ident::new("__seal", Span::call_site()));
-    seal_trait_path.segments.push(PathSegment::from(seal_ident));
-
-
-    let tokens = quote! {
 
+        impl #generics #trait_ident for #target {
+            #(#items)*
+        }
     };
 
     tokens.into()
@@ -0,0 +1,124 @@
+use crate::app::feature_request::{DeviceFeature, DeviceFeatureRequest};
+use std::collections::HashSet;
+
+/// Features the engine cannot run without, every profile requires them and they cannot be demoted.
+const MINIMAL_FEATURES: &[DeviceFeatureRequest] = &[
+    DeviceFeatureRequest::required(DeviceFeature::DynamicRendering),
+    DeviceFeatureRequest::required(DeviceFeature::Synchronization2),
+    DeviceFeatureRequest::required(DeviceFeature::TimelineSemaphore),
+];
+
+/// Features every desktop GPU released in the last decade supports, plus the ones missing on some desktop platforms
+/// (geometry shaders on macOS, wide lines and large points on several drivers) as optional.
+const DESKTOP_FEATURES: &[DeviceFeatureRequest] = &[
+    DeviceFeatureRequest::required(DeviceFeature::ImageCubeArray),
+    DeviceFeatureRequest::required(DeviceFeature::IndependentBlend),
+    DeviceFeatureRequest::required(DeviceFeature::MultiDrawIndirect),
+    DeviceFeatureRequest::required(DeviceFeature::SamplerAnisotropy),
+    DeviceFeatureRequest::required(DeviceFeature::TessellationShader),
+    DeviceFeatureRequest::optional(DeviceFeature::GeometryShader),
+    DeviceFeatureRequest::optional(DeviceFeature::WideLines),
+    DeviceFeatureRequest::optional(DeviceFeature::LargePoints),
+];
+
+const FULL_FEATURES: &[DeviceFeatureRequest] = &[
+    DeviceFeatureRequest::required(DeviceFeature::ImageCubeArray),
+    DeviceFeatureRequest::required(DeviceFeature::IndependentBlend),
+    DeviceFeatureRequest::required(DeviceFeature::MultiDrawIndirect),
+    DeviceFeatureRequest::required(DeviceFeature::SamplerAnisotropy),
+    DeviceFeatureRequest::required(DeviceFeature::TessellationShader),
+    DeviceFeatureRequest::required(DeviceFeature::GeometryShader),
+    DeviceFeatureRequest::required(DeviceFeature::WideLines),
+    DeviceFeatureRequest::required(DeviceFeature::LargePoints),
+];
+
+/// The named feature baselines the engine can be configured with.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EngineProfile {
+    /// Only what the engine itself needs: dynamic rendering, synchronization2 and timeline semaphores.
+    Minimal,
+    /// The minimal baseline plus the features common to desktop GPUs.
+    #[default]
+    Desktop,
+    /// Everything in the desktop profile required, including geometry shaders, wide lines and large points.
+    Full,
+}
+
+impl EngineProfile {
+    pub fn feature_requests(&self) -> Vec<DeviceFeatureRequest> {
+        let profile_features = match self {
+            EngineProfile::Minimal => &[],
+            EngineProfile::Desktop => DESKTOP_FEATURES,
+            EngineProfile::Full => FULL_FEATURES,
+        };
+
+        MINIMAL_FEATURES
+            .iter()
+            .chain(profile_features)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `feature` is part of the minimal baseline shared by every profile.
+    pub fn is_baseline(feature: DeviceFeature) -> bool {
+        MINIMAL_FEATURES.iter().any(|req| req.feature == feature)
+    }
+}
+
+/// The engine profile chosen by the application, along with the profile requirements it demoted to optional.
+///
+/// # Examples
+///
+/// ```
+/// use neuron_engine::app::engine_profile::{EngineProfile, EngineRequirements};
+/// use neuron_engine::app::feature_request::DeviceFeature;
+///
+/// let requirements = EngineRequirements::new(EngineProfile::Full).demote(DeviceFeature::WideLines);
+/// assert!(requirements
+///     .feature_requests()
+///     .iter()
+///     .any(|req| req.feature == DeviceFeature::WideLines && !req.required));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EngineRequirements {
+    pub profile: EngineProfile,
+    pub demoted: HashSet<DeviceFeature>,
+}
+
+impl EngineRequirements {
+    pub fn new(profile: EngineProfile) -> Self {
+        Self {
+            profile,
+            demoted: HashSet::new(),
+        }
+    }
+
+    /// Makes a requirement of the profile optional. Features of the minimal baseline stay required.
+    pub fn demote(mut self, feature: DeviceFeature) -> Self {
+        self.demoted.insert(feature);
+        self
+    }
+
+    pub fn feature_requests(&self) -> Vec<DeviceFeatureRequest> {
+        self.profile
+            .feature_requests()
+            .into_iter()
+            .map(|req| {
+                if self.demoted.contains(&req.feature) && !EngineProfile::is_baseline(req.feature) {
+                    DeviceFeatureRequest::optional(req.feature)
+                } else {
+                    req
+                }
+            })
+            .collect()
+    }
+
+    /// Demoted features that are part of the minimal baseline and therefore ignored.
+    pub fn ignored_demotions(&self) -> Vec<DeviceFeature> {
+        self.demoted
+            .iter()
+            .copied()
+            .filter(|feature| EngineProfile::is_baseline(*feature))
+            .collect()
+    }
+}
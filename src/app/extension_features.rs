@@ -0,0 +1,398 @@
+use crate::app::feature_request::DeviceFeature;
+use ash::{ext, khr, vk};
+use std::ffi::CStr;
+
+/// A single boolean feature member of an extension feature struct, identified by the extension that provides it and
+/// the member name used in the Vulkan specification (e.g. `meshShader`).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ExtensionFeature {
+    pub extension: &'static CStr,
+    pub name: &'static str,
+}
+
+pub struct ExtensionFeatureField<T> {
+    pub name: &'static str,
+    pub get: fn(&T) -> &vk::Bool32,
+    pub get_mut: fn(&mut T) -> &mut vk::Bool32,
+}
+
+/// Describes an extension feature struct that can be chained into `vkGetPhysicalDeviceFeatures2` and
+/// `vkCreateDevice`. Implement this through [`extension_features!`](crate::extension_features) rather than by hand.
+pub trait ExtensionFeatureStruct: 'static {
+    type Struct: vk::ExtendsPhysicalDeviceFeatures2
+        + vk::ExtendsDeviceCreateInfo
+        + Default
+        + Send
+        + Sync
+        + 'static;
+
+    const EXTENSION: &CStr;
+    const FIELDS: &[ExtensionFeatureField<Self::Struct>];
+}
+
+/// Type-erased storage for one registered extension feature struct.
+pub(crate) trait ExtensionFeatureChain: Send + Sync {
+    fn extension(&self) -> &'static CStr;
+    fn get(&self, name: &str) -> Option<&vk::Bool32>;
+    fn get_mut(&mut self, name: &str) -> Option<&mut vk::Bool32>;
+    fn features(&self) -> Vec<ExtensionFeature>;
+    /// A zeroed copy of this struct, used as the destination when writing enabled features.
+    fn empty(&self) -> Box<dyn ExtensionFeatureChain>;
+    fn as_base_mut(&mut self) -> &mut vk::BaseOutStructure<'static>;
+}
+
+struct ExtensionFeatureStorage<T: ExtensionFeatureStruct> {
+    value: T::Struct,
+}
+
+impl<T: ExtensionFeatureStruct> ExtensionFeatureChain for ExtensionFeatureStorage<T> {
+    fn extension(&self) -> &'static CStr {
+        T::EXTENSION
+    }
+
+    fn get(&self, name: &str) -> Option<&vk::Bool32> {
+        T::FIELDS
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| (field.get)(&self.value))
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut vk::Bool32> {
+        T::FIELDS
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| (field.get_mut)(&mut self.value))
+    }
+
+    fn features(&self) -> Vec<ExtensionFeature> {
+        T::FIELDS
+            .iter()
+            .map(|field| ExtensionFeature {
+                extension: T::EXTENSION,
+                name: field.name,
+            })
+            .collect()
+    }
+
+    fn empty(&self) -> Box<dyn ExtensionFeatureChain> {
+        Box::new(Self {
+            value: T::Struct::default(),
+        })
+    }
+
+    fn as_base_mut(&mut self) -> &mut vk::BaseOutStructure<'static> {
+        // every struct implementing `ExtendsPhysicalDeviceFeatures2` starts with `s_type` and `p_next`
+        unsafe { &mut *(&mut self.value as *mut T::Struct).cast::<vk::BaseOutStructure<'static>>() }
+    }
+}
+
+/// The set of extension feature structs the engine knows how to query and enable.
+///
+/// The built-in structs are registered by default, applications add their own in
+/// [`EngineCallbackHandler::on_register_feature_structs`](crate::EngineCallbackHandler::on_register_feature_structs).
+pub struct FeatureRegistry {
+    structs: Vec<Box<dyn ExtensionFeatureChain>>,
+}
+
+impl FeatureRegistry {
+    pub fn empty() -> Self {
+        Self {
+            structs: Vec::new(),
+        }
+    }
+
+    /// Registers `T`, registering a struct for an extension that already has one is a no-op.
+    pub fn register<T: ExtensionFeatureStruct>(&mut self) -> &mut Self {
+        if !self.is_registered(T::EXTENSION) {
+            self.structs.push(Box::new(ExtensionFeatureStorage::<T> {
+                value: T::Struct::default(),
+            }));
+        }
+
+        self
+    }
+
+    pub fn is_registered(&self, extension: &CStr) -> bool {
+        self.structs.iter().any(|s| s.extension() == extension)
+    }
+
+    pub fn extensions(&self) -> impl Iterator<Item = &'static CStr> + '_ {
+        self.structs.iter().map(|s| s.extension())
+    }
+
+    /// Looks up a registered feature by its Vulkan member name (e.g. `meshShader`).
+    pub fn find_feature(&self, name: &str) -> Option<ExtensionFeature> {
+        self.structs
+            .iter()
+            .flat_map(|s| s.features())
+            .find(|feature| feature.name == name)
+    }
+
+    pub(crate) fn instantiate<'a>(
+        &'a self,
+        supported: impl Fn(&CStr) -> bool + 'a,
+    ) -> impl Iterator<Item = Box<dyn ExtensionFeatureChain>> + 'a {
+        self.structs
+            .iter()
+            .filter(move |s| supported(s.extension()))
+            .map(|s| s.empty())
+    }
+}
+
+impl Default for FeatureRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register::<MeshShaderFeatures>()
+            .register::<DescriptorBufferFeatures>()
+            .register::<ShaderObjectFeatures>()
+            .register::<ExtendedDynamicState3Features>()
+            .register::<Robustness2Features>();
+        registry
+    }
+}
+
+/// Declares an extension feature struct along with a [`DeviceFeature`](crate::app::feature_request::DeviceFeature) constant for each of its members.
+///
+/// ```ignore
+/// extension_features! {
+///     pub struct MeshShaderFeatures(vk::PhysicalDeviceMeshShaderFeaturesEXT<'static>, ext::mesh_shader::NAME) {
+///         TASK_SHADER => task_shader: "taskShader",
+///         MESH_SHADER => mesh_shader: "meshShader",
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! extension_features {
+    ($(
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($ty:ty, $extension:expr) {
+            $($constant:ident => $field:ident: $vk_name:literal),* $(,)?
+        }
+    )*) => {$(
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $name {
+            $(
+                pub const $constant: $crate::app::feature_request::DeviceFeature =
+                    $crate::app::feature_request::DeviceFeature::Extension(
+                        $crate::app::extension_features::ExtensionFeature {
+                            extension: $extension,
+                            name: $vk_name,
+                        },
+                    );
+            )*
+        }
+
+        impl $crate::app::extension_features::ExtensionFeatureStruct for $name {
+            type Struct = $ty;
+
+            const EXTENSION: &::std::ffi::CStr = $extension;
+            const FIELDS: &[$crate::app::extension_features::ExtensionFeatureField<$ty>] = &[$(
+                $crate::app::extension_features::ExtensionFeatureField {
+                    name: $vk_name,
+                    get: |s| &s.$field,
+                    get_mut: |s| &mut s.$field,
+                },
+            )*];
+        }
+    )*};
+}
+
+extension_features! {
+    /// Features of `VK_EXT_mesh_shader`.
+    pub struct MeshShaderFeatures(vk::PhysicalDeviceMeshShaderFeaturesEXT<'static>, ext::mesh_shader::NAME) {
+        TASK_SHADER => task_shader: "taskShader",
+        MESH_SHADER => mesh_shader: "meshShader",
+        MULTIVIEW_MESH_SHADER => multiview_mesh_shader: "multiviewMeshShader",
+        PRIMITIVE_FRAGMENT_SHADING_RATE_MESH_SHADER => primitive_fragment_shading_rate_mesh_shader: "primitiveFragmentShadingRateMeshShader",
+        MESH_SHADER_QUERIES => mesh_shader_queries: "meshShaderQueries",
+    }
+
+    /// Features of `VK_EXT_descriptor_buffer`.
+    pub struct DescriptorBufferFeatures(vk::PhysicalDeviceDescriptorBufferFeaturesEXT<'static>, ext::descriptor_buffer::NAME) {
+        DESCRIPTOR_BUFFER => descriptor_buffer: "descriptorBuffer",
+        DESCRIPTOR_BUFFER_CAPTURE_REPLAY => descriptor_buffer_capture_replay: "descriptorBufferCaptureReplay",
+        DESCRIPTOR_BUFFER_IMAGE_LAYOUT_IGNORED => descriptor_buffer_image_layout_ignored: "descriptorBufferImageLayoutIgnored",
+        DESCRIPTOR_BUFFER_PUSH_DESCRIPTORS => descriptor_buffer_push_descriptors: "descriptorBufferPushDescriptors",
+    }
+
+    /// Features of `VK_EXT_shader_object`.
+    pub struct ShaderObjectFeatures(vk::PhysicalDeviceShaderObjectFeaturesEXT<'static>, ext::shader_object::NAME) {
+        SHADER_OBJECT => shader_object: "shaderObject",
+    }
+
+    /// Features of `VK_EXT_extended_dynamic_state3`.
+    pub struct ExtendedDynamicState3Features(vk::PhysicalDeviceExtendedDynamicState3FeaturesEXT<'static>, ext::extended_dynamic_state3::NAME) {
+        TESSELLATION_DOMAIN_ORIGIN => extended_dynamic_state3_tessellation_domain_origin: "extendedDynamicState3TessellationDomainOrigin",
+        DEPTH_CLAMP_ENABLE => extended_dynamic_state3_depth_clamp_enable: "extendedDynamicState3DepthClampEnable",
+        POLYGON_MODE => extended_dynamic_state3_polygon_mode: "extendedDynamicState3PolygonMode",
+        RASTERIZATION_SAMPLES => extended_dynamic_state3_rasterization_samples: "extendedDynamicState3RasterizationSamples",
+        SAMPLE_MASK => extended_dynamic_state3_sample_mask: "extendedDynamicState3SampleMask",
+        ALPHA_TO_COVERAGE_ENABLE => extended_dynamic_state3_alpha_to_coverage_enable: "extendedDynamicState3AlphaToCoverageEnable",
+        ALPHA_TO_ONE_ENABLE => extended_dynamic_state3_alpha_to_one_enable: "extendedDynamicState3AlphaToOneEnable",
+        LOGIC_OP_ENABLE => extended_dynamic_state3_logic_op_enable: "extendedDynamicState3LogicOpEnable",
+        COLOR_BLEND_ENABLE => extended_dynamic_state3_color_blend_enable: "extendedDynamicState3ColorBlendEnable",
+        COLOR_BLEND_EQUATION => extended_dynamic_state3_color_blend_equation: "extendedDynamicState3ColorBlendEquation",
+        COLOR_WRITE_MASK => extended_dynamic_state3_color_write_mask: "extendedDynamicState3ColorWriteMask",
+        RASTERIZATION_STREAM => extended_dynamic_state3_rasterization_stream: "extendedDynamicState3RasterizationStream",
+        CONSERVATIVE_RASTERIZATION_MODE => extended_dynamic_state3_conservative_rasterization_mode: "extendedDynamicState3ConservativeRasterizationMode",
+        EXTRA_PRIMITIVE_OVERESTIMATION_SIZE => extended_dynamic_state3_extra_primitive_overestimation_size: "extendedDynamicState3ExtraPrimitiveOverestimationSize",
+        DEPTH_CLIP_ENABLE => extended_dynamic_state3_depth_clip_enable: "extendedDynamicState3DepthClipEnable",
+        SAMPLE_LOCATIONS_ENABLE => extended_dynamic_state3_sample_locations_enable: "extendedDynamicState3SampleLocationsEnable",
+        COLOR_BLEND_ADVANCED => extended_dynamic_state3_color_blend_advanced: "extendedDynamicState3ColorBlendAdvanced",
+        PROVOKING_VERTEX_MODE => extended_dynamic_state3_provoking_vertex_mode: "extendedDynamicState3ProvokingVertexMode",
+        LINE_RASTERIZATION_MODE => extended_dynamic_state3_line_rasterization_mode: "extendedDynamicState3LineRasterizationMode",
+        LINE_STIPPLE_ENABLE => extended_dynamic_state3_line_stipple_enable: "extendedDynamicState3LineStippleEnable",
+        DEPTH_CLIP_NEGATIVE_ONE_TO_ONE => extended_dynamic_state3_depth_clip_negative_one_to_one: "extendedDynamicState3DepthClipNegativeOneToOne",
+        VIEWPORT_W_SCALING_ENABLE => extended_dynamic_state3_viewport_w_scaling_enable: "extendedDynamicState3ViewportWScalingEnable",
+        VIEWPORT_SWIZZLE => extended_dynamic_state3_viewport_swizzle: "extendedDynamicState3ViewportSwizzle",
+        COVERAGE_TO_COLOR_ENABLE => extended_dynamic_state3_coverage_to_color_enable: "extendedDynamicState3CoverageToColorEnable",
+        COVERAGE_TO_COLOR_LOCATION => extended_dynamic_state3_coverage_to_color_location: "extendedDynamicState3CoverageToColorLocation",
+        COVERAGE_MODULATION_MODE => extended_dynamic_state3_coverage_modulation_mode: "extendedDynamicState3CoverageModulationMode",
+        COVERAGE_MODULATION_TABLE_ENABLE => extended_dynamic_state3_coverage_modulation_table_enable: "extendedDynamicState3CoverageModulationTableEnable",
+        COVERAGE_MODULATION_TABLE => extended_dynamic_state3_coverage_modulation_table: "extendedDynamicState3CoverageModulationTable",
+        COVERAGE_REDUCTION_MODE => extended_dynamic_state3_coverage_reduction_mode: "extendedDynamicState3CoverageReductionMode",
+        REPRESENTATIVE_FRAGMENT_TEST_ENABLE => extended_dynamic_state3_representative_fragment_test_enable: "extendedDynamicState3RepresentativeFragmentTestEnable",
+        SHADING_RATE_IMAGE_ENABLE => extended_dynamic_state3_shading_rate_image_enable: "extendedDynamicState3ShadingRateImageEnable",
+    }
+
+    /// Features of `VK_EXT_robustness2`.
+    pub struct Robustness2Features(vk::PhysicalDeviceRobustness2FeaturesEXT<'static>, ext::robustness2::NAME) {
+        ROBUST_BUFFER_ACCESS2 => robust_buffer_access2: "robustBufferAccess2",
+        ROBUST_IMAGE_ACCESS2 => robust_image_access2: "robustImageAccess2",
+        NULL_DESCRIPTOR => null_descriptor: "nullDescriptor",
+    }
+}
+
+// The structs Vulkan 1.3 features are queried and enabled through on Vulkan 1.2 devices. These are never registered, a
+// device either gets `PhysicalDeviceVulkan13Features` or these.
+extension_features! {
+    pub(crate) struct DynamicRenderingFeatures(vk::PhysicalDeviceDynamicRenderingFeatures<'static>, khr::dynamic_rendering::NAME) {
+        DYNAMIC_RENDERING => dynamic_rendering: "dynamicRendering",
+    }
+
+    pub(crate) struct Synchronization2Features(vk::PhysicalDeviceSynchronization2Features<'static>, khr::synchronization2::NAME) {
+        SYNCHRONIZATION2 => synchronization2: "synchronization2",
+    }
+
+    pub(crate) struct InlineUniformBlockFeatures(vk::PhysicalDeviceInlineUniformBlockFeatures<'static>, ext::inline_uniform_block::NAME) {
+        INLINE_UNIFORM_BLOCK => inline_uniform_block: "inlineUniformBlock",
+        DESCRIPTOR_BINDING_INLINE_UNIFORM_BLOCK_UPDATE_AFTER_BIND => descriptor_binding_inline_uniform_block_update_after_bind: "descriptorBindingInlineUniformBlockUpdateAfterBind",
+    }
+
+    pub(crate) struct PipelineCreationCacheControlFeatures(vk::PhysicalDevicePipelineCreationCacheControlFeatures<'static>, ext::pipeline_creation_cache_control::NAME) {
+        PIPELINE_CREATION_CACHE_CONTROL => pipeline_creation_cache_control: "pipelineCreationCacheControl",
+    }
+
+    pub(crate) struct PrivateDataFeatures(vk::PhysicalDevicePrivateDataFeatures<'static>, ext::private_data::NAME) {
+        PRIVATE_DATA => private_data: "privateData",
+    }
+
+    pub(crate) struct ShaderDemoteToHelperInvocationFeatures(vk::PhysicalDeviceShaderDemoteToHelperInvocationFeatures<'static>, ext::shader_demote_to_helper_invocation::NAME) {
+        SHADER_DEMOTE_TO_HELPER_INVOCATION => shader_demote_to_helper_invocation: "shaderDemoteToHelperInvocation",
+    }
+
+    pub(crate) struct ShaderTerminateInvocationFeatures(vk::PhysicalDeviceShaderTerminateInvocationFeatures<'static>, khr::shader_terminate_invocation::NAME) {
+        SHADER_TERMINATE_INVOCATION => shader_terminate_invocation: "shaderTerminateInvocation",
+    }
+
+    pub(crate) struct SubgroupSizeControlFeatures(vk::PhysicalDeviceSubgroupSizeControlFeatures<'static>, ext::subgroup_size_control::NAME) {
+        COMPUTE_FULL_SUBGROUPS => compute_full_subgroups: "computeFullSubgroups",
+    }
+
+    pub(crate) struct TextureCompressionAstcHdrFeatures(vk::PhysicalDeviceTextureCompressionASTCHDRFeatures<'static>, ext::texture_compression_astc_hdr::NAME) {
+        TEXTURE_COMPRESSION_ASTC_HDR => texture_compression_astc_hdr: "textureCompressionASTC_HDR",
+    }
+
+    pub(crate) struct ZeroInitializeWorkgroupMemoryFeatures(vk::PhysicalDeviceZeroInitializeWorkgroupMemoryFeatures<'static>, khr::zero_initialize_workgroup_memory::NAME) {
+        SHADER_ZERO_INITIALIZE_WORKGROUP_MEMORY => shader_zero_initialize_workgroup_memory: "shaderZeroInitializeWorkgroupMemory",
+    }
+
+    pub(crate) struct ShaderIntegerDotProductFeatures(vk::PhysicalDeviceShaderIntegerDotProductFeatures<'static>, khr::shader_integer_dot_product::NAME) {
+        SHADER_INTEGER_DOT_PRODUCT => shader_integer_dot_product: "shaderIntegerDotProduct",
+    }
+
+    pub(crate) struct Maintenance4Features(vk::PhysicalDeviceMaintenance4Features<'static>, khr::maintenance4::NAME) {
+        MAINTENANCE4 => maintenance4: "maintenance4",
+    }
+
+    pub(crate) struct ImageRobustnessFeatures(vk::PhysicalDeviceImageRobustnessFeatures<'static>, ext::image_robustness::NAME) {
+        ROBUST_IMAGE_ACCESS => robust_image_access: "robustImageAccess",
+    }
+}
+
+/// Maps each Vulkan 1.3 core feature to the extension feature it comes from.
+const VULKAN_1_3_FALLBACKS: &[(DeviceFeature, DeviceFeature)] = &[
+    (DeviceFeature::DynamicRendering, DynamicRenderingFeatures::DYNAMIC_RENDERING),
+    (DeviceFeature::Synchronization2, Synchronization2Features::SYNCHRONIZATION2),
+    (DeviceFeature::InlineUniformBlock, InlineUniformBlockFeatures::INLINE_UNIFORM_BLOCK),
+    (
+        DeviceFeature::DescriptorBindingInlineUniformBlockUpdateAfterBind,
+        InlineUniformBlockFeatures::DESCRIPTOR_BINDING_INLINE_UNIFORM_BLOCK_UPDATE_AFTER_BIND,
+    ),
+    (
+        DeviceFeature::PipelineCreationCacheControl,
+        PipelineCreationCacheControlFeatures::PIPELINE_CREATION_CACHE_CONTROL,
+    ),
+    (DeviceFeature::PrivateData, PrivateDataFeatures::PRIVATE_DATA),
+    (
+        DeviceFeature::ShaderDemoteToHelperInvocation,
+        ShaderDemoteToHelperInvocationFeatures::SHADER_DEMOTE_TO_HELPER_INVOCATION,
+    ),
+    (
+        DeviceFeature::ShaderTerminateInvocation,
+        ShaderTerminateInvocationFeatures::SHADER_TERMINATE_INVOCATION,
+    ),
+    (DeviceFeature::ComputeFullSubgroups, SubgroupSizeControlFeatures::COMPUTE_FULL_SUBGROUPS),
+    (
+        DeviceFeature::TextureCompressionASTCHDR,
+        TextureCompressionAstcHdrFeatures::TEXTURE_COMPRESSION_ASTC_HDR,
+    ),
+    (
+        DeviceFeature::ShaderZeroInitializeWorkgroupMemory,
+        ZeroInitializeWorkgroupMemoryFeatures::SHADER_ZERO_INITIALIZE_WORKGROUP_MEMORY,
+    ),
+    (
+        DeviceFeature::ShaderIntegerDotProduct,
+        ShaderIntegerDotProductFeatures::SHADER_INTEGER_DOT_PRODUCT,
+    ),
+    (DeviceFeature::Maintenance4, Maintenance4Features::MAINTENANCE4),
+    (DeviceFeature::RobustImageAccess, ImageRobustnessFeatures::ROBUST_IMAGE_ACCESS),
+];
+
+pub(crate) fn vulkan_1_3_fallbacks() -> impl Iterator<Item = (DeviceFeature, ExtensionFeature)> {
+    VULKAN_1_3_FALLBACKS
+        .iter()
+        .filter_map(|(core, fallback)| match fallback {
+            DeviceFeature::Extension(fallback) => Some((*core, *fallback)),
+            _ => None,
+        })
+}
+
+/// The extension feature a Vulkan 1.3 core feature is enabled through on a Vulkan 1.2 device.
+pub(crate) fn vulkan_1_3_fallback(feature: DeviceFeature) -> Option<ExtensionFeature> {
+    vulkan_1_3_fallbacks()
+        .find(|(core, _)| *core == feature)
+        .map(|(_, fallback)| fallback)
+}
+
+pub(crate) fn vulkan_1_3_fallback_registry() -> FeatureRegistry {
+    let mut registry = FeatureRegistry::empty();
+    registry
+        .register::<DynamicRenderingFeatures>()
+        .register::<Synchronization2Features>()
+        .register::<InlineUniformBlockFeatures>()
+        .register::<PipelineCreationCacheControlFeatures>()
+        .register::<PrivateDataFeatures>()
+        .register::<ShaderDemoteToHelperInvocationFeatures>()
+        .register::<ShaderTerminateInvocationFeatures>()
+        .register::<SubgroupSizeControlFeatures>()
+        .register::<TextureCompressionAstcHdrFeatures>()
+        .register::<ZeroInitializeWorkgroupMemoryFeatures>()
+        .register::<ShaderIntegerDotProductFeatures>()
+        .register::<Maintenance4Features>()
+        .register::<ImageRobustnessFeatures>();
+    registry
+}
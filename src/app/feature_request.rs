@@ -2,7 +2,12 @@ use std::collections::HashSet;
 use std::ffi::CStr;
 use anyhow::anyhow;
 use ash::vk;
-use crate::render::context::queues::QueueLabel;
+use crate::app::extension_features::{
+    ExtensionFeature, ExtensionFeatureChain, FeatureRegistry, vulkan_1_3_fallback,
+    vulkan_1_3_fallback_registry, vulkan_1_3_fallbacks,
+};
+use crate::render::context::queues::{QueueFamily, QueueLabel};
+use crate::render::context::version::version_at_least;
 
 #[derive(Default)]
 pub struct FeatureStructs<'a> {
@@ -10,18 +15,57 @@ pub struct FeatureStructs<'a> {
     vk11: vk::PhysicalDeviceVulkan11Features<'a>,
     vk12: vk::PhysicalDeviceVulkan12Features<'a>,
     vk13: vk::PhysicalDeviceVulkan13Features<'a>,
+    extensions: Vec<Box<dyn ExtensionFeatureChain>>,
+    /// The Vulkan version of the device, decides whether the 1.3 features live in `vk13` or in `promoted`.
+    api_version: u32,
+    /// Extension structs standing in for `vk13` on Vulkan 1.2 devices, their values are mirrored into `vk13`.
+    promoted: Vec<Box<dyn ExtensionFeatureChain>>,
 }
 
 impl<'a> FeatureStructs<'a> {
+    /// `enabled_extensions` decides which extension feature structs are carried over, the structs of extensions that
+    /// weren't resolved are never chained into device creation.
     pub(crate) fn validate_and_write<'b>(
-        support: FeatureStructs<'b>,
+        support: &FeatureStructs<'b>,
         feature_requests: &[DeviceFeatureRequest],
+        enabled_extensions: &HashSet<&'static CStr>,
     ) -> anyhow::Result<FeatureStructs<'a>> {
-        let mut features = FeatureStructs::<'a>::default();
+        let mut features = FeatureStructs::<'a> {
+            api_version: support.api_version,
+            ..Default::default()
+        };
+        features.extensions = support
+            .extensions
+            .iter()
+            .filter(|s| enabled_extensions.contains(s.extension()))
+            .map(|s| s.empty())
+            .collect();
+        features.promoted = support
+            .promoted
+            .iter()
+            .filter(|s| enabled_extensions.contains(s.extension()))
+            .map(|s| s.empty())
+            .collect();
 
         for req in feature_requests {
+            if let Some(feature) = support.extension_feature(req.feature)
+                && !enabled_extensions.contains(feature.extension)
+            {
+                if req.required {
+                    return Err(anyhow!(
+                        "Missing required feature {:?}, it needs {:?} which is not enabled",
+                        req.feature,
+                        feature.extension
+                    ));
+                }
+
+                continue;
+            }
+
             if support.supports(req.feature) {
-                *features.feature_mut(req.feature) = vk::TRUE;
+                if let Some(value) = features.feature_mut(req.feature) {
+                    *value = vk::TRUE;
+                }
             } else if req.required {
                 return Err(anyhow!("Missing required feature {:?}", req.feature));
             }
@@ -30,8 +74,47 @@ impl<'a> FeatureStructs<'a> {
         Ok(features)
     }
 
+    /// The extension feature `feature` is provided through on this device, if any.
+    fn extension_feature(&self, feature: DeviceFeature) -> Option<ExtensionFeature> {
+        match feature {
+            DeviceFeature::Extension(feature) => Some(feature),
+            feature if !self.has_vulkan_1_3() => vulkan_1_3_fallback(feature),
+            _ => None,
+        }
+    }
+
+    fn has_vulkan_1_3(&self) -> bool {
+        version_at_least(self.api_version, vk::API_VERSION_1_3)
+    }
+
+    /// The extensions the requested features depend on, for Vulkan 1.3 features on a Vulkan 1.2 device these are the
+    /// extensions the features were promoted from.
+    pub(crate) fn required_extensions(&self, feature_requests: &[DeviceFeatureRequest]) -> Vec<ExtensionRequest> {
+        feature_requests
+            .iter()
+            .filter(|req| !matches!(req.feature, DeviceFeature::Extension(_)))
+            .filter_map(|req| {
+                self.extension_feature(req.feature).map(|feature| ExtensionRequest {
+                    name: feature.extension,
+                    required: req.required,
+                })
+            })
+            .collect()
+    }
+
+    fn extension_struct(&self, extension: &CStr) -> Option<&dyn ExtensionFeatureChain> {
+        self.extensions
+            .iter()
+            .find(|s| s.extension() == extension)
+            .map(|s| s.as_ref())
+    }
+
     fn feature_ref(&self, feature: DeviceFeature) -> &vk::Bool32 {
         match feature {
+            DeviceFeature::Extension(feature) => self
+                .extension_struct(feature.extension)
+                .and_then(|s| s.get(feature.name))
+                .unwrap_or(&vk::FALSE),
             DeviceFeature::RobustBufferAccess => &self.features1.robust_buffer_access,
             DeviceFeature::FullDrawIndexUint32 => &self.features1.full_draw_index_uint32,
             DeviceFeature::ImageCubeArray => &self.features1.image_cube_array,
@@ -252,8 +335,20 @@ impl<'a> FeatureStructs<'a> {
         }
     }
 
-    fn feature_mut(&mut self, feature: DeviceFeature) -> &mut vk::Bool32 {
+    fn feature_mut(&mut self, feature: DeviceFeature) -> Option<&mut vk::Bool32> {
         match feature {
+            DeviceFeature::Extension(feature) => self
+                .extensions
+                .iter_mut()
+                .find(|s| s.extension() == feature.extension)
+                .and_then(|s| s.get_mut(feature.name)),
+            feature => Some(self.core_feature_mut(feature)),
+        }
+    }
+
+    fn core_feature_mut(&mut self, feature: DeviceFeature) -> &mut vk::Bool32 {
+        match feature {
+            DeviceFeature::Extension(_) => unreachable!("extension features are not stored in the core structs"),
             DeviceFeature::RobustBufferAccess => &mut self.features1.robust_buffer_access,
             DeviceFeature::FullDrawIndexUint32 => &mut self.features1.full_draw_index_uint32,
             DeviceFeature::ImageCubeArray => &mut self.features1.image_cube_array,
@@ -489,28 +584,120 @@ impl<'a> FeatureStructs<'a> {
     }
 
     pub fn supports(&self, feature: DeviceFeature) -> bool {
-        self.feature_ref(feature).clone() == vk::TRUE
+        *self.feature_ref(feature) == vk::TRUE
     }
 
-    pub fn available(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> Self {
-        let mut feature_struct = Self::default();
-        let mut features2 = vk::PhysicalDeviceFeatures2::default()
-            .push_next(&mut feature_struct.vk11)
-            .push_next(&mut feature_struct.vk12)
-            .push_next(&mut feature_struct.vk13);
+    /// Queries the core feature structs along with every registered extension struct whose extension passes
+    /// `supported`. On Vulkan 1.2 devices the 1.3 features are queried through the extensions they were promoted from.
+    pub fn available(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        api_version: u32,
+        registry: &FeatureRegistry,
+        supported: impl Fn(&CStr) -> bool,
+    ) -> Self {
+        let mut feature_struct = Self {
+            api_version,
+            ..Default::default()
+        };
+        feature_struct.extensions = registry.instantiate(&supported).collect();
+        if !feature_struct.has_vulkan_1_3() {
+            feature_struct.promoted = vulkan_1_3_fallback_registry()
+                .instantiate(&supported)
+                .collect();
+        }
+
+        let features1 = {
+            let has_vulkan_1_3 = feature_struct.has_vulkan_1_3();
+            feature_struct.vk11.p_next = feature_struct.link_extensions().cast();
+            let mut features2 = vk::PhysicalDeviceFeatures2::default()
+                .push_next(&mut feature_struct.vk11)
+                .push_next(&mut feature_struct.vk12);
+
+            if has_vulkan_1_3 {
+                features2 = features2.push_next(&mut feature_struct.vk13);
+            }
 
-        unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+            unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+            features2.features
+        };
+
+        feature_struct.features1 = features1;
+        feature_struct.unlink();
+
+        for (core, fallback) in vulkan_1_3_fallbacks() {
+            if let Some(value) = feature_struct.promoted_value(fallback) {
+                *feature_struct.core_feature_mut(core) = value;
+            }
+        }
 
-        feature_struct.features1 = features2.features;
         feature_struct
     }
 
-    pub(crate) fn make_features_2(&mut self) -> vk::PhysicalDeviceFeatures2 {
-        vk::PhysicalDeviceFeatures2::default()
+    fn promoted_value(&self, feature: ExtensionFeature) -> Option<vk::Bool32> {
+        self.promoted
+            .iter()
+            .find(|s| s.extension() == feature.extension)
+            .and_then(|s| s.get(feature.name))
+            .copied()
+    }
+
+    /// Links the extension structs into a chain and returns its head (null if there are none).
+    fn link_extensions(&mut self) -> *mut vk::BaseOutStructure<'static> {
+        let mut head: *mut vk::BaseOutStructure<'static> = std::ptr::null_mut();
+        for s in self.extensions.iter_mut().chain(self.promoted.iter_mut()).rev() {
+            let base = s.as_base_mut();
+            base.p_next = head;
+            head = base;
+        }
+
+        head
+    }
+
+    /// Clears the `p_next` pointers left behind by a query so the returned value doesn't point into a moved-from chain.
+    fn unlink(&mut self) {
+        self.vk11.p_next = std::ptr::null_mut();
+        self.vk12.p_next = std::ptr::null_mut();
+        self.vk13.p_next = std::ptr::null_mut();
+        self.extensions
+            .iter_mut()
+            .chain(self.promoted.iter_mut())
+            .for_each(|s| s.as_base_mut().p_next = std::ptr::null_mut());
+    }
+
+    pub(crate) fn make_features_2(&mut self) -> vk::PhysicalDeviceFeatures2<'_> {
+        self.unlink();
+
+        for (core, fallback) in vulkan_1_3_fallbacks() {
+            if *self.feature_ref(core) == vk::TRUE
+                && let Some(value) = self
+                    .promoted
+                    .iter_mut()
+                    .find(|s| s.extension() == fallback.extension)
+                    .and_then(|s| s.get_mut(fallback.name))
+            {
+                *value = vk::TRUE;
+            }
+        }
+
+        let has_vulkan_1_3 = self.has_vulkan_1_3();
+        self.vk11.p_next = self.link_extensions().cast();
+
+        let features2 = vk::PhysicalDeviceFeatures2::default()
             .features(self.features1)
             .push_next(&mut self.vk11)
-            .push_next(&mut self.vk12)
-            .push_next(&mut self.vk13)
+            .push_next(&mut self.vk12);
+
+        if has_vulkan_1_3 {
+            features2.push_next(&mut self.vk13)
+        } else {
+            features2
+        }
+    }
+
+    /// The extensions whose feature structs are part of this set.
+    pub fn extensions(&self) -> impl Iterator<Item = &'static CStr> + '_ {
+        self.extensions.iter().map(|s| s.extension())
     }
 
     pub fn get_list(&self) -> HashSet<DeviceFeature> {
@@ -1000,53 +1187,92 @@ impl<'a> FeatureStructs<'a> {
             set.insert(DeviceFeature::Maintenance4);
         }
 
+        for s in &self.extensions {
+            for feature in s.features() {
+                if s.get(feature.name) == Some(&vk::TRUE) {
+                    set.insert(DeviceFeature::Extension(feature));
+                }
+            }
+        }
+
         set
     }
 }
 
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct QueueRequest {
-    pub family: u32,
+    /// Either a family index, or the capabilities the engine uses to pick the family.
+    pub family: QueueFamily,
     pub count: u32,
     pub label: Option<QueueLabel>,
     pub allow_merge: bool,
+    /// The queue priority, between `0.0` and `1.0`. When flexible requests share a queue, the queue gets the highest
+    /// priority among them.
+    pub priority: f32,
+    /// The system-wide priority of the queue family, through `VK_KHR_global_priority`. Applies to every queue created in
+    /// the family, the highest global priority requested in a family is used.
+    pub global_priority: Option<vk::QueueGlobalPriorityKHR>,
 }
 
 impl QueueRequest {
-    pub const fn strict_labeled(family: u32, count: u32, label: QueueLabel) -> Self {
+    pub const DEFAULT_PRIORITY: f32 = 1.0;
+
+    /// Requests `count` exclusive queues.
+    pub const fn strict(family: QueueFamily, count: u32, label: Option<QueueLabel>) -> Self {
+        Self::new(family, count, label, false)
+    }
+
+    /// Requests `count` queues which may be shared with other flexible requests when the family runs out of queues.
+    pub const fn flexible(family: QueueFamily, count: u32, label: Option<QueueLabel>) -> Self {
+        Self::new(family, count, label, true)
+    }
+
+    const fn new(family: QueueFamily, count: u32, label: Option<QueueLabel>, allow_merge: bool) -> Self {
         Self {
-            family, count, label: Some(label), allow_merge: false,
+            family,
+            count,
+            label,
+            allow_merge,
+            priority: Self::DEFAULT_PRIORITY,
+            global_priority: None,
         }
     }
 
+    pub const fn strict_labeled(family: u32, count: u32, label: QueueLabel) -> Self {
+        Self::strict(QueueFamily::Index(family), count, Some(label))
+    }
+
     pub const fn strict_labeled_custom(family: u32, count: u32, label: &'static str) -> Self {
-        Self {
-            family, count, label: Some(QueueLabel::Custom(label)), allow_merge: false,
-        }
+        Self::strict(QueueFamily::Index(family), count, Some(QueueLabel::Custom(label)))
     }
 
     pub const fn strict_unlabeled(family: u32, count: u32) -> Self {
-        Self {
-            family, count, label: None, allow_merge: false,
-        }
+        Self::strict(QueueFamily::Index(family), count, None)
     }
 
     pub const fn flexible_labeled(family: u32, count: u32, label: QueueLabel) -> Self {
-        Self {
-            family, count, label: Some(label), allow_merge: true,
-        }
+        Self::flexible(QueueFamily::Index(family), count, Some(label))
     }
 
     pub const fn flexible_labeled_custom(family: u32, count: u32, label: &'static str) -> Self {
-        Self {
-            family, count, label: Some(QueueLabel::Custom(label)), allow_merge: true,
-        }
+        Self::flexible(QueueFamily::Index(family), count, Some(QueueLabel::Custom(label)))
     }
 
     pub const fn flexible_unlabeled(family: u32, count: u32) -> Self {
-        Self {
-            family, count, label: None, allow_merge: true,
-        }
+        Self::flexible(QueueFamily::Index(family), count, None)
+    }
+
+    pub const fn with_priority(mut self, priority: f32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Priorities above [`vk::QueueGlobalPriorityKHR::MEDIUM`] usually need elevated permissions, the engine falls back
+    /// to the default global priority when the driver refuses them.
+    pub const fn with_global_priority(mut self, global_priority: vk::QueueGlobalPriorityKHR) -> Self {
+        self.global_priority = Some(global_priority);
+        self
     }
 }
 
@@ -1056,6 +1282,12 @@ pub struct ExtensionRequest {
     pub required: bool,
 }
 
+#[derive(Clone, Debug, Hash)]
+pub struct LayerRequest {
+    pub name: &'static CStr,
+    pub required: bool,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum DeviceFeature {
     // Vulkan 1.0
@@ -1186,6 +1418,271 @@ pub enum DeviceFeature {
     DynamicRendering,
     ShaderIntegerDotProduct,
     Maintenance4,
+
+    // Extensions, see `crate::app::extension_features`
+    Extension(ExtensionFeature),
+}
+
+const CORE_FEATURES: &[DeviceFeature] = &[
+    DeviceFeature::RobustBufferAccess,
+    DeviceFeature::FullDrawIndexUint32,
+    DeviceFeature::ImageCubeArray,
+    DeviceFeature::IndependentBlend,
+    DeviceFeature::GeometryShader,
+    DeviceFeature::TessellationShader,
+    DeviceFeature::SampleRateShading,
+    DeviceFeature::DualSourceBlend,
+    DeviceFeature::LogicOperation,
+    DeviceFeature::MultiDrawIndirect,
+    DeviceFeature::WideLines,
+    DeviceFeature::LargePoints,
+    DeviceFeature::AlphaToOne,
+    DeviceFeature::MultiViewport,
+    DeviceFeature::SamplerAnisotropy,
+    DeviceFeature::TextureCompressionETC2,
+    DeviceFeature::TextureCompressionASTCLDR,
+    DeviceFeature::TextureCompressionBC,
+    DeviceFeature::OcclusionQueryPrecise,
+    DeviceFeature::PipelineStatisticsQuery,
+    DeviceFeature::VertexPipelineStoresAndAtomics,
+    DeviceFeature::FragmentStoresAndAtomics,
+    DeviceFeature::ShaderTessellationAndGeometryPointSize,
+    DeviceFeature::ShaderImageGatherExtended,
+    DeviceFeature::ShaderStorageImageExtendedFormats,
+    DeviceFeature::ShaderStorageImageMultisample,
+    DeviceFeature::ShaderStorageImageReadWithoutFormat,
+    DeviceFeature::ShaderStorageImageWriteWithoutFormat,
+    DeviceFeature::ShaderUniformBufferArrayDynamicIndexing,
+    DeviceFeature::ShaderSampledImageArrayDynamicIndexing,
+    DeviceFeature::ShaderStorageBufferArrayDynamicIndexing,
+    DeviceFeature::ShaderStorageImageArrayDynamicIndexing,
+    DeviceFeature::ShaderClipDistance,
+    DeviceFeature::ShaderCullDistance,
+    DeviceFeature::ShaderFloat64,
+    DeviceFeature::ShaderInt64,
+    DeviceFeature::ShaderInt16,
+    DeviceFeature::ShaderResourceResidency,
+    DeviceFeature::ShaderResourceMinLod,
+    DeviceFeature::SparseBinding,
+    DeviceFeature::SparseResidencyBuffer,
+    DeviceFeature::SparseResidencyImage2D,
+    DeviceFeature::SparseResidencyImage3D,
+    DeviceFeature::SparseResidency2Samples,
+    DeviceFeature::SparseResidency4Samples,
+    DeviceFeature::SparseResidency8Samples,
+    DeviceFeature::SparseResidency16Samples,
+    DeviceFeature::VariableMultisampleRate,
+    DeviceFeature::InheritedQueries,
+    DeviceFeature::StorageBuffer16BitAccess,
+    DeviceFeature::UniformAndStorageBuffer16BitAccess,
+    DeviceFeature::StoragePushConstant16,
+    DeviceFeature::StorageInputOutput16,
+    DeviceFeature::Multiview,
+    DeviceFeature::MultiviewGeometryShader,
+    DeviceFeature::MultiviewTessellationShader,
+    DeviceFeature::VariablePointersStorageBuffer,
+    DeviceFeature::VariablePointers,
+    DeviceFeature::ProtectedMemory,
+    DeviceFeature::SamplerYcbcrConversion,
+    DeviceFeature::ShaderDrawParameters,
+    DeviceFeature::SamplerMirrorClampToEdge,
+    DeviceFeature::DrawIndirectCount,
+    DeviceFeature::StorageBuffer8BitAccess,
+    DeviceFeature::UniformAndStorageBuffer8BitAccess,
+    DeviceFeature::ShaderBufferInt64Atomics,
+    DeviceFeature::ShaderSharedInt64Atomics,
+    DeviceFeature::ShaderFloat16,
+    DeviceFeature::ShaderInt8,
+    DeviceFeature::DescriptorIndexing,
+    DeviceFeature::ShaderInputAttachmentArrayDynamicIndexing,
+    DeviceFeature::ShaderUniformTexelBufferArrayDynamicIndexing,
+    DeviceFeature::ShaderStorageTexelBufferArrayDynamicIndexing,
+    DeviceFeature::ShaderUniformBufferArrayNonUniformIndexing,
+    DeviceFeature::ShaderSampledImageArrayNonUniformIndexing,
+    DeviceFeature::ShaderStorageBufferArrayNonUniformIndexing,
+    DeviceFeature::ShaderStorageImageArrayNonUniformIndexing,
+    DeviceFeature::ShaderInputAttachmentArrayNonUniformIndexing,
+    DeviceFeature::ShaderUniformTexelBufferArrayNonUniformIndexing,
+    DeviceFeature::ShaderStorageTexelBufferArrayNonUniformIndexing,
+    DeviceFeature::DescriptorBindingUniformBufferUpdateAfterBind,
+    DeviceFeature::DescriptorBindingSampledImageUpdateAfterBind,
+    DeviceFeature::DescriptorBindingStorageImageUpdateAfterBind,
+    DeviceFeature::DescriptorBindingStorageBufferUpdateAfterBind,
+    DeviceFeature::DescriptorBindingUniformTexelBufferUpdateAfterBind,
+    DeviceFeature::DescriptorBindingStorageTexelBufferUpdateAfterBind,
+    DeviceFeature::DescriptorBindingUpdateUnusedWhilePending,
+    DeviceFeature::DescriptorBindingPartiallyBound,
+    DeviceFeature::DescriptorBindingVariableDescriptorCount,
+    DeviceFeature::RuntimeDescriptorArray,
+    DeviceFeature::SamplerFilterMinmax,
+    DeviceFeature::ScalarBlockLayout,
+    DeviceFeature::ImagelessFramebuffer,
+    DeviceFeature::UniformBufferStandardLayout,
+    DeviceFeature::ShaderSubgroupExtendedTypes,
+    DeviceFeature::SeparateDepthStencilLayouts,
+    DeviceFeature::HostQueryReset,
+    DeviceFeature::TimelineSemaphore,
+    DeviceFeature::BufferDeviceAddress,
+    DeviceFeature::BufferDeviceAddressCaptureReplay,
+    DeviceFeature::BufferDeviceAddressMultiDevice,
+    DeviceFeature::VulkanMemoryModel,
+    DeviceFeature::VulkanMemoryModelDeviceScope,
+    DeviceFeature::VulkanMemoryModelAvailabilityVisibilityChains,
+    DeviceFeature::ShaderOutputViewportIndex,
+    DeviceFeature::ShaderOutputLayer,
+    DeviceFeature::SubgroupBroadcastDynamicId,
+    DeviceFeature::RobustImageAccess,
+    DeviceFeature::InlineUniformBlock,
+    DeviceFeature::DescriptorBindingInlineUniformBlockUpdateAfterBind,
+    DeviceFeature::PipelineCreationCacheControl,
+    DeviceFeature::PrivateData,
+    DeviceFeature::ShaderDemoteToHelperInvocation,
+    DeviceFeature::ShaderTerminateInvocation,
+    DeviceFeature::ComputeFullSubgroups,
+    DeviceFeature::Synchronization2,
+    DeviceFeature::TextureCompressionASTCHDR,
+    DeviceFeature::ShaderZeroInitializeWorkgroupMemory,
+    DeviceFeature::DynamicRendering,
+    DeviceFeature::ShaderIntegerDotProduct,
+    DeviceFeature::Maintenance4,
+];
+
+impl DeviceFeature {
+    /// The name of the member of the Vulkan feature struct holding this feature (e.g. `dynamicRendering`).
+    pub fn vulkan_name(&self) -> &'static str {
+        match self {
+            DeviceFeature::RobustBufferAccess => "robustBufferAccess",
+            DeviceFeature::FullDrawIndexUint32 => "fullDrawIndexUint32",
+            DeviceFeature::ImageCubeArray => "imageCubeArray",
+            DeviceFeature::IndependentBlend => "independentBlend",
+            DeviceFeature::GeometryShader => "geometryShader",
+            DeviceFeature::TessellationShader => "tessellationShader",
+            DeviceFeature::SampleRateShading => "sampleRateShading",
+            DeviceFeature::DualSourceBlend => "dualSrcBlend",
+            DeviceFeature::LogicOperation => "logicOp",
+            DeviceFeature::MultiDrawIndirect => "multiDrawIndirect",
+            DeviceFeature::WideLines => "wideLines",
+            DeviceFeature::LargePoints => "largePoints",
+            DeviceFeature::AlphaToOne => "alphaToOne",
+            DeviceFeature::MultiViewport => "multiViewport",
+            DeviceFeature::SamplerAnisotropy => "samplerAnisotropy",
+            DeviceFeature::TextureCompressionETC2 => "textureCompressionETC2",
+            DeviceFeature::TextureCompressionASTCLDR => "textureCompressionASTC_LDR",
+            DeviceFeature::TextureCompressionBC => "textureCompressionBC",
+            DeviceFeature::OcclusionQueryPrecise => "occlusionQueryPrecise",
+            DeviceFeature::PipelineStatisticsQuery => "pipelineStatisticsQuery",
+            DeviceFeature::VertexPipelineStoresAndAtomics => "vertexPipelineStoresAndAtomics",
+            DeviceFeature::FragmentStoresAndAtomics => "fragmentStoresAndAtomics",
+            DeviceFeature::ShaderTessellationAndGeometryPointSize => "shaderTessellationAndGeometryPointSize",
+            DeviceFeature::ShaderImageGatherExtended => "shaderImageGatherExtended",
+            DeviceFeature::ShaderStorageImageExtendedFormats => "shaderStorageImageExtendedFormats",
+            DeviceFeature::ShaderStorageImageMultisample => "shaderStorageImageMultisample",
+            DeviceFeature::ShaderStorageImageReadWithoutFormat => "shaderStorageImageReadWithoutFormat",
+            DeviceFeature::ShaderStorageImageWriteWithoutFormat => "shaderStorageImageWriteWithoutFormat",
+            DeviceFeature::ShaderUniformBufferArrayDynamicIndexing => "shaderUniformBufferArrayDynamicIndexing",
+            DeviceFeature::ShaderSampledImageArrayDynamicIndexing => "shaderSampledImageArrayDynamicIndexing",
+            DeviceFeature::ShaderStorageBufferArrayDynamicIndexing => "shaderStorageBufferArrayDynamicIndexing",
+            DeviceFeature::ShaderStorageImageArrayDynamicIndexing => "shaderStorageImageArrayDynamicIndexing",
+            DeviceFeature::ShaderClipDistance => "shaderClipDistance",
+            DeviceFeature::ShaderCullDistance => "shaderCullDistance",
+            DeviceFeature::ShaderFloat64 => "shaderFloat64",
+            DeviceFeature::ShaderInt64 => "shaderInt64",
+            DeviceFeature::ShaderInt16 => "shaderInt16",
+            DeviceFeature::ShaderResourceResidency => "shaderResourceResidency",
+            DeviceFeature::ShaderResourceMinLod => "shaderResourceMinLod",
+            DeviceFeature::SparseBinding => "sparseBinding",
+            DeviceFeature::SparseResidencyBuffer => "sparseResidencyBuffer",
+            DeviceFeature::SparseResidencyImage2D => "sparseResidencyImage2D",
+            DeviceFeature::SparseResidencyImage3D => "sparseResidencyImage3D",
+            DeviceFeature::SparseResidency2Samples => "sparseResidency2Samples",
+            DeviceFeature::SparseResidency4Samples => "sparseResidency4Samples",
+            DeviceFeature::SparseResidency8Samples => "sparseResidency8Samples",
+            DeviceFeature::SparseResidency16Samples => "sparseResidency16Samples",
+            DeviceFeature::VariableMultisampleRate => "variableMultisampleRate",
+            DeviceFeature::InheritedQueries => "inheritedQueries",
+            DeviceFeature::StorageBuffer16BitAccess => "storageBuffer16BitAccess",
+            DeviceFeature::UniformAndStorageBuffer16BitAccess => "uniformAndStorageBuffer16BitAccess",
+            DeviceFeature::StoragePushConstant16 => "storagePushConstant16",
+            DeviceFeature::StorageInputOutput16 => "storageInputOutput16",
+            DeviceFeature::Multiview => "multiview",
+            DeviceFeature::MultiviewGeometryShader => "multiviewGeometryShader",
+            DeviceFeature::MultiviewTessellationShader => "multiviewTessellationShader",
+            DeviceFeature::VariablePointersStorageBuffer => "variablePointersStorageBuffer",
+            DeviceFeature::VariablePointers => "variablePointers",
+            DeviceFeature::ProtectedMemory => "protectedMemory",
+            DeviceFeature::SamplerYcbcrConversion => "samplerYcbcrConversion",
+            DeviceFeature::ShaderDrawParameters => "shaderDrawParameters",
+            DeviceFeature::SamplerMirrorClampToEdge => "samplerMirrorClampToEdge",
+            DeviceFeature::DrawIndirectCount => "drawIndirectCount",
+            DeviceFeature::StorageBuffer8BitAccess => "storageBuffer8BitAccess",
+            DeviceFeature::UniformAndStorageBuffer8BitAccess => "uniformAndStorageBuffer8BitAccess",
+            DeviceFeature::ShaderBufferInt64Atomics => "shaderBufferInt64Atomics",
+            DeviceFeature::ShaderSharedInt64Atomics => "shaderSharedInt64Atomics",
+            DeviceFeature::ShaderFloat16 => "shaderFloat16",
+            DeviceFeature::ShaderInt8 => "shaderInt8",
+            DeviceFeature::DescriptorIndexing => "descriptorIndexing",
+            DeviceFeature::ShaderInputAttachmentArrayDynamicIndexing => "shaderInputAttachmentArrayDynamicIndexing",
+            DeviceFeature::ShaderUniformTexelBufferArrayDynamicIndexing => "shaderUniformTexelBufferArrayDynamicIndexing",
+            DeviceFeature::ShaderStorageTexelBufferArrayDynamicIndexing => "shaderStorageTexelBufferArrayDynamicIndexing",
+            DeviceFeature::ShaderUniformBufferArrayNonUniformIndexing => "shaderUniformBufferArrayNonUniformIndexing",
+            DeviceFeature::ShaderSampledImageArrayNonUniformIndexing => "shaderSampledImageArrayNonUniformIndexing",
+            DeviceFeature::ShaderStorageBufferArrayNonUniformIndexing => "shaderStorageBufferArrayNonUniformIndexing",
+            DeviceFeature::ShaderStorageImageArrayNonUniformIndexing => "shaderStorageImageArrayNonUniformIndexing",
+            DeviceFeature::ShaderInputAttachmentArrayNonUniformIndexing => "shaderInputAttachmentArrayNonUniformIndexing",
+            DeviceFeature::ShaderUniformTexelBufferArrayNonUniformIndexing => "shaderUniformTexelBufferArrayNonUniformIndexing",
+            DeviceFeature::ShaderStorageTexelBufferArrayNonUniformIndexing => "shaderStorageTexelBufferArrayNonUniformIndexing",
+            DeviceFeature::DescriptorBindingUniformBufferUpdateAfterBind => "descriptorBindingUniformBufferUpdateAfterBind",
+            DeviceFeature::DescriptorBindingSampledImageUpdateAfterBind => "descriptorBindingSampledImageUpdateAfterBind",
+            DeviceFeature::DescriptorBindingStorageImageUpdateAfterBind => "descriptorBindingStorageImageUpdateAfterBind",
+            DeviceFeature::DescriptorBindingStorageBufferUpdateAfterBind => "descriptorBindingStorageBufferUpdateAfterBind",
+            DeviceFeature::DescriptorBindingUniformTexelBufferUpdateAfterBind => "descriptorBindingUniformTexelBufferUpdateAfterBind",
+            DeviceFeature::DescriptorBindingStorageTexelBufferUpdateAfterBind => "descriptorBindingStorageTexelBufferUpdateAfterBind",
+            DeviceFeature::DescriptorBindingUpdateUnusedWhilePending => "descriptorBindingUpdateUnusedWhilePending",
+            DeviceFeature::DescriptorBindingPartiallyBound => "descriptorBindingPartiallyBound",
+            DeviceFeature::DescriptorBindingVariableDescriptorCount => "descriptorBindingVariableDescriptorCount",
+            DeviceFeature::RuntimeDescriptorArray => "runtimeDescriptorArray",
+            DeviceFeature::SamplerFilterMinmax => "samplerFilterMinmax",
+            DeviceFeature::ScalarBlockLayout => "scalarBlockLayout",
+            DeviceFeature::ImagelessFramebuffer => "imagelessFramebuffer",
+            DeviceFeature::UniformBufferStandardLayout => "uniformBufferStandardLayout",
+            DeviceFeature::ShaderSubgroupExtendedTypes => "shaderSubgroupExtendedTypes",
+            DeviceFeature::SeparateDepthStencilLayouts => "separateDepthStencilLayouts",
+            DeviceFeature::HostQueryReset => "hostQueryReset",
+            DeviceFeature::TimelineSemaphore => "timelineSemaphore",
+            DeviceFeature::BufferDeviceAddress => "bufferDeviceAddress",
+            DeviceFeature::BufferDeviceAddressCaptureReplay => "bufferDeviceAddressCaptureReplay",
+            DeviceFeature::BufferDeviceAddressMultiDevice => "bufferDeviceAddressMultiDevice",
+            DeviceFeature::VulkanMemoryModel => "vulkanMemoryModel",
+            DeviceFeature::VulkanMemoryModelDeviceScope => "vulkanMemoryModelDeviceScope",
+            DeviceFeature::VulkanMemoryModelAvailabilityVisibilityChains => "vulkanMemoryModelAvailabilityVisibilityChains",
+            DeviceFeature::ShaderOutputViewportIndex => "shaderOutputViewportIndex",
+            DeviceFeature::ShaderOutputLayer => "shaderOutputLayer",
+            DeviceFeature::SubgroupBroadcastDynamicId => "subgroupBroadcastDynamicId",
+            DeviceFeature::RobustImageAccess => "robustImageAccess",
+            DeviceFeature::InlineUniformBlock => "inlineUniformBlock",
+            DeviceFeature::DescriptorBindingInlineUniformBlockUpdateAfterBind => "descriptorBindingInlineUniformBlockUpdateAfterBind",
+            DeviceFeature::PipelineCreationCacheControl => "pipelineCreationCacheControl",
+            DeviceFeature::PrivateData => "privateData",
+            DeviceFeature::ShaderDemoteToHelperInvocation => "shaderDemoteToHelperInvocation",
+            DeviceFeature::ShaderTerminateInvocation => "shaderTerminateInvocation",
+            DeviceFeature::ComputeFullSubgroups => "computeFullSubgroups",
+            DeviceFeature::Synchronization2 => "synchronization2",
+            DeviceFeature::TextureCompressionASTCHDR => "textureCompressionASTC_HDR",
+            DeviceFeature::ShaderZeroInitializeWorkgroupMemory => "shaderZeroInitializeWorkgroupMemory",
+            DeviceFeature::DynamicRendering => "dynamicRendering",
+            DeviceFeature::ShaderIntegerDotProduct => "shaderIntegerDotProduct",
+            DeviceFeature::Maintenance4 => "maintenance4",
+            DeviceFeature::Extension(feature) => feature.name,
+        }
+    }
+
+    /// Looks up a core feature by the name of its struct member, see [`DeviceFeature::vulkan_name`].
+    pub fn from_vulkan_name(name: &str) -> Option<DeviceFeature> {
+        CORE_FEATURES
+            .iter()
+            .copied()
+            .find(|feature| feature.vulkan_name() == name)
+    }
 }
 
 #[derive(Clone, Debug, Hash)]
@@ -1226,6 +1723,22 @@ impl ExtensionRequest {
     }
 }
 
+impl LayerRequest {
+    pub const fn required(name: &'static CStr) -> LayerRequest {
+        Self {
+            name,
+            required: true,
+        }
+    }
+
+    pub const fn optional(name: &'static CStr) -> LayerRequest {
+        Self {
+            name,
+            required: false,
+        }
+    }
+}
+
 pub trait RequestHelper<R> {
     fn optional(self, value: R) -> Self;
     fn required(self, value: R) -> Self;
@@ -1254,4 +1767,16 @@ impl RequestHelper<&'static CStr> for &mut Vec<ExtensionRequest> {
         self.push(ExtensionRequest::required(value));
         self
     }
-}
\ No newline at end of file
+}
+
+impl RequestHelper<&'static CStr> for &mut Vec<LayerRequest> {
+    fn optional(self, value: &'static CStr) -> Self {
+        self.push(LayerRequest::optional(value));
+        self
+    }
+
+    fn required(self, value: &'static CStr) -> Self {
+        self.push(LayerRequest::required(value));
+        self
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{Engine, EngineCallbackHandler};
+use ash::vk;
+use log::error;
 use winit::application::ApplicationHandler;
 use winit::event::WindowEvent;
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::WindowId;
 
+pub mod engine_profile;
+pub mod extension_features;
 pub mod feature_request;
+pub mod vulkan_profile;
 
 #[allow(unused_variables)]
 pub trait Application: EngineCallbackHandler {
@@ -21,6 +26,9 @@ pub trait Application: EngineCallbackHandler {
     fn on_about_to_wait(&mut self, event_loop: &ActiveEventLoop, engine: &mut Engine) {}
 
     fn on_redraw_window(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, engine: &mut Engine) {}
+
+    /// Called once the swapchain of a window was recreated with a new extent, before the window is redrawn.
+    fn on_window_resized(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, extent: vk::Extent2D, engine: &mut Engine) {}
 }
 
 pub struct ApplicationWrapper<A: Application> {
@@ -47,7 +55,13 @@ impl<A: Application> ApplicationHandler for ApplicationWrapper<A> {
 
         match event {
             WindowEvent::ActivationTokenDone { .. } => {}
-            WindowEvent::Resized(_) => {}
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                if let Some(window) = engine.get_window(&window_id) {
+                    let mut window = window.borrow_mut();
+                    window.request_reconfigure();
+                    window.window().request_redraw();
+                }
+            }
             WindowEvent::Moved(_) => {}
             WindowEvent::CloseRequested => {
                 if self.app.on_window_try_close(event_loop, window_id, &mut engine) {
@@ -75,11 +89,23 @@ impl<A: Application> ApplicationHandler for ApplicationWrapper<A> {
             WindowEvent::TouchpadPressure { .. } => {}
             WindowEvent::AxisMotion { .. } => {}
             WindowEvent::Touch(_) => {}
-            WindowEvent::ScaleFactorChanged { .. } => {}
             WindowEvent::ThemeChanged(_) => {}
             WindowEvent::Occluded(_) => {}
             WindowEvent::RedrawRequested => {
-                self.app.on_redraw_window(event_loop, window_id, &mut engine);
+                let ready = engine
+                    .get_window(&window_id)
+                    .map_or(Ok(false), |window| window.borrow_mut().update_swapchain());
+                self.notify_resized(event_loop, window_id, &mut engine);
+
+                match ready {
+                    Ok(true) => {
+                        self.app.on_redraw_window(event_loop, window_id, &mut engine);
+                        self.notify_resized(event_loop, window_id, &mut engine);
+                    }
+                    // minimized, rendering resumes once the window is resized
+                    Ok(false) => {}
+                    Err(e) => error!("[window] Failed to recreate the swapchain: {}", e),
+                }
             }
         }
 
@@ -90,11 +116,26 @@ impl<A: Application> ApplicationHandler for ApplicationWrapper<A> {
         // TODO: redraw requests here
         let Some(engine) = self.engine.take() else { return; };
 
+        for window in engine.windows().values() {
+            if let Err(e) = window.borrow_mut().complete_frames() {
+                error!("[window] Failed to query the frames in flight: {}", e);
+            }
+        }
+
+        let vulkan = engine.vulkan();
+        if let Err(e) = vulkan.collect_garbage() {
+            error!("[context] Failed to destroy deferred resources: {}", e);
+        }
+        vulkan.deletion_queue().advance_frame();
+
         if engine.window_count() == 0 {
             event_loop.exit();
         } else {
             engine.windows().iter().for_each(|(_, window)| {
-                window.borrow().window().request_redraw();
+                let window = window.borrow();
+                if !window.is_minimized() {
+                    window.window().request_redraw();
+                }
             })
         }
 
@@ -109,6 +150,16 @@ impl<A: Application> ApplicationHandler for ApplicationWrapper<A> {
 }
 
 impl<A: Application> ApplicationWrapper<A> {
+    fn notify_resized(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, engine: &mut Engine) {
+        let resized = engine
+            .get_window(&window_id)
+            .and_then(|window| window.borrow_mut().take_resized());
+
+        if let Some(extent) = resized {
+            self.app.on_window_resized(event_loop, window_id, extent, engine);
+        }
+    }
+
     pub fn wrap(mut app: A, event_loop: &EventLoop<()>) -> anyhow::Result<Self> {
         let engine = Engine::init(event_loop, &mut app)?;
 
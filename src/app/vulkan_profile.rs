@@ -0,0 +1,765 @@
+use crate::app::extension_features::FeatureRegistry;
+use crate::app::feature_request::{DeviceFeature, DeviceFeatureRequest, ExtensionRequest};
+use crate::render::context::limits::{LimitValue, limits_table};
+use crate::render::context::physical_device::PhysicalDeviceInfo;
+use crate::render::context::version::{format_version, promoted_to_core, version_at_least};
+use anyhow::anyhow;
+use ash::vk;
+use log::warn;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    capabilities: BTreeMap<String, CapabilitiesJson>,
+    #[serde(default)]
+    profiles: BTreeMap<String, ProfileJson>,
+}
+
+#[derive(Deserialize)]
+struct CapabilitiesJson {
+    #[serde(default)]
+    extensions: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    features: BTreeMap<String, BTreeMap<String, serde_json::Value>>,
+    #[serde(default)]
+    properties: BTreeMap<String, serde_json::Value>,
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ProfileJson {
+    #[serde(rename = "api-version")]
+    api_version: String,
+    label: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    capabilities: Vec<CapabilityRef>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CapabilityRef {
+    One(String),
+    AnyOf(Vec<String>),
+}
+
+/// Profiles loaded from a Khronos Vulkan Profiles JSON file (e.g. `VP_KHR_roadmap.json` or `VP_ANDROID_baseline_2022.json`).
+///
+/// Extensions, features (core features and features of registered extension feature structs) and
+/// `VkPhysicalDeviceProperties::limits` are imported. Everything else (formats, queue families, other property structs)
+/// is listed in [`VulkanProfile::unchecked`] and logged when loading.
+#[derive(Clone, Debug)]
+pub struct VulkanProfiles {
+    profiles: Vec<VulkanProfile>,
+}
+
+impl VulkanProfiles {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::load_with_registry(path, &FeatureRegistry::default())
+    }
+
+    /// Like [`VulkanProfiles::load`], resolving extension features against `registry` instead of the built-in structs.
+    pub fn load_with_registry(path: impl AsRef<Path>, registry: &FeatureRegistry) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read Vulkan profiles file {:?}: {}", path, e))?;
+        Self::from_json_with_registry(&json, registry)
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Self::from_json_with_registry(json, &FeatureRegistry::default())
+    }
+
+    pub fn from_json_with_registry(json: &str, registry: &FeatureRegistry) -> anyhow::Result<Self> {
+        let file: ProfilesFile = serde_json::from_str(json)?;
+
+        let mut capabilities = BTreeMap::new();
+        for (name, json) in &file.capabilities {
+            capabilities.insert(name.clone(), ProfileCapabilities::parse(name, json, registry)?);
+        }
+
+        let mut profiles = Vec::new();
+        for (name, json) in file.profiles {
+            let mut groups = Vec::new();
+            for capability in &json.capabilities {
+                let names = match capability {
+                    CapabilityRef::One(name) => std::slice::from_ref(name),
+                    CapabilityRef::AnyOf(names) => names.as_slice(),
+                };
+
+                let group = names
+                    .iter()
+                    .map(|capability| {
+                        capabilities.get(capability).cloned().ok_or_else(|| {
+                            anyhow!("Profile {} references unknown capabilities {}", name, capability)
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<ProfileCapabilities>>>()?;
+
+                groups.push(group);
+            }
+
+            let profile = VulkanProfile {
+                api_version: parse_api_version(&json.api_version)
+                    .ok_or_else(|| anyhow!("Profile {} has an invalid api-version {:?}", name, json.api_version))?,
+                name,
+                label: json.label,
+                description: json.description,
+                capabilities: groups,
+            };
+
+            for unchecked in profile.unchecked() {
+                warn!(
+                    "[profiles] {}: {} is not checked by the engine",
+                    profile.name, unchecked
+                );
+            }
+
+            profiles.push(profile);
+        }
+
+        Ok(Self { profiles })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VulkanProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    pub fn profiles(&self) -> &[VulkanProfile] {
+        &self.profiles
+    }
+}
+
+/// One named block of the `capabilities` section of a profiles file.
+#[derive(Clone, Debug)]
+pub struct ProfileCapabilities {
+    pub name: String,
+    pub extensions: Vec<&'static CStr>,
+    pub features: Vec<DeviceFeature>,
+    pub limits: Vec<(String, serde_json::Value)>,
+    pub unchecked: Vec<String>,
+}
+
+impl ProfileCapabilities {
+    fn parse(name: &str, json: &CapabilitiesJson, registry: &FeatureRegistry) -> anyhow::Result<Self> {
+        let mut capabilities = ProfileCapabilities {
+            name: name.to_owned(),
+            extensions: Vec::new(),
+            features: Vec::new(),
+            limits: Vec::new(),
+            unchecked: Vec::new(),
+        };
+
+        for extension in json.extensions.keys() {
+            capabilities.extensions.push(intern(extension)?);
+        }
+
+        for (structure, members) in &json.features {
+            for (member, value) in members {
+                let feature = DeviceFeature::from_vulkan_name(member)
+                    .or_else(|| registry.find_feature(member).map(DeviceFeature::Extension));
+
+                match (feature, value.as_bool()) {
+                    (Some(feature), Some(true)) => capabilities.features.push(feature),
+                    (_, Some(false)) => {}
+                    _ => capabilities.unchecked.push(format!("{}::{}", structure, member)),
+                }
+            }
+        }
+
+        let known_limits = limits_table(&vk::PhysicalDeviceLimits::default());
+        for (structure, properties) in &json.properties {
+            let Some(properties) = properties.as_object() else {
+                capabilities.unchecked.push(structure.clone());
+                continue;
+            };
+
+            for (member, value) in properties {
+                match (structure.as_str(), member.as_str(), value.as_object()) {
+                    ("VkPhysicalDeviceProperties", "limits", Some(limits)) => {
+                        for (limit, value) in limits {
+                            if known_limits.iter().any(|(name, _)| name == limit) {
+                                capabilities.limits.push((limit.clone(), value.clone()));
+                            } else {
+                                capabilities
+                                    .unchecked
+                                    .push(format!("{}::limits::{}", structure, limit));
+                            }
+                        }
+                    }
+                    _ => capabilities.unchecked.push(format!("{}::{}", structure, member)),
+                }
+            }
+        }
+
+        capabilities.unchecked.extend(
+            json.other
+                .iter()
+                .filter(|(_, value)| !is_empty(value))
+                .map(|(key, _)| key.clone()),
+        );
+
+        Ok(capabilities)
+    }
+
+    fn check(&self, physical_device: &PhysicalDeviceInfo) -> Vec<ProfileFailure> {
+        let mut failures = Vec::new();
+        let api_version = physical_device.negotiated_api_version();
+
+        for extension in &self.extensions {
+            if !physical_device.supports_extension(extension) && !promoted_to_core(extension, api_version) {
+                failures.push(ProfileFailure::Extension {
+                    capability: self.name.clone(),
+                    extension: extension.to_string_lossy().into_owned(),
+                });
+            }
+        }
+
+        for feature in &self.features {
+            if !physical_device.supports_feature(*feature) {
+                failures.push(ProfileFailure::Feature {
+                    capability: self.name.clone(),
+                    feature: *feature,
+                });
+            }
+        }
+
+        let device_limits = limits_table(physical_device.limits());
+        for (limit, required) in &self.limits {
+            let supported = device_limits
+                .iter()
+                .find(|(name, _)| name == limit)
+                .map(|(_, value)| value.clone());
+
+            let satisfied = supported
+                .as_ref()
+                .and_then(|value| value.satisfies(limit, required))
+                .unwrap_or(false);
+
+            if !satisfied {
+                failures.push(ProfileFailure::Limit {
+                    capability: self.name.clone(),
+                    limit: limit.clone(),
+                    required: required.clone(),
+                    supported,
+                });
+            }
+        }
+
+        failures
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VulkanProfile {
+    pub name: String,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub api_version: u32,
+    /// Every group must be satisfied, a group is satisfied by any one of its capabilities.
+    pub capabilities: Vec<Vec<ProfileCapabilities>>,
+}
+
+impl VulkanProfile {
+    /// Everything in the profile that the engine doesn't check or enable.
+    pub fn unchecked(&self) -> Vec<String> {
+        let mut unchecked: Vec<String> = Vec::new();
+        for capabilities in self.capabilities.iter().flatten() {
+            for entry in &capabilities.unchecked {
+                let entry = format!("{} ({})", entry, capabilities.name);
+                if !unchecked.contains(&entry) {
+                    unchecked.push(entry);
+                }
+            }
+        }
+
+        unchecked
+    }
+
+    /// For each group, the first capabilities block `physical_device` satisfies (or the first block if none is).
+    pub fn select_capabilities(&self, physical_device: &PhysicalDeviceInfo) -> Vec<&ProfileCapabilities> {
+        self.capabilities
+            .iter()
+            .filter_map(|group| {
+                group
+                    .iter()
+                    .find(|capabilities| capabilities.check(physical_device).is_empty())
+                    .or(group.first())
+            })
+            .collect()
+    }
+
+    pub fn extension_requests(&self, physical_device: &PhysicalDeviceInfo) -> Vec<ExtensionRequest> {
+        self.select_capabilities(physical_device)
+            .iter()
+            .flat_map(|capabilities| capabilities.extensions.iter())
+            .map(|extension| ExtensionRequest::required(extension))
+            .collect()
+    }
+
+    pub fn feature_requests(&self, physical_device: &PhysicalDeviceInfo) -> Vec<DeviceFeatureRequest> {
+        self.select_capabilities(physical_device)
+            .iter()
+            .flat_map(|capabilities| capabilities.features.iter())
+            .map(|feature| DeviceFeatureRequest::required(*feature))
+            .collect()
+    }
+
+    /// Lists every capability of this profile that `physical_device` fails.
+    pub fn check(&self, physical_device: &PhysicalDeviceInfo) -> ProfileReport {
+        let mut failures = Vec::new();
+
+        if !version_at_least(physical_device.negotiated_api_version(), self.api_version) {
+            failures.push(ProfileFailure::ApiVersion {
+                required: self.api_version,
+                supported: physical_device.negotiated_api_version(),
+            });
+        }
+
+        for group in &self.capabilities {
+            let group_failures = group
+                .iter()
+                .map(|capabilities| capabilities.check(physical_device))
+                .collect::<Vec<Vec<ProfileFailure>>>();
+
+            if !group_failures.iter().any(|failures| failures.is_empty()) {
+                failures.extend(group_failures.into_iter().flatten());
+            }
+        }
+
+        ProfileReport {
+            profile: self.name.clone(),
+            device: physical_device.to_string(),
+            failures,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ProfileFailure {
+    ApiVersion {
+        required: u32,
+        supported: u32,
+    },
+    Extension {
+        capability: String,
+        extension: String,
+    },
+    Feature {
+        capability: String,
+        feature: DeviceFeature,
+    },
+    Limit {
+        capability: String,
+        limit: String,
+        required: serde_json::Value,
+        supported: Option<LimitValue>,
+    },
+}
+
+impl Display for ProfileFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileFailure::ApiVersion {
+                required,
+                supported,
+            } => write!(
+                f,
+                "Vulkan {} required, device uses {}",
+                format_version(*required),
+                format_version(*supported)
+            ),
+            ProfileFailure::Extension {
+                capability,
+                extension,
+            } => write!(f, "[{}] missing extension {}", capability, extension),
+            ProfileFailure::Feature {
+                capability,
+                feature,
+            } => write!(f, "[{}] missing feature {}", capability, feature.vulkan_name()),
+            ProfileFailure::Limit {
+                capability,
+                limit,
+                required,
+                supported: Some(supported),
+            } => write!(
+                f,
+                "[{}] limit {} is {}, {} required",
+                capability, limit, supported, required
+            ),
+            ProfileFailure::Limit {
+                capability,
+                limit,
+                required,
+                supported: None,
+            } => write!(f, "[{}] limit {} is unknown, {} required", capability, limit, required),
+        }
+    }
+}
+
+/// The result of checking a physical device against a [`VulkanProfile`].
+#[derive(Clone, Debug)]
+pub struct ProfileReport {
+    pub profile: String,
+    pub device: String,
+    pub failures: Vec<ProfileFailure>,
+}
+
+impl ProfileReport {
+    pub fn is_supported(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_supported() {
+            return write!(f, "{} supports {}", self.device, self.profile);
+        }
+
+        write!(
+            f,
+            "{} does not support {} ({} failures)",
+            self.device,
+            self.profile,
+            self.failures.len()
+        )?;
+
+        for failure in &self.failures {
+            write!(f, "\n    {}", failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_empty(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Object(map) => map.is_empty(),
+        serde_json::Value::Array(values) => values.is_empty(),
+        serde_json::Value::Null => true,
+        _ => false,
+    }
+}
+
+fn parse_api_version(version: &str) -> Option<u32> {
+    let mut parts = version.split('.').map(|part| part.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next()?.ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some(vk::make_api_version(0, major, minor, patch))
+}
+
+/// Extension requests hold `&'static CStr`, so names read from profile files are leaked once and reused afterwards.
+fn intern(name: &str) -> anyhow::Result<&'static CStr> {
+    static INTERNED: Mutex<Vec<&'static CStr>> = Mutex::new(Vec::new());
+
+    let mut interned = INTERNED.lock().map_err(|_| anyhow!("Extension name table poisoned"))?;
+    if let Some(existing) = interned.iter().find(|existing| existing.to_bytes() == name.as_bytes()) {
+        return Ok(existing);
+    }
+
+    let leaked: &'static CStr = Box::leak(CString::new(name)?.into_boxed_c_str());
+    interned.push(leaked);
+    Ok(leaked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::khr;
+
+    const PROFILES: &str = r#"{
+        "$schema": "https://schema.khronos.org/vulkan/profiles-0.8.2-276.json#",
+        "capabilities": {
+            "baseline": {
+                "extensions": {
+                    "VK_KHR_swapchain": 70,
+                    "VK_KHR_synchronization2": 1
+                },
+                "features": {
+                    "VkPhysicalDeviceFeatures": {
+                        "wideLines": false
+                    },
+                    "VkPhysicalDeviceVulkan13Features": {
+                        "notARealFeature": true
+                    }
+                },
+                "properties": {
+                    "VkPhysicalDeviceProperties": {
+                        "limits": {
+                            "maxImageDimension2D": 4096,
+                            "notARealLimit": 1
+                        },
+                        "sparseProperties": {
+                            "residencyStandard2DBlockShape": true
+                        }
+                    },
+                    "VkPhysicalDeviceVulkan11Properties": {
+                        "subgroupSize": 4
+                    }
+                },
+                "formats": {
+                    "VK_FORMAT_R8G8B8A8_UNORM": {}
+                },
+                "queueFamiliesProperties": []
+            },
+            "anisotropy": {
+                "features": {
+                    "VkPhysicalDeviceFeatures": {
+                        "samplerAnisotropy": true
+                    }
+                }
+            },
+            "large_points": {
+                "properties": {
+                    "VkPhysicalDeviceProperties": {
+                        "limits": {
+                            "pointSizeRange": [1.0, 64.0]
+                        }
+                    }
+                }
+            }
+        },
+        "profiles": {
+            "VP_TEST_baseline": {
+                "version": 1,
+                "api-version": "1.3.204",
+                "label": "Test baseline",
+                "description": "Swapchain and synchronization2 on Vulkan 1.3",
+                "capabilities": ["baseline"]
+            },
+            "VP_TEST_extended": {
+                "api-version": "1.3",
+                "capabilities": ["baseline", ["anisotropy", "large_points"]]
+            }
+        }
+    }"#;
+
+    fn profiles() -> VulkanProfiles {
+        VulkanProfiles::from_json(PROFILES).unwrap()
+    }
+
+    /// Vulkan 1.3 with the swapchain extension, large enough images and large points.
+    fn capable_device() -> PhysicalDeviceInfo {
+        let properties = vk::PhysicalDeviceProperties::default()
+            .device_name(c"Capable GPU")
+            .unwrap()
+            .api_version(vk::make_api_version(0, 1, 3, 280))
+            .limits(vk::PhysicalDeviceLimits {
+                max_image_dimension2_d: 8192,
+                point_size_range: [1.0, 64.0],
+                ..Default::default()
+            });
+        PhysicalDeviceInfo::for_tests(0, properties, 0, &[khr::swapchain::NAME])
+    }
+
+    /// Vulkan 1.2 without any extension, small images and small points.
+    fn limited_device() -> PhysicalDeviceInfo {
+        let properties = vk::PhysicalDeviceProperties::default()
+            .device_name(c"Limited GPU")
+            .unwrap()
+            .api_version(vk::make_api_version(0, 1, 2, 198))
+            .limits(vk::PhysicalDeviceLimits {
+                max_image_dimension2_d: 2048,
+                point_size_range: [1.0, 8.0],
+                ..Default::default()
+            });
+        PhysicalDeviceInfo::for_tests(1, properties, 0, &[])
+    }
+
+    #[test]
+    fn profiles_are_parsed() {
+        let profiles = profiles();
+        assert_eq!(profiles.profiles().len(), 2);
+        assert!(profiles.get("VP_TEST_missing").is_none());
+
+        let profile = profiles.get("VP_TEST_baseline").unwrap();
+        assert_eq!(profile.label.as_deref(), Some("Test baseline"));
+        assert_eq!(
+            profile.description.as_deref(),
+            Some("Swapchain and synchronization2 on Vulkan 1.3")
+        );
+        assert_eq!(profile.api_version, vk::make_api_version(0, 1, 3, 204));
+        assert_eq!(profile.capabilities.len(), 1);
+
+        let baseline = &profile.capabilities[0][0];
+        assert_eq!(baseline.name, "baseline");
+        assert_eq!(
+            baseline.extensions,
+            vec![khr::swapchain::NAME, khr::synchronization2::NAME]
+        );
+        assert!(baseline.features.is_empty());
+        assert_eq!(
+            baseline.limits,
+            vec![("maxImageDimension2D".to_owned(), serde_json::json!(4096))]
+        );
+
+        let extended = profiles.get("VP_TEST_extended").unwrap();
+        assert_eq!(extended.api_version, vk::API_VERSION_1_3);
+        assert_eq!(extended.label, None);
+        let groups = extended
+            .capabilities
+            .iter()
+            .map(|group| group.iter().map(|capabilities| capabilities.name.as_str()).collect())
+            .collect::<Vec<Vec<&str>>>();
+        assert_eq!(groups, vec![vec!["baseline"], vec!["anisotropy", "large_points"]]);
+        assert_eq!(extended.capabilities[1][0].features, vec![DeviceFeature::SamplerAnisotropy]);
+    }
+
+    #[test]
+    fn unknown_sections_are_unchecked() {
+        let profiles = profiles();
+        assert_eq!(
+            profiles.get("VP_TEST_extended").unwrap().unchecked(),
+            vec![
+                "VkPhysicalDeviceVulkan13Features::notARealFeature (baseline)",
+                "VkPhysicalDeviceProperties::limits::notARealLimit (baseline)",
+                "VkPhysicalDeviceProperties::sparseProperties (baseline)",
+                "VkPhysicalDeviceVulkan11Properties::subgroupSize (baseline)",
+                "formats (baseline)",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_sections_default_to_empty() {
+        let profiles = VulkanProfiles::from_json(
+            r#"{ "capabilities": { "empty": {} }, "profiles": { "VP_TEST_empty": { "api-version": "1.2" } } }"#,
+        )
+        .unwrap();
+        let profile = profiles.get("VP_TEST_empty").unwrap();
+        assert_eq!(profile.api_version, vk::API_VERSION_1_2);
+        assert!(profile.capabilities.is_empty());
+        assert!(profile.unchecked().is_empty());
+
+        assert!(VulkanProfiles::from_json("{}").unwrap().profiles().is_empty());
+    }
+
+    #[test]
+    fn invalid_profiles_are_rejected() {
+        let error = VulkanProfiles::from_json(
+            r#"{ "profiles": { "VP_TEST_broken": { "api-version": "1.3", "capabilities": ["missing"] } } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Profile VP_TEST_broken references unknown capabilities missing"
+        );
+
+        let error = VulkanProfiles::from_json(r#"{ "profiles": { "VP_TEST_broken": { "api-version": "1" } } }"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Profile VP_TEST_broken has an invalid api-version \"1\""
+        );
+
+        assert!(VulkanProfiles::from_json(r#"{ "profiles": { "VP_TEST_broken": {} } }"#).is_err());
+        assert!(VulkanProfiles::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn api_versions_parse_with_and_without_patch() {
+        assert_eq!(parse_api_version("1.3"), Some(vk::API_VERSION_1_3));
+        assert_eq!(parse_api_version("1.3.204"), Some(vk::make_api_version(0, 1, 3, 204)));
+        assert_eq!(parse_api_version("1"), None);
+        assert_eq!(parse_api_version("1.x"), None);
+        assert_eq!(parse_api_version("1.3.x"), None);
+    }
+
+    #[test]
+    fn capable_devices_support_the_profiles() {
+        let profiles = profiles();
+        let device = capable_device();
+
+        let report = profiles.get("VP_TEST_baseline").unwrap().check(&device);
+        assert!(report.is_supported());
+        assert_eq!(report.to_string(), format!("{} supports VP_TEST_baseline", device));
+
+        // either capabilities block satisfies the second group, large points are chosen over anisotropy
+        let extended = profiles.get("VP_TEST_extended").unwrap();
+        assert!(extended.check(&device).is_supported());
+        let selected = extended
+            .select_capabilities(&device)
+            .iter()
+            .map(|capabilities| capabilities.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(selected, vec!["baseline", "large_points"]);
+        assert!(extended.feature_requests(&device).is_empty());
+        let extensions = extended
+            .extension_requests(&device)
+            .iter()
+            .map(|request| (request.name, request.required))
+            .collect::<Vec<(&CStr, bool)>>();
+        assert_eq!(
+            extensions,
+            vec![(khr::swapchain::NAME, true), (khr::synchronization2::NAME, true)]
+        );
+    }
+
+    #[test]
+    fn version_mismatches_are_reported() {
+        let profiles = profiles();
+        let device = limited_device();
+
+        let report = profiles.get("VP_TEST_baseline").unwrap().check(&device);
+        assert!(!report.is_supported());
+        assert!(matches!(
+            report.failures[0],
+            ProfileFailure::ApiVersion {
+                required,
+                supported,
+            } if required == vk::make_api_version(0, 1, 3, 204) && supported == vk::API_VERSION_1_2
+        ));
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "{} does not support VP_TEST_baseline (4 failures)\n    \
+                 Vulkan 1.3.204 required, device uses 1.2.0\n    \
+                 [baseline] missing extension VK_KHR_swapchain\n    \
+                 [baseline] missing extension VK_KHR_synchronization2\n    \
+                 [baseline] limit maxImageDimension2D is 2048, 4096 required",
+                device
+            )
+        );
+    }
+
+    #[test]
+    fn groups_report_every_failing_alternative() {
+        let report = profiles().get("VP_TEST_extended").unwrap().check(&limited_device());
+        let failures = report
+            .failures
+            .iter()
+            .skip(4)
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            failures,
+            vec![
+                "[anisotropy] missing feature samplerAnisotropy",
+                "[large_points] limit pointSizeRange is [1.0, 8.0], [1.0,64.0] required",
+            ]
+        );
+    }
+
+    #[test]
+    fn failures_describe_unknown_limits() {
+        let failure = ProfileFailure::Limit {
+            capability: "baseline".to_owned(),
+            limit: "maxImageDimension2D".to_owned(),
+            required: serde_json::json!(4096),
+            supported: None,
+        };
+        assert_eq!(
+            failure.to_string(),
+            "[baseline] limit maxImageDimension2D is unknown, 4096 required"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Prints a [`DeviceReport`](neuron_engine::render::context::report::DeviceReport) for every Vulkan device, for attaching to bug reports.
+//! Devices the engine can't use get a [`DeviceFailure`](neuron_engine::render::context::report::DeviceFailure) instead.
+//!
+//! Usage: `neuron-info [--json]`
+
+use neuron_engine::EngineCallbackHandler;
+use neuron_engine::app::engine_profile::{EngineProfile, EngineRequirements};
+use neuron_engine::app::extension_features::FeatureRegistry;
+use neuron_engine::render::context::VulkanContext;
+use neuron_engine::render::context::debug::ValidationSettings;
+use neuron_engine::render::context::instance::Instance;
+use neuron_engine::render::context::physical_device::{PHYSICAL_DEVICE_OVERRIDE_ENV, PhysicalDeviceSelector};
+use neuron_engine::render::context::report::{DeviceFailure, DeviceReport};
+use serde::Serialize;
+
+/// Configures a headless context with the minimal engine profile, so that every device the engine could run on gets a
+/// report.
+struct NeuronInfo {
+    device: Option<usize>,
+}
+
+impl EngineCallbackHandler for NeuronInfo {
+    fn name(&self) -> &str {
+        "neuron-info"
+    }
+
+    fn validation_settings(&self) -> ValidationSettings {
+        ValidationSettings::disabled()
+    }
+
+    fn engine_requirements(&self) -> EngineRequirements {
+        EngineRequirements::new(EngineProfile::Minimal)
+    }
+
+    fn physical_device_override(&self) -> Option<PhysicalDeviceSelector> {
+        self.device.map(PhysicalDeviceSelector::Index)
+    }
+}
+
+/// One entry of the output, in device enumeration order.
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum DeviceEntry {
+    Usable(DeviceReport),
+    Unusable(DeviceFailure),
+}
+
+fn main() -> anyhow::Result<()> {
+    let json = std::env::args().skip(1).any(|arg| arg == "--json");
+
+    // Every device is selected in turn through `physical_device_override`, which the environment variable would win
+    // over.
+    // SAFETY: no other thread is running yet.
+    unsafe { std::env::remove_var(PHYSICAL_DEVICE_OVERRIDE_ENV) };
+
+    let physical_devices = {
+        let instance = Instance::new(None, &mut NeuronInfo { device: None })?;
+        instance.query_physical_devices(&FeatureRegistry::default())?
+    };
+
+    let mut entries = Vec::new();
+    for (index, info) in physical_devices.iter().enumerate() {
+        match VulkanContext::new_headless(&mut NeuronInfo {
+            device: Some(index),
+        }) {
+            Ok(context) => entries.push(DeviceEntry::Usable(context.report())),
+            Err(e) => entries.push(DeviceEntry::Unusable(DeviceFailure::new(
+                index,
+                info.as_ref().ok(),
+                &e,
+            ))),
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for entry in &entries {
+            match entry {
+                DeviceEntry::Usable(report) => println!("{}", report),
+                DeviceEntry::Unusable(failure) => println!("{}", failure),
+            }
+        }
+    }
+
+    Ok(())
+}
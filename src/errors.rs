@@ -1,3 +1,6 @@
+use crate::app::feature_request::DeviceFeature;
+use crate::render::context::device::LazyQueue;
+use crate::render::context::queues::{QueueCapabilities, QueueLabel};
 use ash::vk;
 use thiserror::Error;
 pub use winit::error::OsError;
@@ -24,12 +27,173 @@ pub enum CreateWindowError {
     VulkanError(#[from] vk::Result),
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Clone, PartialEq)]
 pub enum QueueRequestValidationError {
-    #[error("Not enough queues in queue family {family:?} (requested {req:?}, available {avail:?})")]
+    #[error("Queue request #{request} ({label:?}) targets queue family {family:?}, but the device only has {family_count:?} queue families")]
+    UnknownFamily {
+        request: usize,
+        label: Option<QueueLabel>,
+        family: u32,
+        family_count: u32,
+    },
+
+    #[error("No queue family matches queue request #{request} ({label:?}), it needs {capabilities:?}")]
+    NoMatchingFamily {
+        request: usize,
+        label: Option<QueueLabel>,
+        capabilities: QueueCapabilities,
+    },
+
+    #[error("Queue request #{request} ({label:?}) has priority {priority:?}, queue priorities must be between 0.0 and 1.0")]
+    InvalidPriority {
+        request: usize,
+        label: Option<QueueLabel>,
+        priority: f32,
+    },
+
+    #[error("Not enough queues in queue family {family:?} for queue request #{request} ({label:?}): {req:?} exclusive queues requested, {avail:?} available")]
     NotEnoughQueuesInFamily {
+        request: usize,
+        label: Option<QueueLabel>,
         family: u32,
         req: u32,
         avail: u32,
-    }
-}
\ No newline at end of file
+    },
+
+    #[error("No queues left in queue family {family:?} for queue request #{request} ({label:?}): exclusive requests use all {avail:?} queues")]
+    NoQueuesForFlexibleRequest {
+        request: usize,
+        label: Option<QueueLabel>,
+        family: u32,
+        avail: u32,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum SubmitError {
+    #[error("No device queue matches {0:?}")]
+    QueueNotFound(LazyQueue),
+
+    #[error(transparent)]
+    VulkanError(#[from] vk::Result),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum RenderGraphError {
+    #[error("Pass {pass:?} uses resource #{resource:?}, which is not declared in the graph")]
+    UnknownResource { pass: String, resource: usize },
+
+    #[error("Pass {pass:?} uses {resource:?} in both {first:?} and {second:?}")]
+    ConflictingLayouts {
+        pass: String,
+        resource: String,
+        first: vk::ImageLayout,
+        second: vk::ImageLayout,
+    },
+
+    #[error("Pass {pass:?} reads transient resource {resource:?} before any pass writes it")]
+    UninitializedRead { pass: String, resource: String },
+
+    #[error("Pass {pass:?} runs on {label:?}, graph passes run on the Graphics, Compute or Transfer queues")]
+    UnsupportedQueue { pass: String, label: QueueLabel },
+
+    #[error("Pass {pass:?} runs on {label:?}, but no queue has that label")]
+    MissingQueue { pass: String, label: QueueLabel },
+}
+
+#[derive(Debug, Error)]
+pub enum GraphExecuteError {
+    #[error("Resource {0:?} is used by the graph but has no physical resource bound")]
+    UnboundResource(String),
+
+    #[error("No batch of the graph runs on the {0:?} queue")]
+    NoBatchOnQueue(QueueLabel),
+
+    #[error("The graph was compiled from a different render graph")]
+    GraphMismatch,
+
+    #[error("Pass {pass:?} failed to record: {source}")]
+    Pass {
+        pass: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    #[error(transparent)]
+    SubmitError(#[from] SubmitError),
+
+    #[error(transparent)]
+    VulkanError(#[from] vk::Result),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum FeatureError {
+    #[error("Device feature {0:?} is not enabled")]
+    NotEnabled(DeviceFeature),
+}
+
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error(transparent)]
+    AllocationError(#[from] gpu_allocator::AllocationError),
+
+    #[error("The memory of the resource is not host visible")]
+    NotMapped,
+
+    #[error("Range of {size} bytes at offset {offset} is out of bounds of a {capacity} bytes resource")]
+    OutOfBounds { offset: u64, size: u64, capacity: u64 },
+
+    #[error("The buffer was created without {0:?} usage")]
+    MissingUsage(vk::BufferUsageFlags),
+
+    #[error(transparent)]
+    FeatureError(#[from] FeatureError),
+
+    #[error(transparent)]
+    VulkanError(#[from] vk::Result),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum FrameSetError {
+    #[error("A frame set needs between 1 and {max} values, got {0}", max = crate::render::frame_set::MAX_FRAMES_IN_FLIGHT)]
+    InvalidFrameCount(usize),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum ImageStateError {
+    #[error("Image {0:?} is not registered with the image state tracker")]
+    UntrackedImage(vk::Image),
+}
+
+#[derive(Debug, Error)]
+pub enum HandoffError {
+    #[error("No device queue matches {0:?}")]
+    QueueNotFound(LazyQueue),
+}
+
+#[derive(Debug, Error)]
+pub enum UploadError {
+    #[error("No queue has the {0:?} label")]
+    MissingQueue(QueueLabel),
+
+    #[error("Mip level {mip_level} is out of range, the image has {mip_levels} mip levels")]
+    InvalidMipLevel { mip_level: u32, mip_levels: u32 },
+
+    #[error("The texel layout of {0:?} is unknown, uploads can't be validated")]
+    UnsupportedFormat(vk::Format),
+
+    #[error("The upload covers {expected} bytes of the image, got {actual}")]
+    InvalidDataSize { expected: u64, actual: u64 },
+
+    #[error("Mip level {mip_level} of the image is already uploaded in this batch")]
+    DuplicateImageUpload { mip_level: u32 },
+
+    #[error(transparent)]
+    MemoryError(#[from] MemoryError),
+
+    #[error(transparent)]
+    SubmitError(#[from] SubmitError),
+
+    #[error(transparent)]
+    VulkanError(#[from] vk::Result),
+}
@@ -8,11 +8,18 @@ use crate::errors::CreateWindowError;
 use crate::render::context::device::Device;
 use crate::render::context::instance::Instance;
 use crate::render::context::VulkanContext;
+use crate::app::engine_profile::EngineRequirements;
+use crate::app::extension_features::FeatureRegistry;
+use crate::app::vulkan_profile::VulkanProfile;
+use crate::render::context::debug::ValidationSettings;
+use crate::render::context::version::ApiVersionRequest;
+use crate::render::context::physical_device::{PhysicalDeviceInfo, PhysicalDeviceSelector};
 use app::feature_request::{
-    DeviceFeatureRequest, ExtensionRequest, FeatureStructs, QueueRequest,
+    DeviceFeatureRequest, ExtensionRequest, FeatureStructs, LayerRequest, QueueRequest,
 };
 use ash::vk;
-use render::window::WindowData;
+use render::frame_set::FramesInFlight;
+use render::window::{SwapchainPreferences, WindowData};
 use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
 use std::sync;
@@ -27,8 +34,7 @@ pub mod utils;
 
 
 
-pub const ENGINE_NAME: &'static CStr =
-    unsafe { CStr::from_bytes_with_nul_unchecked(b"neuron-engine\0") };
+pub const ENGINE_NAME: &CStr = c"neuron-engine";
 pub const ENGINE_VERSION: u32 = vk::make_api_version(0, 0, 1, 0); // TODO: use env! for version
 
 
@@ -36,6 +42,7 @@ pub const ENGINE_VERSION: u32 = vk::make_api_version(0, 0, 1, 0); // TODO: use e
 pub struct Engine {
     windows: HashMap<WindowId, Arc<RefCell<WindowData>>>,
     vulkan_context: Arc<VulkanContext>,
+    frames_in_flight: FramesInFlight,
 }
 
 #[allow(unused_variables)]
@@ -47,6 +54,11 @@ pub trait EngineCallbackHandler {
         (1, 0, 0)
     }
 
+    /// The Vulkan versions the application needs and would like to use. Defaults to Vulkan 1.3.
+    fn api_version(&self) -> ApiVersionRequest {
+        ApiVersionRequest::default()
+    }
+
     fn on_request_device_extensions(&mut self, requested_extensions: &mut Vec<ExtensionRequest>) {}
     fn on_request_instance_extensions(&mut self, requested_extensions: &mut Vec<ExtensionRequest>) {
     }
@@ -54,28 +66,57 @@ pub trait EngineCallbackHandler {
     fn on_resolve_device_extensions(&mut self, extensions: &HashSet<&'static CStr>) {}
     fn on_resolve_instance_extensions(&mut self, extensions: &HashSet<&'static CStr>) {}
 
+    fn on_request_instance_layers(&mut self, requested_layers: &mut Vec<LayerRequest>) {}
+    fn on_resolve_instance_layers(&mut self, layers: &HashSet<&'static CStr>) {}
+
+    /// The frames in flight of new windows, each window can change its own through
+    /// [`WindowData::set_frames_in_flight`]. Defaults to 2.
+    fn frames_in_flight(&self) -> FramesInFlight {
+        FramesInFlight::default()
+    }
+
+    /// Controls the validation layer and debug messenger. Defaults to enabled in debug builds and disabled in release builds.
+    fn validation_settings(&self) -> ValidationSettings {
+        ValidationSettings::default()
+    }
+
+    /// The engine profile and the profile requirements to demote to optional. Defaults to [`EngineProfile::Desktop`](app::engine_profile::EngineProfile::Desktop).
+    fn engine_requirements(&self) -> EngineRequirements {
+        EngineRequirements::default()
+    }
+
+    /// Vulkan Profiles the selected device must support (see [`VulkanProfiles`](app::vulkan_profile::VulkanProfiles)).
+    /// Devices failing a profile are rejected, and the extensions and features of the profiles are requested on device creation.
+    fn vulkan_profiles(&self) -> Vec<VulkanProfile> {
+        vec![]
+    }
+
+    /// Registers additional extension feature structs (see [`extension_features!`]). The built-in structs are already registered.
+    fn on_register_feature_structs(&mut self, registry: &mut FeatureRegistry) {}
+
     fn on_request_features(&mut self, requested_features: &mut Vec<DeviceFeatureRequest>) {}
     fn on_resolve_features<'a>(&mut self, features: &FeatureStructs<'a>) {}
 
     ///
     /// This function is not self-mutable since there is no cross-system guarantees on this (unlike the extensions functions which will always be called once at the same point in execution on all systems).
-    /// TODO: wrap physical devices with an easier to work with wrapper.
-    fn validate_physical_device(
-        &self,
-        physical_device: vk::PhysicalDevice,
-        instance: &ash::Instance,
-    ) -> bool {
-        true
+    /// Return an error to reject the device, the error is logged as the reason for the rejection.
+    fn validate_physical_device(&self, physical_device: &PhysicalDeviceInfo) -> anyhow::Result<()> {
+        Ok(())
     }
 
-    fn on_instance(&mut self, instance: &Instance) {}
-    fn on_physical_device(
-        &mut self,
-        physical_device: vk::PhysicalDevice,
-        instance: &Instance,
-    ) {
+    /// Ranks the physical devices that passed validation, the highest score is selected.
+    fn score_physical_device(&self, physical_device: &PhysicalDeviceInfo) -> i64 {
+        physical_device.default_score()
     }
 
+    /// Forces the selection of a physical device. The `NEURON_PHYSICAL_DEVICE` environment variable takes precedence over this.
+    fn physical_device_override(&self) -> Option<PhysicalDeviceSelector> {
+        None
+    }
+
+    fn on_instance(&mut self, instance: &Instance) {}
+    fn on_physical_device(&mut self, physical_device: &PhysicalDeviceInfo, instance: &Instance) {}
+
     fn on_device(&mut self, device: &Device) {}
 
     /// Use this function to request queues to be obtained (use only if absolutely required, most of the time the system allocates the right queues. The main use case is if you need video queues).
@@ -97,7 +138,7 @@ pub trait EngineCallbackHandler {
     /// struct MyHandler;
     ///
     /// impl EngineCallbackHandler for MyHandler {
-    ///     fn on_queue_selection(&mut self, existing_requests: &[QueueRequest], families: Vec<QueueFamilyProperties>) -> Vec<QueueRequest> {
+    ///     fn on_queue_selection(&mut self, existing_requests: &[QueueRequest], families: Vec<QueueFamilyProperties>) -> anyhow::Result<Vec<QueueRequest>> {
     ///         let mut video_encode_queue: Option<usize> = None;
     ///         let mut video_decode_queue: Option<usize> = None;
     ///
@@ -113,19 +154,16 @@ pub trait EngineCallbackHandler {
     ///
     ///         let mut requests: Vec<QueueRequest> = Vec::new();
     ///         if let Some(i) = video_encode_queue {
-    ///             requests.push(QueueRequest { family: i as u32, count: 1, label: Some(QueueLabel::VideoEncode), allow_merge: true });
+    ///             requests.push(QueueRequest::flexible_labeled(i as u32, 1, QueueLabel::VideoEncode));
     ///         }
     ///
     ///         if let Some(i) = video_decode_queue {
-    ///             requests.push(QueueRequest { family: i as u32, count: 1, label: Some(QueueLabel::VideoDecode), allow_merge: true });
+    ///             requests.push(QueueRequest::flexible_labeled(i as u32, 1, QueueLabel::VideoDecode));
     ///         }
     ///
-    ///         requests
+    ///         Ok(requests)
     ///     }
     /// }
-
-    ///
-    ///
     /// ```
     fn on_queue_selection(
         &mut self,
@@ -146,6 +184,7 @@ impl Engine {
         let mut engine = Self {
             windows: HashMap::new(),
             vulkan_context: Arc::new(VulkanContext::new(event_loop, app)?),
+            frames_in_flight: app.frames_in_flight(),
         };
 
         app.on_engine_ready(&mut engine)?;
@@ -158,9 +197,22 @@ impl Engine {
         event_loop: &ActiveEventLoop,
         attributes: WindowAttributes,
     ) -> Result<sync::Weak<RefCell<WindowData>>, CreateWindowError> {
+        self.create_window_with_preferences(event_loop, attributes, SwapchainPreferences::default())
+    }
+
+    /// Creates a window whose swapchain follows `preferences`, they can be changed later through
+    /// [`WindowData::set_swapchain_preferences`].
+    pub fn create_window_with_preferences(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+        preferences: SwapchainPreferences,
+    ) -> Result<sync::Weak<RefCell<WindowData>>, CreateWindowError> {
+        #[allow(clippy::arc_with_non_send_sync)]
         let window = Arc::new(RefCell::new(WindowData::new(
             self,
             event_loop.create_window(attributes)?,
+            preferences,
         )?));
         let window_id = window.borrow().window().id();
         let weakref = Arc::downgrade(&window);
@@ -180,6 +232,11 @@ impl Engine {
         &self.windows
     }
 
+    /// The frames in flight new windows are created with.
+    pub fn frames_in_flight(&self) -> FramesInFlight {
+        self.frames_in_flight
+    }
+
     pub fn vulkan(&self) -> Arc<VulkanContext> {
         self.vulkan_context.clone()
     }
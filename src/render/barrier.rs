@@ -0,0 +1,389 @@
+use ash::vk;
+
+/// The pipeline stages and memory accesses on one side of a dependency.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Access {
+    pub stages: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+}
+
+impl Access {
+    pub const NONE: Self = Self::new(vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE);
+    pub const TRANSFER_READ: Self = Self::new(
+        vk::PipelineStageFlags2::TRANSFER,
+        vk::AccessFlags2::TRANSFER_READ,
+    );
+    pub const TRANSFER_WRITE: Self = Self::new(
+        vk::PipelineStageFlags2::TRANSFER,
+        vk::AccessFlags2::TRANSFER_WRITE,
+    );
+    pub const COLOR_ATTACHMENT_WRITE: Self = Self::new(
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+    );
+    pub const DEPTH_STENCIL_ATTACHMENT_WRITE: Self = Self::new(
+        vk::PipelineStageFlags2::from_raw(
+            vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS.as_raw()
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS.as_raw(),
+        ),
+        vk::AccessFlags2::from_raw(
+            vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.as_raw()
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw(),
+        ),
+    );
+    pub const FRAGMENT_SHADER_READ: Self = Self::new(
+        vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        vk::AccessFlags2::SHADER_SAMPLED_READ,
+    );
+    pub const COMPUTE_SHADER_READ: Self = Self::new(
+        vk::PipelineStageFlags2::COMPUTE_SHADER,
+        vk::AccessFlags2::SHADER_STORAGE_READ,
+    );
+    pub const COMPUTE_SHADER_WRITE: Self = Self::new(
+        vk::PipelineStageFlags2::COMPUTE_SHADER,
+        vk::AccessFlags2::SHADER_STORAGE_WRITE,
+    );
+    pub const ALL_COMMANDS: Self = Self::new(
+        vk::PipelineStageFlags2::ALL_COMMANDS,
+        vk::AccessFlags2::from_raw(
+            vk::AccessFlags2::MEMORY_READ.as_raw() | vk::AccessFlags2::MEMORY_WRITE.as_raw(),
+        ),
+    );
+
+    pub const fn new(stages: vk::PipelineStageFlags2, access: vk::AccessFlags2) -> Self {
+        Self { stages, access }
+    }
+
+    /// The source scope of a barrier following a semaphore wait for work using `self`: the stages of that use, or
+    /// every stage when it has none, without any access. Waiting on those stages orders the barrier after the
+    /// signalling queue's work.
+    pub fn wait_scope(&self) -> Self {
+        let stages = if self.stages.is_empty() {
+            vk::PipelineStageFlags2::ALL_COMMANDS
+        } else {
+            self.stages
+        };
+        Self::new(stages, vk::AccessFlags2::NONE)
+    }
+}
+
+/// The whole image (every mip level and array layer) for `aspect_mask`.
+pub fn full_subresource_range(aspect_mask: vk::ImageAspectFlags) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange::default()
+        .aspect_mask(aspect_mask)
+        .base_mip_level(0)
+        .level_count(vk::REMAINING_MIP_LEVELS)
+        .base_array_layer(0)
+        .layer_count(vk::REMAINING_ARRAY_LAYERS)
+}
+
+/// A global memory barrier.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryBarrier {
+    pub src: Access,
+    pub dst: Access,
+}
+
+impl MemoryBarrier {
+    pub fn new(src: Access, dst: Access) -> Self {
+        Self { src, dst }
+    }
+
+    pub fn to_vk(&self) -> vk::MemoryBarrier2<'static> {
+        vk::MemoryBarrier2::default()
+            .src_stage_mask(self.src.stages)
+            .src_access_mask(self.src.access)
+            .dst_stage_mask(self.dst.stages)
+            .dst_access_mask(self.dst.access)
+    }
+}
+
+/// A buffer memory barrier, covering the whole buffer unless [`BufferBarrier::range`] is used.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferBarrier {
+    pub buffer: vk::Buffer,
+    pub src: Access,
+    pub dst: Access,
+    pub src_queue_family: u32,
+    pub dst_queue_family: u32,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+}
+
+impl BufferBarrier {
+    pub fn new(buffer: vk::Buffer, src: Access, dst: Access) -> Self {
+        Self {
+            buffer,
+            src,
+            dst,
+            src_queue_family: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family: vk::QUEUE_FAMILY_IGNORED,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+        }
+    }
+
+    pub fn range(mut self, offset: vk::DeviceSize, size: vk::DeviceSize) -> Self {
+        self.offset = offset;
+        self.size = size;
+        self
+    }
+
+    /// Makes the barrier a queue family ownership transfer from `src` to `dst`.
+    pub fn queue_family_transfer(mut self, src: u32, dst: u32) -> Self {
+        self.src_queue_family = src;
+        self.dst_queue_family = dst;
+        self
+    }
+
+    pub fn to_vk(&self) -> vk::BufferMemoryBarrier2<'static> {
+        vk::BufferMemoryBarrier2::default()
+            .buffer(self.buffer)
+            .src_stage_mask(self.src.stages)
+            .src_access_mask(self.src.access)
+            .dst_stage_mask(self.dst.stages)
+            .dst_access_mask(self.dst.access)
+            .src_queue_family_index(self.src_queue_family)
+            .dst_queue_family_index(self.dst_queue_family)
+            .offset(self.offset)
+            .size(self.size)
+    }
+}
+
+/// An image memory barrier and layout transition, covering every color subresource unless
+/// [`ImageBarrier::subresource_range`] is used.
+#[derive(Copy, Clone, Debug)]
+pub struct ImageBarrier {
+    pub image: vk::Image,
+    pub src: Access,
+    pub dst: Access,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+    pub src_queue_family: u32,
+    pub dst_queue_family: u32,
+    pub subresource_range: vk::ImageSubresourceRange,
+}
+
+impl ImageBarrier {
+    pub fn new(image: vk::Image, old_layout: vk::ImageLayout, new_layout: vk::ImageLayout) -> Self {
+        Self {
+            image,
+            src: Access::NONE,
+            dst: Access::NONE,
+            old_layout,
+            new_layout,
+            src_queue_family: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family: vk::QUEUE_FAMILY_IGNORED,
+            subresource_range: full_subresource_range(vk::ImageAspectFlags::COLOR),
+        }
+    }
+
+    pub fn src(mut self, src: Access) -> Self {
+        self.src = src;
+        self
+    }
+
+    pub fn dst(mut self, dst: Access) -> Self {
+        self.dst = dst;
+        self
+    }
+
+    /// Makes the barrier a queue family ownership transfer from `src` to `dst`.
+    pub fn queue_family_transfer(mut self, src: u32, dst: u32) -> Self {
+        self.src_queue_family = src;
+        self.dst_queue_family = dst;
+        self
+    }
+
+    pub fn subresource_range(mut self, subresource_range: vk::ImageSubresourceRange) -> Self {
+        self.subresource_range = subresource_range;
+        self
+    }
+
+    pub fn to_vk(&self) -> vk::ImageMemoryBarrier2<'static> {
+        vk::ImageMemoryBarrier2::default()
+            .image(self.image)
+            .src_stage_mask(self.src.stages)
+            .src_access_mask(self.src.access)
+            .dst_stage_mask(self.dst.stages)
+            .dst_access_mask(self.dst.access)
+            .old_layout(self.old_layout)
+            .new_layout(self.new_layout)
+            .src_queue_family_index(self.src_queue_family)
+            .dst_queue_family_index(self.dst_queue_family)
+            .subresource_range(self.subresource_range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn wait_scopes_keep_the_stages_without_access() {
+        assert_eq!(
+            Access::FRAGMENT_SHADER_READ.wait_scope(),
+            Access::new(
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::NONE
+            )
+        );
+        assert_eq!(
+            Access::NONE.wait_scope(),
+            Access::new(
+                vk::PipelineStageFlags2::ALL_COMMANDS,
+                vk::AccessFlags2::NONE
+            )
+        );
+    }
+
+    #[test]
+    fn memory_barriers_copy_both_sides() {
+        let barrier =
+            MemoryBarrier::new(Access::TRANSFER_WRITE, Access::FRAGMENT_SHADER_READ).to_vk();
+
+        assert_eq!(barrier.s_type, vk::StructureType::MEMORY_BARRIER_2);
+        assert_eq!(barrier.src_stage_mask, vk::PipelineStageFlags2::TRANSFER);
+        assert_eq!(barrier.src_access_mask, vk::AccessFlags2::TRANSFER_WRITE);
+        assert_eq!(
+            barrier.dst_stage_mask,
+            vk::PipelineStageFlags2::FRAGMENT_SHADER
+        );
+        assert_eq!(
+            barrier.dst_access_mask,
+            vk::AccessFlags2::SHADER_SAMPLED_READ
+        );
+    }
+
+    #[test]
+    fn buffer_barriers_cover_the_whole_buffer_by_default() {
+        let buffer = vk::Buffer::from_raw(1);
+        let barrier = BufferBarrier::new(
+            buffer,
+            Access::COMPUTE_SHADER_WRITE,
+            Access::COMPUTE_SHADER_READ,
+        )
+        .to_vk();
+
+        assert_eq!(barrier.buffer, buffer);
+        assert_eq!(
+            barrier.src_stage_mask,
+            vk::PipelineStageFlags2::COMPUTE_SHADER
+        );
+        assert_eq!(
+            barrier.src_access_mask,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE
+        );
+        assert_eq!(
+            barrier.dst_stage_mask,
+            vk::PipelineStageFlags2::COMPUTE_SHADER
+        );
+        assert_eq!(
+            barrier.dst_access_mask,
+            vk::AccessFlags2::SHADER_STORAGE_READ
+        );
+        assert_eq!(barrier.src_queue_family_index, vk::QUEUE_FAMILY_IGNORED);
+        assert_eq!(barrier.dst_queue_family_index, vk::QUEUE_FAMILY_IGNORED);
+        assert_eq!(barrier.offset, 0);
+        assert_eq!(barrier.size, vk::WHOLE_SIZE);
+    }
+
+    #[test]
+    fn buffer_barriers_carry_ranges_and_transfers() {
+        let barrier = BufferBarrier::new(
+            vk::Buffer::from_raw(1),
+            Access::TRANSFER_WRITE,
+            Access::NONE,
+        )
+        .range(256, 1024)
+        .queue_family_transfer(2, 0)
+        .to_vk();
+
+        assert_eq!(barrier.offset, 256);
+        assert_eq!(barrier.size, 1024);
+        assert_eq!(barrier.src_queue_family_index, 2);
+        assert_eq!(barrier.dst_queue_family_index, 0);
+        assert_eq!(barrier.dst_stage_mask, vk::PipelineStageFlags2::NONE);
+    }
+
+    #[test]
+    fn image_barriers_transition_every_color_subresource_by_default() {
+        let image = vk::Image::from_raw(1);
+        let barrier = ImageBarrier::new(
+            image,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        )
+        .dst(Access::COLOR_ATTACHMENT_WRITE)
+        .to_vk();
+
+        assert_eq!(barrier.image, image);
+        assert_eq!(barrier.old_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(
+            barrier.new_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(barrier.src_stage_mask, vk::PipelineStageFlags2::NONE);
+        assert_eq!(barrier.src_access_mask, vk::AccessFlags2::NONE);
+        assert_eq!(
+            barrier.dst_stage_mask,
+            vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+        );
+        assert_eq!(
+            barrier.dst_access_mask,
+            vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+        );
+        assert_eq!(barrier.src_queue_family_index, vk::QUEUE_FAMILY_IGNORED);
+        assert_eq!(barrier.dst_queue_family_index, vk::QUEUE_FAMILY_IGNORED);
+
+        let range = barrier.subresource_range;
+        assert_eq!(range.aspect_mask, vk::ImageAspectFlags::COLOR);
+        assert_eq!(
+            (range.base_mip_level, range.level_count),
+            (0, vk::REMAINING_MIP_LEVELS)
+        );
+        assert_eq!(
+            (range.base_array_layer, range.layer_count),
+            (0, vk::REMAINING_ARRAY_LAYERS)
+        );
+    }
+
+    #[test]
+    fn image_barriers_carry_ranges_and_transfers() {
+        let barrier = ImageBarrier::new(
+            vk::Image::from_raw(1),
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+        .src(Access::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        .dst(Access::FRAGMENT_SHADER_READ)
+        .queue_family_transfer(0, 1)
+        .subresource_range(
+            full_subresource_range(vk::ImageAspectFlags::DEPTH)
+                .base_mip_level(2)
+                .level_count(1)
+                .base_array_layer(3)
+                .layer_count(2),
+        )
+        .to_vk();
+
+        assert_eq!(
+            barrier.src_stage_mask,
+            vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS
+        );
+        assert_eq!(
+            barrier.src_access_mask,
+            vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+        );
+        assert_eq!(barrier.src_queue_family_index, 0);
+        assert_eq!(barrier.dst_queue_family_index, 1);
+
+        let range = barrier.subresource_range;
+        assert_eq!(range.aspect_mask, vk::ImageAspectFlags::DEPTH);
+        assert_eq!((range.base_mip_level, range.level_count), (2, 1));
+        assert_eq!((range.base_array_layer, range.layer_count), (3, 2));
+    }
+}
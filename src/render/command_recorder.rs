@@ -1,7 +1,14 @@
-use crate::render::context::device::LazyQueue;
+use crate::app::feature_request::DeviceFeature;
+use crate::errors::{ImageStateError, SubmitError};
+use crate::render::barrier::{BufferBarrier, ImageBarrier, MemoryBarrier};
 use crate::render::context::VulkanContext;
+use crate::render::context::device::LazyQueue;
+use crate::render::context::submission::SubmissionHandle;
+use crate::render::handoff::OwnershipHandoff;
+use crate::render::image_state::{ImageStateTracker, ImageUsage};
 use ash::prelude::VkResult;
 use ash::vk;
+use log::error;
 use neuron_procmacro::sealed;
 use std::sync::Arc;
 
@@ -9,18 +16,65 @@ pub struct CommandRecorder<'a> {
     command_buffer: &'a vk::CommandBuffer,
     vulkan: Arc<VulkanContext>,
     auto_submit: Option<AutoSubmitInfo>,
+    ended: bool,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum GenericSemaphore {
     Binary(vk::Semaphore, vk::PipelineStageFlags2),
     Timeline(vk::Semaphore, u64, vk::PipelineStageFlags2),
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SemaphoreInfo {
     pub semaphore: GenericSemaphore,
     pub device_index: Option<u32>,
 }
 
+impl SemaphoreInfo {
+    pub fn binary(semaphore: vk::Semaphore, stage_mask: vk::PipelineStageFlags2) -> Self {
+        Self {
+            semaphore: GenericSemaphore::Binary(semaphore, stage_mask),
+            device_index: None,
+        }
+    }
+
+    pub fn timeline(
+        semaphore: vk::Semaphore,
+        value: u64,
+        stage_mask: vk::PipelineStageFlags2,
+    ) -> Self {
+        Self {
+            semaphore: GenericSemaphore::Timeline(semaphore, value, stage_mask),
+            device_index: None,
+        }
+    }
+
+    /// The device of a device group executing the wait or signal.
+    pub fn device_index(mut self, device_index: u32) -> Self {
+        self.device_index = Some(device_index);
+        self
+    }
+
+    fn submit_info(&self) -> vk::SemaphoreSubmitInfo<'static> {
+        let info = match self.semaphore {
+            GenericSemaphore::Binary(semaphore, stage_mask) => vk::SemaphoreSubmitInfo::default()
+                .semaphore(semaphore)
+                .stage_mask(stage_mask),
+            GenericSemaphore::Timeline(semaphore, value, stage_mask) => {
+                vk::SemaphoreSubmitInfo::default()
+                    .semaphore(semaphore)
+                    .value(value)
+                    .stage_mask(stage_mask)
+            }
+        };
+
+        info.device_index(self.device_index.unwrap_or(0))
+    }
+}
+
+/// The semaphores, fence and additional command buffers of a submission.
+#[derive(Clone, Debug, Default)]
 pub struct CommandBufferSyncInfo {
     wait_semaphores: Vec<SemaphoreInfo>,
     signal_semaphores: Vec<SemaphoreInfo>,
@@ -28,6 +82,53 @@ pub struct CommandBufferSyncInfo {
     fence: Option<vk::Fence>,
 }
 
+impl CommandBufferSyncInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wait(mut self, semaphore: SemaphoreInfo) -> Self {
+        self.wait_semaphores.push(semaphore);
+        self
+    }
+
+    pub fn signal(mut self, semaphore: SemaphoreInfo) -> Self {
+        self.signal_semaphores.push(semaphore);
+        self
+    }
+
+    /// Waits for `submission`, possibly on another queue, before `stages` run.
+    pub fn wait_submission(
+        self,
+        submission: SubmissionHandle,
+        stages: vk::PipelineStageFlags2,
+    ) -> Self {
+        self.wait(submission.wait_info(stages))
+    }
+
+    /// A command buffer submitted before the ones passed to [`AutoSubmitInfo::submit`].
+    pub fn command_buffer(mut self, command_buffer: vk::CommandBuffer) -> Self {
+        self.command_buffers.push(command_buffer);
+        self
+    }
+
+    /// The fence signaled once the submission completes.
+    pub fn fence(mut self, fence: vk::Fence) -> Self {
+        self.fence = Some(fence);
+        self
+    }
+
+    pub fn wait_semaphores(&self) -> &[SemaphoreInfo] {
+        &self.wait_semaphores
+    }
+
+    pub fn signal_semaphores(&self) -> &[SemaphoreInfo] {
+        &self.signal_semaphores
+    }
+}
+
+/// Where and how a [`CommandRecorder`] created by [`CommandBufferExt::begin_auto_submit`] submits its command buffer.
+#[derive(Clone, Debug)]
 pub struct AutoSubmitInfo {
     queue: LazyQueue,
     sync_info: CommandBufferSyncInfo,
@@ -42,6 +143,7 @@ impl<'a> CommandRecorder<'a> {
             command_buffer,
             vulkan,
             auto_submit: None,
+            ended: false,
         }
     }
 
@@ -54,35 +156,567 @@ impl<'a> CommandRecorder<'a> {
             command_buffer,
             vulkan,
             auto_submit: Some(auto_submit),
+            ended: false,
+        }
+    }
+
+    /// Ends the command buffer and submits it if the recorder was created with [`CommandBufferExt::begin_auto_submit`],
+    /// returning the submission.
+    ///
+    /// Dropping the recorder does the same, but can only log errors.
+    pub fn finish(mut self) -> Result<Option<SubmissionHandle>, SubmitError> {
+        self.end()
+    }
+
+    fn end(&mut self) -> Result<Option<SubmissionHandle>, SubmitError> {
+        if self.ended {
+            return Ok(None);
+        }
+        self.ended = true;
+
+        unsafe {
+            self.vulkan
+                .device()
+                .end_command_buffer(*self.command_buffer)
+        }?;
+
+        match &self.auto_submit {
+            Some(auto_submit) => Ok(Some(
+                auto_submit.submit(&[*self.command_buffer], &self.vulkan)?,
+            )),
+            None => Ok(None),
         }
     }
 }
 
-extend_type!(vk::CommandBuffer) {
+/// Typed wrappers around the `vkCmd*` functions, recording into the recorder's command buffer.
+///
+/// Validity of the recorded commands (handles, layouts, render pass state) is still up to the caller.
+impl CommandRecorder<'_> {
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        *self.command_buffer
+    }
+
+    pub fn vulkan(&self) -> &Arc<VulkanContext> {
+        &self.vulkan
+    }
+
+    /// Records a `vkCmdPipelineBarrier2`, or `vkCmdPipelineBarrier2KHR` on Vulkan 1.2 devices.
+    pub fn pipeline_barrier(
+        &mut self,
+        memory_barriers: &[MemoryBarrier],
+        buffer_barriers: &[BufferBarrier],
+        image_barriers: &[ImageBarrier],
+    ) -> &mut Self {
+        let memory_barriers = memory_barriers
+            .iter()
+            .map(MemoryBarrier::to_vk)
+            .collect::<Vec<_>>();
+        let buffer_barriers = buffer_barriers
+            .iter()
+            .map(BufferBarrier::to_vk)
+            .collect::<Vec<_>>();
+        let image_barriers = image_barriers
+            .iter()
+            .map(ImageBarrier::to_vk)
+            .collect::<Vec<_>>();
+
+        let dependency_info = vk::DependencyInfo::default()
+            .memory_barriers(&memory_barriers)
+            .buffer_memory_barriers(&buffer_barriers)
+            .image_memory_barriers(&image_barriers);
+
+        let device = self.vulkan.device();
+        unsafe {
+            match device.loader().synchronization2() {
+                Some(synchronization2) => {
+                    synchronization2.cmd_pipeline_barrier2(*self.command_buffer, &dependency_info)
+                }
+                None => device.cmd_pipeline_barrier2(*self.command_buffer, &dependency_info),
+            }
+        }
+
+        self
+    }
+
+    pub fn memory_barrier(&mut self, barrier: MemoryBarrier) -> &mut Self {
+        self.pipeline_barrier(&[barrier], &[], &[])
+    }
+
+    pub fn buffer_barrier(&mut self, barrier: BufferBarrier) -> &mut Self {
+        self.pipeline_barrier(&[], &[barrier], &[])
+    }
+
+    pub fn image_barrier(&mut self, barrier: ImageBarrier) -> &mut Self {
+        self.pipeline_barrier(&[], &[], &[barrier])
+    }
+
+    /// Declares the next use of the whole `image` and records the barriers `tracker` emits for it.
+    pub fn transition_image(
+        &mut self,
+        tracker: &mut ImageStateTracker,
+        image: vk::Image,
+        usage: ImageUsage,
+        queue_family: u32,
+    ) -> Result<&mut Self, ImageStateError> {
+        let barriers = tracker.transition(image, None, usage, queue_family)?;
+        if !barriers.is_empty() {
+            self.pipeline_barrier(&[], &[], &barriers);
+        }
+        Ok(self)
+    }
+
+    /// Records the release half of `handoff`, on a command buffer of its source queue.
+    pub fn release(&mut self, handoff: &OwnershipHandoff) -> &mut Self {
+        let (buffers, images) = handoff.release_barriers();
+        if !buffers.is_empty() || !images.is_empty() {
+            self.pipeline_barrier(&[], &buffers, &images);
+        }
+        self
+    }
+
+    /// Records the acquire half of `handoff`, on a command buffer of its destination queue submitted after the
+    /// release.
+    pub fn acquire(&mut self, handoff: &OwnershipHandoff) -> &mut Self {
+        let (buffers, images) = handoff.acquire_barriers();
+        if !buffers.is_empty() || !images.is_empty() {
+            self.pipeline_barrier(&[], &buffers, &images);
+        }
+        self
+    }
+
+    pub fn copy_buffer(
+        &mut self,
+        src: vk::Buffer,
+        dst: vk::Buffer,
+        regions: &[vk::BufferCopy],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan
+                .device()
+                .cmd_copy_buffer(*self.command_buffer, src, dst, regions)
+        };
+        self
+    }
 
+    pub fn copy_image(
+        &mut self,
+        src: vk::Image,
+        src_layout: vk::ImageLayout,
+        dst: vk::Image,
+        dst_layout: vk::ImageLayout,
+        regions: &[vk::ImageCopy],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_copy_image(
+                *self.command_buffer,
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                regions,
+            )
+        };
+        self
+    }
+
+    pub fn copy_buffer_to_image(
+        &mut self,
+        src: vk::Buffer,
+        dst: vk::Image,
+        dst_layout: vk::ImageLayout,
+        regions: &[vk::BufferImageCopy],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_copy_buffer_to_image(
+                *self.command_buffer,
+                src,
+                dst,
+                dst_layout,
+                regions,
+            )
+        };
+        self
+    }
+
+    pub fn copy_image_to_buffer(
+        &mut self,
+        src: vk::Image,
+        src_layout: vk::ImageLayout,
+        dst: vk::Buffer,
+        regions: &[vk::BufferImageCopy],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_copy_image_to_buffer(
+                *self.command_buffer,
+                src,
+                src_layout,
+                dst,
+                regions,
+            )
+        };
+        self
+    }
+
+    pub fn blit_image(
+        &mut self,
+        src: vk::Image,
+        src_layout: vk::ImageLayout,
+        dst: vk::Image,
+        dst_layout: vk::ImageLayout,
+        regions: &[vk::ImageBlit],
+        filter: vk::Filter,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_blit_image(
+                *self.command_buffer,
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                regions,
+                filter,
+            )
+        };
+        self
+    }
+
+    pub fn fill_buffer(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        data: u32,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan
+                .device()
+                .cmd_fill_buffer(*self.command_buffer, buffer, offset, size, data)
+        };
+        self
+    }
+
+    pub fn clear_color_image(
+        &mut self,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        color: vk::ClearColorValue,
+        ranges: &[vk::ImageSubresourceRange],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_clear_color_image(
+                *self.command_buffer,
+                image,
+                layout,
+                &color,
+                ranges,
+            )
+        };
+        self
+    }
+
+    pub fn clear_depth_stencil_image(
+        &mut self,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        value: vk::ClearDepthStencilValue,
+        ranges: &[vk::ImageSubresourceRange],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_clear_depth_stencil_image(
+                *self.command_buffer,
+                image,
+                layout,
+                &value,
+                ranges,
+            )
+        };
+        self
+    }
+
+    /// Records a `vkCmdBeginRendering`, or `vkCmdBeginRenderingKHR` on Vulkan 1.2 devices.
+    pub fn begin_rendering(&mut self, rendering_info: &vk::RenderingInfo) -> &mut Self {
+        let device = self.vulkan.device();
+        unsafe {
+            match device.loader().dynamic_rendering() {
+                Some(dynamic_rendering) => {
+                    dynamic_rendering.cmd_begin_rendering(*self.command_buffer, rendering_info)
+                }
+                None => device.cmd_begin_rendering(*self.command_buffer, rendering_info),
+            }
+        }
+        self
+    }
+
+    pub fn end_rendering(&mut self) -> &mut Self {
+        let device = self.vulkan.device();
+        unsafe {
+            match device.loader().dynamic_rendering() {
+                Some(dynamic_rendering) => {
+                    dynamic_rendering.cmd_end_rendering(*self.command_buffer)
+                }
+                None => device.cmd_end_rendering(*self.command_buffer),
+            }
+        }
+        self
+    }
+
+    pub fn set_viewports(&mut self, first_viewport: u32, viewports: &[vk::Viewport]) -> &mut Self {
+        unsafe {
+            self.vulkan
+                .device()
+                .cmd_set_viewport(*self.command_buffer, first_viewport, viewports)
+        };
+        self
+    }
+
+    pub fn set_scissors(&mut self, first_scissor: u32, scissors: &[vk::Rect2D]) -> &mut Self {
+        unsafe {
+            self.vulkan
+                .device()
+                .cmd_set_scissor(*self.command_buffer, first_scissor, scissors)
+        };
+        self
+    }
+
+    pub fn bind_pipeline(
+        &mut self,
+        bind_point: vk::PipelineBindPoint,
+        pipeline: vk::Pipeline,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan
+                .device()
+                .cmd_bind_pipeline(*self.command_buffer, bind_point, pipeline)
+        };
+        self
+    }
+
+    pub fn bind_descriptor_sets(
+        &mut self,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+        dynamic_offsets: &[u32],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_bind_descriptor_sets(
+                *self.command_buffer,
+                bind_point,
+                layout,
+                first_set,
+                descriptor_sets,
+                dynamic_offsets,
+            )
+        };
+        self
+    }
+
+    pub fn push_constants(
+        &mut self,
+        layout: vk::PipelineLayout,
+        stages: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &[u8],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_push_constants(
+                *self.command_buffer,
+                layout,
+                stages,
+                offset,
+                constants,
+            )
+        };
+        self
+    }
+
+    pub fn bind_vertex_buffers(
+        &mut self,
+        first_binding: u32,
+        buffers: &[vk::Buffer],
+        offsets: &[vk::DeviceSize],
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_bind_vertex_buffers(
+                *self.command_buffer,
+                first_binding,
+                buffers,
+                offsets,
+            )
+        };
+        self
+    }
+
+    pub fn bind_index_buffer(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        index_type: vk::IndexType,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_bind_index_buffer(
+                *self.command_buffer,
+                buffer,
+                offset,
+                index_type,
+            )
+        };
+        self
+    }
+
+    pub fn draw(
+        &mut self,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_draw(
+                *self.command_buffer,
+                vertex_count,
+                instance_count,
+                first_vertex,
+                first_instance,
+            )
+        };
+        self
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_draw_indexed(
+                *self.command_buffer,
+                index_count,
+                instance_count,
+                first_index,
+                vertex_offset,
+                first_instance,
+            )
+        };
+        self
+    }
+
+    pub fn draw_indirect(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    ) -> &mut Self {
+        for (offset, draw_count) in self.indirect_draws(offset, draw_count, stride) {
+            unsafe {
+                self.vulkan.device().cmd_draw_indirect(
+                    *self.command_buffer,
+                    buffer,
+                    offset,
+                    draw_count,
+                    stride,
+                )
+            };
+        }
+        self
+    }
+
+    pub fn draw_indexed_indirect(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    ) -> &mut Self {
+        for (offset, draw_count) in self.indirect_draws(offset, draw_count, stride) {
+            unsafe {
+                self.vulkan.device().cmd_draw_indexed_indirect(
+                    *self.command_buffer,
+                    buffer,
+                    offset,
+                    draw_count,
+                    stride,
+                )
+            };
+        }
+        self
+    }
+
+    /// The `(offset, draw_count)` of each indirect draw call for `draw_count` draws. Without `multiDrawIndirect` a
+    /// draw call can only read one command, so the draws are split into one call per command.
+    fn indirect_draws(
+        &self,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    ) -> Vec<(vk::DeviceSize, u32)> {
+        if draw_count <= 1 || self.vulkan.has_feature(DeviceFeature::MultiDrawIndirect) {
+            vec![(offset, draw_count)]
+        } else {
+            (0..draw_count)
+                .map(|draw| (offset + draw as vk::DeviceSize * stride as vk::DeviceSize, 1))
+                .collect()
+        }
+    }
+
+    pub fn dispatch(
+        &mut self,
+        group_count_x: u32,
+        group_count_y: u32,
+        group_count_z: u32,
+    ) -> &mut Self {
+        unsafe {
+            self.vulkan.device().cmd_dispatch(
+                *self.command_buffer,
+                group_count_x,
+                group_count_y,
+                group_count_z,
+            )
+        };
+        self
+    }
+
+    pub fn dispatch_indirect(&mut self, buffer: vk::Buffer, offset: vk::DeviceSize) -> &mut Self {
+        unsafe {
+            self.vulkan
+                .device()
+                .cmd_dispatch_indirect(*self.command_buffer, buffer, offset)
+        };
+        self
+    }
 }
 
 #[sealed(vk::CommandBuffer)]
+#[allow(private_bounds)]
 pub trait CommandBufferExt {
-    fn begin(&self, vulkan: Arc<VulkanContext>, one_time_submit: bool)
-    -> VkResult<CommandRecorder>;
+    fn begin(
+        &self,
+        vulkan: Arc<VulkanContext>,
+        one_time_submit: bool,
+    ) -> VkResult<CommandRecorder<'_>>;
     fn begin_auto_submit(
         &self,
         vulkan: Arc<VulkanContext>,
         one_time_submit: bool,
         auto_submit_info: AutoSubmitInfo,
-    ) -> VkResult<CommandRecorder>;
+    ) -> VkResult<CommandRecorder<'_>>;
 }
 
-impl<'a> CommandBufferExt for vk::CommandBuffer {
+impl CommandBufferExt for vk::CommandBuffer {
     fn begin(
         &self,
         vulkan: Arc<VulkanContext>,
         one_time_submit: bool,
-    ) -> VkResult<CommandRecorder> {
+    ) -> VkResult<CommandRecorder<'_>> {
         unsafe {
             vulkan.device().begin_command_buffer(
-                self.clone(),
+                *self,
                 &vk::CommandBufferBeginInfo::default().flags(if one_time_submit {
                     vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
                 } else {
@@ -99,10 +733,10 @@ impl<'a> CommandBufferExt for vk::CommandBuffer {
         vulkan: Arc<VulkanContext>,
         one_time_submit: bool,
         auto_submit_info: AutoSubmitInfo,
-    ) -> VkResult<CommandRecorder> {
+    ) -> VkResult<CommandRecorder<'_>> {
         unsafe {
             vulkan.device().begin_command_buffer(
-                self.clone(),
+                *self,
                 &vk::CommandBufferBeginInfo::default().flags(if one_time_submit {
                     vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
                 } else {
@@ -121,18 +755,71 @@ impl<'a> CommandBufferExt for vk::CommandBuffer {
 
 impl<'a> Drop for CommandRecorder<'a> {
     fn drop(&mut self) {
-        unsafe {
-            if let Ok(_) = self.vulkan.device().end_command_buffer(self.command_buffer.clone()) {
-                if let Some(auto_submit) = &self.auto_submit {
-                    _ = auto_submit.submit(&[self.command_buffer.clone()], self.vulkan.clone());
-                }
-            }
+        if let Err(e) = self.end() {
+            error!(
+                "[command_recorder] Failed to end or submit command buffer {:?}: {}",
+                self.command_buffer, e
+            );
         }
     }
 }
 
 impl AutoSubmitInfo {
-    pub(crate) fn submit(&self, command_buffers: &[vk::CommandBuffer], vulkan: Arc<VulkanContext>) -> VkResult<()> {
+    pub fn new(queue: LazyQueue, sync_info: CommandBufferSyncInfo) -> Self {
+        Self { queue, sync_info }
+    }
 
+    pub fn queue(&self) -> LazyQueue {
+        self.queue
     }
-}
\ No newline at end of file
+
+    pub fn sync_info(&self) -> &CommandBufferSyncInfo {
+        &self.sync_info
+    }
+
+    /// Submits the sync info's command buffers followed by `command_buffers` through `vkQueueSubmit2`.
+    pub fn submit(
+        &self,
+        command_buffers: &[vk::CommandBuffer],
+        vulkan: &VulkanContext,
+    ) -> Result<SubmissionHandle, SubmitError> {
+        let queue = vulkan
+            .device()
+            .get_lazy_queue(self.queue)
+            .ok_or(SubmitError::QueueNotFound(self.queue))?;
+
+        let wait_semaphores = self
+            .sync_info
+            .wait_semaphores
+            .iter()
+            .map(SemaphoreInfo::submit_info)
+            .collect::<Vec<vk::SemaphoreSubmitInfo>>();
+
+        let signal_semaphores = self
+            .sync_info
+            .signal_semaphores
+            .iter()
+            .map(SemaphoreInfo::submit_info)
+            .collect::<Vec<vk::SemaphoreSubmitInfo>>();
+
+        let command_buffer_infos = self
+            .sync_info
+            .command_buffers
+            .iter()
+            .chain(command_buffers)
+            .map(|command_buffer| {
+                vk::CommandBufferSubmitInfo::default().command_buffer(*command_buffer)
+            })
+            .collect::<Vec<vk::CommandBufferSubmitInfo>>();
+
+        let submit_info = vk::SubmitInfo2::default()
+            .wait_semaphore_infos(&wait_semaphores)
+            .command_buffer_infos(&command_buffer_infos)
+            .signal_semaphore_infos(&signal_semaphores);
+
+        Ok(queue.submit2(
+            &[submit_info],
+            self.sync_info.fence.unwrap_or(vk::Fence::null()),
+        )?)
+    }
+}
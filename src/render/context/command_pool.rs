@@ -1,14 +1,18 @@
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
 use ash::prelude::VkResult;
 use ash::vk;
 use crate::render::context::VulkanContext;
-use crate::render::frame_set::{FrameSet, MAX_FRAMES_IN_FLIGHT};
+use crate::render::context::deletion_queue::LastUse;
+use crate::render::context::submission::SubmissionHandle;
+use crate::render::frame_set::{FrameSet, FramesInFlight};
 
 pub struct CommandPool {
     vulkan_context: Arc<VulkanContext>,
     pool: vk::CommandPool,
     queue_family: u32,
+    last_use: LastUse,
 }
 
 impl CommandPool {
@@ -23,12 +27,16 @@ impl CommandPool {
             vulkan_context,
             pool,
             queue_family,
+            last_use: LastUse::default(),
         })
     }
 
-    pub fn allocate_command_buffer_set(&self) -> VkResult<FrameSet<vk::CommandBuffer>> {
-        let command_buffers = self.allocate_command_buffers(MAX_FRAMES_IN_FLIGHT)?;
-        Ok(FrameSet::from(command_buffers))
+    /// Allocates a command buffer per frame in flight, use the count of the window the buffers record frames for.
+    pub fn allocate_command_buffer_set(&self, frames_in_flight: FramesInFlight) -> VkResult<FrameSet<vk::CommandBuffer>> {
+        let mut command_buffers = self.allocate_command_buffers(frames_in_flight.get())?.into_iter();
+        Ok(FrameSet::create_factory(frames_in_flight, |_| {
+            command_buffers.next().expect("a command buffer was allocated for every frame")
+        }))
     }
 
     pub fn allocate_command_buffers(&self, count: usize) -> VkResult<Vec<vk::CommandBuffer>> {
@@ -40,9 +48,22 @@ impl CommandPool {
         }
     }
 
+    /// Frees command buffers allocated from this pool, they must not be pending execution anymore.
+    pub fn free_command_buffers(&self, command_buffers: &[vk::CommandBuffer]) {
+        unsafe {
+            self.vulkan_context.device().free_command_buffers(self.pool, command_buffers)
+        }
+    }
+
     pub fn queue_family(&self) -> u32 {
         self.queue_family
     }
+
+    /// Records that command buffers of this pool are pending until `semaphore` reaches `value`. Once dropped, the pool
+    /// is destroyed after every recorded value is reached instead of after the current frame.
+    pub fn mark_used(&self, semaphore: vk::Semaphore, value: u64) {
+        self.last_use.mark(semaphore, value);
+    }
 }
 
 impl Deref for CommandPool {
@@ -55,9 +76,78 @@ impl Deref for CommandPool {
 
 impl Drop for CommandPool {
     fn drop(&mut self) {
-        unsafe {
-            let _ = self.vulkan_context.device().wait_queues(self.queue_family);
-            self.vulkan_context.device().destroy_command_pool(self.pool, None);
+        let pool = self.pool;
+        let deletion_queue = self.vulkan_context.deletion_queue();
+        deletion_queue.defer(self.last_use.take_key(deletion_queue), move |vulkan| unsafe {
+            vulkan.device().destroy_command_pool(pool, None);
+        });
+    }
+}
+
+struct PendingCommandBuffer {
+    family: u32,
+    command_buffer: vk::CommandBuffer,
+    submission: SubmissionHandle,
+}
+
+/// Command buffers for one-off submissions, allocated from a [`CommandPool`] per queue family and freed once the
+/// submission they were recorded for completed, so submissions can be kept in flight without waiting.
+pub struct CommandBufferRecycler {
+    vulkan_context: Arc<VulkanContext>,
+    pools: HashMap<u32, CommandPool>,
+    pending: Vec<PendingCommandBuffer>,
+}
+
+impl CommandBufferRecycler {
+    pub fn new(vulkan_context: Arc<VulkanContext>) -> Self {
+        Self {
+            vulkan_context,
+            pools: HashMap::new(),
+            pending: vec![],
+        }
+    }
+
+    /// Allocates a command buffer for a queue of `family`, hand it to [`CommandBufferRecycler::track`] once submitted.
+    pub fn allocate(&mut self, family: u32) -> VkResult<vk::CommandBuffer> {
+        if !self.pools.contains_key(&family) {
+            let pool = CommandPool::new(self.vulkan_context.clone(), family, false)?;
+            self.pools.insert(family, pool);
+        }
+        Ok(self.pools[&family].allocate_command_buffers(1)?[0])
+    }
+
+    /// Frees `command_buffer` once `submission` completes, its pool is kept alive until then even if the recycler is
+    /// dropped.
+    pub fn track(&mut self, family: u32, command_buffer: vk::CommandBuffer, submission: SubmissionHandle) {
+        if let Some(pool) = self.pools.get(&family) {
+            pool.mark_used(submission.semaphore(), submission.value());
         }
+        self.pending.push(PendingCommandBuffer {
+            family,
+            command_buffer,
+            submission,
+        });
     }
-}
\ No newline at end of file
+
+    /// Frees the command buffers of completed submissions.
+    pub fn reclaim(&mut self) -> VkResult<()> {
+        let mut completed = vec![];
+        let mut pending = Vec::with_capacity(self.pending.len());
+        for command_buffer in self.pending.drain(..) {
+            if command_buffer.submission.is_complete(&self.vulkan_context)? {
+                completed.push(command_buffer);
+            } else {
+                pending.push(command_buffer);
+            }
+        }
+        self.pending = pending;
+
+        for pending in completed {
+            if let Some(pool) = self.pools.get(&pending.family) {
+                pool.free_command_buffers(&[pending.command_buffer]);
+            }
+        }
+
+        Ok(())
+    }
+}
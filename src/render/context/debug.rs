@@ -0,0 +1,211 @@
+use ash::prelude::VkResult;
+use ash::{ext, vk};
+use log::Level;
+use std::borrow::Cow;
+use std::ffi::{CStr, c_void};
+use std::slice;
+
+pub const KHRONOS_VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+const LAYER_SETTING_VALIDATE_SYNC: &CStr = c"validate_sync";
+const LAYER_SETTING_GPU_ASSISTED: &CStr = c"gpuav_enable";
+
+/// Controls the validation layer and the debug-utils messenger set up by the instance.
+///
+/// The default enables validation and the messenger in debug builds and disables both in release builds.
+#[derive(Clone, Debug)]
+pub struct ValidationSettings {
+    /// Request `VK_LAYER_KHRONOS_validation` (as an optional layer).
+    pub validation_layer: bool,
+    /// Create a `VK_EXT_debug_utils` messenger that routes messages into `log`.
+    pub debug_messenger: bool,
+    /// Enable synchronization validation through `VK_EXT_layer_settings`.
+    pub synchronization_validation: bool,
+    /// Enable GPU-assisted validation through `VK_EXT_layer_settings`.
+    pub gpu_assisted_validation: bool,
+    pub message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        Self {
+            validation_layer: cfg!(debug_assertions),
+            debug_messenger: cfg!(debug_assertions),
+            synchronization_validation: false,
+            gpu_assisted_validation: false,
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_types: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        }
+    }
+}
+
+impl ValidationSettings {
+    /// Validation and the messenger disabled regardless of build profile.
+    pub fn disabled() -> Self {
+        Self {
+            validation_layer: false,
+            debug_messenger: false,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn wants_layer_settings(&self) -> bool {
+        self.validation_layer && (self.synchronization_validation || self.gpu_assisted_validation)
+    }
+
+    pub(crate) fn messenger_create_info(&self) -> vk::DebugUtilsMessengerCreateInfoEXT<'static> {
+        vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(self.message_severity)
+            .message_type(self.message_types)
+            .pfn_user_callback(Some(debug_callback))
+    }
+
+    pub(crate) fn layer_settings(&self) -> Vec<vk::LayerSettingEXT<'static>> {
+        const TRUE: &vk::Bool32 = &vk::TRUE;
+
+        let mut settings = Vec::new();
+
+        if self.synchronization_validation {
+            settings.push(bool_layer_setting(LAYER_SETTING_VALIDATE_SYNC, TRUE));
+        }
+
+        if self.gpu_assisted_validation {
+            settings.push(bool_layer_setting(LAYER_SETTING_GPU_ASSISTED, TRUE));
+        }
+
+        settings
+    }
+}
+
+fn bool_layer_setting(
+    name: &'static CStr,
+    value: &'static vk::Bool32,
+) -> vk::LayerSettingEXT<'static> {
+    let mut setting = vk::LayerSettingEXT::default()
+        .layer_name(KHRONOS_VALIDATION_LAYER)
+        .setting_name(name)
+        .ty(vk::LayerSettingTypeEXT::BOOL32)
+        .values(bytemuck::bytes_of(value));
+
+    // `values` stores the byte length, but the layer expects the number of `ty` elements.
+    setting.value_count = 1;
+    setting
+}
+
+pub struct DebugMessenger {
+    loader: ext::debug_utils::Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+}
+
+impl DebugMessenger {
+    pub(crate) fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        settings: &ValidationSettings,
+    ) -> VkResult<Self> {
+        let loader = ext::debug_utils::Instance::new(entry, instance);
+        let messenger =
+            unsafe { loader.create_debug_utils_messenger(&settings.messenger_create_info(), None) }?;
+
+        Ok(Self { loader, messenger })
+    }
+
+    pub fn loader(&self) -> &ext::debug_utils::Instance {
+        &self.loader
+    }
+
+    pub fn messenger(&self) -> vk::DebugUtilsMessengerEXT {
+        self.messenger
+    }
+}
+
+impl Drop for DebugMessenger {
+    fn drop(&mut self) {
+        unsafe {
+            self.loader
+                .destroy_debug_utils_messenger(self.messenger, None);
+        }
+    }
+}
+
+fn severity_level(severity: vk::DebugUtilsMessageSeverityFlagsEXT) -> Level {
+    if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        Level::Error
+    } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        Level::Warn
+    } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        Level::Info
+    } else {
+        Level::Trace
+    }
+}
+
+fn cstr_or_empty<'a>(ptr: Option<&'a CStr>) -> Cow<'a, str> {
+    ptr.map(CStr::to_string_lossy).unwrap_or(Cow::Borrowed(""))
+}
+
+unsafe fn raw_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() || count == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(ptr, count as usize) }
+    }
+}
+
+unsafe extern "system" fn debug_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    _p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    let level = severity_level(message_severity);
+    if !log::log_enabled!(target: "neuron_engine::vulkan", level) {
+        return vk::FALSE;
+    }
+
+    let Some(data) = (unsafe { p_callback_data.as_ref() }) else {
+        return vk::FALSE;
+    };
+
+    unsafe {
+        let id_name = cstr_or_empty(data.message_id_name_as_c_str());
+        let message = cstr_or_empty(data.message_as_c_str());
+
+        let mut text = format!(
+            "[vulkan/debug] [{:?}] {} ({:#x}): {}",
+            message_types, id_name, data.message_id_number, message
+        );
+
+        for object in raw_slice(data.p_objects, data.object_count) {
+            let name = cstr_or_empty(object.object_name_as_c_str());
+            text.push_str(&format!(
+                "\n    object: {:?} {:#x} {:?}",
+                object.object_type, object.object_handle, name
+            ));
+        }
+
+        for label in raw_slice(data.p_queue_labels, data.queue_label_count) {
+            text.push_str(&format!(
+                "\n    queue label: {:?}",
+                cstr_or_empty(label.label_name_as_c_str())
+            ));
+        }
+
+        for label in raw_slice(data.p_cmd_buf_labels, data.cmd_buf_label_count) {
+            text.push_str(&format!(
+                "\n    command buffer label: {:?}",
+                cstr_or_empty(label.label_name_as_c_str())
+            ));
+        }
+
+        log::log!(target: "neuron_engine::vulkan", level, "{}", text);
+    }
+
+    vk::FALSE
+}
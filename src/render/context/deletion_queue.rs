@@ -0,0 +1,396 @@
+use crate::render::context::VulkanContext;
+use crate::render::context::queue::Queue;
+use ash::prelude::VkResult;
+use ash::vk;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Destroys a deferred resource, called with the context once the GPU is done with it.
+pub type Deleter = Box<dyn FnOnce(&VulkanContext) + Send>;
+
+/// When a deferred deletion can run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeletionKey {
+    /// Once every frame source completed the frame, see [`DeletionQueue::current_frame`].
+    Frame(u64),
+    /// Once every timeline semaphore reached its value.
+    Timeline(Vec<(vk::Semaphore, u64)>),
+    /// Only once the device is idle.
+    Idle,
+}
+
+/// A registered producer of frames, such as a window, see [`DeletionQueue::register_frame_source`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FrameSource(u64);
+
+struct DeletionState {
+    frame: u64,
+    next_source: u64,
+    /// The last frame each source knows to be complete on the GPU.
+    sources: HashMap<FrameSource, u64>,
+    pending: Vec<(DeletionKey, Deleter)>,
+}
+
+/// Defers the destruction of resources until the GPU is done with them, owned by the [`VulkanContext`].
+///
+/// Deletions are keyed either on a frame or on timeline semaphore values. Frames are counted by the event loop, which
+/// advances the frame once per iteration, and completed by the frame sources: every window reports the frames its
+/// in-flight fences covered. Without any frame source (headless contexts), [`DeletionQueue::frame_key`] keys
+/// deletions on the last value submitted to every queue instead.
+pub struct DeletionQueue {
+    state: Mutex<DeletionState>,
+    queues: Vec<Arc<Queue>>,
+}
+
+impl DeletionQueue {
+    pub(crate) fn new(queues: Vec<Arc<Queue>>) -> Self {
+        Self {
+            state: Mutex::new(DeletionState {
+                frame: 1,
+                next_source: 0,
+                sources: HashMap::new(),
+                pending: vec![],
+            }),
+            queues,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, DeletionState> {
+        // deleters run outside of the lock, the state is always consistent
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The frame being recorded, starting at 1.
+    pub fn current_frame(&self) -> u64 {
+        self.state().frame
+    }
+
+    /// Ends the current frame and returns the new one.
+    pub fn advance_frame(&self) -> u64 {
+        let mut state = self.state();
+        state.frame += 1;
+        state.frame
+    }
+
+    /// Registers a frame source, frame keyed deletions wait for it until it is unregistered. The frames before the
+    /// current one count as complete for the new source.
+    pub fn register_frame_source(&self) -> FrameSource {
+        let mut state = self.state();
+        let source = FrameSource(state.next_source);
+        state.next_source += 1;
+        let completed = state.frame - 1;
+        state.sources.insert(source, completed);
+        source
+    }
+
+    pub fn unregister_frame_source(&self, source: FrameSource) {
+        self.state().sources.remove(&source);
+    }
+
+    /// Reports that the GPU finished the work `source` submitted up to and including `frame`.
+    pub fn complete_frame(&self, source: FrameSource, frame: u64) {
+        if let Some(completed) = self.state().sources.get_mut(&source) {
+            *completed = (*completed).max(frame);
+        }
+    }
+
+    /// The last frame every source completed, `None` without frame sources.
+    pub fn completed_frame(&self) -> Option<u64> {
+        self.state().sources.values().copied().min()
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    pub fn defer<F: FnOnce(&VulkanContext) + Send + 'static>(&self, key: DeletionKey, deleter: F) {
+        self.state().pending.push((key, Box::new(deleter)));
+    }
+
+    /// The key of a deletion that waits for everything submitted so far: the current frame, or the last value
+    /// submitted to every queue when there is no frame source to complete frames.
+    pub fn frame_key(&self) -> DeletionKey {
+        self.frame_key_with(|| {
+            self.queues
+                .iter()
+                .map(|queue| {
+                    let submission = queue.last_submission();
+                    (submission.semaphore(), submission.value())
+                })
+                .collect()
+        })
+    }
+
+    fn frame_key_with<L: FnOnce() -> Vec<(vk::Semaphore, u64)>>(
+        &self,
+        last_submitted: L,
+    ) -> DeletionKey {
+        {
+            let state = self.state();
+            if !state.sources.is_empty() {
+                return DeletionKey::Frame(state.frame);
+            }
+        }
+
+        // queues that never had a submission have nothing to wait for
+        DeletionKey::Timeline(
+            last_submitted()
+                .into_iter()
+                .filter(|(_, value)| *value > 0)
+                .collect(),
+        )
+    }
+
+    /// Defers `deleter` until the work submitted so far is complete, see [`DeletionQueue::frame_key`].
+    pub fn defer_frame<F: FnOnce(&VulkanContext) + Send + 'static>(&self, deleter: F) {
+        self.defer(self.frame_key(), deleter);
+    }
+
+    /// Defers `deleter` until every semaphore reached its value.
+    pub fn defer_timeline<F: FnOnce(&VulkanContext) + Send + 'static>(
+        &self,
+        points: &[(vk::Semaphore, u64)],
+        deleter: F,
+    ) {
+        self.defer(DeletionKey::Timeline(points.to_vec()), deleter);
+    }
+
+    /// Removes the deletions whose key is reached, `counter` queries timeline semaphore values.
+    fn take_ready<C: FnMut(vk::Semaphore) -> VkResult<u64>>(
+        &self,
+        mut counter: C,
+    ) -> VkResult<Vec<Deleter>> {
+        let mut state = self.state();
+        let completed_frame = state.sources.values().copied().min();
+        let mut reached: HashMap<vk::Semaphore, u64> = HashMap::new();
+
+        let mut ready = vec![];
+        let mut remaining = Vec::with_capacity(state.pending.len());
+        for (key, deleter) in state.pending.drain(..) {
+            let is_ready = match &key {
+                DeletionKey::Frame(frame) => completed_frame.is_some_and(|c| c >= *frame),
+                DeletionKey::Timeline(points) => {
+                    let mut is_ready = true;
+                    for (semaphore, value) in points {
+                        let current = match reached.get(semaphore) {
+                            Some(current) => *current,
+                            None => {
+                                let current = counter(*semaphore)?;
+                                reached.insert(*semaphore, current);
+                                current
+                            }
+                        };
+
+                        if current < *value {
+                            is_ready = false;
+                            break;
+                        }
+                    }
+                    is_ready
+                }
+                DeletionKey::Idle => false,
+            };
+
+            if is_ready {
+                ready.push(deleter);
+            } else {
+                remaining.push((key, deleter));
+            }
+        }
+        state.pending = remaining;
+
+        Ok(ready)
+    }
+
+    /// Runs the deletions whose key is reached.
+    pub(crate) fn collect(&self, vulkan: &VulkanContext) -> VkResult<()> {
+        let ready = self.take_ready(|semaphore| unsafe {
+            vulkan.device().get_semaphore_counter_value(semaphore)
+        })?;
+
+        for deleter in ready {
+            deleter(vulkan);
+        }
+
+        Ok(())
+    }
+
+    /// Runs every deletion.
+    ///
+    /// # Safety
+    ///
+    /// The GPU must be done with every deferred resource, for example right after
+    /// [`Device::wait_idle`](crate::render::context::device::Device::wait_idle).
+    pub(crate) unsafe fn collect_idle(&self, vulkan: &VulkanContext) {
+        // deleters may defer other deletions, keep going until nothing is left
+        loop {
+            let pending = std::mem::take(&mut self.state().pending);
+            if pending.is_empty() {
+                break;
+            }
+
+            for (_, deleter) in pending {
+                deleter(vulkan);
+            }
+        }
+    }
+}
+
+impl Debug for DeletionQueue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("DeletionQueue")
+            .field("frame", &state.frame)
+            .field("sources", &state.sources)
+            .field("pending", &state.pending.len())
+            .finish()
+    }
+}
+
+/// Records the timeline semaphore values after which the GPU no longer uses a resource, to key its deletion.
+#[derive(Debug, Default)]
+pub(crate) struct LastUse(Mutex<Vec<(vk::Semaphore, u64)>>);
+
+impl LastUse {
+    pub(crate) fn mark(&self, semaphore: vk::Semaphore, value: u64) {
+        let mut last_use = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match last_use.iter_mut().find(|(s, _)| *s == semaphore) {
+            Some((_, last)) => *last = (*last).max(value),
+            None => last_use.push((semaphore, value)),
+        }
+    }
+
+    /// The recorded timeline values, or the [`DeletionQueue::frame_key`] of `queue` when none were recorded.
+    pub(crate) fn take_key(&self, queue: &DeletionQueue) -> DeletionKey {
+        let last_use = std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        if last_use.is_empty() {
+            queue.frame_key()
+        } else {
+            DeletionKey::Timeline(last_use)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    fn never(_: vk::Semaphore) -> VkResult<u64> {
+        panic!("no timeline deletion is pending")
+    }
+
+    #[test]
+    fn frame_deletions_wait_for_every_source() {
+        let queue = DeletionQueue::new(vec![]);
+        let window_a = queue.register_frame_source();
+        let window_b = queue.register_frame_source();
+
+        queue.defer_frame(|_| {});
+        assert_eq!(queue.advance_frame(), 2);
+        queue.defer_frame(|_| {});
+
+        assert!(queue.take_ready(never).unwrap().is_empty());
+
+        queue.complete_frame(window_a, 2);
+        queue.complete_frame(window_b, 1);
+        assert_eq!(queue.completed_frame(), Some(1));
+        assert_eq!(queue.take_ready(never).unwrap().len(), 1);
+        assert_eq!(queue.pending_count(), 1);
+
+        // completion never goes backwards
+        queue.complete_frame(window_a, 0);
+        queue.unregister_frame_source(window_b);
+        assert_eq!(queue.take_ready(never).unwrap().len(), 1);
+        assert_eq!(queue.pending_count(), 0);
+    }
+
+    #[test]
+    fn deletions_without_sources_wait_for_submitted_work() {
+        let queue = DeletionQueue::new(vec![]);
+        let graphics = vk::Semaphore::from_raw(1);
+        let transfer = vk::Semaphore::from_raw(2);
+
+        let key = queue.frame_key_with(|| vec![(graphics, 4), (transfer, 0)]);
+        assert_eq!(key, DeletionKey::Timeline(vec![(graphics, 4)]));
+        queue.defer(key, |_| {});
+        assert!(queue.take_ready(|_| Ok(3)).unwrap().is_empty());
+        assert_eq!(queue.take_ready(|_| Ok(4)).unwrap().len(), 1);
+
+        // nothing submitted yet, nothing to wait for
+        queue.defer_frame(|_| {});
+        assert_eq!(queue.take_ready(never).unwrap().len(), 1);
+
+        let window = queue.register_frame_source();
+        assert_eq!(
+            queue.frame_key_with(|| panic!("frame sources key on frames")),
+            DeletionKey::Frame(1)
+        );
+        queue.unregister_frame_source(window);
+    }
+
+    #[test]
+    fn late_sources_complete_earlier_frames() {
+        let queue = DeletionQueue::new(vec![]);
+        queue.defer(DeletionKey::Frame(1), |_| {});
+        queue.advance_frame();
+        queue.advance_frame();
+
+        assert!(queue.take_ready(never).unwrap().is_empty());
+        assert_eq!(queue.pending_count(), 1);
+
+        // a late source does not hold back frames that happened before it
+        let window = queue.register_frame_source();
+        assert_eq!(queue.completed_frame(), Some(2));
+        assert_eq!(queue.take_ready(never).unwrap().len(), 1);
+        queue.unregister_frame_source(window);
+    }
+
+    #[test]
+    fn timeline_deletions_wait_for_every_value() {
+        let queue = DeletionQueue::new(vec![]);
+        let graphics = vk::Semaphore::from_raw(1);
+        let compute = vk::Semaphore::from_raw(2);
+
+        queue.defer_timeline(&[(graphics, 3)], |_| {});
+        queue.defer_timeline(&[(graphics, 2), (compute, 5)], |_| {});
+        queue.defer(DeletionKey::Idle, |_| {});
+
+        let mut queries = 0;
+        let ready = queue
+            .take_ready(|semaphore| {
+                queries += 1;
+                Ok(if semaphore == graphics { 3 } else { 4 })
+            })
+            .unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(queries, 2);
+
+        let ready = queue.take_ready(|_| Ok(5)).unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(queue.pending_count(), 1);
+    }
+
+    #[test]
+    fn last_use_keys() {
+        let queue = DeletionQueue::new(vec![]);
+        let window = queue.register_frame_source();
+        let last_use = LastUse::default();
+        let a = vk::Semaphore::from_raw(1);
+        let b = vk::Semaphore::from_raw(2);
+        last_use.mark(a, 3);
+        last_use.mark(b, 1);
+        last_use.mark(a, 2);
+        last_use.mark(a, 5);
+
+        assert_eq!(
+            last_use.take_key(&queue),
+            DeletionKey::Timeline(vec![(a, 5), (b, 1)])
+        );
+        assert_eq!(last_use.take_key(&queue), DeletionKey::Frame(1));
+
+        queue.unregister_frame_source(window);
+        assert_eq!(last_use.take_key(&queue), DeletionKey::Timeline(vec![]));
+    }
+}
@@ -1,43 +1,52 @@
 use crate::EngineCallbackHandler;
+use crate::app::engine_profile::EngineRequirements;
+use crate::errors::FeatureError;
 use crate::app::feature_request::{
     DeviceFeature, DeviceFeatureRequest, ExtensionRequest, FeatureStructs, QueueRequest,
 };
-use crate::errors::QueueRequestValidationError;
 use crate::render::context::instance::Instance;
+use crate::render::context::physical_device::PhysicalDeviceInfo;
 use crate::render::context::platform;
-use crate::render::context::queues::{QueueLabel, QueueLabels, QueueRef, UnlabeledQueues};
+use crate::render::context::queue::Queue;
+use crate::render::context::queue_planner::{QueueFamilyPlan, QueuePlanner};
+use crate::render::context::queues::{
+    QueueCapabilities, QueueFamily, QueueFamilyInfo, QueueLabel, QueueLabels, QueueRef,
+    UnlabeledQueues,
+};
+use crate::render::context::version::{format_version, promoted_to_core, version_at_least};
 use anyhow::anyhow;
 use ash::prelude::VkResult;
-use ash::{khr, vk};
+use ash::{ext, khr, vk};
 use log::{debug, info, trace, warn};
 use std::collections::{HashMap, HashSet};
-use std::ffi::{CStr, CString, c_char};
-use std::iter::repeat_n;
+use std::ffi::{CStr, c_char};
 use std::ops::{Deref, DerefMut};
-use winit::event_loop::EventLoop;
-use winit::raw_window_handle::HasDisplayHandle;
+use std::sync::Arc;
+use winit::raw_window_handle::RawDisplayHandle;
+
+const REQUIRED_DEVICE_EXTENSIONS: &[ExtensionRequest] = &[];
 
-const REQUIRED_DEVICE_EXTENSIONS: &'static [ExtensionRequest] =
+const REQUIRED_PRESENTATION_DEVICE_EXTENSIONS: &[ExtensionRequest] =
     &[ExtensionRequest::required(khr::swapchain::NAME)];
 
-const REQUIRED_FEATURES: &'static [DeviceFeatureRequest] = &[
-    DeviceFeatureRequest::required(DeviceFeature::DynamicRendering),
-    DeviceFeatureRequest::required(DeviceFeature::GeometryShader),
-    DeviceFeatureRequest::required(DeviceFeature::TessellationShader),
-    DeviceFeatureRequest::required(DeviceFeature::WideLines),
-    DeviceFeatureRequest::required(DeviceFeature::LargePoints),
-    DeviceFeatureRequest::required(DeviceFeature::Synchronization2),
-    DeviceFeatureRequest::required(DeviceFeature::TimelineSemaphore),
+/// Requested when a queue request sets a global priority, either one enables it.
+const GLOBAL_PRIORITY_DEVICE_EXTENSIONS: &[ExtensionRequest] = &[
+    ExtensionRequest::optional(khr::global_priority::NAME),
+    ExtensionRequest::optional(ext::global_priority::NAME),
 ];
 
 pub struct Device {
     device: ash::Device,
-    queues: HashMap<u32, Vec<vk::Queue>>,
+    queues: HashMap<u32, Vec<Arc<Queue>>>,
     queue_labels: QueueLabels,
     unlabeled_queues: UnlabeledQueues,
     loader: DeviceLoader,
+    api_version: u32,
+    enabled_features: HashSet<DeviceFeature>,
+    enabled_extensions: HashSet<&'static CStr>,
 }
 
+/// A queue to resolve once the device exists, see [`Device::get_lazy_queue`].
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum LazyQueue {
     Labeled(QueueLabel),
@@ -47,129 +56,84 @@ pub enum LazyQueue {
     Ref(QueueRef),
 }
 
+impl From<QueueLabel> for LazyQueue {
+    fn from(label: QueueLabel) -> Self {
+        Self::Labeled(label)
+    }
+}
+
+impl From<QueueRef> for LazyQueue {
+    fn from(queue: QueueRef) -> Self {
+        Self::Ref(queue)
+    }
+}
+
 impl Device {
+    /// Creates the logical device. When `display_handle` is `None` the device is headless: no presentation queue is
+    /// labeled and the swapchain extension is not requested.
     pub fn new<A: EngineCallbackHandler>(
-        event_loop: &EventLoop<()>,
+        display_handle: Option<RawDisplayHandle>,
         instance: &Instance,
-        physical_device: vk::PhysicalDevice,
+        physical_device_info: &PhysicalDeviceInfo,
         app: &mut A,
     ) -> anyhow::Result<Device> {
-        let queue_family_properties =
-            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
-
-        let mut graphics: Option<u32> = None;
-        let mut transfer: Option<u32> = None;
-        let mut compute: Option<u32> = None;
-        let mut presentation: Option<u32> = None;
-        let mut queue_availability: HashMap<u32, u32> = HashMap::new();
-        let mut total_queue_availability: HashMap<u32, u32> = HashMap::new();
-
-        let raw_display_handle = event_loop.display_handle()?.as_raw();
-
-        queue_family_properties
+        let physical_device = physical_device_info.handle();
+        let queue_family_properties = physical_device_info.queue_families().to_vec();
+        let queue_families = queue_family_properties
             .iter()
             .enumerate()
-            .for_each(|(i, props)| {
-                queue_availability.insert(i as u32, props.queue_count);
-                total_queue_availability.insert(i as u32, props.queue_count);
-                if props.queue_flags.contains(vk::QueueFlags::GRAPHICS) && graphics.is_none() {
-                    graphics = Some(i as u32);
-                    trace!("[device/queues] Found graphics queue: {:?}", i);
-                }
-
-                if props.queue_flags.contains(vk::QueueFlags::TRANSFER)
-                    && !props
-                        .queue_flags
-                        .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
-                    && transfer.is_none()
-                {
-                    transfer = Some(i as u32);
-                    trace!("[device/queues] Found exclusive transfer queue: {:?}", i);
-                }
-
-                if props.queue_flags.contains(vk::QueueFlags::COMPUTE) && compute.is_none() {
-                    compute = Some(i as u32);
-                    trace!("[device/queues] Found compute queue: {:?}", i);
-                }
-
-                if presentation.is_none()
-                    && platform::can_present(
-                        &raw_display_handle,
-                        i as u32,
-                        instance,
-                        physical_device,
-                    )
-                {
-                    presentation = Some(i as u32);
-                    trace!("[device/queues] Found presentation queue: {:?}", i);
-                }
-            });
-
-        let Some(graphics) = graphics else {
-            return Err(anyhow!("No graphics queue family found"));
-        };
-
-        let Some(compute) = compute else {
-            return Err(anyhow!(
-                "No compute queue family found (this indicates a non-conformant vulkan implementation)"
-            ));
-        };
-
-        if transfer.is_none() {
-            debug!(
-                "[device/queues] No exclusive transfer queue found, falling back on primary graphics queue."
-            );
-        }
+            .map(|(i, properties)| QueueFamilyInfo {
+                properties: *properties,
+                video_codec_operations: physical_device_info.video_codec_operations(i as u32),
+                presentation: display_handle.is_some_and(|display_handle| {
+                    platform::can_present(&display_handle, i as u32, instance, physical_device)
+                }),
+            })
+            .collect::<Vec<QueueFamilyInfo>>();
 
         let mut queue_requests = vec![
-            // QueueRequest {
-            //     family: graphics,
-            //     count: 1,
-            //     label: Some(QueueLabel::Graphics),
-            //     allow_merge: true,
-            // },
-            // QueueRequest {
-            //     family: transfer.unwrap_or(graphics),
-            //     count: 1,
-            //     label: Some(QueueLabel::Transfer),
-            //     allow_merge: false,
-            // },
-            // QueueRequest {
-            //     family: compute,
-            //     count: 1,
-            //     label: Some(QueueLabel::Compute),
-            //     allow_merge: true,
-            // },
-            QueueRequest::flexible_labeled(graphics, 1, QueueLabel::Graphics),
-            QueueRequest::flexible_labeled(compute, 1, QueueLabel::Compute),
+            QueueRequest::flexible(QueueFamily::GRAPHICS, 1, Some(QueueLabel::Graphics)),
+            QueueRequest::flexible(QueueFamily::COMPUTE, 1, Some(QueueLabel::Compute)),
         ];
 
-        if let Some(transfer) = transfer {
-            queue_requests.push(QueueRequest::strict_labeled(
-                transfer,
-                1,
-                QueueLabel::Transfer,
-            ));
-        } else {
-            warn!("No exclusive transfer queue family. Falling back on primary graphics queue.");
-            queue_requests.push(QueueRequest::flexible_labeled(
-                graphics,
-                1,
-                QueueLabel::Transfer,
-            ));
+        // a dedicated transfer family gets an exclusive queue, otherwise transfers share a queue with other work
+        match QueueCapabilities::TRANSFER.select_family(&queue_families) {
+            Some(transfer)
+                if !queue_families[transfer as usize]
+                    .properties
+                    .queue_flags
+                    .intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE) =>
+            {
+                trace!("[device/queues] Found exclusive transfer queue: {:?}", transfer);
+                queue_requests.push(QueueRequest::strict_labeled(
+                    transfer,
+                    1,
+                    QueueLabel::Transfer,
+                ));
+            }
+            _ => {
+                warn!("No exclusive transfer queue family. Falling back on a shared queue.");
+                queue_requests.push(QueueRequest::flexible(
+                    QueueFamily::TRANSFER,
+                    1,
+                    Some(QueueLabel::Transfer),
+                ));
+            }
         }
 
-        if let Some(presentation) = presentation {
-            queue_requests.push(QueueRequest::flexible_labeled(
-                presentation,
+        if display_handle.is_none() {
+            debug!("[device/queues] Headless device, skipping presentation queue");
+        } else if queue_families.iter().any(|family| family.presentation) {
+            queue_requests.push(QueueRequest::flexible(
+                QueueFamily::PRESENTATION,
                 1,
-                QueueLabel::Presentation,
-            ))
+                Some(QueueLabel::Presentation),
+            ));
         } else {
             warn!(
                 "No queue family supports presentation. Operations running on windows will not work properly."
             );
-        };
+        }
 
         {
             let mut user_requests =
@@ -177,339 +141,98 @@ impl Device {
             queue_requests.append(&mut user_requests);
         }
 
-        // Validate requests now
-        let mut strict_requests: HashMap<u32, u32> = HashMap::new(); // all of these must be exclusives
-        let mut flexible_requests: HashMap<u32, u32> = HashMap::new(); // all of these may not be exclusives (allowed to merge together)
-
-        let mut strict_labels: HashMap<u32, Vec<QueueLabel>> = HashMap::new();
-        let mut flexible_labels: HashMap<u32, Vec<QueueLabel>> = HashMap::new();
-
-        let mut strict_labels_counts: HashMap<QueueLabel, HashMap<u32, usize>> = HashMap::new();
-        let mut flexible_labels_counts: HashMap<QueueLabel, HashMap<u32, usize>> = HashMap::new();
-
         trace!("[device/queues] Processing and validating queue requests");
-        for req in queue_requests {
-            if req.allow_merge {
-                if let Some(count) = flexible_requests.get(&(req.family as u32)).cloned() {
-                    flexible_requests.insert(req.family as u32, count + req.count);
-                    trace!(
-                        "[device/queues/flexible request] (update) family: {:?}, count: {:?} (old: {:?})",
-                        req.family,
-                        count + req.count,
-                        count
-                    );
-                } else {
-                    flexible_requests.insert(req.family as u32, req.count);
-                    trace!(
-                        "[device/queues/flexible request] family: {:?}, count: {:?}",
-                        req.family, req.count
-                    );
-                }
-
-                if let Some(label) = req.label {
-                    trace!(
-                        "[device/queues/flexible request] label: {:?}, family: {:?}, count: {:?}",
-                        label, req.family, req.count
-                    );
-
-                    if let Some(labels) = flexible_labels.get_mut(&req.family) {
-                        labels.push(label);
-                    } else {
-                        flexible_labels.insert(req.family, vec![label]);
-                    }
-
-                    if let Some(counts) = flexible_labels_counts.get_mut(&label) {
-                        if let Some(count) = counts.get_mut(&req.family) {
-                            *count += req.count as usize;
-                        } else {
-                            counts.insert(req.family, req.count as usize);
-                        }
-                    } else {
-                        flexible_labels_counts
-                            .insert(label, HashMap::from([(req.family, req.count as usize)]));
-                    }
-                }
-            } else {
-                if let Some(count) = strict_requests.get(&(req.family)).cloned() {
-                    strict_requests.insert(req.family, count + req.count);
-                    trace!(
-                        "[device/queues/strict request] (update) family: {:?}, count: {:?} (old: {:?})",
-                        req.family,
-                        count + req.count,
-                        count
-                    );
-                } else {
-                    strict_requests.insert(req.family, req.count);
-                    trace!(
-                        "[device/queues/strict request] family: {:?}, count: {:?}",
-                        req.family, req.count
-                    );
-                }
-
-                if let Some(label) = req.label {
-                    trace!(
-                        "[device/queues/strict request] label: {:?}, family: {:?}",
-                        label, req.family
-                    );
-
-                    if let Some(labels) = strict_labels.get_mut(&req.family) {
-                        labels.push(label);
-                    } else {
-                        strict_labels.insert(req.family, vec![label]);
-                    }
-
-                    if let Some(counts) = strict_labels_counts.get_mut(&label) {
-                        if let Some(count) = counts.get_mut(&req.family) {
-                            *count += req.count as usize;
-                        } else {
-                            counts.insert(req.family, req.count as usize);
-                        }
-                    } else {
-                        strict_labels_counts
-                            .insert(label, HashMap::from([(req.family, req.count as usize)]));
-                    }
-                }
-            }
-        }
-
-        let mut unlabeled = UnlabeledQueues::new();
-        let mut labeled = QueueLabels::new();
+        queue_requests
+            .iter()
+            .for_each(|req| trace!("[device/queues/#] - {:?}", req));
 
-        let mut flexible_starts: HashMap<u32, u32> = HashMap::new();
+        let queue_plan =
+            QueuePlanner::new(&queue_families).plan(&queue_requests)?;
 
-        for (family, mut count) in strict_requests.clone() {
-            let mut end_index: u32 = 0;
+        queue_plan.families.iter().for_each(|family| {
             trace!(
-                "[device/queues/strict request/processing] Processing request: (family: {:?}, count: {:?})",
-                family, count
-            );
-
-            if let Some(available) = queue_availability.get_mut(&family) {
-                if count > available.clone() {
-                    return Err(QueueRequestValidationError::NotEnoughQueuesInFamily {
-                        family,
-                        req: count + flexible_requests.get(&family).map(|_| 1).unwrap_or(0),
-                        avail: total_queue_availability.get(&family).cloned().unwrap_or(0),
-                    }
-                    .into());
-                }
-
-                trace!(
-                    "[device/queues/strict request/processing] Allocating {:?} queues from queue family {:?} (out of {:?} total available)",
-                    count, family, available
-                );
+                "[device/queues/configure] Queue family {:?} configured for {:?} queues (priorities: {:?}, global priority: {:?})",
+                family.family,
+                family.count(),
+                family.priorities,
+                family.global_priority
+            )
+        });
+
+        let wants_global_priority = queue_plan
+            .families
+            .iter()
+            .any(|family| family.global_priority.is_some());
 
-                *available -= count;
-            }
+        let engine_requirements = app.engine_requirements();
+        debug!(
+            "[device/features] Engine profile: {:?}",
+            engine_requirements.profile
+        );
 
-            if let Some(labels) = strict_labels.get(&family) {
-                trace!("[device/queues/strict request] Beginning label allocation");
-                for label in labels {
-                    let rc = strict_labels_counts
-                        .get(&label)
-                        .and_then(|counts| counts.get(&family))
-                        .cloned()
-                        .unwrap_or(1);
-                    for _ in 0..rc {
-                        trace!(
-                            "[device/queues/strict request/label allocation] Allocating queue #{:?} in family {:?} to label {:?}",
-                            end_index, family, label
-                        );
-                        if let Some(queues) = labeled.get_mut(label) {
-                            queues.push(QueueRef {
-                                family,
-                                index: end_index,
-                            });
-                        } else {
-                            labeled.insert(label.clone(), vec![QueueRef {
-                                family,
-                                index: end_index,
-                            }]);
-                        }
-                        end_index += 1;
-                        count -= 1;
-                    }
-                }
-            }
+        engine_requirements
+            .ignored_demotions()
+            .iter()
+            .for_each(|f| {
+                warn!(
+                    "[device/features] {:?} is part of the engine baseline and cannot be demoted",
+                    f
+                )
+            });
 
-            // unlabeled
-            if count > 0 {
-                trace!(
-                    "[device/queues/strict request/processing] Marked {:?} queues (#{:?} through #{:?}) in family {:?} as unlabeled",
-                    count,
-                    end_index,
-                    end_index + count - 1,
-                    family
-                );
-                unlabeled.insert(
-                    family,
-                    (end_index..end_index + count).collect::<HashSet<u32>>(),
-                );
-                end_index += count;
-            }
+        let vulkan_profiles = app.vulkan_profiles();
 
-            flexible_starts.insert(family, end_index);
-            trace!(
-                "[device/queues/strict request/processing] Flexible requests on family {:?} will start from queue #{:?}",
-                family, end_index
-            );
+        let mut requested_features: Vec<DeviceFeatureRequest> =
+            engine_requirements.feature_requests();
+        for profile in &vulkan_profiles {
+            debug!("[device/profiles] Requesting the features of {}", profile.name);
+            requested_features.extend(profile.feature_requests(physical_device_info));
         }
 
-        for (family, mut count) in flexible_requests {
-            trace!(
-                "[device/queues/flexible request/processing] Processing request: (family: {:?}, count: {:?})",
-                family, count
-            );
+        debug!("[device/features] Engine requested features:");
+        requested_features
+            .iter()
+            .for_each(|f| debug!("[device/features/#] - {:?}", f));
 
-            if let Some(total) = total_queue_availability.get(&family).cloned() {
-                if let Some(available) = queue_availability.get_mut(&family) {
-                    trace!(
-                        "[device/queues/flexible request/processing] {:?} out of {:?} queues available in family {:?}",
-                        available, total, family
-                    );
-                    if available.clone() <= 0 {
-                        return Err(QueueRequestValidationError::NotEnoughQueuesInFamily {
-                            family: family.clone(),
-                            req: strict_requests.get(&family).cloned().unwrap_or(0) + 1,
-                            avail: total,
-                        }
-                        .into());
-                    }
-
-                    if count > available.clone() {
-                        trace!(
-                            "[device/queues/flexible request/processing] More queues requested than available queues for family {:?}, some will be merged. (requested {:?}, available {:?})",
-                            family, count, available
-                        );
-                        *available = 0;
-                    } else {
-                        trace!(
-                            "[device/queues/flexible request/processing] No queue merging is required for family {:?} (requested {:?}, available {:?})",
-                            family, count, available
-                        );
-                        *available -= count;
-                    }
-
-                    let flexible_range = flexible_starts.get(&family).cloned().unwrap_or(0)..total;
-                    let mut o_index = 0;
-
-                    trace!(
-                        "[device/queues/flexible request/processing] Flexible queue range is queues #{:?} through #{:?} for family {:?}",
-                        flexible_range.start,
-                        flexible_range.end - 1,
-                        family
-                    );
-
-                    if let Some(labels) = flexible_labels.get(&family) {
-                        for label in labels {
-                            let rc = flexible_labels_counts
-                                .get(&label)
-                                .and_then(|counts| counts.get(&family))
-                                .cloned()
-                                .unwrap_or(1);
-                            trace!(
-                                "[device/queues/flexible request/label allocation] Will allocate {:?} queues in family {:?} to label {:?}",
-                                rc, family, label
-                            );
-                            for _ in 0..rc {
-                                let index =
-                                    flexible_range.start + (o_index % flexible_range.len()) as u32;
-                                if let Some(queues) = labeled.get_mut(label) {
-                                    queues.push(QueueRef { family, index });
-                                } else {
-                                    labeled.insert(label.clone(), vec![QueueRef { family, index }]);
-                                }
-
-                                trace!(
-                                    "[device/queues/flexible request/label allocation] Allocating queue #{:?} in family {:?} to label {:?}",
-                                    index, family, label
-                                );
-
-                                o_index += 1;
-                                count -= 1;
-                            }
-                        }
-                    }
-
-                    // unlabeled
-                    if count > 0 {
-                        let indices = (o_index..o_index + count as usize)
-                            .map(|i| flexible_range.start + (i % flexible_range.len()) as u32)
-                            .collect::<HashSet<u32>>();
-
-                        trace!(
-                            "[device/queues/flexible request/processing] Marked {:?} queues in family {:?} as unlabeled (in virtual space, range is: {:?} through {:?}, maps to indices: {:?})",
-                            count,
-                            family,
-                            o_index,
-                            o_index + (count as usize) - 1,
-                            indices
-                        );
-
-                        unlabeled.insert(family, indices);
-                    }
-                }
-            } else {
-                return Err(QueueRequestValidationError::NotEnoughQueuesInFamily {
-                    family,
-                    req: strict_requests.get(&family).cloned().unwrap_or(0) + 1,
-                    avail: 0,
-                }
-                .into());
-            }
-        }
+        app.on_request_features(&mut requested_features);
 
-        let mut device_queue_create_infos = Vec::<vk::DeviceQueueCreateInfo>::new();
-        let mut priorities: HashMap<u32, Vec<f32>> = HashMap::new();
-
-        for (f, total) in total_queue_availability {
-            if let Some(real) = queue_availability.get(&f) {
-                if real.clone() == total {
-                    trace!(
-                        "[device/queues/configure] Skipping queue family {:?} (no requests)",
-                        f
-                    );
-                    continue;
-                }
+        trace!("[device/features] Requested features:");
+        requested_features
+            .iter()
+            .for_each(|f| debug!("[device/features/#] - {:?}", f));
 
-                let this_priorities = repeat_n(1.0f32, (total - real) as usize).collect();
-                trace!(
-                    "[device/queues/configure] Priorities for {:?} queues allocated in family {:?}: {:?}",
-                    total - real,
-                    f,
-                    this_priorities
-                );
-                priorities.insert(f, this_priorities);
-            }
+        let mut requested_extensions: Vec<ExtensionRequest> = Vec::from(REQUIRED_DEVICE_EXTENSIONS);
+        if display_handle.is_some() {
+            requested_extensions.extend_from_slice(REQUIRED_PRESENTATION_DEVICE_EXTENSIONS);
+        }
+        if wants_global_priority {
+            requested_extensions.extend_from_slice(GLOBAL_PRIORITY_DEVICE_EXTENSIONS);
         }
+        let api_version = physical_device_info.negotiated_api_version();
+        let feature_extensions = physical_device_info
+            .features()
+            .required_extensions(requested_features.as_slice());
 
-        for (f, prio) in priorities.iter() {
-            device_queue_create_infos.push(
-                vk::DeviceQueueCreateInfo::default()
-                    .queue_priorities(prio.as_slice())
-                    .queue_family_index(f.clone()),
-            );
-            trace!(
-                "[device/queues/configure] Queue family {:?} configured for {:?} queues",
-                f,
-                prio.len()
+        if !feature_extensions.is_empty() {
+            debug!(
+                "[device/extensions] Requesting the extensions of Vulkan 1.3 features (device uses Vulkan {})",
+                format_version(api_version)
             );
         }
 
-        let mut requested_extensions: Vec<ExtensionRequest> = Vec::from(REQUIRED_DEVICE_EXTENSIONS);
+        requested_extensions.extend(feature_extensions);
+        for profile in &vulkan_profiles {
+            debug!("[device/profiles] Requesting the extensions of {}", profile.name);
+            requested_extensions.extend(profile.extension_requests(physical_device_info));
+        }
+
         trace!("[device/extensions] Beginning device extension selection");
         trace!("[device/extensions] Engine requests:");
         requested_extensions
             .iter()
             .for_each(|e| trace!("[device/extensions/#] - {:?}", e));
 
-        let extension_properties =
-            unsafe { instance.enumerate_device_extension_properties(physical_device) }?;
-
-        let available_extensions = extension_properties
-            .iter()
-            .map(|props| unsafe { CStr::from_ptr(props.extension_name.as_ptr()).to_owned() })
-            .collect::<HashSet<CString>>();
+        let available_extensions = physical_device_info.extensions();
 
         trace!("[device/extensions] Available Extensions");
         available_extensions
@@ -523,15 +246,20 @@ impl Device {
             .iter()
             .for_each(|e| trace!("[device/extensions/#] - {:?}", e));
 
+        // extensions promoted to core in the device version count as resolved even if the driver stopped advertising them
+        let is_available = |name: &CStr| {
+            available_extensions.contains(name) || promoted_to_core(name, api_version)
+        };
+
         let missing = requested_extensions
             .iter()
-            .filter(|req| req.required && !available_extensions.contains(&req.name.to_owned()))
+            .filter(|req| req.required && !is_available(req.name))
             .map(|req| req.name)
             .collect::<Vec<&'static CStr>>();
 
         let missing_optionals = requested_extensions
             .iter()
-            .filter(|req| !req.required && !available_extensions.contains(&req.name.to_owned()))
+            .filter(|req| !req.required && !is_available(req.name))
             .map(|req| req.name)
             .collect::<Vec<&'static CStr>>();
 
@@ -548,7 +276,7 @@ impl Device {
 
         let extensions_set = requested_extensions
             .iter()
-            .filter(|req| available_extensions.contains(&req.name.to_owned()))
+            .filter(|req| is_available(req.name))
             .map(|req| req.name)
             .collect::<HashSet<&'static CStr>>();
 
@@ -561,31 +289,22 @@ impl Device {
 
         let extensions = extensions_set
             .iter()
+            .filter(|n| available_extensions.contains(**n))
             .map(|n| n.as_ptr())
             .collect::<Vec<*const c_char>>();
 
-        let mut requested_features: Vec<DeviceFeatureRequest> = Vec::from(REQUIRED_FEATURES);
-        debug!("[device/features] Engine requested features:");
-        requested_features
-            .iter()
-            .for_each(|f| debug!("[device/features/#] - {:?}", f));
-
-        app.on_request_features(&mut requested_features);
-
-        trace!("[device/features] Requested features:");
-        requested_features
-            .iter()
-            .for_each(|f| debug!("[device/features/#] - {:?}", f));
-
-        let available_features = FeatureStructs::available(instance, physical_device);
+        let available_features = physical_device_info.features();
         let available_features_list = available_features.get_list();
         trace!("[device/features] Available features:");
         available_features_list
             .iter()
             .for_each(|f| trace!("[device/features/#] - {:?}", f));
 
-        let mut device_features_sets =
-            FeatureStructs::validate_and_write(available_features, requested_features.as_slice())?;
+        let mut device_features_sets = FeatureStructs::validate_and_write(
+            available_features,
+            requested_features.as_slice(),
+            &extensions_set,
+        )?;
 
         let resolved_features_list = device_features_sets.get_list();
 
@@ -596,52 +315,235 @@ impl Device {
 
         app.on_resolve_features(&device_features_sets);
 
+        let enabled_features = resolved_features_list;
+        let enabled_extensions = extensions_set;
+
+        let global_priority = wants_global_priority
+            && (enabled_extensions.contains(khr::global_priority::NAME)
+                || enabled_extensions.contains(ext::global_priority::NAME));
+        if wants_global_priority && !global_priority {
+            warn!(
+                "[device/queues] Global queue priorities are not supported by this device, using the default global priority"
+            );
+        }
+
+        let mut global_priority_infos = queue_plan
+            .families
+            .iter()
+            .map(|family| {
+                vk::DeviceQueueGlobalPriorityCreateInfoKHR::default().global_priority(
+                    family
+                        .global_priority
+                        .unwrap_or(vk::QueueGlobalPriorityKHR::MEDIUM),
+                )
+            })
+            .collect::<Vec<vk::DeviceQueueGlobalPriorityCreateInfoKHR>>();
+
+        let device_queue_create_infos = queue_plan
+            .families
+            .iter()
+            .zip(global_priority_infos.iter_mut())
+            .map(|(family, global_priority_info)| {
+                let create_info = Self::queue_create_info(family);
+                if global_priority && family.global_priority.is_some() {
+                    create_info.push_next(global_priority_info)
+                } else {
+                    create_info
+                }
+            })
+            .collect::<Vec<vk::DeviceQueueCreateInfo>>();
+
         let mut device_features = device_features_sets.make_features_2();
 
-        let create_info = vk::DeviceCreateInfo::default()
-            .enabled_extension_names(extensions.as_slice())
-            .queue_create_infos(device_queue_create_infos.as_slice())
-            .push_next(&mut device_features);
+        let result = unsafe {
+            instance.create_device(
+                physical_device,
+                &vk::DeviceCreateInfo::default()
+                    .enabled_extension_names(extensions.as_slice())
+                    .queue_create_infos(device_queue_create_infos.as_slice())
+                    .push_next(&mut device_features),
+                None,
+            )
+        };
 
-        let device = unsafe { instance.create_device(physical_device, &create_info, None) }?;
+        let device = match result {
+            // priorities above medium may need privileges the process doesn't have
+            Err(vk::Result::ERROR_NOT_PERMITTED_KHR) if global_priority => {
+                warn!(
+                    "[device/queues] The driver refused the requested global queue priorities, retrying with the default global priority"
+                );
+
+                let device_queue_create_infos = queue_plan
+                    .families
+                    .iter()
+                    .map(Self::queue_create_info)
+                    .collect::<Vec<vk::DeviceQueueCreateInfo>>();
+
+                unsafe {
+                    instance.create_device(
+                        physical_device,
+                        &vk::DeviceCreateInfo::default()
+                            .enabled_extension_names(extensions.as_slice())
+                            .queue_create_infos(device_queue_create_infos.as_slice())
+                            .push_next(&mut device_features),
+                        None,
+                    )
+                }?
+            }
+            result => result?,
+        };
 
         info!("[vulkan/device] Successfully created device");
 
-        let queues = device_queue_create_infos
+        let device_loader = DeviceLoader::load(instance, &device, api_version);
+
+        let mut queue_ref_labels: HashMap<QueueRef, Vec<QueueLabel>> = HashMap::new();
+        for (label, refs) in &queue_plan.labels {
+            for queue_ref in refs {
+                let labels = queue_ref_labels.entry(*queue_ref).or_default();
+                if !labels.contains(label) {
+                    labels.push(*label);
+                }
+            }
+        }
+
+        let queues = queue_plan
+            .families
             .iter()
-            .cloned()
-            .map(|ci| (ci.queue_family_index, ci.queue_count))
-            .map(|(family, count)| {
-                (
-                    family,
-                    (0..count)
-                        .map(|i| unsafe { device.get_device_queue(family, i) })
-                        .collect::<Vec<vk::Queue>>(),
-                )
+            .map(|family| {
+                let queues = (0..family.count())
+                    .map(|index| {
+                        let queue_ref = QueueRef {
+                            family: family.family,
+                            index,
+                        };
+                        let labels = queue_ref_labels.remove(&queue_ref).unwrap_or_default();
+                        Ok(Arc::new(Queue::new(&device, &device_loader, queue_ref, labels)?))
+                    })
+                    .collect::<VkResult<Vec<Arc<Queue>>>>()?;
+                Ok((family.family, queues))
             })
-            .collect::<HashMap<u32, Vec<vk::Queue>>>();
+            .collect::<VkResult<HashMap<u32, Vec<Arc<Queue>>>>>()?;
 
         info!(
             "[device/queues] Successfully loaded {:?} device queues",
             queues.iter().fold(0usize, |a, (_, v)| a + v.len())
         );
 
-        let device_loader = DeviceLoader::load(&instance, &device);
-
         Ok(Device {
             device,
             queues,
-            queue_labels: labeled,
-            unlabeled_queues: unlabeled,
+            queue_labels: queue_plan.labels,
+            unlabeled_queues: queue_plan.unlabeled,
             loader: device_loader,
+            api_version,
+            enabled_features,
+            enabled_extensions,
         })
     }
 
+    fn queue_create_info(family: &QueueFamilyPlan) -> vk::DeviceQueueCreateInfo<'_> {
+        vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(family.family)
+            .queue_priorities(family.priorities.as_slice())
+    }
+
+    /// Checks the requirements the engine itself places on a physical device, before any application requests.
+    pub(crate) fn check_engine_requirements(
+        physical_device_info: &PhysicalDeviceInfo,
+        presentation: bool,
+        minimum_api_version: u32,
+        requirements: &EngineRequirements,
+    ) -> anyhow::Result<()> {
+        if !version_at_least(physical_device_info.negotiated_api_version(), minimum_api_version) {
+            return Err(anyhow!(
+                "device only supports Vulkan {}, Vulkan {} is required",
+                format_version(physical_device_info.api_version()),
+                format_version(minimum_api_version)
+            ));
+        }
+
+        if !physical_device_info.has_queue_family(vk::QueueFlags::GRAPHICS) {
+            return Err(anyhow!("no graphics queue family"));
+        }
+
+        if !physical_device_info.has_queue_family(vk::QueueFlags::COMPUTE) {
+            return Err(anyhow!("no compute queue family"));
+        }
+
+        let mut required_extensions = REQUIRED_DEVICE_EXTENSIONS.to_vec();
+        if presentation {
+            required_extensions.extend_from_slice(REQUIRED_PRESENTATION_DEVICE_EXTENSIONS);
+        }
+
+        let missing_extensions = required_extensions
+            .iter()
+            .filter(|req| req.required && !physical_device_info.supports_extension(req.name))
+            .map(|req| req.name)
+            .collect::<Vec<&'static CStr>>();
+
+        if !missing_extensions.is_empty() {
+            return Err(anyhow!(
+                "missing required extensions: {:?}",
+                missing_extensions
+            ));
+        }
+
+        let missing_features = requirements
+            .feature_requests()
+            .iter()
+            .filter(|req| req.required && !physical_device_info.supports_feature(req.feature))
+            .map(|req| req.feature)
+            .collect::<Vec<DeviceFeature>>();
+
+        if !missing_features.is_empty() {
+            return Err(anyhow!(
+                "missing required features: {:?}",
+                missing_features
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn device(&self) -> &ash::Device {
         &self.device
     }
 
-    pub fn queues(&self) -> &HashMap<u32, Vec<vk::Queue>> {
+    /// The Vulkan version negotiated for this device.
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    /// The features enabled on this device. Check these before relying on anything outside the engine baseline, the
+    /// profile and the application can make most features optional.
+    pub fn enabled_features(&self) -> &HashSet<DeviceFeature> {
+        &self.enabled_features
+    }
+
+    pub fn has_feature(&self, feature: DeviceFeature) -> bool {
+        self.enabled_features.contains(&feature)
+    }
+
+    /// Like [`Device::has_feature`], but as an error naming the feature for subsystems that can't work without it.
+    pub fn require_feature(&self, feature: DeviceFeature) -> Result<(), FeatureError> {
+        if self.has_feature(feature) {
+            Ok(())
+        } else {
+            Err(FeatureError::NotEnabled(feature))
+        }
+    }
+
+    /// The extensions enabled on this device, including requested extensions that are core in its Vulkan version.
+    pub fn enabled_extensions(&self) -> &HashSet<&'static CStr> {
+        &self.enabled_extensions
+    }
+
+    pub fn has_extension(&self, extension: &CStr) -> bool {
+        self.enabled_extensions.contains(extension)
+    }
+
+    pub fn queues(&self) -> &HashMap<u32, Vec<Arc<Queue>>> {
         &self.queues
     }
 
@@ -664,51 +566,59 @@ impl Device {
             .cloned()
     }
 
-    pub fn get_labeled_queue(&self, label: QueueLabel) -> Option<vk::Queue> {
-        self.queue_labels
-            .get(&label)
-            .and_then(|v| v.first())
-            .and_then(|qr| self.get_queue(qr.clone()))
+    pub fn get_labeled_queue(&self, label: QueueLabel) -> Option<Arc<Queue>> {
+        self.get_labeled_queue_ref(label)
+            .and_then(|qr| self.get_queue(qr))
     }
 
-    pub fn get_queue(&self, queue_ref: QueueRef) -> Option<vk::Queue> {
+    pub fn get_queue(&self, queue_ref: QueueRef) -> Option<Arc<Queue>> {
         self.queues
             .get(&queue_ref.family)
             .and_then(|queues| queues.get(queue_ref.index as usize))
             .cloned()
     }
 
+    /// Waits for every queue to be idle. All queues are locked while waiting, as `vkDeviceWaitIdle` requires.
     pub fn wait_idle(&self) -> VkResult<()> {
+        let mut families = self.queues.keys().copied().collect::<Vec<u32>>();
+        families.sort();
+
+        let _guards = families
+            .iter()
+            .flat_map(|family| self.queues[family].iter())
+            .map(|queue| queue.lock())
+            .collect::<Vec<_>>();
+
         unsafe { self.device.device_wait_idle() }
     }
 
     pub fn wait_queues(&self, family: u32) -> VkResult<()> {
-        unsafe {
-            if let Some(queues) = self.queues.get(&family) {
-                for q in queues {
-                    self.queue_wait_idle(q.clone())?;
-                }
+        if let Some(queues) = self.queues.get(&family) {
+            for queue in queues {
+                queue.wait_idle()?;
             }
         }
 
         Ok(())
     }
 
-    pub fn get_unlabeled_queue(&self, family: u32) -> Option<vk::Queue> {
+    pub fn get_unlabeled_queue(&self, family: u32) -> Option<Arc<Queue>> {
         self.unlabeled_queues
             .get(&family)
-            .and_then(|indices| indices.iter().next().cloned())
+            .and_then(|indices| indices.iter().min().cloned())
             .and_then(|index| self.get_queue(QueueRef { family, index }))
     }
 
-    pub fn get_lazy_queue(&self, queue: LazyQueue) -> Option<(u32, vk::Queue)> {
+    pub fn get_lazy_queue(&self, queue: LazyQueue) -> Option<Arc<Queue>> {
         match queue {
-            LazyQueue::Labeled(label) => self
-                .get_labeled_queue_ref(label)
-                .and_then(|qr| Some((qr.family, self.get_queue(qr)?))),
-            LazyQueue::Unlabeled(family) => Some((family, self.get_unlabeled_queue(family)?)),
-            LazyQueue::Direct(family, q) => Some((family, q)),
-            LazyQueue::Family(family) => Some((family, self.get_queue_by_family(family))),
+            LazyQueue::Labeled(label) => self.get_labeled_queue(label),
+            LazyQueue::Unlabeled(family) => self.get_unlabeled_queue(family),
+            LazyQueue::Direct(family, handle) => self
+                .queues
+                .get(&family)
+                .and_then(|queues| queues.iter().find(|queue| queue.handle() == handle))
+                .cloned(),
+            LazyQueue::Family(family) => self.get_queue(QueueRef { family, index: 0 }),
             LazyQueue::Ref(qr) => self.get_queue(qr),
         }
     }
@@ -729,16 +639,34 @@ impl DerefMut for Device {
 
 pub struct DeviceLoader {
     swapchain: khr::swapchain::Device,
+    synchronization2: Option<khr::synchronization2::Device>,
+    dynamic_rendering: Option<khr::dynamic_rendering::Device>,
 }
 
 impl DeviceLoader {
-    pub fn load(instance: &ash::Instance, device: &ash::Device) -> Self {
+    pub fn load(instance: &ash::Instance, device: &ash::Device, api_version: u32) -> Self {
+        let promoted = version_at_least(api_version, vk::API_VERSION_1_3);
+
         Self {
             swapchain: khr::swapchain::Device::new(instance, device),
+            synchronization2: (!promoted)
+                .then(|| khr::synchronization2::Device::new(instance, device)),
+            dynamic_rendering: (!promoted)
+                .then(|| khr::dynamic_rendering::Device::new(instance, device)),
         }
     }
 
     pub fn swapchain(&self) -> &khr::swapchain::Device {
         &self.swapchain
     }
+
+    /// `VK_KHR_synchronization2` on Vulkan 1.2 devices, `None` when the core functions are available.
+    pub fn synchronization2(&self) -> Option<&khr::synchronization2::Device> {
+        self.synchronization2.as_ref()
+    }
+
+    /// `VK_KHR_dynamic_rendering` on Vulkan 1.2 devices, `None` when the core functions are available.
+    pub fn dynamic_rendering(&self) -> Option<&khr::dynamic_rendering::Device> {
+        self.dynamic_rendering.as_ref()
+    }
 }
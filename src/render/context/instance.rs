@@ -1,23 +1,33 @@
-use crate::app::feature_request::ExtensionRequest;
+use crate::app::extension_features::FeatureRegistry;
+use crate::app::feature_request::{ExtensionRequest, LayerRequest};
+use crate::render::context::debug::{DebugMessenger, KHRONOS_VALIDATION_LAYER};
+use crate::render::context::device::Device;
+use crate::render::context::physical_device::{PhysicalDeviceInfo, PhysicalDeviceSelector};
+use crate::render::context::version::format_version;
 use crate::{ENGINE_NAME, ENGINE_VERSION, EngineCallbackHandler};
 use anyhow::anyhow;
-use ash::{khr, vk};
-use log::{debug, info, trace};
+use ash::{ext, khr, vk};
+use log::{debug, info, trace, warn};
 use std::collections::HashSet;
 use std::ffi::{CStr, CString, c_char};
 use std::ops::{Deref, DerefMut};
-use winit::event_loop::EventLoop;
-use winit::raw_window_handle::HasDisplayHandle;
+use winit::raw_window_handle::RawDisplayHandle;
 
 pub struct Instance {
     entry: ash::Entry,
     instance: ash::Instance,
     loader: InstanceLoader,
+    layers: HashSet<&'static CStr>,
+    extensions: HashSet<&'static CStr>,
+    debug_messenger: Option<DebugMessenger>,
+    api_version: u32,
+    minimum_api_version: u32,
 }
 
 impl Instance {
+    /// Creates the instance. When `display_handle` is `None` the instance is headless and no surface extensions are requested.
     pub fn new<A: EngineCallbackHandler>(
-        event_loop: &EventLoop<()>,
+        display_handle: Option<RawDisplayHandle>,
         app: &mut A,
     ) -> anyhow::Result<Self> {
         #[cfg(feature = "vulkan_linked")]
@@ -31,8 +41,124 @@ impl Instance {
             ash::Entry::load()
         }?;
 
-        let required_extensions =
-            ash_window::enumerate_required_extensions(event_loop.display_handle()?.as_raw())?;
+        let api_version_request = app.api_version();
+        let minimum_api_version = api_version_request.effective_minimum();
+        let loader_version = unsafe { entry.try_enumerate_instance_version() }?
+            .unwrap_or(vk::API_VERSION_1_0);
+
+        debug!(
+            "[instance/version] Loader version: {}, requested: minimum {}, preferred {}",
+            format_version(loader_version),
+            format_version(api_version_request.minimum),
+            format_version(api_version_request.preferred)
+        );
+
+        let api_version = api_version_request.negotiate(loader_version)?;
+
+        info!(
+            "[vulkan/instance] Using Vulkan {}",
+            format_version(api_version)
+        );
+
+        let validation_settings = app.validation_settings();
+        debug!(
+            "[instance/validation] Validation settings: {:?}",
+            validation_settings
+        );
+
+        let mut requested_layers: Vec<LayerRequest> = Vec::new();
+        if validation_settings.validation_layer {
+            requested_layers.push(LayerRequest::optional(KHRONOS_VALIDATION_LAYER));
+        }
+
+        app.on_request_instance_layers(&mut requested_layers);
+
+        trace!("[instance/layers] Requested instance layers");
+        requested_layers
+            .iter()
+            .for_each(|layer| trace!("[instance/layers/#] - {:?}", layer));
+
+        let layer_properties = unsafe { entry.enumerate_instance_layer_properties() }?;
+
+        let available_layers = layer_properties
+            .iter()
+            .map(|props| unsafe { CStr::from_ptr(props.layer_name.as_ptr()).to_owned() })
+            .collect::<HashSet<CString>>();
+
+        trace!("[instance/layers] Available instance layers:");
+        available_layers
+            .iter()
+            .for_each(|layer| trace!("[instance/layers/#] - {:?}", layer));
+
+        let missing_layers = requested_layers
+            .iter()
+            .filter(|req| req.required && !available_layers.contains(&req.name.to_owned()))
+            .map(|req| req.name)
+            .collect::<Vec<&'static CStr>>();
+
+        if !missing_layers.is_empty() {
+            return Err(anyhow!(
+                "Missing required instance layers: {:?}",
+                missing_layers
+            ));
+        }
+
+        let missing_optional_layers = requested_layers
+            .iter()
+            .filter(|req| !req.required && !available_layers.contains(&req.name.to_owned()))
+            .map(|req| req.name)
+            .collect::<Vec<&'static CStr>>();
+
+        if !missing_optional_layers.is_empty() {
+            debug!("[instance/layers] Missing optional instance layers:");
+            missing_optional_layers
+                .iter()
+                .for_each(|layer| debug!("[instance/layers/#] - {:?}", layer));
+        }
+
+        // The loader stacks layers in the order they are enabled, so keep the request order.
+        let mut enabled_layers: Vec<&'static CStr> = Vec::new();
+        for req in requested_layers
+            .iter()
+            .filter(|req| available_layers.contains(&req.name.to_owned()))
+        {
+            if !enabled_layers.contains(&req.name) {
+                enabled_layers.push(req.name);
+            }
+        }
+
+        debug!("[instance/layers] Resolved instance layers:");
+        enabled_layers
+            .iter()
+            .for_each(|layer| debug!("[instance/layers/#] - {:?}", layer));
+
+        let layers_set = enabled_layers
+            .iter()
+            .copied()
+            .collect::<HashSet<&'static CStr>>();
+
+        let validation_enabled = layers_set.contains(KHRONOS_VALIDATION_LAYER);
+        if validation_settings.validation_layer && !validation_enabled {
+            warn!(
+                "[instance/validation] Validation was requested but {:?} is not available. Validation messages will not be reported.",
+                KHRONOS_VALIDATION_LAYER
+            );
+        }
+
+        app.on_resolve_instance_layers(&layers_set);
+
+        let layers = enabled_layers
+            .iter()
+            .map(|n| n.as_ptr())
+            .collect::<Vec<*const c_char>>();
+
+        let required_extensions: &[*const c_char] = match display_handle {
+            Some(display_handle) => ash_window::enumerate_required_extensions(display_handle)?,
+            None => {
+                debug!("[instance/extensions] Headless instance, skipping surface extensions");
+                &[]
+            }
+        };
 
         let mut requested_extensions = required_extensions
             .iter()
@@ -44,7 +170,7 @@ impl Instance {
             "[instance/extensions] System required instance extensions: {}",
             required_extensions
                 .iter()
-                .map(|e| unsafe { CStr::from_ptr(e.clone()) }
+                .map(|e| unsafe { CStr::from_ptr(*e) }
                     .to_str()
                     .unwrap()
                     .to_owned())
@@ -52,6 +178,14 @@ impl Instance {
                 .unwrap_or("".to_owned())
         );
 
+        if validation_settings.debug_messenger {
+            requested_extensions.push(ExtensionRequest::optional(ext::debug_utils::NAME));
+        }
+
+        if validation_enabled && validation_settings.wants_layer_settings() {
+            requested_extensions.push(ExtensionRequest::optional(ext::layer_settings::NAME));
+        }
+
         app.on_request_instance_extensions(&mut requested_extensions);
 
         trace!("[instance/extensions] Requested instance extensions");
@@ -59,7 +193,14 @@ impl Instance {
             .iter()
             .for_each(|ext| trace!("[instance/extensions/#] - {:?}", ext));
 
-        let extension_properties = unsafe { entry.enumerate_instance_extension_properties(None) }?;
+        let mut extension_properties =
+            unsafe { entry.enumerate_instance_extension_properties(None) }?;
+
+        // Layers can provide instance extensions of their own (the validation layer provides VK_EXT_layer_settings for example).
+        for layer in enabled_layers.iter() {
+            extension_properties
+                .append(&mut unsafe { entry.enumerate_instance_extension_properties(Some(layer)) }?);
+        }
 
         let available_extensions = extension_properties
             .iter()
@@ -125,7 +266,7 @@ impl Instance {
         );
 
         let application_info = vk::ApplicationInfo::default()
-            .api_version(vk::API_VERSION_1_3)
+            .api_version(api_version)
             .engine_name(ENGINE_NAME)
             .engine_version(ENGINE_VERSION)
             .application_name(app_name.as_c_str())
@@ -136,47 +277,228 @@ impl Instance {
                 app_version.2,
             ));
 
-        let create_info = vk::InstanceCreateInfo::default()
+        let use_debug_messenger =
+            validation_settings.debug_messenger && extensions_set.contains(ext::debug_utils::NAME);
+        let use_layer_settings = validation_settings.wants_layer_settings()
+            && extensions_set.contains(ext::layer_settings::NAME);
+
+        if validation_settings.wants_layer_settings() && !use_layer_settings {
+            warn!(
+                "[instance/validation] Synchronization or GPU-assisted validation was requested but {:?} is not available.",
+                ext::layer_settings::NAME
+            );
+        }
+
+        let layer_settings = validation_settings.layer_settings();
+        let mut layer_settings_create_info =
+            vk::LayerSettingsCreateInfoEXT::default().settings(layer_settings.as_slice());
+
+        // Chaining the messenger info lets us receive messages from instance creation and destruction too.
+        let mut messenger_create_info = validation_settings.messenger_create_info();
+
+        let mut create_info = vk::InstanceCreateInfo::default()
+            .enabled_layer_names(&layers)
             .enabled_extension_names(&extensions)
             .application_info(&application_info);
 
+        if use_layer_settings {
+            create_info = create_info.push_next(&mut layer_settings_create_info);
+        }
+
+        if use_debug_messenger {
+            create_info = create_info.push_next(&mut messenger_create_info);
+        }
+
         let instance = unsafe { entry.create_instance(&create_info, None) }?;
 
         info!("[vulkan/instance] Successfully created vulkan instance.");
 
+        let debug_messenger = if use_debug_messenger {
+            let messenger = DebugMessenger::new(&entry, &instance, &validation_settings)?;
+            debug!("[instance/validation] Debug messenger installed");
+            Some(messenger)
+        } else {
+            None
+        };
+
         let loader = InstanceLoader::load(&entry, &instance);
 
         Ok(Instance {
             entry,
             instance,
             loader,
+            layers: layers_set,
+            extensions: extensions_set,
+            debug_messenger,
+            api_version,
+            minimum_api_version,
         })
     }
 
+    /// Selects the physical device to use.
+    ///
+    /// Every device is checked against the engine requirements and [`EngineCallbackHandler::validate_physical_device`],
+    /// and the remaining candidates are ranked with [`EngineCallbackHandler::score_physical_device`]. A device can be
+    /// forced through the [`PHYSICAL_DEVICE_OVERRIDE_ENV`](crate::render::context::physical_device::PHYSICAL_DEVICE_OVERRIDE_ENV)
+    /// environment variable or [`EngineCallbackHandler::physical_device_override`] (the environment variable wins).
+    ///
+    /// The extension feature structs in `feature_registry` are queried for every device that supports their extension.
     pub fn select_physical_device<A: EngineCallbackHandler>(
         &self,
         app: &mut A,
-    ) -> anyhow::Result<vk::PhysicalDevice> {
-        let physical_devices = unsafe { self.enumerate_physical_devices() }?;
+        presentation: bool,
+        feature_registry: &FeatureRegistry,
+    ) -> anyhow::Result<PhysicalDeviceInfo> {
+        let physical_devices = self.query_physical_devices(feature_registry)?;
+
+        let selector = PhysicalDeviceSelector::from_env().or_else(|| app.physical_device_override());
+        if let Some(selector) = &selector {
+            info!(
+                "[vulkan/physical device] Physical device override: {}",
+                selector
+            );
+        }
+
+        let engine_requirements = app.engine_requirements();
+        let vulkan_profiles = app.vulkan_profiles();
+
+        let mut candidates: Vec<(i64, PhysicalDeviceInfo)> = Vec::new();
+        let mut forced: Option<PhysicalDeviceInfo> = None;
+        let mut forced_rejections: Vec<String> = Vec::new();
+
+        for (index, info) in physical_devices.into_iter().enumerate() {
+            let info = match info {
+                Ok(info) => info,
+                Err(reason) => {
+                    info!(
+                        "[vulkan/physical device] Rejected device #{}: failed to query it: {}",
+                        index, reason
+                    );
+
+                    if selector == Some(PhysicalDeviceSelector::Index(index)) {
+                        forced_rejections.push(format!("#{}: {}", index, reason));
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(selector) = &selector
+                && !info.matches(selector)
+            {
+                debug!(
+                    "[vulkan/physical device] Skipping {}: does not match the override ({})",
+                    info, selector
+                );
+                continue;
+            }
 
-        for physical_device in physical_devices {
-            if app.validate_physical_device(physical_device, &self.instance) {
-                let properties = unsafe { self.get_physical_device_properties(physical_device) };
+            let verdict = Device::check_engine_requirements(
+                &info,
+                presentation,
+                self.minimum_api_version,
+                &engine_requirements,
+            )
+            .and_then(|_| {
+                vulkan_profiles
+                    .iter()
+                    .map(|profile| profile.check(&info))
+                    .find(|report| !report.is_supported())
+                    .map_or(Ok(()), |report| Err(anyhow!("{}", report)))
+            })
+            .and_then(|_| app.validate_physical_device(&info));
+
+            if let Err(reason) = verdict {
                 info!(
-                    "[vulkan/physical device] Selected Physical Device: {}",
-                    properties.device_name_as_c_str()?.to_str()?
+                    "[vulkan/physical device] Rejected {}: {}",
+                    info, reason
                 );
-                return Ok(physical_device);
+
+                if selector.is_some() {
+                    forced_rejections.push(format!("{}: {}", info, reason));
+                }
+                continue;
+            }
+
+            if selector.is_some() {
+                forced.get_or_insert(info);
+                continue;
             }
+
+            let score = app.score_physical_device(&info);
+            debug!(
+                "[vulkan/physical device] Candidate {} (score: {})",
+                info, score
+            );
+            candidates.push((score, info));
         }
 
-        Err(anyhow!("Failed to find a suitable physical device"))
+        let selected = match (selector, forced) {
+            (_, Some(info)) => info,
+            (Some(selector), None) if forced_rejections.is_empty() => {
+                return Err(anyhow!(
+                    "No physical device matches the override ({})",
+                    selector
+                ));
+            }
+            (Some(selector), None) => {
+                return Err(anyhow!(
+                    "The physical device override ({}) only matches unusable devices: {}",
+                    selector,
+                    forced_rejections.join("; ")
+                ));
+            }
+            (None, None) => candidates
+                .into_iter()
+                .max_by_key(|(score, info)| (*score, std::cmp::Reverse(info.index())))
+                .map(|(_, info)| info)
+                .ok_or_else(|| anyhow!("Failed to find a suitable physical device"))?,
+        };
+
+        info!(
+            "[vulkan/physical device] Selected Physical Device: {}",
+            selected
+        );
+
+        Ok(selected)
+    }
+
+    /// Queries every physical device in enumeration order. A device that fails to query is kept as its error, so that
+    /// positions match [`PhysicalDeviceSelector::Index`].
+    pub fn query_physical_devices(
+        &self,
+        feature_registry: &FeatureRegistry,
+    ) -> anyhow::Result<Vec<anyhow::Result<PhysicalDeviceInfo>>> {
+        let physical_devices = unsafe { self.enumerate_physical_devices() }?;
+
+        Ok(physical_devices
+            .into_iter()
+            .enumerate()
+            .map(|(index, physical_device)| {
+                PhysicalDeviceInfo::query(
+                    &self.instance,
+                    physical_device,
+                    index,
+                    self.api_version,
+                    feature_registry,
+                )
+            })
+            .collect())
     }
 
     pub fn load_extension<E, F: FnOnce(&ash::Entry, &ash::Instance) -> E>(&self, f: F) -> E {
         f(&self.entry, &self.instance)
     }
 
+    /// The Vulkan version the instance was created with, negotiated between the application request and the loader.
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    /// The lowest Vulkan version a physical device may have to be selected.
+    pub fn minimum_api_version(&self) -> u32 {
+        self.minimum_api_version
+    }
+
     pub fn entry(&self) -> &ash::Entry {
         &self.entry
     }
@@ -188,6 +510,18 @@ impl Instance {
     pub fn loader(&self) -> &InstanceLoader {
         &self.loader
     }
+
+    pub fn layers(&self) -> &HashSet<&'static CStr> {
+        &self.layers
+    }
+
+    pub fn extensions(&self) -> &HashSet<&'static CStr> {
+        &self.extensions
+    }
+
+    pub fn debug_messenger(&self) -> Option<&DebugMessenger> {
+        self.debug_messenger.as_ref()
+    }
 }
 
 impl Deref for Instance {
@@ -0,0 +1,265 @@
+use ash::vk;
+use serde::{Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+
+/// The value of a single member of `VkPhysicalDeviceLimits`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum LimitValue {
+    UInt(u64),
+    Int(i64),
+    Float(f32),
+    Bool(bool),
+    UIntArray(Vec<u64>),
+    FloatArray(Vec<f32>),
+    #[serde(serialize_with = "serialize_sample_counts")]
+    SampleCounts(vk::SampleCountFlags),
+}
+
+const SAMPLE_COUNTS: &[(vk::SampleCountFlags, &str)] = &[
+    (vk::SampleCountFlags::TYPE_1, "VK_SAMPLE_COUNT_1_BIT"),
+    (vk::SampleCountFlags::TYPE_2, "VK_SAMPLE_COUNT_2_BIT"),
+    (vk::SampleCountFlags::TYPE_4, "VK_SAMPLE_COUNT_4_BIT"),
+    (vk::SampleCountFlags::TYPE_8, "VK_SAMPLE_COUNT_8_BIT"),
+    (vk::SampleCountFlags::TYPE_16, "VK_SAMPLE_COUNT_16_BIT"),
+    (vk::SampleCountFlags::TYPE_32, "VK_SAMPLE_COUNT_32_BIT"),
+    (vk::SampleCountFlags::TYPE_64, "VK_SAMPLE_COUNT_64_BIT"),
+];
+
+/// The names of the bits in `flags`, as they are spelled in the Vulkan headers and in Vulkan Profiles files.
+pub fn sample_count_names(flags: vk::SampleCountFlags) -> Vec<&'static str> {
+    SAMPLE_COUNTS
+        .iter()
+        .filter(|(bit, _)| flags.contains(*bit))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+pub fn sample_count_from_name(name: &str) -> Option<vk::SampleCountFlags> {
+    SAMPLE_COUNTS
+        .iter()
+        .find(|(_, bit_name)| *bit_name == name)
+        .map(|(bit, _)| *bit)
+}
+
+fn serialize_sample_counts<S: Serializer>(
+    flags: &vk::SampleCountFlags,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(sample_count_names(*flags))
+}
+
+impl Display for LimitValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitValue::UInt(value) => write!(f, "{}", value),
+            LimitValue::Int(value) => write!(f, "{}", value),
+            LimitValue::Float(value) => write!(f, "{}", value),
+            LimitValue::Bool(value) => write!(f, "{}", value),
+            LimitValue::UIntArray(values) => write!(f, "{:?}", values),
+            LimitValue::FloatArray(values) => write!(f, "{:?}", values),
+            LimitValue::SampleCounts(flags) => write!(f, "{}", sample_count_names(*flags).join(" | ")),
+        }
+    }
+}
+
+/// How a device limit is compared against a required value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LimitComparison {
+    /// The device value must be at least the required value (e.g. `maxImageDimension2D`).
+    AtLeast,
+    /// The device value must be at most the required value (alignments, granularities and the `min*` limits).
+    AtMost,
+    /// A `[min, max]` range that must contain the required range.
+    Range,
+    /// A set of flags that must contain the required flags.
+    Contains,
+    /// A boolean that must be true if the required value is true.
+    Enabled,
+}
+
+impl LimitComparison {
+    pub fn for_limit(name: &str) -> Self {
+        match name {
+            "pointSizeRange" | "lineWidthRange" | "viewportBoundsRange" => LimitComparison::Range,
+            "timestampComputeAndGraphics" | "strictLines" | "standardSampleLocations" => {
+                LimitComparison::Enabled
+            }
+            name if name.ends_with("SampleCounts") => LimitComparison::Contains,
+            name if name.starts_with("min")
+                || name.ends_with("Alignment")
+                || name.ends_with("Granularity")
+                || name == "nonCoherentAtomSize"
+                || name == "timestampPeriod" =>
+            {
+                LimitComparison::AtMost
+            }
+            _ => LimitComparison::AtLeast,
+        }
+    }
+}
+
+impl LimitValue {
+    /// Checks this device value against `required` with the comparison used for `name`. Returns `None` if `required`
+    /// doesn't have a shape that can be compared with this value.
+    pub fn satisfies(&self, name: &str, required: &serde_json::Value) -> Option<bool> {
+        let comparison = LimitComparison::for_limit(name);
+
+        let compare = |actual: f64, required: f64| match comparison {
+            LimitComparison::AtMost => actual <= required,
+            _ => actual >= required,
+        };
+
+        match (self, comparison) {
+            (LimitValue::UInt(value), _) => Some(compare(*value as f64, required.as_f64()?)),
+            (LimitValue::Int(value), _) => Some(compare(*value as f64, required.as_f64()?)),
+            (LimitValue::Float(value), _) => Some(compare(*value as f64, required.as_f64()?)),
+            (LimitValue::Bool(value), _) => Some(*value || !required.as_bool()?),
+            (LimitValue::UIntArray(values), _) => {
+                let required = required.as_array()?;
+                if required.len() != values.len() {
+                    return None;
+                }
+
+                values
+                    .iter()
+                    .zip(required)
+                    .map(|(value, required)| Some(compare(*value as f64, required.as_f64()?)))
+                    .collect::<Option<Vec<bool>>>()
+                    .map(|results| results.into_iter().all(|ok| ok))
+            }
+            (LimitValue::FloatArray(values), LimitComparison::Range) => {
+                let required = required.as_array()?;
+                if required.len() != 2 || values.len() != 2 {
+                    return None;
+                }
+
+                Some(
+                    values[0] as f64 <= required[0].as_f64()?
+                        && values[1] as f64 >= required[1].as_f64()?,
+                )
+            }
+            (LimitValue::FloatArray(_), _) => None,
+            (LimitValue::SampleCounts(flags), _) => {
+                let mut required_flags = vk::SampleCountFlags::empty();
+                for name in required.as_array()? {
+                    required_flags |= sample_count_from_name(name.as_str()?)?;
+                }
+
+                Some(flags.contains(required_flags))
+            }
+        }
+    }
+}
+
+/// Every member of `VkPhysicalDeviceLimits`, keyed by its Vulkan name.
+pub fn limits_table(limits: &vk::PhysicalDeviceLimits) -> Vec<(&'static str, LimitValue)> {
+    vec![
+        ("maxImageDimension1D", LimitValue::UInt(limits.max_image_dimension1_d as u64)),
+        ("maxImageDimension2D", LimitValue::UInt(limits.max_image_dimension2_d as u64)),
+        ("maxImageDimension3D", LimitValue::UInt(limits.max_image_dimension3_d as u64)),
+        ("maxImageDimensionCube", LimitValue::UInt(limits.max_image_dimension_cube as u64)),
+        ("maxImageArrayLayers", LimitValue::UInt(limits.max_image_array_layers as u64)),
+        ("maxTexelBufferElements", LimitValue::UInt(limits.max_texel_buffer_elements as u64)),
+        ("maxUniformBufferRange", LimitValue::UInt(limits.max_uniform_buffer_range as u64)),
+        ("maxStorageBufferRange", LimitValue::UInt(limits.max_storage_buffer_range as u64)),
+        ("maxPushConstantsSize", LimitValue::UInt(limits.max_push_constants_size as u64)),
+        ("maxMemoryAllocationCount", LimitValue::UInt(limits.max_memory_allocation_count as u64)),
+        ("maxSamplerAllocationCount", LimitValue::UInt(limits.max_sampler_allocation_count as u64)),
+        ("bufferImageGranularity", LimitValue::UInt(limits.buffer_image_granularity)),
+        ("sparseAddressSpaceSize", LimitValue::UInt(limits.sparse_address_space_size)),
+        ("maxBoundDescriptorSets", LimitValue::UInt(limits.max_bound_descriptor_sets as u64)),
+        ("maxPerStageDescriptorSamplers", LimitValue::UInt(limits.max_per_stage_descriptor_samplers as u64)),
+        ("maxPerStageDescriptorUniformBuffers", LimitValue::UInt(limits.max_per_stage_descriptor_uniform_buffers as u64)),
+        ("maxPerStageDescriptorStorageBuffers", LimitValue::UInt(limits.max_per_stage_descriptor_storage_buffers as u64)),
+        ("maxPerStageDescriptorSampledImages", LimitValue::UInt(limits.max_per_stage_descriptor_sampled_images as u64)),
+        ("maxPerStageDescriptorStorageImages", LimitValue::UInt(limits.max_per_stage_descriptor_storage_images as u64)),
+        ("maxPerStageDescriptorInputAttachments", LimitValue::UInt(limits.max_per_stage_descriptor_input_attachments as u64)),
+        ("maxPerStageResources", LimitValue::UInt(limits.max_per_stage_resources as u64)),
+        ("maxDescriptorSetSamplers", LimitValue::UInt(limits.max_descriptor_set_samplers as u64)),
+        ("maxDescriptorSetUniformBuffers", LimitValue::UInt(limits.max_descriptor_set_uniform_buffers as u64)),
+        ("maxDescriptorSetUniformBuffersDynamic", LimitValue::UInt(limits.max_descriptor_set_uniform_buffers_dynamic as u64)),
+        ("maxDescriptorSetStorageBuffers", LimitValue::UInt(limits.max_descriptor_set_storage_buffers as u64)),
+        ("maxDescriptorSetStorageBuffersDynamic", LimitValue::UInt(limits.max_descriptor_set_storage_buffers_dynamic as u64)),
+        ("maxDescriptorSetSampledImages", LimitValue::UInt(limits.max_descriptor_set_sampled_images as u64)),
+        ("maxDescriptorSetStorageImages", LimitValue::UInt(limits.max_descriptor_set_storage_images as u64)),
+        ("maxDescriptorSetInputAttachments", LimitValue::UInt(limits.max_descriptor_set_input_attachments as u64)),
+        ("maxVertexInputAttributes", LimitValue::UInt(limits.max_vertex_input_attributes as u64)),
+        ("maxVertexInputBindings", LimitValue::UInt(limits.max_vertex_input_bindings as u64)),
+        ("maxVertexInputAttributeOffset", LimitValue::UInt(limits.max_vertex_input_attribute_offset as u64)),
+        ("maxVertexInputBindingStride", LimitValue::UInt(limits.max_vertex_input_binding_stride as u64)),
+        ("maxVertexOutputComponents", LimitValue::UInt(limits.max_vertex_output_components as u64)),
+        ("maxTessellationGenerationLevel", LimitValue::UInt(limits.max_tessellation_generation_level as u64)),
+        ("maxTessellationPatchSize", LimitValue::UInt(limits.max_tessellation_patch_size as u64)),
+        ("maxTessellationControlPerVertexInputComponents", LimitValue::UInt(limits.max_tessellation_control_per_vertex_input_components as u64)),
+        ("maxTessellationControlPerVertexOutputComponents", LimitValue::UInt(limits.max_tessellation_control_per_vertex_output_components as u64)),
+        ("maxTessellationControlPerPatchOutputComponents", LimitValue::UInt(limits.max_tessellation_control_per_patch_output_components as u64)),
+        ("maxTessellationControlTotalOutputComponents", LimitValue::UInt(limits.max_tessellation_control_total_output_components as u64)),
+        ("maxTessellationEvaluationInputComponents", LimitValue::UInt(limits.max_tessellation_evaluation_input_components as u64)),
+        ("maxTessellationEvaluationOutputComponents", LimitValue::UInt(limits.max_tessellation_evaluation_output_components as u64)),
+        ("maxGeometryShaderInvocations", LimitValue::UInt(limits.max_geometry_shader_invocations as u64)),
+        ("maxGeometryInputComponents", LimitValue::UInt(limits.max_geometry_input_components as u64)),
+        ("maxGeometryOutputComponents", LimitValue::UInt(limits.max_geometry_output_components as u64)),
+        ("maxGeometryOutputVertices", LimitValue::UInt(limits.max_geometry_output_vertices as u64)),
+        ("maxGeometryTotalOutputComponents", LimitValue::UInt(limits.max_geometry_total_output_components as u64)),
+        ("maxFragmentInputComponents", LimitValue::UInt(limits.max_fragment_input_components as u64)),
+        ("maxFragmentOutputAttachments", LimitValue::UInt(limits.max_fragment_output_attachments as u64)),
+        ("maxFragmentDualSrcAttachments", LimitValue::UInt(limits.max_fragment_dual_src_attachments as u64)),
+        ("maxFragmentCombinedOutputResources", LimitValue::UInt(limits.max_fragment_combined_output_resources as u64)),
+        ("maxComputeSharedMemorySize", LimitValue::UInt(limits.max_compute_shared_memory_size as u64)),
+        ("maxComputeWorkGroupCount", LimitValue::UIntArray(limits.max_compute_work_group_count.iter().map(|v| *v as u64).collect())),
+        ("maxComputeWorkGroupInvocations", LimitValue::UInt(limits.max_compute_work_group_invocations as u64)),
+        ("maxComputeWorkGroupSize", LimitValue::UIntArray(limits.max_compute_work_group_size.iter().map(|v| *v as u64).collect())),
+        ("subPixelPrecisionBits", LimitValue::UInt(limits.sub_pixel_precision_bits as u64)),
+        ("subTexelPrecisionBits", LimitValue::UInt(limits.sub_texel_precision_bits as u64)),
+        ("mipmapPrecisionBits", LimitValue::UInt(limits.mipmap_precision_bits as u64)),
+        ("maxDrawIndexedIndexValue", LimitValue::UInt(limits.max_draw_indexed_index_value as u64)),
+        ("maxDrawIndirectCount", LimitValue::UInt(limits.max_draw_indirect_count as u64)),
+        ("maxSamplerLodBias", LimitValue::Float(limits.max_sampler_lod_bias)),
+        ("maxSamplerAnisotropy", LimitValue::Float(limits.max_sampler_anisotropy)),
+        ("maxViewports", LimitValue::UInt(limits.max_viewports as u64)),
+        ("maxViewportDimensions", LimitValue::UIntArray(limits.max_viewport_dimensions.iter().map(|v| *v as u64).collect())),
+        ("viewportBoundsRange", LimitValue::FloatArray(limits.viewport_bounds_range.to_vec())),
+        ("viewportSubPixelBits", LimitValue::UInt(limits.viewport_sub_pixel_bits as u64)),
+        ("minMemoryMapAlignment", LimitValue::UInt(limits.min_memory_map_alignment as u64)),
+        ("minTexelBufferOffsetAlignment", LimitValue::UInt(limits.min_texel_buffer_offset_alignment)),
+        ("minUniformBufferOffsetAlignment", LimitValue::UInt(limits.min_uniform_buffer_offset_alignment)),
+        ("minStorageBufferOffsetAlignment", LimitValue::UInt(limits.min_storage_buffer_offset_alignment)),
+        ("minTexelOffset", LimitValue::Int(limits.min_texel_offset as i64)),
+        ("maxTexelOffset", LimitValue::UInt(limits.max_texel_offset as u64)),
+        ("minTexelGatherOffset", LimitValue::Int(limits.min_texel_gather_offset as i64)),
+        ("maxTexelGatherOffset", LimitValue::UInt(limits.max_texel_gather_offset as u64)),
+        ("minInterpolationOffset", LimitValue::Float(limits.min_interpolation_offset)),
+        ("maxInterpolationOffset", LimitValue::Float(limits.max_interpolation_offset)),
+        ("subPixelInterpolationOffsetBits", LimitValue::UInt(limits.sub_pixel_interpolation_offset_bits as u64)),
+        ("maxFramebufferWidth", LimitValue::UInt(limits.max_framebuffer_width as u64)),
+        ("maxFramebufferHeight", LimitValue::UInt(limits.max_framebuffer_height as u64)),
+        ("maxFramebufferLayers", LimitValue::UInt(limits.max_framebuffer_layers as u64)),
+        ("framebufferColorSampleCounts", LimitValue::SampleCounts(limits.framebuffer_color_sample_counts)),
+        ("framebufferDepthSampleCounts", LimitValue::SampleCounts(limits.framebuffer_depth_sample_counts)),
+        ("framebufferStencilSampleCounts", LimitValue::SampleCounts(limits.framebuffer_stencil_sample_counts)),
+        ("framebufferNoAttachmentsSampleCounts", LimitValue::SampleCounts(limits.framebuffer_no_attachments_sample_counts)),
+        ("maxColorAttachments", LimitValue::UInt(limits.max_color_attachments as u64)),
+        ("sampledImageColorSampleCounts", LimitValue::SampleCounts(limits.sampled_image_color_sample_counts)),
+        ("sampledImageIntegerSampleCounts", LimitValue::SampleCounts(limits.sampled_image_integer_sample_counts)),
+        ("sampledImageDepthSampleCounts", LimitValue::SampleCounts(limits.sampled_image_depth_sample_counts)),
+        ("sampledImageStencilSampleCounts", LimitValue::SampleCounts(limits.sampled_image_stencil_sample_counts)),
+        ("storageImageSampleCounts", LimitValue::SampleCounts(limits.storage_image_sample_counts)),
+        ("maxSampleMaskWords", LimitValue::UInt(limits.max_sample_mask_words as u64)),
+        ("timestampComputeAndGraphics", LimitValue::Bool(limits.timestamp_compute_and_graphics == vk::TRUE)),
+        ("timestampPeriod", LimitValue::Float(limits.timestamp_period)),
+        ("maxClipDistances", LimitValue::UInt(limits.max_clip_distances as u64)),
+        ("maxCullDistances", LimitValue::UInt(limits.max_cull_distances as u64)),
+        ("maxCombinedClipAndCullDistances", LimitValue::UInt(limits.max_combined_clip_and_cull_distances as u64)),
+        ("discreteQueuePriorities", LimitValue::UInt(limits.discrete_queue_priorities as u64)),
+        ("pointSizeRange", LimitValue::FloatArray(limits.point_size_range.to_vec())),
+        ("lineWidthRange", LimitValue::FloatArray(limits.line_width_range.to_vec())),
+        ("pointSizeGranularity", LimitValue::Float(limits.point_size_granularity)),
+        ("lineWidthGranularity", LimitValue::Float(limits.line_width_granularity)),
+        ("strictLines", LimitValue::Bool(limits.strict_lines == vk::TRUE)),
+        ("standardSampleLocations", LimitValue::Bool(limits.standard_sample_locations == vk::TRUE)),
+        ("optimalBufferCopyOffsetAlignment", LimitValue::UInt(limits.optimal_buffer_copy_offset_alignment)),
+        ("optimalBufferCopyRowPitchAlignment", LimitValue::UInt(limits.optimal_buffer_copy_row_pitch_alignment)),
+        ("nonCoherentAtomSize", LimitValue::UInt(limits.non_coherent_atom_size)),
+    ]
+}
@@ -1,23 +1,42 @@
+pub mod debug;
+pub mod deletion_queue;
 pub mod device;
 pub mod instance;
+pub mod limits;
+pub mod physical_device;
 pub mod platform;
+pub mod queue;
+pub mod queue_planner;
 pub mod queues;
+pub mod report;
+pub mod submission;
 pub mod command_pool;
+pub mod version;
 
+use crate::app::extension_features::FeatureRegistry;
+use crate::app::feature_request::DeviceFeature;
 use crate::errors::CreateSurfaceError;
+use crate::render::context::deletion_queue::DeletionQueue;
 use crate::render::context::device::Device;
 use crate::render::context::instance::Instance;
-use crate::render::frame_set::FrameSet;
+use crate::render::context::physical_device::PhysicalDeviceInfo;
+use crate::render::context::report::DeviceReport;
+use crate::render::frame_set::{FrameSet, FramesInFlight};
+use crate::render::memory::MemoryAllocator;
 use crate::EngineCallbackHandler;
 use ash::prelude::VkResult;
 use ash::vk;
+use log::error;
 use winit::event_loop::EventLoop;
-use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle};
 
 pub struct VulkanContext {
     instance: Instance,
-    physical_device: vk::PhysicalDevice,
+    physical_device: PhysicalDeviceInfo,
     device: Device,
+    memory: MemoryAllocator,
+    deletion_queue: DeletionQueue,
+    headless: bool,
 }
 
 impl VulkanContext {
@@ -25,20 +44,46 @@ impl VulkanContext {
         event_loop: &EventLoop<()>,
         app: &mut A,
     ) -> anyhow::Result<Self> {
-        let instance = Instance::new(event_loop, app)?;
+        Self::create(Some(event_loop.display_handle()?.as_raw()), app)
+    }
+
+    /// Creates a context without a display connection, for compute-only tools, tests and machines without a display.
+    ///
+    /// Surface extensions, the swapchain extension and the [`QueueLabel::Presentation`](queues::QueueLabel::Presentation)
+    /// queue are skipped, everything else is negotiated exactly like a windowed context. Windows cannot be created on a
+    /// headless context.
+    pub fn new_headless<A: EngineCallbackHandler>(app: &mut A) -> anyhow::Result<Self> {
+        Self::create(None, app)
+    }
+
+    fn create<A: EngineCallbackHandler>(
+        display_handle: Option<RawDisplayHandle>,
+        app: &mut A,
+    ) -> anyhow::Result<Self> {
+        let instance = Instance::new(display_handle, app)?;
 
         app.on_instance(&instance);
 
-        let physical_device = instance.select_physical_device(app)?;
-        app.on_physical_device(physical_device, &instance);
+        let mut feature_registry = FeatureRegistry::default();
+        app.on_register_feature_structs(&mut feature_registry);
+
+        let physical_device =
+            instance.select_physical_device(app, display_handle.is_some(), &feature_registry)?;
+        app.on_physical_device(&physical_device, &instance);
 
-        let device = Device::new(event_loop, &instance, physical_device, app)?;
+        let device = Device::new(display_handle, &instance, &physical_device, app)?;
         app.on_device(&device);
 
+        let memory = MemoryAllocator::new(&instance, &device, &physical_device)?;
+        let deletion_queue = DeletionQueue::new(device.queues().values().flatten().cloned().collect());
+
         Ok(Self {
             instance,
             physical_device,
             device,
+            memory,
+            deletion_queue,
+            headless: display_handle.is_none(),
         })
     }
 
@@ -65,13 +110,59 @@ impl VulkanContext {
     }
 
     pub fn physical_device(&self) -> vk::PhysicalDevice {
-        self.physical_device
+        self.physical_device.handle()
+    }
+
+    pub fn physical_device_info(&self) -> &PhysicalDeviceInfo {
+        &self.physical_device
     }
 
     pub fn instance(&self) -> &Instance {
         &self.instance
     }
 
+    /// The device memory allocator backing [`Buffer`](crate::render::memory::Buffer)s and
+    /// [`Image`](crate::render::memory::Image)s.
+    pub fn memory(&self) -> &MemoryAllocator {
+        &self.memory
+    }
+
+    /// The queue of resources waiting for the GPU before being destroyed.
+    pub fn deletion_queue(&self) -> &DeletionQueue {
+        &self.deletion_queue
+    }
+
+    /// Destroys the deferred resources the GPU is done with, the event loop calls it once per frame.
+    pub fn collect_garbage(&self) -> VkResult<()> {
+        self.deletion_queue.collect(self)
+    }
+
+    /// Waits for the device to be idle and destroys every deferred resource.
+    pub fn collect_garbage_idle(&self) -> VkResult<()> {
+        self.device.wait_idle()?;
+        unsafe { self.deletion_queue.collect_idle(self) };
+        Ok(())
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
+
+    /// Whether `feature` was enabled on the device, see [`Device::has_feature`].
+    pub fn has_feature(&self, feature: DeviceFeature) -> bool {
+        self.device.has_feature(feature)
+    }
+
+    /// Captures the selected device, the enabled extensions and features, the queue assignments and the limits.
+    pub fn report(&self) -> DeviceReport {
+        DeviceReport::new(self)
+    }
+
+    /// The Vulkan version negotiated for the device, see [`EngineCallbackHandler::api_version`].
+    pub fn api_version(&self) -> u32 {
+        self.device.api_version()
+    }
+
     pub fn create_semaphore(&self) -> VkResult<vk::Semaphore> {
         unsafe {
             self.device
@@ -79,6 +170,20 @@ impl VulkanContext {
         }
     }
 
+    /// Creates a timeline semaphore starting at `initial_value`.
+    pub fn create_timeline_semaphore(&self, initial_value: u64) -> VkResult<vk::Semaphore> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+
+        unsafe {
+            self.device.create_semaphore(
+                &vk::SemaphoreCreateInfo::default().push_next(&mut type_info),
+                None,
+            )
+        }
+    }
+
     pub fn create_fence(&self) -> VkResult<vk::Fence> {
         unsafe {
             self.device
@@ -103,7 +208,7 @@ impl VulkanContext {
             self.instance
                 .loader()
                 .surface()
-                .get_physical_device_surface_present_modes(self.physical_device, surface)
+                .get_physical_device_surface_present_modes(self.physical_device(), surface)
         }
     }
 
@@ -115,7 +220,7 @@ impl VulkanContext {
             self.instance
                 .loader()
                 .surface()
-                .get_physical_device_surface_formats(self.physical_device, surface)
+                .get_physical_device_surface_formats(self.physical_device(), surface)
         }
     }
 
@@ -127,20 +232,20 @@ impl VulkanContext {
             self.instance
                 .loader()
                 .surface()
-                .get_physical_device_surface_capabilities(self.physical_device, surface)
+                .get_physical_device_surface_capabilities(self.physical_device(), surface)
         }
     }
 
-    pub fn create_semaphores(&self) -> VkResult<FrameSet<vk::Semaphore>> {
-        FrameSet::<VkResult<vk::Semaphore>>::create_factory(|_| self.create_semaphore()).promote_errors()
+    pub fn create_semaphores(&self, frames_in_flight: FramesInFlight) -> VkResult<FrameSet<vk::Semaphore>> {
+        FrameSet::<VkResult<vk::Semaphore>>::create_factory(frames_in_flight, |_| self.create_semaphore()).promote_errors()
     }
 
-    pub fn create_fences(&self) -> VkResult<FrameSet<vk::Fence>> {
-        FrameSet::<VkResult<vk::Fence>>::create_factory(|_| self.create_fence()).promote_errors()
+    pub fn create_fences(&self, frames_in_flight: FramesInFlight) -> VkResult<FrameSet<vk::Fence>> {
+        FrameSet::<VkResult<vk::Fence>>::create_factory(frames_in_flight, |_| self.create_fence()).promote_errors()
     }
 
-    pub fn create_fences_signaled(&self) -> VkResult<FrameSet<vk::Fence>> {
-        FrameSet::<VkResult<vk::Fence>>::create_factory(|_| self.create_fence_signaled()).promote_errors()
+    pub fn create_fences_signaled(&self, frames_in_flight: FramesInFlight) -> VkResult<FrameSet<vk::Fence>> {
+        FrameSet::<VkResult<vk::Fence>>::create_factory(frames_in_flight, |_| self.create_fence_signaled()).promote_errors()
     }
 
     pub fn wait_for_fence(&self, fence: vk::Fence) -> VkResult<()> {
@@ -159,3 +264,12 @@ impl VulkanContext {
         unsafe { self.device.reset_fences(fences) }
     }
 }
+
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        // deferred resources hold allocations, they have to go before the allocator
+        if let Err(e) = self.collect_garbage_idle() {
+            error!("[context] Failed to wait for the device before destroying deferred resources: {}", e);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use crate::app::extension_features::FeatureRegistry;
+use crate::app::feature_request::{DeviceFeature, FeatureStructs};
+use crate::render::context::version::{major_minor, promoted_to_core};
+use ash::{khr, vk};
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Environment variable used to force the selection of a physical device (see [`PhysicalDeviceSelector`]).
+pub const PHYSICAL_DEVICE_OVERRIDE_ENV: &str = "NEURON_PHYSICAL_DEVICE";
+
+#[derive(Clone, Debug)]
+pub struct DriverInfo {
+    pub id: vk::DriverId,
+    pub name: String,
+    pub info: String,
+    pub conformance_version: vk::ConformanceVersion,
+}
+
+/// Everything the engine knows about a physical device before creating a logical device on it.
+pub struct PhysicalDeviceInfo {
+    handle: vk::PhysicalDevice,
+    index: usize,
+    properties: vk::PhysicalDeviceProperties,
+    negotiated_api_version: u32,
+    device_uuid: [u8; vk::UUID_SIZE],
+    driver: Option<DriverInfo>,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    queue_families: Vec<vk::QueueFamilyProperties>,
+    video_codec_operations: Vec<vk::VideoCodecOperationFlagsKHR>,
+    extensions: HashSet<CString>,
+    features: FeatureStructs<'static>,
+}
+
+impl PhysicalDeviceInfo {
+    pub(crate) fn query(
+        instance: &ash::Instance,
+        handle: vk::PhysicalDevice,
+        index: usize,
+        instance_api_version: u32,
+        feature_registry: &FeatureRegistry,
+    ) -> anyhow::Result<Self> {
+        let properties = unsafe { instance.get_physical_device_properties(handle) };
+        let negotiated_api_version = major_minor(properties.api_version).min(instance_api_version);
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(handle) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(handle) };
+
+        let extensions = unsafe { instance.enumerate_device_extension_properties(handle) }?
+            .iter()
+            .map(|props| unsafe { CStr::from_ptr(props.extension_name.as_ptr()).to_owned() })
+            .collect::<HashSet<CString>>();
+
+        let has_driver_properties = promoted_to_core(khr::driver_properties::NAME, negotiated_api_version)
+            || extensions.contains(khr::driver_properties::NAME);
+
+        let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+        let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+        {
+            let mut properties2 = vk::PhysicalDeviceProperties2::default().push_next(&mut id_properties);
+            if has_driver_properties {
+                properties2 = properties2.push_next(&mut driver_properties);
+            }
+            unsafe { instance.get_physical_device_properties2(handle, &mut properties2) };
+        }
+
+        let driver = if has_driver_properties {
+            Some(DriverInfo {
+                id: driver_properties.driver_id,
+                name: driver_properties
+                    .driver_name_as_c_str()?
+                    .to_string_lossy()
+                    .into_owned(),
+                info: driver_properties
+                    .driver_info_as_c_str()?
+                    .to_string_lossy()
+                    .into_owned(),
+                conformance_version: driver_properties.conformance_version,
+            })
+        } else {
+            None
+        };
+
+        let video_codec_operations = if extensions.contains(khr::video_queue::NAME) {
+            Self::query_video_codec_operations(instance, handle)
+        } else {
+            vec![vk::VideoCodecOperationFlagsKHR::empty(); queue_families.len()]
+        };
+
+        let features = FeatureStructs::available(
+            instance,
+            handle,
+            negotiated_api_version,
+            feature_registry,
+            |extension| {
+                extensions.contains(extension) || promoted_to_core(extension, negotiated_api_version)
+            },
+        );
+
+        Ok(Self {
+            handle,
+            index,
+            properties,
+            negotiated_api_version,
+            device_uuid: id_properties.device_uuid,
+            driver,
+            memory_properties,
+            queue_families,
+            video_codec_operations,
+            extensions,
+            features,
+        })
+    }
+
+    fn query_video_codec_operations(
+        instance: &ash::Instance,
+        handle: vk::PhysicalDevice,
+    ) -> Vec<vk::VideoCodecOperationFlagsKHR> {
+        let count = unsafe { instance.get_physical_device_queue_family_properties2_len(handle) };
+        let mut video_properties = vec![vk::QueueFamilyVideoPropertiesKHR::default(); count];
+        {
+            let mut properties = video_properties
+                .iter_mut()
+                .map(|video| vk::QueueFamilyProperties2::default().push_next(video))
+                .collect::<Vec<vk::QueueFamilyProperties2>>();
+            unsafe { instance.get_physical_device_queue_family_properties2(handle, &mut properties) };
+        }
+
+        video_properties
+            .iter()
+            .map(|video| video.video_codec_operations)
+            .collect()
+    }
+
+    pub fn handle(&self) -> vk::PhysicalDevice {
+        self.handle
+    }
+
+    /// Index of this device in the order returned by `vkEnumeratePhysicalDevices`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn name(&self) -> String {
+        self.properties
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    pub fn device_type(&self) -> vk::PhysicalDeviceType {
+        self.properties.device_type
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        Uuid::from_bytes(self.device_uuid)
+    }
+
+    /// The Vulkan version reported by the device.
+    pub fn api_version(&self) -> u32 {
+        self.properties.api_version
+    }
+
+    /// The Vulkan version the engine uses with this device: the lower of the device version and the instance version.
+    pub fn negotiated_api_version(&self) -> u32 {
+        self.negotiated_api_version
+    }
+
+    pub fn driver_version(&self) -> u32 {
+        self.properties.driver_version
+    }
+
+    pub fn vendor_id(&self) -> u32 {
+        self.properties.vendor_id
+    }
+
+    pub fn device_id(&self) -> u32 {
+        self.properties.device_id
+    }
+
+    pub fn properties(&self) -> &vk::PhysicalDeviceProperties {
+        &self.properties
+    }
+
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.properties.limits
+    }
+
+    /// Driver identification, only available on Vulkan 1.2 devices or with `VK_KHR_driver_properties`.
+    pub fn driver(&self) -> Option<&DriverInfo> {
+        self.driver.as_ref()
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    pub fn memory_heaps(&self) -> &[vk::MemoryHeap] {
+        self.memory_properties.memory_heaps_as_slice()
+    }
+
+    pub fn memory_types(&self) -> &[vk::MemoryType] {
+        self.memory_properties.memory_types_as_slice()
+    }
+
+    /// Total size of all device-local memory heaps, in bytes.
+    pub fn device_local_memory(&self) -> u64 {
+        self.memory_heaps()
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    pub fn queue_families(&self) -> &[vk::QueueFamilyProperties] {
+        &self.queue_families
+    }
+
+    /// The video codec operations supported by a queue family, empty without `VK_KHR_video_queue`.
+    pub fn video_codec_operations(&self, family: u32) -> vk::VideoCodecOperationFlagsKHR {
+        self.video_codec_operations
+            .get(family as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn has_queue_family(&self, flags: vk::QueueFlags) -> bool {
+        self.queue_families
+            .iter()
+            .any(|family| family.queue_flags.contains(flags))
+    }
+
+    pub fn extensions(&self) -> &HashSet<CString> {
+        &self.extensions
+    }
+
+    pub fn supports_extension(&self, extension: &CStr) -> bool {
+        self.extensions.contains(extension)
+    }
+
+    pub fn features(&self) -> &FeatureStructs<'static> {
+        &self.features
+    }
+
+    pub fn supports_feature(&self, feature: DeviceFeature) -> bool {
+        self.features.supports(feature)
+    }
+
+    /// The score used when the application does not provide its own: discrete over integrated over virtual over CPU,
+    /// ties broken by the amount of device-local memory.
+    pub fn default_score(&self) -> i64 {
+        let type_score: i64 = match self.device_type() {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+
+        // device-local memory in MiB is comfortably below 1 << 32
+        type_score << 32 | (self.device_local_memory() >> 20).min(u32::MAX as u64) as i64
+    }
+
+    pub(crate) fn matches(&self, selector: &PhysicalDeviceSelector) -> bool {
+        match selector {
+            PhysicalDeviceSelector::Index(index) => self.index == *index,
+            PhysicalDeviceSelector::Uuid(uuid) => &self.uuid() == uuid,
+            PhysicalDeviceSelector::Name(name) => self
+                .name()
+                .to_lowercase()
+                .contains(name.to_lowercase().as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl PhysicalDeviceInfo {
+    /// A device that was never queried, with no features, one queue family and a single heap of `device_local_memory`
+    /// bytes.
+    pub(crate) fn for_tests(
+        index: usize,
+        properties: vk::PhysicalDeviceProperties,
+        device_local_memory: u64,
+        extensions: &[&CStr],
+    ) -> Self {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_heap_count: 1,
+            ..Default::default()
+        };
+        memory_properties.memory_heaps[0] = vk::MemoryHeap {
+            size: device_local_memory,
+            flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+        };
+
+        Self {
+            handle: vk::PhysicalDevice::null(),
+            index,
+            properties,
+            negotiated_api_version: major_minor(properties.api_version),
+            device_uuid: [index as u8; vk::UUID_SIZE],
+            driver: None,
+            memory_properties,
+            queue_families: vec![vk::QueueFamilyProperties::default()
+                .queue_flags(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER)
+                .queue_count(1)],
+            video_codec_operations: vec![vk::VideoCodecOperationFlagsKHR::empty()],
+            extensions: extensions.iter().map(|&extension| extension.to_owned()).collect(),
+            features: FeatureStructs::default(),
+        }
+    }
+}
+
+impl Display for PhysicalDeviceInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {} ({:?}, {})",
+            self.index,
+            self.name(),
+            self.device_type(),
+            self.uuid()
+        )
+    }
+}
+
+/// Forces the selection of a specific physical device.
+///
+/// Parsed from [`PHYSICAL_DEVICE_OVERRIDE_ENV`] as an index if the value is a number, as a UUID if it is one, and
+/// otherwise as a case-insensitive substring of the device name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PhysicalDeviceSelector {
+    Index(usize),
+    Name(String),
+    Uuid(Uuid),
+}
+
+impl PhysicalDeviceSelector {
+    pub fn from_env() -> Option<Self> {
+        std::env::var(PHYSICAL_DEVICE_OVERRIDE_ENV)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .and_then(|value| value.parse().ok())
+    }
+}
+
+impl FromStr for PhysicalDeviceSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(index) = s.parse::<usize>() {
+            Ok(Self::Index(index))
+        } else if let Ok(uuid) = Uuid::parse_str(s) {
+            Ok(Self::Uuid(uuid))
+        } else {
+            Ok(Self::Name(s.to_owned()))
+        }
+    }
+}
+
+impl Display for PhysicalDeviceSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhysicalDeviceSelector::Index(index) => write!(f, "index {}", index),
+            PhysicalDeviceSelector::Name(name) => write!(f, "name {:?}", name),
+            PhysicalDeviceSelector::Uuid(uuid) => write!(f, "uuid {}", uuid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+
+    fn device(index: usize, name: &CStr, device_type: vk::PhysicalDeviceType, memory: u64) -> PhysicalDeviceInfo {
+        let properties = vk::PhysicalDeviceProperties::default()
+            .device_name(name)
+            .unwrap()
+            .device_type(device_type)
+            .api_version(vk::API_VERSION_1_3);
+        PhysicalDeviceInfo::for_tests(index, properties, memory, &[])
+    }
+
+    #[test]
+    fn selectors_parse_indices_uuids_and_names() {
+        assert_eq!("1".parse(), Ok(PhysicalDeviceSelector::Index(1)));
+        assert_eq!(" 2 ".parse(), Ok(PhysicalDeviceSelector::Index(2)));
+        assert_eq!(
+            "67e55044-10b1-426f-9247-bb680e5fe0c8".parse(),
+            Ok(PhysicalDeviceSelector::Uuid(Uuid::from_u128(
+                0x67e55044_10b1_426f_9247_bb680e5fe0c8
+            )))
+        );
+        assert_eq!(
+            " GeForce RTX ".parse(),
+            Ok(PhysicalDeviceSelector::Name("GeForce RTX".to_owned()))
+        );
+        assert_eq!("-1".parse(), Ok(PhysicalDeviceSelector::Name("-1".to_owned())));
+    }
+
+    #[test]
+    fn selectors_display_their_kind() {
+        assert_eq!(PhysicalDeviceSelector::Index(3).to_string(), "index 3");
+        assert_eq!(PhysicalDeviceSelector::Name("Arc".to_owned()).to_string(), "name \"Arc\"");
+        assert_eq!(
+            PhysicalDeviceSelector::Uuid(Uuid::nil()).to_string(),
+            "uuid 00000000-0000-0000-0000-000000000000"
+        );
+    }
+
+    #[test]
+    fn selectors_match_index_uuid_or_name() {
+        let info = device(1, c"AMD Radeon RX 7900", vk::PhysicalDeviceType::DISCRETE_GPU, 0);
+
+        assert!(info.matches(&PhysicalDeviceSelector::Index(1)));
+        assert!(!info.matches(&PhysicalDeviceSelector::Index(0)));
+        assert!(info.matches(&PhysicalDeviceSelector::Uuid(info.uuid())));
+        assert!(!info.matches(&PhysicalDeviceSelector::Uuid(Uuid::nil())));
+        assert!(info.matches(&PhysicalDeviceSelector::Name("radeon rx".to_owned())));
+        assert!(!info.matches(&PhysicalDeviceSelector::Name("GeForce".to_owned())));
+    }
+
+    #[test]
+    fn device_type_outranks_memory() {
+        let score = |device_type, memory| device(0, c"GPU", device_type, memory).default_score();
+
+        let discrete = score(vk::PhysicalDeviceType::DISCRETE_GPU, 256 * MIB);
+        let integrated = score(vk::PhysicalDeviceType::INTEGRATED_GPU, 64 * 1024 * MIB);
+        let virtual_gpu = score(vk::PhysicalDeviceType::VIRTUAL_GPU, 64 * 1024 * MIB);
+        let cpu = score(vk::PhysicalDeviceType::CPU, 64 * 1024 * MIB);
+        let other = score(vk::PhysicalDeviceType::OTHER, 64 * 1024 * MIB);
+
+        assert!(discrete > integrated);
+        assert!(integrated > virtual_gpu);
+        assert!(virtual_gpu > cpu);
+        assert!(cpu > other);
+    }
+
+    #[test]
+    fn memory_breaks_ties() {
+        let score = |memory| device(0, c"GPU", vk::PhysicalDeviceType::DISCRETE_GPU, memory).default_score();
+
+        assert!(score(16 * 1024 * MIB) > score(8 * 1024 * MIB));
+        assert_eq!(score(8 * 1024 * MIB), score(8 * 1024 * MIB + MIB - 1));
+        // absurd heap sizes saturate instead of spilling into the device type
+        assert!(score(u64::MAX) < 5 << 32);
+    }
+}
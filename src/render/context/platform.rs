@@ -36,18 +36,18 @@ unsafe fn grab_visualid_xcb(_connection: *mut xcb_connection_t, _screen: c_int)
 }
 
 pub(crate) fn can_present(display_handle: &RawDisplayHandle, family: u32, instance: &Instance, physical_device: vk::PhysicalDevice) -> bool {
-    match display_handle {
-        &RawDisplayHandle::Windows(_) => unsafe {
+    match *display_handle {
+        RawDisplayHandle::Windows(_) => unsafe {
             let loader = instance.load_extension(khr::win32_surface::Instance::new);
 
             loader.get_physical_device_win32_presentation_support(physical_device, family)
         },
-        &RawDisplayHandle::Xlib(dh) => unsafe {
+        RawDisplayHandle::Xlib(dh) => unsafe {
             let loader = instance.load_extension(khr::xlib_surface::Instance::new);
             let display: *mut Display = dh.display.unwrap().as_ptr() as *mut _;
             loader.get_physical_device_xlib_presentation_support(physical_device, family, display, grab_visualid_xlib(display, dh.screen))
         },
-        &RawDisplayHandle::Xcb(dh) => unsafe {
+        RawDisplayHandle::Xcb(dh) => unsafe {
             let loader = instance.load_extension(khr::xcb_surface::Instance::new);
             let connection: *mut xcb_connection_t = dh.connection.unwrap().as_ptr() as *mut _;
             loader.get_physical_device_xcb_presentation_support(physical_device, family, connection.as_mut().unwrap(), grab_visualid_xcb(connection, dh.screen))
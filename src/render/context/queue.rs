@@ -0,0 +1,202 @@
+use crate::render::context::device::DeviceLoader;
+use crate::render::context::queues::{QueueLabel, QueueRef};
+use crate::render::context::submission::SubmissionHandle;
+use ash::prelude::VkResult;
+use ash::{khr, vk};
+use std::fmt::{Debug, Formatter};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// A device queue, shared by every label that resolved to it.
+///
+/// Vulkan requires access to a queue to be externally synchronized, and flexible requests can alias several labels
+/// onto the same queue, so submitting, presenting and waiting go through a lock held by the queue.
+///
+/// Every queue owns a timeline semaphore: each submit signals its next value once everything submitted so far
+/// completed, and returns it as a [`SubmissionHandle`].
+pub struct Queue {
+    handle: vk::Queue,
+    queue_ref: QueueRef,
+    labels: Vec<QueueLabel>,
+    /// Guards the queue, holds the last value submitted to the timeline.
+    lock: Mutex<u64>,
+    timeline: vk::Semaphore,
+    device: ash::Device,
+    swapchain: khr::swapchain::Device,
+    synchronization2: Option<khr::synchronization2::Device>,
+}
+
+impl Queue {
+    pub(crate) fn new(
+        device: &ash::Device,
+        loader: &DeviceLoader,
+        queue_ref: QueueRef,
+        labels: Vec<QueueLabel>,
+    ) -> VkResult<Self> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let timeline = unsafe {
+            device.create_semaphore(
+                &vk::SemaphoreCreateInfo::default().push_next(&mut type_info),
+                None,
+            )
+        }?;
+
+        Ok(Self {
+            handle: unsafe { device.get_device_queue(queue_ref.family, queue_ref.index) },
+            queue_ref,
+            labels,
+            lock: Mutex::new(0),
+            timeline,
+            device: device.clone(),
+            swapchain: loader.swapchain().clone(),
+            synchronization2: loader.synchronization2().cloned(),
+        })
+    }
+
+    /// The raw queue handle. Use [`Queue::with_lock`] for any operation that needs external synchronization.
+    pub fn handle(&self) -> vk::Queue {
+        self.handle
+    }
+
+    pub fn family(&self) -> u32 {
+        self.queue_ref.family
+    }
+
+    pub fn index(&self) -> u32 {
+        self.queue_ref.index
+    }
+
+    pub fn queue_ref(&self) -> QueueRef {
+        self.queue_ref
+    }
+
+    /// Every label assigned to this queue.
+    pub fn labels(&self) -> &[QueueLabel] {
+        &self.labels
+    }
+
+    pub fn has_label(&self, label: QueueLabel) -> bool {
+        self.labels.contains(&label)
+    }
+
+    /// The timeline semaphore signaled by every submit to the queue.
+    pub fn timeline(&self) -> vk::Semaphore {
+        self.timeline
+    }
+
+    /// The last submission to the queue, with value 0 when nothing was submitted yet.
+    pub fn last_submission(&self) -> SubmissionHandle {
+        SubmissionHandle::new(self.queue_ref, self.timeline, *self.lock())
+    }
+
+    /// The last timeline value the GPU reached on this queue.
+    pub fn completed_value(&self) -> VkResult<u64> {
+        unsafe { self.device.get_semaphore_counter_value(self.timeline) }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, u64> {
+        // the lock only protects the queue handle, there is no state a panic could leave inconsistent
+        self.lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `f` with exclusive access to the queue, for queue operations the wrapper doesn't cover.
+    pub fn with_lock<R, F: FnOnce(vk::Queue) -> R>(&self, f: F) -> R {
+        let _guard = self.lock();
+        f(self.handle)
+    }
+
+    /// Submits through `vkQueueSubmit`, followed by a batch signaling the next timeline value.
+    pub fn submit(
+        &self,
+        submits: &[vk::SubmitInfo],
+        fence: vk::Fence,
+    ) -> VkResult<SubmissionHandle> {
+        let mut last_value = self.lock();
+        let value = *last_value + 1;
+
+        let signal_values = [value];
+        let mut timeline_info =
+            vk::TimelineSemaphoreSubmitInfo::default().signal_semaphore_values(&signal_values);
+        let timeline = [self.timeline];
+        let submits = submits
+            .iter()
+            .copied()
+            .chain(std::iter::once(
+                vk::SubmitInfo::default()
+                    .signal_semaphores(&timeline)
+                    .push_next(&mut timeline_info),
+            ))
+            .collect::<Vec<vk::SubmitInfo>>();
+
+        unsafe { self.device.queue_submit(self.handle, &submits, fence) }?;
+
+        *last_value = value;
+        Ok(SubmissionHandle::new(self.queue_ref, self.timeline, value))
+    }
+
+    /// Submits through `vkQueueSubmit2`, or `vkQueueSubmit2KHR` on Vulkan 1.2 devices, followed by a batch signaling
+    /// the next timeline value.
+    pub fn submit2(
+        &self,
+        submits: &[vk::SubmitInfo2],
+        fence: vk::Fence,
+    ) -> VkResult<SubmissionHandle> {
+        let mut last_value = self.lock();
+        let value = *last_value + 1;
+
+        // a signal operation waits for every earlier submission to the queue, not only its own batch
+        let signal = [vk::SemaphoreSubmitInfo::default()
+            .semaphore(self.timeline)
+            .value(value)
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)];
+        let submits = submits
+            .iter()
+            .copied()
+            .chain(std::iter::once(
+                vk::SubmitInfo2::default().signal_semaphore_infos(&signal),
+            ))
+            .collect::<Vec<vk::SubmitInfo2>>();
+
+        unsafe {
+            match &self.synchronization2 {
+                Some(synchronization2) => {
+                    synchronization2.queue_submit2(self.handle, &submits, fence)
+                }
+                None => self.device.queue_submit2(self.handle, &submits, fence),
+            }
+        }?;
+
+        *last_value = value;
+        Ok(SubmissionHandle::new(self.queue_ref, self.timeline, value))
+    }
+
+    /// Presents swapchain images, returns whether the swapchain is suboptimal.
+    pub fn present(&self, present_info: &vk::PresentInfoKHR) -> VkResult<bool> {
+        let _guard = self.lock();
+        unsafe { self.swapchain.queue_present(self.handle, present_info) }
+    }
+
+    pub fn wait_idle(&self) -> VkResult<()> {
+        let _guard = self.lock();
+        unsafe { self.device.queue_wait_idle(self.handle) }
+    }
+}
+
+impl Debug for Queue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Queue")
+            .field("handle", &self.handle)
+            .field("family", &self.queue_ref.family)
+            .field("index", &self.queue_ref.index)
+            .field("labels", &self.labels)
+            .field("timeline", &self.timeline)
+            .finish()
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_semaphore(self.timeline, None) };
+    }
+}
@@ -0,0 +1,883 @@
+use crate::app::feature_request::QueueRequest;
+use crate::errors::QueueRequestValidationError;
+use crate::render::context::queues::{
+    QueueFamily, QueueFamilyInfo, QueueLabel, QueueLabels, QueueRef, UnlabeledQueues,
+};
+use ash::vk;
+use log::trace;
+use std::collections::BTreeMap;
+
+/// The queues to create in a single queue family.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueueFamilyPlan {
+    pub family: u32,
+    /// One priority per queue, the length is the number of queues created in the family.
+    pub priorities: Vec<f32>,
+    /// The highest global priority requested in the family.
+    pub global_priority: Option<vk::QueueGlobalPriorityKHR>,
+}
+
+impl QueueFamilyPlan {
+    pub fn count(&self) -> u32 {
+        self.priorities.len() as u32
+    }
+}
+
+/// The result of [`QueuePlanner::plan`]: which queues to create and how they are assigned to labels.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueuePlan {
+    /// Sorted by family index, families without requests are left out.
+    pub families: Vec<QueueFamilyPlan>,
+    pub labels: QueueLabels,
+    pub unlabeled: UnlabeledQueues,
+}
+
+impl QueuePlan {
+    pub fn family(&self, family: u32) -> Option<&QueueFamilyPlan> {
+        self.families.iter().find(|plan| plan.family == family)
+    }
+
+    pub fn queue_count(&self) -> u32 {
+        self.families.iter().map(QueueFamilyPlan::count).sum()
+    }
+}
+
+/// Assigns [`QueueRequest`]s to concrete queues of a device, without touching Vulkan.
+///
+/// Requests targeting [`QueueFamily::Capabilities`] are first resolved to the family picked by
+/// [`QueueCapabilities::select_family`](crate::render::context::queues::QueueCapabilities::select_family).
+/// Strict requests get exclusive queues, allocated from the start of their family in request order, and fail if the
+/// family does not have enough queues. Flexible requests share the queues left over after the strict requests: they are
+/// laid out in request order and wrap around to the first leftover queue when there are more requested than available.
+///
+/// Each queue gets the priority of its request, or the highest priority of the requests sharing it, so merging never
+/// lowers the priority of a latency-sensitive queue.
+///
+/// # Examples
+///
+/// ```
+/// use ash::vk;
+/// use neuron_engine::app::feature_request::QueueRequest;
+/// use neuron_engine::render::context::queue_planner::QueuePlanner;
+/// use neuron_engine::render::context::queues::{QueueFamilyInfo, QueueLabel, QueueRef};
+///
+/// let families: [QueueFamilyInfo; 1] = [vk::QueueFamilyProperties::default()
+///     .queue_flags(vk::QueueFlags::GRAPHICS)
+///     .queue_count(2)
+///     .into()];
+///
+/// let plan = QueuePlanner::new(&families)
+///     .plan(&[
+///         QueueRequest::strict_labeled(0, 1, QueueLabel::Transfer),
+///         QueueRequest::flexible_labeled(0, 3, QueueLabel::Graphics),
+///     ])
+///     .unwrap();
+///
+/// assert_eq!(plan.family(0).unwrap().count(), 2);
+/// assert_eq!(plan.labels[&QueueLabel::Graphics], vec![QueueRef { family: 0, index: 1 }; 3]);
+/// ```
+pub struct QueuePlanner<'a> {
+    families: &'a [QueueFamilyInfo],
+}
+
+impl<'a> QueuePlanner<'a> {
+    pub fn new(families: &'a [QueueFamilyInfo]) -> Self {
+        Self { families }
+    }
+
+    pub fn plan(
+        &self,
+        requests: &[QueueRequest],
+    ) -> Result<QueuePlan, QueueRequestValidationError> {
+        let requests = requests
+            .iter()
+            .enumerate()
+            .map(|(index, req)| self.resolve(index, req))
+            .collect::<Result<Vec<ResolvedRequest>, QueueRequestValidationError>>()?;
+
+        // queues reserved by strict requests, flexible requests use the rest of the family
+        let mut strict_counts: BTreeMap<u32, u32> = BTreeMap::new();
+
+        for (index, req) in requests.iter().enumerate() {
+            let available = self.families[req.family as usize].properties.queue_count;
+            if !(0.0..=1.0).contains(&req.priority) {
+                return Err(QueueRequestValidationError::InvalidPriority {
+                    request: index,
+                    label: req.label,
+                    priority: req.priority,
+                });
+            }
+
+            if req.allow_merge {
+                continue;
+            }
+
+            let count = strict_counts.entry(req.family).or_insert(0);
+            *count += req.count;
+            if *count > available {
+                return Err(QueueRequestValidationError::NotEnoughQueuesInFamily {
+                    request: index,
+                    label: req.label,
+                    family: req.family,
+                    req: *count,
+                    avail: available,
+                });
+            }
+        }
+
+        let mut plan = QueuePlan::default();
+        let mut priorities: BTreeMap<u32, Vec<f32>> = BTreeMap::new();
+        let mut global_priorities: BTreeMap<u32, vk::QueueGlobalPriorityKHR> = BTreeMap::new();
+
+        for req in requests.iter().filter(|req| req.count > 0) {
+            if let Some(global_priority) = req.global_priority {
+                let highest = global_priorities
+                    .entry(req.family)
+                    .or_insert(global_priority);
+                if global_priority.as_raw() > highest.as_raw() {
+                    *highest = global_priority;
+                }
+            }
+        }
+
+        trace!("[device/queues/planner] Allocating strict requests");
+        let mut strict_next: BTreeMap<u32, u32> = BTreeMap::new();
+        for req in requests
+            .iter()
+            .filter(|req| !req.allow_merge && req.count > 0)
+        {
+            let next = strict_next.entry(req.family).or_insert(0);
+            let indices = *next..*next + req.count;
+            *next = indices.end;
+            raise_priorities(
+                priorities.entry(req.family).or_default(),
+                indices.clone(),
+                req.priority,
+            );
+
+            trace!(
+                "[device/queues/planner] Queues #{:?} through #{:?} in family {:?} are exclusive to {:?}",
+                indices.start,
+                indices.end - 1,
+                req.family,
+                req.label
+            );
+            plan.assign(req.family, req.label, indices);
+        }
+
+        trace!("[device/queues/planner] Allocating flexible requests");
+        // position of the next flexible queue in each family, before wrapping around
+        let mut flexible_next: BTreeMap<u32, u32> = BTreeMap::new();
+        for (index, req) in requests.iter().enumerate() {
+            if !req.allow_merge || req.count == 0 {
+                continue;
+            }
+
+            let total = self.families[req.family as usize].properties.queue_count;
+            let start = strict_counts.get(&req.family).copied().unwrap_or(0);
+            if start >= total {
+                return Err(QueueRequestValidationError::NoQueuesForFlexibleRequest {
+                    request: index,
+                    label: req.label,
+                    family: req.family,
+                    avail: total,
+                });
+            }
+
+            let shared = total - start;
+            let next = flexible_next.entry(req.family).or_insert(0);
+            let indices = (*next..*next + req.count)
+                .map(|i| start + i % shared)
+                .collect::<Vec<u32>>();
+            *next += req.count;
+
+            if *next > shared {
+                trace!(
+                    "[device/queues/planner] Family {:?} has {:?} shared queues for {:?} flexible queues, some will be merged",
+                    req.family, shared, next
+                );
+            }
+
+            raise_priorities(
+                priorities.entry(req.family).or_default(),
+                indices.iter().copied(),
+                req.priority,
+            );
+
+            trace!(
+                "[device/queues/planner] Queues {:?} in family {:?} are shared with {:?}",
+                indices, req.family, req.label
+            );
+            plan.assign(req.family, req.label, indices);
+        }
+
+        plan.families = priorities
+            .into_iter()
+            .map(|(family, priorities)| QueueFamilyPlan {
+                family,
+                priorities,
+                global_priority: global_priorities.get(&family).copied(),
+            })
+            .collect();
+
+        Ok(plan)
+    }
+
+    fn resolve(
+        &self,
+        index: usize,
+        req: &QueueRequest,
+    ) -> Result<ResolvedRequest, QueueRequestValidationError> {
+        let family = match req.family {
+            QueueFamily::Index(family) if (family as usize) < self.families.len() => family,
+            QueueFamily::Index(family) => {
+                return Err(QueueRequestValidationError::UnknownFamily {
+                    request: index,
+                    label: req.label,
+                    family,
+                    family_count: self.families.len() as u32,
+                });
+            }
+            QueueFamily::Capabilities(capabilities) => {
+                let family = capabilities.select_family(self.families).ok_or(
+                    QueueRequestValidationError::NoMatchingFamily {
+                        request: index,
+                        label: req.label,
+                        capabilities,
+                    },
+                )?;
+                trace!(
+                    "[device/queues/planner] Request #{:?} ({:?}) resolved to family {:?}",
+                    index, req.label, family
+                );
+                family
+            }
+        };
+
+        Ok(ResolvedRequest {
+            family,
+            count: req.count,
+            label: req.label,
+            allow_merge: req.allow_merge,
+            priority: req.priority,
+            global_priority: req.global_priority,
+        })
+    }
+}
+
+/// A [`QueueRequest`] with its family index resolved.
+struct ResolvedRequest {
+    family: u32,
+    count: u32,
+    label: Option<QueueLabel>,
+    allow_merge: bool,
+    priority: f32,
+    global_priority: Option<vk::QueueGlobalPriorityKHR>,
+}
+
+/// Raises the priority of the queues at `indices` to at least `priority`, growing the family as needed.
+fn raise_priorities(
+    priorities: &mut Vec<f32>,
+    indices: impl IntoIterator<Item = u32>,
+    priority: f32,
+) {
+    for index in indices {
+        let index = index as usize;
+        if priorities.len() <= index {
+            priorities.resize(index + 1, 0.0);
+        }
+        priorities[index] = priorities[index].max(priority);
+    }
+}
+
+impl QueuePlan {
+    fn assign(
+        &mut self,
+        family: u32,
+        label: Option<QueueLabel>,
+        indices: impl IntoIterator<Item = u32>,
+    ) {
+        match label {
+            Some(label) => self
+                .labels
+                .entry(label)
+                .or_default()
+                .extend(indices.into_iter().map(|index| QueueRef { family, index })),
+            None => self.unlabeled.entry(family).or_default().extend(indices),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::context::queues::QueueCapabilities;
+    use std::collections::HashSet;
+
+    fn family(flags: vk::QueueFlags, count: u32) -> QueueFamilyInfo {
+        vk::QueueFamilyProperties::default()
+            .queue_flags(flags)
+            .queue_count(count)
+            .into()
+    }
+
+    /// A typical discrete GPU: a graphics family with 16 queues, a transfer-only family with 2 and a compute family with 8.
+    fn discrete() -> Vec<QueueFamilyInfo> {
+        vec![
+            family(
+                vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+                16,
+            ),
+            family(vk::QueueFlags::TRANSFER, 2),
+            family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER, 8),
+        ]
+    }
+
+    /// A device exposing a single queue, like many integrated and mobile GPUs.
+    fn single_queue() -> Vec<QueueFamilyInfo> {
+        vec![family(
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            1,
+        )]
+    }
+
+    fn refs(family: u32, indices: impl IntoIterator<Item = u32>) -> Vec<QueueRef> {
+        indices
+            .into_iter()
+            .map(|index| QueueRef { family, index })
+            .collect()
+    }
+
+    fn set(indices: impl IntoIterator<Item = u32>) -> HashSet<u32> {
+        indices.into_iter().collect()
+    }
+
+    #[test]
+    fn no_requests_plan_nothing() {
+        let plan = QueuePlanner::new(&discrete()).plan(&[]).unwrap();
+        assert_eq!(plan, QueuePlan::default());
+    }
+
+    #[test]
+    fn flexible_request_uses_the_first_queue() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics)])
+            .unwrap();
+
+        assert_eq!(
+            plan.families,
+            vec![QueueFamilyPlan {
+                family: 0,
+                priorities: vec![1.0],
+                global_priority: None,
+            }]
+        );
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [0]));
+        assert!(plan.unlabeled.is_empty());
+    }
+
+    #[test]
+    fn strict_requests_are_allocated_in_request_order() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::strict_labeled(0, 2, QueueLabel::Graphics),
+                QueueRequest::strict_unlabeled(0, 1),
+                QueueRequest::strict_labeled_custom(0, 1, "Exclusive"),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().count(), 4);
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [0, 1]));
+        assert_eq!(plan.unlabeled[&0], set([2]));
+        assert_eq!(plan.labels[&QueueLabel::Custom("Exclusive")], refs(0, [3]));
+    }
+
+    #[test]
+    fn flexible_requests_start_after_strict_requests() {
+        // the flexible request comes first but must not take the strict queue
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::strict_labeled(0, 1, QueueLabel::Transfer),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().count(), 2);
+        assert_eq!(plan.labels[&QueueLabel::Transfer], refs(0, [0]));
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [1]));
+    }
+
+    #[test]
+    fn flexible_requests_without_merging_get_distinct_queues() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Presentation),
+                QueueRequest::flexible_labeled(2, 1, QueueLabel::Compute),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().count(), 2);
+        assert_eq!(plan.family(2).unwrap().count(), 1);
+        assert!(plan.family(1).is_none());
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [0]));
+        assert_eq!(plan.labels[&QueueLabel::Presentation], refs(0, [1]));
+        assert_eq!(plan.labels[&QueueLabel::Compute], refs(2, [0]));
+    }
+
+    #[test]
+    fn flexible_requests_wrap_around_on_a_single_queue() {
+        let plan = QueuePlanner::new(&single_queue())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Compute),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Transfer),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Presentation),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.queue_count(), 1);
+        for label in [
+            QueueLabel::Graphics,
+            QueueLabel::Compute,
+            QueueLabel::Transfer,
+            QueueLabel::Presentation,
+        ] {
+            assert_eq!(plan.labels[&label], refs(0, [0]));
+        }
+    }
+
+    #[test]
+    fn flexible_requests_wrap_around_within_the_shared_range() {
+        // queue 0 is exclusive, the 3 flexible queues wrap around queues 1 and 2
+        let families = vec![family(vk::QueueFlags::GRAPHICS, 3)];
+        let plan = QueuePlanner::new(&families)
+            .plan(&[
+                QueueRequest::strict_labeled(0, 1, QueueLabel::Transfer),
+                QueueRequest::flexible_labeled(0, 3, QueueLabel::Graphics),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().count(), 3);
+        assert_eq!(plan.labels[&QueueLabel::Transfer], refs(0, [0]));
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [1, 2, 1]));
+    }
+
+    #[test]
+    fn merge_wraparound_continues_across_requests() {
+        let families = vec![family(vk::QueueFlags::GRAPHICS, 2)];
+        let plan = QueuePlanner::new(&families)
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 3, QueueLabel::Graphics),
+                QueueRequest::flexible_labeled(0, 2, QueueLabel::Compute),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().count(), 2);
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [0, 1, 0]));
+        assert_eq!(plan.labels[&QueueLabel::Compute], refs(0, [1, 0]));
+    }
+
+    #[test]
+    fn example_project_requests() {
+        // the engine requests followed by the example project's `on_queue_selection`
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::flexible_labeled(2, 1, QueueLabel::Compute),
+                QueueRequest::strict_labeled(1, 1, QueueLabel::Transfer),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Presentation),
+                QueueRequest::flexible_labeled(1, 2, QueueLabel::Transfer),
+                QueueRequest::flexible_labeled_custom(0, 22, "Graphics Extras"),
+                QueueRequest::strict_labeled_custom(0, 1, "Graphics Exclusive"),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().count(), 16);
+        assert_eq!(plan.family(1).unwrap().count(), 2);
+        assert_eq!(plan.family(2).unwrap().count(), 1);
+
+        assert_eq!(
+            plan.labels[&QueueLabel::Custom("Graphics Exclusive")],
+            refs(0, [0])
+        );
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [1]));
+        assert_eq!(plan.labels[&QueueLabel::Presentation], refs(0, [2]));
+
+        // 22 queues over the 15 shared queues (#1 through #15), starting after graphics and presentation
+        let extras = &plan.labels[&QueueLabel::Custom("Graphics Extras")];
+        assert_eq!(extras.len(), 22);
+        assert_eq!(*extras, refs(0, (3..=15).chain(1..=9)));
+        assert!(extras.iter().all(|r| r.index != 0));
+
+        // the strict transfer queue stays exclusive, the flexible ones merge onto the remaining queue
+        assert_eq!(plan.labels[&QueueLabel::Transfer], refs(1, [0, 1, 1]));
+        assert_eq!(plan.labels[&QueueLabel::Compute], refs(2, [0]));
+        assert!(plan.unlabeled.is_empty());
+    }
+
+    #[test]
+    fn unlabeled_queues_combine_strict_and_flexible_requests() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::strict_unlabeled(2, 2),
+                QueueRequest::flexible_unlabeled(2, 2),
+                QueueRequest::flexible_unlabeled(2, 1),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(2).unwrap().count(), 5);
+        assert_eq!(plan.unlabeled[&2], set(0..5));
+        assert!(plan.labels.is_empty());
+    }
+
+    #[test]
+    fn a_label_can_span_families_and_strictness() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(2, 1, QueueLabel::Compute),
+                QueueRequest::strict_labeled(2, 1, QueueLabel::Compute),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Compute),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            plan.labels[&QueueLabel::Compute],
+            vec![
+                QueueRef {
+                    family: 2,
+                    index: 0
+                },
+                QueueRef {
+                    family: 2,
+                    index: 1
+                },
+                QueueRef {
+                    family: 0,
+                    index: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_count_requests_are_ignored() {
+        let plan = QueuePlanner::new(&single_queue())
+            .plan(&[
+                QueueRequest::strict_labeled(0, 0, QueueLabel::Transfer),
+                QueueRequest::flexible_labeled(0, 0, QueueLabel::Compute),
+                QueueRequest::strict_labeled(0, 1, QueueLabel::Graphics),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.queue_count(), 1);
+        assert!(!plan.labels.contains_key(&QueueLabel::Transfer));
+        assert!(!plan.labels.contains_key(&QueueLabel::Compute));
+    }
+
+    #[test]
+    fn priorities_cover_every_created_queue() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::strict_unlabeled(0, 3),
+                QueueRequest::flexible_unlabeled(0, 40),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().priorities, vec![1.0; 16]);
+        assert_eq!(plan.queue_count(), 16);
+    }
+
+    #[test]
+    fn requests_keep_their_priority() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::strict_labeled(0, 1, QueueLabel::Graphics).with_priority(1.0),
+                QueueRequest::strict_labeled_custom(0, 2, "Streaming").with_priority(0.25),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Compute).with_priority(0.5),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            plan.family(0).unwrap().priorities,
+            vec![1.0, 0.25, 0.25, 0.5]
+        );
+    }
+
+    #[test]
+    fn merged_queues_take_the_highest_priority() {
+        let families = vec![family(vk::QueueFlags::GRAPHICS, 2)];
+        let plan = QueuePlanner::new(&families)
+            .plan(&[
+                QueueRequest::flexible_labeled_custom(0, 1, "Streaming").with_priority(0.1),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Compute).with_priority(0.3),
+                // wraps around onto the streaming queue
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics).with_priority(0.9),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [0]));
+        assert_eq!(plan.family(0).unwrap().priorities, vec![0.9, 0.3]);
+    }
+
+    #[test]
+    fn strict_queues_are_not_raised_by_flexible_requests() {
+        let families = vec![family(vk::QueueFlags::GRAPHICS, 2)];
+        let plan = QueuePlanner::new(&families)
+            .plan(&[
+                QueueRequest::strict_unlabeled(0, 1).with_priority(0.0),
+                QueueRequest::flexible_labeled(0, 4, QueueLabel::Graphics),
+            ])
+            .unwrap();
+
+        assert_eq!(plan.family(0).unwrap().priorities, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn families_use_the_highest_global_priority() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics)
+                    .with_global_priority(vk::QueueGlobalPriorityKHR::HIGH),
+                QueueRequest::flexible_unlabeled(0, 1)
+                    .with_global_priority(vk::QueueGlobalPriorityKHR::LOW),
+                QueueRequest::flexible_labeled(2, 1, QueueLabel::Compute),
+                // zero count requests don't create queues, so they don't raise the family either
+                QueueRequest::flexible_unlabeled(2, 0)
+                    .with_global_priority(vk::QueueGlobalPriorityKHR::REALTIME),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            plan.family(0).unwrap().global_priority,
+            Some(vk::QueueGlobalPriorityKHR::HIGH)
+        );
+        assert_eq!(plan.family(2).unwrap().global_priority, None);
+    }
+
+    #[test]
+    fn out_of_range_priority_fails() {
+        let error = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Compute).with_priority(1.5),
+            ])
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            QueueRequestValidationError::InvalidPriority {
+                request: 1,
+                label: Some(QueueLabel::Compute),
+                priority: 1.5,
+            }
+        );
+    }
+
+    #[test]
+    fn capability_requests_use_the_family_preferences() {
+        let plan = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible(QueueFamily::GRAPHICS, 1, Some(QueueLabel::Graphics)),
+                QueueRequest::flexible(QueueFamily::COMPUTE, 1, Some(QueueLabel::Compute)),
+                QueueRequest::flexible(
+                    QueueFamily::ASYNC_COMPUTE,
+                    1,
+                    Some(QueueLabel::Custom("Async")),
+                ),
+                QueueRequest::strict(QueueFamily::TRANSFER, 1, Some(QueueLabel::Transfer)),
+            ])
+            .unwrap();
+
+        // compute shares the graphics family, transfers and async compute get the dedicated families
+        assert_eq!(plan.labels[&QueueLabel::Graphics], refs(0, [0]));
+        assert_eq!(plan.labels[&QueueLabel::Compute], refs(0, [1]));
+        assert_eq!(plan.labels[&QueueLabel::Custom("Async")], refs(2, [0]));
+        assert_eq!(plan.labels[&QueueLabel::Transfer], refs(1, [0]));
+    }
+
+    #[test]
+    fn transfer_requests_fall_back_on_the_least_capable_family() {
+        // graphics and compute families support transfers without reporting them
+        let families = vec![
+            family(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE, 4),
+            family(vk::QueueFlags::COMPUTE, 2),
+        ];
+        let plan = QueuePlanner::new(&families)
+            .plan(&[QueueRequest::flexible(
+                QueueFamily::TRANSFER,
+                1,
+                Some(QueueLabel::Transfer),
+            )])
+            .unwrap();
+
+        assert_eq!(plan.labels[&QueueLabel::Transfer], refs(1, [0]));
+    }
+
+    #[test]
+    fn presentation_requests_need_a_presenting_family() {
+        let mut families = discrete();
+        families[2].presentation = true;
+
+        let plan = QueuePlanner::new(&families)
+            .plan(&[QueueRequest::flexible(
+                QueueFamily::PRESENTATION,
+                1,
+                Some(QueueLabel::Presentation),
+            )])
+            .unwrap();
+        assert_eq!(plan.labels[&QueueLabel::Presentation], refs(2, [0]));
+
+        let error = QueuePlanner::new(&discrete())
+            .plan(&[QueueRequest::flexible(
+                QueueFamily::PRESENTATION,
+                1,
+                Some(QueueLabel::Presentation),
+            )])
+            .unwrap_err();
+        assert_eq!(
+            error,
+            QueueRequestValidationError::NoMatchingFamily {
+                request: 0,
+                label: Some(QueueLabel::Presentation),
+                capabilities: QueueCapabilities::PRESENTATION,
+            }
+        );
+    }
+
+    #[test]
+    fn video_timestamp_and_granularity_requirements() {
+        let mut families = discrete();
+        families[0].properties.timestamp_valid_bits = 64;
+        families[0].properties.min_image_transfer_granularity =
+            vk::Extent3D::default().width(1).height(1).depth(1);
+        families[1].properties.min_image_transfer_granularity = vk::Extent3D::default();
+        families[2].properties.timestamp_valid_bits = 36;
+        families.push(family(vk::QueueFlags::VIDEO_DECODE_KHR, 1));
+        families[3].video_codec_operations = vk::VideoCodecOperationFlagsKHR::DECODE_H264
+            | vk::VideoCodecOperationFlagsKHR::DECODE_H265;
+
+        let decode = QueueCapabilities::new(vk::QueueFlags::VIDEO_DECODE_KHR)
+            .with_video_codec_operations(vk::VideoCodecOperationFlagsKHR::DECODE_H265);
+        let timestamps = QueueCapabilities::ASYNC_COMPUTE.with_min_timestamp_valid_bits(48);
+        let fine_transfers = QueueCapabilities::TRANSFER.with_max_image_transfer_granularity(
+            vk::Extent3D::default().width(1).height(1).depth(1),
+        );
+
+        assert_eq!(decode.select_family(&families), Some(3));
+        assert_eq!(timestamps.select_family(&families), Some(0));
+        assert_eq!(fine_transfers.select_family(&families), Some(0));
+
+        let av1 = QueueCapabilities::new(vk::QueueFlags::VIDEO_DECODE_KHR)
+            .with_video_codec_operations(vk::VideoCodecOperationFlagsKHR::DECODE_AV1);
+        let error = QueuePlanner::new(&families)
+            .plan(&[
+                QueueRequest::flexible(decode.into(), 1, Some(QueueLabel::VideoDecode)),
+                QueueRequest::flexible(av1.into(), 1, Some(QueueLabel::VideoDecode)),
+            ])
+            .unwrap_err();
+        assert_eq!(
+            error,
+            QueueRequestValidationError::NoMatchingFamily {
+                request: 1,
+                label: Some(QueueLabel::VideoDecode),
+                capabilities: av1,
+            }
+        );
+    }
+
+    #[test]
+    fn strict_request_larger_than_the_family_fails() {
+        let error = QueuePlanner::new(&discrete())
+            .plan(&[QueueRequest::strict_labeled(1, 3, QueueLabel::Transfer)])
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            QueueRequestValidationError::NotEnoughQueuesInFamily {
+                request: 0,
+                label: Some(QueueLabel::Transfer),
+                family: 1,
+                req: 3,
+                avail: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn strict_shortfall_names_the_request_that_exceeded_the_family() {
+        let error = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(1, 4, QueueLabel::Graphics),
+                QueueRequest::strict_labeled(1, 1, QueueLabel::Transfer),
+                QueueRequest::strict_labeled_custom(1, 2, "Uploads"),
+            ])
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            QueueRequestValidationError::NotEnoughQueuesInFamily {
+                request: 2,
+                label: Some(QueueLabel::Custom("Uploads")),
+                family: 1,
+                req: 3,
+                avail: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn flexible_request_without_shared_queues_fails() {
+        let error = QueuePlanner::new(&single_queue())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::strict_labeled(0, 1, QueueLabel::Transfer),
+            ])
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            QueueRequestValidationError::NoQueuesForFlexibleRequest {
+                request: 0,
+                label: Some(QueueLabel::Graphics),
+                family: 0,
+                avail: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_family_fails() {
+        let error = QueuePlanner::new(&discrete())
+            .plan(&[
+                QueueRequest::flexible_labeled(0, 1, QueueLabel::Graphics),
+                QueueRequest::flexible_labeled(3, 1, QueueLabel::VideoDecode),
+            ])
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            QueueRequestValidationError::UnknownFamily {
+                request: 1,
+                label: Some(QueueLabel::VideoDecode),
+                family: 3,
+                family_count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn errors_mention_the_request() {
+        let error = QueuePlanner::new(&single_queue())
+            .plan(&[QueueRequest::strict_labeled_custom(
+                0,
+                2,
+                "Graphics Exclusive",
+            )])
+            .unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.contains("#0"), "{}", message);
+        assert!(message.contains("Graphics Exclusive"), "{}", message);
+    }
+}
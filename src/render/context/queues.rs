@@ -1,3 +1,4 @@
+use ash::vk;
 use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -19,3 +20,207 @@ pub struct QueueRef {
 
 pub type QueueLabels = HashMap<QueueLabel, Vec<QueueRef>>;
 pub type UnlabeledQueues = HashMap<u32, HashSet<u32>>;
+
+/// What the engine knows about a queue family when assigning queue requests to it.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct QueueFamilyInfo {
+    pub properties: vk::QueueFamilyProperties,
+    /// The codec operations supported by the family, empty when `VK_KHR_video_queue` is not supported.
+    pub video_codec_operations: vk::VideoCodecOperationFlagsKHR,
+    /// Whether the family can present to the display the device was created for, always `false` on headless devices.
+    pub presentation: bool,
+}
+
+impl From<vk::QueueFamilyProperties> for QueueFamilyInfo {
+    fn from(properties: vk::QueueFamilyProperties) -> Self {
+        Self {
+            properties,
+            ..Default::default()
+        }
+    }
+}
+
+impl QueueFamilyInfo {
+    /// The flags of the family, including [`vk::QueueFlags::TRANSFER`] for graphics and compute families which support
+    /// transfers without having to report it.
+    pub fn effective_flags(&self) -> vk::QueueFlags {
+        let flags = self.properties.queue_flags;
+        if flags.intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE) {
+            flags | vk::QueueFlags::TRANSFER
+        } else {
+            flags
+        }
+    }
+}
+
+/// How to choose between several queue families matching the same [`QueueCapabilities`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum QueueFamilyPreference {
+    /// The first matching family, which is usually the universal graphics family (how compute queues are picked).
+    #[default]
+    Shared,
+    /// The family with the fewest capabilities beyond the requested ones, such as a dedicated transfer or async compute
+    /// family.
+    Exclusive,
+}
+
+/// The capabilities a queue needs, used to pick a queue family instead of hard-coding its index.
+///
+/// # Examples
+///
+/// ```
+/// use ash::vk;
+/// use neuron_engine::render::context::queues::{QueueCapabilities, QueueFamilyInfo};
+///
+/// let families: Vec<QueueFamilyInfo> = vec![
+///     vk::QueueFamilyProperties::default()
+///         .queue_flags(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+///         .queue_count(16)
+///         .into(),
+///     vk::QueueFamilyProperties::default()
+///         .queue_flags(vk::QueueFlags::TRANSFER)
+///         .queue_count(2)
+///         .into(),
+/// ];
+///
+/// assert_eq!(QueueCapabilities::COMPUTE.select_family(&families), Some(0));
+/// assert_eq!(QueueCapabilities::TRANSFER.select_family(&families), Some(1));
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueCapabilities {
+    pub flags: vk::QueueFlags,
+    pub presentation: bool,
+    pub video_codec_operations: vk::VideoCodecOperationFlagsKHR,
+    pub min_timestamp_valid_bits: u32,
+    /// The coarsest image transfer granularity the queue can work with. Families that only transfer whole mip levels
+    /// (a granularity of `(0, 0, 0)`) never match.
+    pub max_image_transfer_granularity: Option<vk::Extent3D>,
+    pub preference: QueueFamilyPreference,
+}
+
+impl QueueCapabilities {
+    pub const GRAPHICS: Self = Self::new(vk::QueueFlags::GRAPHICS);
+    pub const COMPUTE: Self = Self::new(vk::QueueFlags::COMPUTE);
+    /// Compute on a family without graphics when the device has one.
+    pub const ASYNC_COMPUTE: Self = Self::new(vk::QueueFlags::COMPUTE).exclusive();
+    /// Transfers on a dedicated transfer family when the device has one.
+    pub const TRANSFER: Self = Self::new(vk::QueueFlags::TRANSFER).exclusive();
+    pub const PRESENTATION: Self = Self::new(vk::QueueFlags::empty()).with_presentation();
+
+    pub const fn new(flags: vk::QueueFlags) -> Self {
+        Self {
+            flags,
+            presentation: false,
+            video_codec_operations: vk::VideoCodecOperationFlagsKHR::empty(),
+            min_timestamp_valid_bits: 0,
+            max_image_transfer_granularity: None,
+            preference: QueueFamilyPreference::Shared,
+        }
+    }
+
+    pub const fn exclusive(mut self) -> Self {
+        self.preference = QueueFamilyPreference::Exclusive;
+        self
+    }
+
+    pub const fn with_presentation(mut self) -> Self {
+        self.presentation = true;
+        self
+    }
+
+    pub const fn with_video_codec_operations(
+        mut self,
+        operations: vk::VideoCodecOperationFlagsKHR,
+    ) -> Self {
+        self.video_codec_operations = operations;
+        self
+    }
+
+    pub const fn with_min_timestamp_valid_bits(mut self, bits: u32) -> Self {
+        self.min_timestamp_valid_bits = bits;
+        self
+    }
+
+    pub const fn with_max_image_transfer_granularity(mut self, granularity: vk::Extent3D) -> Self {
+        self.max_image_transfer_granularity = Some(granularity);
+        self
+    }
+
+    pub fn matches(&self, family: &QueueFamilyInfo) -> bool {
+        let granularity = family.properties.min_image_transfer_granularity;
+
+        family.properties.queue_count > 0
+            && family.effective_flags().contains(self.flags)
+            && (!self.presentation || family.presentation)
+            && family
+                .video_codec_operations
+                .contains(self.video_codec_operations)
+            && family.properties.timestamp_valid_bits >= self.min_timestamp_valid_bits
+            && self.max_image_transfer_granularity.is_none_or(|max| {
+                granularity.width > 0
+                    && granularity.height > 0
+                    && granularity.depth > 0
+                    && granularity.width <= max.width
+                    && granularity.height <= max.height
+                    && granularity.depth <= max.depth
+            })
+    }
+
+    /// The index of the best family for these capabilities, see [`QueueFamilyPreference`].
+    pub fn select_family(&self, families: &[QueueFamilyInfo]) -> Option<u32> {
+        let mut matching = families
+            .iter()
+            .enumerate()
+            .filter(|(_, family)| self.matches(family));
+
+        match self.preference {
+            QueueFamilyPreference::Shared => matching.next(),
+            QueueFamilyPreference::Exclusive => {
+                matching.min_by_key(|(index, family)| (self.extra_capabilities(family), *index))
+            }
+        }
+        .map(|(index, _)| index as u32)
+    }
+
+    /// Weighs the capabilities of `family` that weren't asked for, graphics being the least exclusive.
+    fn extra_capabilities(&self, family: &QueueFamilyInfo) -> u32 {
+        let extra = family.effective_flags() & !self.flags;
+        [
+            (vk::QueueFlags::GRAPHICS, 4),
+            (vk::QueueFlags::COMPUTE, 2),
+            (vk::QueueFlags::VIDEO_DECODE_KHR, 1),
+            (vk::QueueFlags::VIDEO_ENCODE_KHR, 1),
+        ]
+        .iter()
+        .filter(|(flag, _)| extra.contains(*flag))
+        .map(|(_, weight)| weight)
+        .sum()
+    }
+}
+
+/// The queue family a [`QueueRequest`](crate::app::feature_request::QueueRequest) targets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QueueFamily {
+    Index(u32),
+    Capabilities(QueueCapabilities),
+}
+
+impl QueueFamily {
+    pub const GRAPHICS: Self = Self::Capabilities(QueueCapabilities::GRAPHICS);
+    pub const COMPUTE: Self = Self::Capabilities(QueueCapabilities::COMPUTE);
+    pub const ASYNC_COMPUTE: Self = Self::Capabilities(QueueCapabilities::ASYNC_COMPUTE);
+    pub const TRANSFER: Self = Self::Capabilities(QueueCapabilities::TRANSFER);
+    pub const PRESENTATION: Self = Self::Capabilities(QueueCapabilities::PRESENTATION);
+}
+
+impl From<u32> for QueueFamily {
+    fn from(index: u32) -> Self {
+        Self::Index(index)
+    }
+}
+
+impl From<QueueCapabilities> for QueueFamily {
+    fn from(capabilities: QueueCapabilities) -> Self {
+        Self::Capabilities(capabilities)
+    }
+}
@@ -0,0 +1,493 @@
+use crate::app::feature_request::DeviceFeature;
+use crate::render::context::VulkanContext;
+use crate::render::context::limits::{LimitValue, limits_table};
+use crate::render::context::physical_device::PhysicalDeviceInfo;
+use crate::render::context::queues::{QueueLabel, QueueRef};
+use crate::render::context::version::format_version;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
+
+/// A snapshot of how a [`VulkanContext`] was configured: the selected device, what was enabled on it, how queues were
+/// assigned and the device limits. Serializes to JSON through `serde`, and to readable text through [`Display`].
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceReport {
+    pub device: DeviceSummary,
+    pub instance_api_version: String,
+    pub instance_layers: Vec<String>,
+    pub instance_extensions: Vec<String>,
+    pub device_extensions: Vec<String>,
+    pub features: Vec<String>,
+    pub queue_families: Vec<QueueFamilyReport>,
+    pub labeled_queues: BTreeMap<String, Vec<QueueRefReport>>,
+    pub unlabeled_queues: BTreeMap<u32, Vec<u32>>,
+    #[serde(serialize_with = "serialize_limits")]
+    pub limits: Vec<(&'static str, LimitValue)>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceSummary {
+    pub index: usize,
+    pub name: String,
+    pub device_type: String,
+    pub uuid: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: String,
+    pub negotiated_api_version: String,
+    pub driver_version: u32,
+    pub driver: Option<DriverSummary>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DriverSummary {
+    pub id: String,
+    pub name: String,
+    pub info: String,
+    pub conformance_version: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueFamilyReport {
+    pub index: u32,
+    pub flags: String,
+    pub queue_count: u32,
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct QueueRefReport {
+    pub family: u32,
+    pub index: u32,
+}
+
+impl From<&QueueRef> for QueueRefReport {
+    fn from(queue_ref: &QueueRef) -> Self {
+        Self {
+            family: queue_ref.family,
+            index: queue_ref.index,
+        }
+    }
+}
+
+impl DeviceSummary {
+    pub fn new(info: &PhysicalDeviceInfo) -> Self {
+        Self {
+            index: info.index(),
+            name: info.name(),
+            device_type: format!("{:?}", info.device_type()),
+            uuid: info.uuid().to_string(),
+            vendor_id: info.vendor_id(),
+            device_id: info.device_id(),
+            api_version: format_version(info.api_version()),
+            negotiated_api_version: format_version(info.negotiated_api_version()),
+            driver_version: info.driver_version(),
+            driver: info.driver().map(|driver| DriverSummary {
+                id: format!("{:?}", driver.id),
+                name: driver.name.clone(),
+                info: driver.info.clone(),
+                conformance_version: format!(
+                    "{}.{}.{}.{}",
+                    driver.conformance_version.major,
+                    driver.conformance_version.minor,
+                    driver.conformance_version.subminor,
+                    driver.conformance_version.patch
+                ),
+            }),
+        }
+    }
+}
+
+/// Why no context could be created on a device, along with what is known about the device.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceFailure {
+    pub index: usize,
+    /// `None` if the device couldn't be queried.
+    pub device: Option<DeviceSummary>,
+    pub error: String,
+    #[serde(serialize_with = "serialize_limits")]
+    pub limits: Vec<(&'static str, LimitValue)>,
+}
+
+impl DeviceFailure {
+    pub fn new(index: usize, info: Option<&PhysicalDeviceInfo>, error: &anyhow::Error) -> Self {
+        Self {
+            index,
+            device: info.map(DeviceSummary::new),
+            error: format!("{:#}", error),
+            limits: info.map(|info| limits_table(info.limits())).unwrap_or_default(),
+        }
+    }
+}
+
+impl DeviceReport {
+    pub fn new(context: &VulkanContext) -> Self {
+        let info = context.physical_device_info();
+        let device = context.device();
+
+        let labeled_queues = device
+            .queue_labels()
+            .iter()
+            .map(|(label, refs)| {
+                (
+                    label_name(label),
+                    refs.iter().map(QueueRefReport::from).collect(),
+                )
+            })
+            .collect();
+
+        let unlabeled_queues = device
+            .unlabeled_queues()
+            .iter()
+            .map(|(family, indices)| {
+                let mut indices = indices.iter().copied().collect::<Vec<u32>>();
+                indices.sort();
+                (*family, indices)
+            })
+            .collect();
+
+        let mut features = device
+            .enabled_features()
+            .iter()
+            .map(feature_name)
+            .collect::<Vec<String>>();
+        features.sort();
+
+        Self {
+            device: DeviceSummary::new(info),
+            instance_api_version: format_version(context.instance().api_version()),
+            instance_layers: sorted_names(context.instance().layers()),
+            instance_extensions: sorted_names(context.instance().extensions()),
+            device_extensions: sorted_names(device.enabled_extensions()),
+            features,
+            queue_families: info
+                .queue_families()
+                .iter()
+                .enumerate()
+                .map(|(index, family)| QueueFamilyReport {
+                    index: index as u32,
+                    flags: format!("{:?}", family.queue_flags),
+                    queue_count: family.queue_count,
+                })
+                .collect(),
+            labeled_queues,
+            unlabeled_queues,
+            limits: limits_table(info.limits()),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn label_name(label: &QueueLabel) -> String {
+    match label {
+        QueueLabel::Custom(name) => format!("Custom({})", name),
+        label => format!("{:?}", label),
+    }
+}
+
+fn feature_name(feature: &DeviceFeature) -> String {
+    match feature {
+        DeviceFeature::Extension(feature) => {
+            format!("{}::{}", feature.extension.to_string_lossy(), feature.name)
+        }
+        feature => feature.vulkan_name().to_owned(),
+    }
+}
+
+fn sorted_names(names: &HashSet<&'static CStr>) -> Vec<String> {
+    let mut names = names
+        .iter()
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    names.sort();
+    names
+}
+
+fn serialize_limits<S: Serializer>(
+    limits: &Vec<(&'static str, LimitValue)>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(limits.iter().map(|(name, value)| (*name, value)))
+}
+
+fn write_list(f: &mut Formatter<'_>, title: &str, items: &[String]) -> std::fmt::Result {
+    writeln!(f, "{} ({}):", title, items.len())?;
+    for item in items {
+        writeln!(f, "    {}", item)?;
+    }
+    Ok(())
+}
+
+fn write_limits(f: &mut Formatter<'_>, limits: &[(&'static str, LimitValue)]) -> std::fmt::Result {
+    writeln!(f, "Limits:")?;
+    for (name, value) in limits {
+        writeln!(f, "    {}: {}", name, value)?;
+    }
+    Ok(())
+}
+
+impl Display for DeviceSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Device #{}: {} ({})",
+            self.index, self.name, self.device_type
+        )?;
+        writeln!(f, "    UUID: {}", self.uuid)?;
+        writeln!(
+            f,
+            "    Vendor: {:#06x}, device: {:#06x}",
+            self.vendor_id, self.device_id
+        )?;
+        writeln!(
+            f,
+            "    Vulkan: {} (using {})",
+            self.api_version, self.negotiated_api_version
+        )?;
+        match &self.driver {
+            Some(driver) => writeln!(
+                f,
+                "    Driver: {} {} ({}, conformance {})",
+                driver.name, driver.info, driver.id, driver.conformance_version
+            ),
+            None => writeln!(f, "    Driver version: {:#x}", self.driver_version),
+        }
+    }
+}
+
+impl Display for DeviceReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.device)?;
+        writeln!(f, "Instance Vulkan version: {}", self.instance_api_version)?;
+        write_list(f, "Instance layers", &self.instance_layers)?;
+        write_list(f, "Instance extensions", &self.instance_extensions)?;
+        write_list(f, "Device extensions", &self.device_extensions)?;
+        write_list(f, "Enabled features", &self.features)?;
+
+        writeln!(f, "Queue families ({}):", self.queue_families.len())?;
+        for family in &self.queue_families {
+            writeln!(
+                f,
+                "    #{}: {} x{}",
+                family.index, family.flags, family.queue_count
+            )?;
+        }
+
+        writeln!(f, "Labeled queues:")?;
+        for (label, refs) in &self.labeled_queues {
+            let refs = refs
+                .iter()
+                .map(|r| format!("{}:{}", r.family, r.index))
+                .collect::<Vec<String>>();
+            writeln!(f, "    {}: {}", label, refs.join(", "))?;
+        }
+
+        writeln!(f, "Unlabeled queues:")?;
+        for (family, indices) in &self.unlabeled_queues {
+            writeln!(f, "    family {}: {:?}", family, indices)?;
+        }
+
+        write_limits(f, &self.limits)
+    }
+}
+
+impl Display for DeviceFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.device {
+            Some(device) => write!(f, "{}", device)?,
+            None => writeln!(f, "Device #{}", self.index)?,
+        }
+        writeln!(f, "Unusable: {}", self.error)?;
+
+        if self.limits.is_empty() {
+            Ok(())
+        } else {
+            write_limits(f, &self.limits)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk;
+    use serde_json::json;
+
+    fn summary() -> DeviceSummary {
+        DeviceSummary {
+            index: 0,
+            name: "Test GPU".to_owned(),
+            device_type: "DISCRETE_GPU".to_owned(),
+            uuid: "00000000-0000-0000-0000-000000000000".to_owned(),
+            vendor_id: 0x10de,
+            device_id: 0x2684,
+            api_version: "1.3.280".to_owned(),
+            negotiated_api_version: "1.3.0".to_owned(),
+            driver_version: 0x8c0c0000,
+            driver: Some(DriverSummary {
+                id: "NVIDIA_PROPRIETARY".to_owned(),
+                name: "NVIDIA".to_owned(),
+                info: "560.35".to_owned(),
+                conformance_version: "1.3.8.2".to_owned(),
+            }),
+        }
+    }
+
+    fn report() -> DeviceReport {
+        DeviceReport {
+            device: summary(),
+            instance_api_version: "1.3.0".to_owned(),
+            instance_layers: vec![],
+            instance_extensions: vec!["VK_EXT_debug_utils".to_owned()],
+            device_extensions: vec!["VK_KHR_swapchain".to_owned()],
+            features: vec!["samplerAnisotropy".to_owned(), "timelineSemaphore".to_owned()],
+            queue_families: vec![
+                QueueFamilyReport {
+                    index: 0,
+                    flags: "GRAPHICS | COMPUTE | TRANSFER".to_owned(),
+                    queue_count: 16,
+                },
+                QueueFamilyReport {
+                    index: 1,
+                    flags: "TRANSFER".to_owned(),
+                    queue_count: 2,
+                },
+            ],
+            labeled_queues: BTreeMap::from([
+                ("Graphics".to_owned(), vec![QueueRefReport { family: 0, index: 0 }]),
+                (
+                    "Transfer".to_owned(),
+                    vec![QueueRefReport { family: 1, index: 0 }, QueueRefReport { family: 1, index: 1 }],
+                ),
+            ]),
+            unlabeled_queues: BTreeMap::from([(0, vec![1, 2])]),
+            limits: vec![
+                ("maxImageDimension2D", LimitValue::UInt(32768)),
+                (
+                    "framebufferColorSampleCounts",
+                    LimitValue::SampleCounts(vk::SampleCountFlags::TYPE_1 | vk::SampleCountFlags::TYPE_4),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn reports_serialize_to_json() {
+        let value = serde_json::from_str::<serde_json::Value>(&report().to_json().unwrap()).unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "device": {
+                    "index": 0,
+                    "name": "Test GPU",
+                    "device_type": "DISCRETE_GPU",
+                    "uuid": "00000000-0000-0000-0000-000000000000",
+                    "vendor_id": 0x10de,
+                    "device_id": 0x2684,
+                    "api_version": "1.3.280",
+                    "negotiated_api_version": "1.3.0",
+                    "driver_version": 0x8c0c0000u32,
+                    "driver": {
+                        "id": "NVIDIA_PROPRIETARY",
+                        "name": "NVIDIA",
+                        "info": "560.35",
+                        "conformance_version": "1.3.8.2"
+                    }
+                },
+                "instance_api_version": "1.3.0",
+                "instance_layers": [],
+                "instance_extensions": ["VK_EXT_debug_utils"],
+                "device_extensions": ["VK_KHR_swapchain"],
+                "features": ["samplerAnisotropy", "timelineSemaphore"],
+                "queue_families": [
+                    { "index": 0, "flags": "GRAPHICS | COMPUTE | TRANSFER", "queue_count": 16 },
+                    { "index": 1, "flags": "TRANSFER", "queue_count": 2 }
+                ],
+                "labeled_queues": {
+                    "Graphics": [{ "family": 0, "index": 0 }],
+                    "Transfer": [{ "family": 1, "index": 0 }, { "family": 1, "index": 1 }]
+                },
+                "unlabeled_queues": { "0": [1, 2] },
+                "limits": {
+                    "maxImageDimension2D": 32768,
+                    "framebufferColorSampleCounts": ["VK_SAMPLE_COUNT_1_BIT", "VK_SAMPLE_COUNT_4_BIT"]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn reports_display_as_text() {
+        assert_eq!(
+            report().to_string(),
+            "\
+Device #0: Test GPU (DISCRETE_GPU)
+    UUID: 00000000-0000-0000-0000-000000000000
+    Vendor: 0x10de, device: 0x2684
+    Vulkan: 1.3.280 (using 1.3.0)
+    Driver: NVIDIA 560.35 (NVIDIA_PROPRIETARY, conformance 1.3.8.2)
+Instance Vulkan version: 1.3.0
+Instance layers (0):
+Instance extensions (1):
+    VK_EXT_debug_utils
+Device extensions (1):
+    VK_KHR_swapchain
+Enabled features (2):
+    samplerAnisotropy
+    timelineSemaphore
+Queue families (2):
+    #0: GRAPHICS | COMPUTE | TRANSFER x16
+    #1: TRANSFER x2
+Labeled queues:
+    Graphics: 0:0
+    Transfer: 1:0, 1:1
+Unlabeled queues:
+    family 0: [1, 2]
+Limits:
+    maxImageDimension2D: 32768
+    framebufferColorSampleCounts: VK_SAMPLE_COUNT_1_BIT | VK_SAMPLE_COUNT_4_BIT
+"
+        );
+    }
+
+    #[test]
+    fn failures_keep_what_is_known_about_the_device() {
+        let properties = vk::PhysicalDeviceProperties::default()
+            .device_name(c"Old GPU")
+            .unwrap()
+            .device_type(vk::PhysicalDeviceType::INTEGRATED_GPU)
+            .api_version(vk::API_VERSION_1_1);
+        let info = PhysicalDeviceInfo::for_tests(1, properties, 0, &[]);
+        let error = anyhow::anyhow!("Vulkan 1.2 required").context("Device #1 is unusable");
+
+        let failure = DeviceFailure::new(1, Some(&info), &error);
+        assert_eq!(failure.error, "Device #1 is unusable: Vulkan 1.2 required");
+        assert_eq!(failure.limits.len(), limits_table(info.limits()).len());
+
+        let text = failure.to_string();
+        assert!(text.starts_with("Device #1: Old GPU (INTEGRATED_GPU)\n"));
+        assert!(text.contains("\nUnusable: Device #1 is unusable: Vulkan 1.2 required\nLimits:\n"));
+
+        let value = serde_json::to_value(&failure).unwrap();
+        assert_eq!(value["index"], 1);
+        assert_eq!(value["device"]["name"], "Old GPU");
+        assert_eq!(value["device"]["api_version"], "1.1.0");
+        assert_eq!(value["error"], "Device #1 is unusable: Vulkan 1.2 required");
+        assert_eq!(value["limits"]["maxImageDimension2D"], 0);
+    }
+
+    #[test]
+    fn failures_without_device_info() {
+        let failure = DeviceFailure::new(2, None, &anyhow::anyhow!("VK_ERROR_INITIALIZATION_FAILED"));
+
+        assert_eq!(failure.to_string(), "Device #2\nUnusable: VK_ERROR_INITIALIZATION_FAILED\n");
+        assert_eq!(
+            serde_json::to_value(&failure).unwrap(),
+            json!({ "index": 2, "device": null, "error": "VK_ERROR_INITIALIZATION_FAILED", "limits": {} })
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use crate::render::command_recorder::SemaphoreInfo;
+use crate::render::context::VulkanContext;
+use crate::render::context::queues::QueueRef;
+use ash::prelude::VkResult;
+use ash::vk;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long the thread behind a [`SubmissionFuture`] waits before checking whether the future was dropped.
+const FUTURE_WAIT_SLICE: Duration = Duration::from_millis(50);
+
+/// A submission to a queue, identified by the value it signals on the queue's timeline semaphore.
+///
+/// Every submit through a [`Queue`](crate::render::context::queue::Queue) returns one. The handle can be waited on from
+/// the host, polled, awaited, or waited on by a submission to another queue through [`SubmissionHandle::wait_info`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SubmissionHandle {
+    queue: QueueRef,
+    semaphore: vk::Semaphore,
+    value: u64,
+}
+
+impl SubmissionHandle {
+    pub(crate) fn new(queue: QueueRef, semaphore: vk::Semaphore, value: u64) -> Self {
+        Self {
+            queue,
+            semaphore,
+            value,
+        }
+    }
+
+    /// The queue the submission ran on.
+    pub fn queue(&self) -> QueueRef {
+        self.queue
+    }
+
+    /// The timeline semaphore of the queue.
+    pub fn semaphore(&self) -> vk::Semaphore {
+        self.semaphore
+    }
+
+    /// The value the timeline semaphore reaches once the submission and every earlier submission to the queue
+    /// completed.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// A wait on the submission for another submission, blocking `stages` until it completes.
+    pub fn wait_info(&self, stages: vk::PipelineStageFlags2) -> SemaphoreInfo {
+        SemaphoreInfo::timeline(self.semaphore, self.value, stages)
+    }
+
+    /// Returns whether the submission completed, without blocking.
+    pub fn is_complete(&self, vulkan: &VulkanContext) -> VkResult<bool> {
+        Ok(unsafe { vulkan.device().get_semaphore_counter_value(self.semaphore) }? >= self.value)
+    }
+
+    /// Blocks until the submission completed.
+    pub fn wait(&self, vulkan: &VulkanContext) -> VkResult<()> {
+        Self::wait_all(vulkan, &[*self])
+    }
+
+    /// Blocks until the submission completed or `timeout` elapsed, returns whether it completed.
+    pub fn wait_timeout(&self, vulkan: &VulkanContext, timeout: Duration) -> VkResult<bool> {
+        wait_semaphores(
+            vulkan,
+            &[*self],
+            timeout.as_nanos().min(u64::MAX as u128) as u64,
+        )
+    }
+
+    /// Blocks until every submission in `handles` completed, the handles can belong to different queues.
+    pub fn wait_all(vulkan: &VulkanContext, handles: &[SubmissionHandle]) -> VkResult<()> {
+        wait_semaphores(vulkan, handles, u64::MAX).map(|_| ())
+    }
+
+    /// A future resolving once the submission completed.
+    ///
+    /// ```no_run
+    /// # use neuron_engine::render::context::VulkanContext;
+    /// # use neuron_engine::render::context::submission::SubmissionHandle;
+    /// # use std::sync::Arc;
+    /// # fn example(vulkan: Arc<VulkanContext>, upload: SubmissionHandle) -> ash::prelude::VkResult<()> {
+    /// pollster::block_on(upload.future(vulkan))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn future(&self, vulkan: Arc<VulkanContext>) -> SubmissionFuture {
+        SubmissionFuture {
+            vulkan,
+            handle: *self,
+            waiter: None,
+        }
+    }
+}
+
+/// Waits on the timeline values of `handles`, returns `false` on timeout.
+fn wait_semaphores(
+    vulkan: &VulkanContext,
+    handles: &[SubmissionHandle],
+    timeout: u64,
+) -> VkResult<bool> {
+    if handles.is_empty() {
+        return Ok(true);
+    }
+
+    let semaphores = handles
+        .iter()
+        .map(SubmissionHandle::semaphore)
+        .collect::<Vec<vk::Semaphore>>();
+    let values = handles
+        .iter()
+        .map(SubmissionHandle::value)
+        .collect::<Vec<u64>>();
+
+    let result = unsafe {
+        vulkan.device().wait_semaphores(
+            &vk::SemaphoreWaitInfo::default()
+                .semaphores(&semaphores)
+                .values(&values),
+            timeout,
+        )
+    };
+
+    match result {
+        Ok(()) => Ok(true),
+        Err(vk::Result::TIMEOUT) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+struct Waiter {
+    waker: Arc<Mutex<Waker>>,
+    cancelled: Arc<AtomicBool>,
+    thread: JoinHandle<VkResult<()>>,
+}
+
+/// A [`Future`] resolving once a submission completed, see [`SubmissionHandle::future`].
+///
+/// Polling checks the timeline semaphore; while the submission is pending, a thread waits on the semaphore and wakes
+/// the task once it is signaled, so the future works with any executor, such as `pollster::block_on`.
+pub struct SubmissionFuture {
+    vulkan: Arc<VulkanContext>,
+    handle: SubmissionHandle,
+    waiter: Option<Waiter>,
+}
+
+impl SubmissionFuture {
+    pub fn handle(&self) -> SubmissionHandle {
+        self.handle
+    }
+}
+
+impl Future for SubmissionFuture {
+    type Output = VkResult<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(waiter) = self.waiter.take_if(|waiter| waiter.thread.is_finished()) {
+            return Poll::Ready(
+                waiter
+                    .thread
+                    .join()
+                    .unwrap_or(Err(vk::Result::ERROR_UNKNOWN)),
+            );
+        }
+
+        match self.handle.is_complete(&self.vulkan) {
+            Ok(true) => return Poll::Ready(Ok(())),
+            Ok(false) => {}
+            Err(e) => return Poll::Ready(Err(e)),
+        }
+
+        match &self.waiter {
+            Some(waiter) => {
+                waiter
+                    .waker
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone_from(cx.waker());
+            }
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let cancelled = Arc::new(AtomicBool::new(false));
+
+                let vulkan = self.vulkan.clone();
+                let handle = self.handle;
+                let thread_waker = waker.clone();
+                let thread_cancelled = cancelled.clone();
+                let thread = std::thread::spawn(move || {
+                    let mut result = Ok(());
+                    while !thread_cancelled.load(Ordering::Relaxed) {
+                        match handle.wait_timeout(&vulkan, FUTURE_WAIT_SLICE) {
+                            Ok(false) => {}
+                            Ok(true) => break,
+                            Err(e) => {
+                                result = Err(e);
+                                break;
+                            }
+                        }
+                    }
+
+                    // errors wake the task as well, the next poll returns them
+                    thread_waker
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .wake_by_ref();
+                    result
+                });
+
+                self.waiter = Some(Waiter {
+                    waker,
+                    cancelled,
+                    thread,
+                });
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for SubmissionFuture {
+    fn drop(&mut self) {
+        if let Some(waiter) = &self.waiter {
+            waiter.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
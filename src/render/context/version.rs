@@ -0,0 +1,216 @@
+use anyhow::anyhow;
+use ash::{ext, khr, vk};
+use std::ffi::CStr;
+
+/// `ash` is generated against the 1.3 headers, so it doesn't define this one yet.
+pub const API_VERSION_1_4: u32 = vk::make_api_version(0, 1, 4, 0);
+
+/// The oldest Vulkan version the engine can run on. Vulkan 1.3 features are enabled through their original extensions
+/// on 1.2 devices.
+pub const ENGINE_MINIMUM_API_VERSION: u32 = vk::API_VERSION_1_2;
+
+/// Device extensions that were promoted to core, along with the version that promoted them.
+pub const PROMOTED_DEVICE_EXTENSIONS: &[(&CStr, u32)] = &[
+    // Vulkan 1.2
+    (khr::_8bit_storage::NAME, vk::API_VERSION_1_2),
+    (khr::buffer_device_address::NAME, vk::API_VERSION_1_2),
+    (khr::create_renderpass2::NAME, vk::API_VERSION_1_2),
+    (khr::depth_stencil_resolve::NAME, vk::API_VERSION_1_2),
+    (khr::draw_indirect_count::NAME, vk::API_VERSION_1_2),
+    (khr::driver_properties::NAME, vk::API_VERSION_1_2),
+    (khr::image_format_list::NAME, vk::API_VERSION_1_2),
+    (khr::imageless_framebuffer::NAME, vk::API_VERSION_1_2),
+    (khr::sampler_mirror_clamp_to_edge::NAME, vk::API_VERSION_1_2),
+    (khr::separate_depth_stencil_layouts::NAME, vk::API_VERSION_1_2),
+    (khr::shader_atomic_int64::NAME, vk::API_VERSION_1_2),
+    (khr::shader_float16_int8::NAME, vk::API_VERSION_1_2),
+    (khr::shader_float_controls::NAME, vk::API_VERSION_1_2),
+    (khr::shader_subgroup_extended_types::NAME, vk::API_VERSION_1_2),
+    (khr::spirv_1_4::NAME, vk::API_VERSION_1_2),
+    (khr::timeline_semaphore::NAME, vk::API_VERSION_1_2),
+    (khr::uniform_buffer_standard_layout::NAME, vk::API_VERSION_1_2),
+    (khr::vulkan_memory_model::NAME, vk::API_VERSION_1_2),
+    (ext::descriptor_indexing::NAME, vk::API_VERSION_1_2),
+    (ext::host_query_reset::NAME, vk::API_VERSION_1_2),
+    (ext::sampler_filter_minmax::NAME, vk::API_VERSION_1_2),
+    (ext::scalar_block_layout::NAME, vk::API_VERSION_1_2),
+    (ext::separate_stencil_usage::NAME, vk::API_VERSION_1_2),
+    (ext::shader_viewport_index_layer::NAME, vk::API_VERSION_1_2),
+    // Vulkan 1.3
+    (khr::copy_commands2::NAME, vk::API_VERSION_1_3),
+    (khr::dynamic_rendering::NAME, vk::API_VERSION_1_3),
+    (khr::format_feature_flags2::NAME, vk::API_VERSION_1_3),
+    (khr::maintenance4::NAME, vk::API_VERSION_1_3),
+    (khr::shader_integer_dot_product::NAME, vk::API_VERSION_1_3),
+    (khr::shader_non_semantic_info::NAME, vk::API_VERSION_1_3),
+    (khr::shader_terminate_invocation::NAME, vk::API_VERSION_1_3),
+    (khr::synchronization2::NAME, vk::API_VERSION_1_3),
+    (khr::zero_initialize_workgroup_memory::NAME, vk::API_VERSION_1_3),
+    (ext::extended_dynamic_state::NAME, vk::API_VERSION_1_3),
+    (ext::extended_dynamic_state2::NAME, vk::API_VERSION_1_3),
+    (ext::image_robustness::NAME, vk::API_VERSION_1_3),
+    (ext::inline_uniform_block::NAME, vk::API_VERSION_1_3),
+    (ext::pipeline_creation_cache_control::NAME, vk::API_VERSION_1_3),
+    (ext::private_data::NAME, vk::API_VERSION_1_3),
+    (ext::shader_demote_to_helper_invocation::NAME, vk::API_VERSION_1_3),
+    (ext::subgroup_size_control::NAME, vk::API_VERSION_1_3),
+    (ext::texel_buffer_alignment::NAME, vk::API_VERSION_1_3),
+    (ext::texture_compression_astc_hdr::NAME, vk::API_VERSION_1_3),
+    (ext::tooling_info::NAME, vk::API_VERSION_1_3),
+    (ext::ycbcr_2plane_444_formats::NAME, vk::API_VERSION_1_3),
+    // Vulkan 1.4
+    (khr::dynamic_rendering_local_read::NAME, API_VERSION_1_4),
+    (khr::global_priority::NAME, API_VERSION_1_4),
+    (khr::index_type_uint8::NAME, API_VERSION_1_4),
+    (khr::line_rasterization::NAME, API_VERSION_1_4),
+    (khr::load_store_op_none::NAME, API_VERSION_1_4),
+    (khr::maintenance5::NAME, API_VERSION_1_4),
+    (khr::maintenance6::NAME, API_VERSION_1_4),
+    (khr::map_memory2::NAME, API_VERSION_1_4),
+    (khr::push_descriptor::NAME, API_VERSION_1_4),
+    (khr::shader_expect_assume::NAME, API_VERSION_1_4),
+    (khr::shader_float_controls2::NAME, API_VERSION_1_4),
+    (khr::shader_subgroup_rotate::NAME, API_VERSION_1_4),
+    (khr::vertex_attribute_divisor::NAME, API_VERSION_1_4),
+    (ext::host_image_copy::NAME, API_VERSION_1_4),
+    (ext::pipeline_protected_access::NAME, API_VERSION_1_4),
+    (ext::pipeline_robustness::NAME, API_VERSION_1_4),
+];
+
+/// The minimum and preferred Vulkan versions of an application, see
+/// [`EngineCallbackHandler::api_version`](crate::EngineCallbackHandler::api_version).
+///
+/// The engine uses the highest version up to `preferred` that both the loader and the selected device support, and
+/// rejects devices below `minimum`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ApiVersionRequest {
+    pub minimum: u32,
+    pub preferred: u32,
+}
+
+impl ApiVersionRequest {
+    pub const fn new(minimum: u32, preferred: u32) -> Self {
+        Self { minimum, preferred }
+    }
+
+    pub const fn exact(version: u32) -> Self {
+        Self::new(version, version)
+    }
+
+    /// The lowest version accepted for this request: `minimum`, raised to [`ENGINE_MINIMUM_API_VERSION`].
+    pub fn effective_minimum(&self) -> u32 {
+        major_minor(self.minimum.max(ENGINE_MINIMUM_API_VERSION))
+    }
+
+    /// The instance version to use with a loader supporting `loader_version`: `preferred`, lowered to what the loader
+    /// supports but never below [`ApiVersionRequest::effective_minimum`]. Fails if the loader is older than that.
+    pub fn negotiate(&self, loader_version: u32) -> anyhow::Result<u32> {
+        let minimum = self.effective_minimum();
+        if !version_at_least(loader_version, minimum) {
+            return Err(anyhow!(
+                "The Vulkan loader only supports Vulkan {}, Vulkan {} is required",
+                format_version(loader_version),
+                format_version(minimum)
+            ));
+        }
+
+        Ok(major_minor(self.preferred).max(minimum).min(major_minor(loader_version)))
+    }
+}
+
+impl Default for ApiVersionRequest {
+    fn default() -> Self {
+        Self::exact(vk::API_VERSION_1_3)
+    }
+}
+
+/// Compares versions by major and minor only, patch versions don't change the available API.
+pub fn version_at_least(version: u32, required: u32) -> bool {
+    (vk::api_version_major(version), vk::api_version_minor(version))
+        >= (vk::api_version_major(required), vk::api_version_minor(required))
+}
+
+/// Whether `extension` is part of core Vulkan at `version`.
+pub fn promoted_to_core(extension: &CStr, version: u32) -> bool {
+    PROMOTED_DEVICE_EXTENSIONS
+        .iter()
+        .any(|(name, promoted)| *name == extension && version_at_least(version, *promoted))
+}
+
+pub fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+/// Clamps `version` to its major and minor parts (e.g. a device reporting 1.3.280 is used as 1.3).
+pub fn major_minor(version: u32) -> u32 {
+    vk::make_api_version(
+        0,
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_by_major_and_minor() {
+        assert!(version_at_least(vk::make_api_version(0, 1, 3, 0), vk::API_VERSION_1_3));
+        assert!(version_at_least(vk::make_api_version(0, 1, 3, 0), vk::make_api_version(0, 1, 3, 280)));
+        assert!(version_at_least(API_VERSION_1_4, vk::API_VERSION_1_3));
+        assert!(!version_at_least(vk::make_api_version(0, 1, 2, 999), vk::API_VERSION_1_3));
+        assert_eq!(major_minor(vk::make_api_version(0, 1, 3, 280)), vk::API_VERSION_1_3);
+        assert_eq!(format_version(vk::make_api_version(0, 1, 3, 280)), "1.3.280");
+    }
+
+    #[test]
+    fn promotion_depends_on_the_version() {
+        assert!(promoted_to_core(khr::synchronization2::NAME, vk::API_VERSION_1_3));
+        assert!(promoted_to_core(khr::synchronization2::NAME, API_VERSION_1_4));
+        assert!(!promoted_to_core(khr::synchronization2::NAME, vk::API_VERSION_1_2));
+        assert!(promoted_to_core(khr::timeline_semaphore::NAME, vk::API_VERSION_1_2));
+        assert!(!promoted_to_core(khr::swapchain::NAME, API_VERSION_1_4));
+    }
+
+    #[test]
+    fn preferred_version_is_used_when_the_loader_supports_it() {
+        let request = ApiVersionRequest::new(vk::API_VERSION_1_2, vk::API_VERSION_1_3);
+        assert_eq!(request.negotiate(vk::make_api_version(0, 1, 3, 280)).unwrap(), vk::API_VERSION_1_3);
+        assert_eq!(request.negotiate(API_VERSION_1_4).unwrap(), vk::API_VERSION_1_3);
+    }
+
+    #[test]
+    fn older_loaders_lower_the_version_down_to_the_minimum() {
+        let request = ApiVersionRequest::new(vk::API_VERSION_1_2, API_VERSION_1_4);
+        assert_eq!(request.negotiate(vk::API_VERSION_1_3).unwrap(), vk::API_VERSION_1_3);
+        assert_eq!(request.negotiate(vk::make_api_version(0, 1, 2, 198)).unwrap(), vk::API_VERSION_1_2);
+
+        let error = request.negotiate(vk::API_VERSION_1_1).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The Vulkan loader only supports Vulkan 1.1.0, Vulkan 1.2.0 is required"
+        );
+    }
+
+    #[test]
+    fn the_engine_minimum_overrides_lower_requests() {
+        let request = ApiVersionRequest::exact(vk::API_VERSION_1_0);
+        assert_eq!(request.effective_minimum(), ENGINE_MINIMUM_API_VERSION);
+        assert_eq!(request.negotiate(vk::API_VERSION_1_3).unwrap(), ENGINE_MINIMUM_API_VERSION);
+        assert!(request.negotiate(vk::API_VERSION_1_1).is_err());
+    }
+
+    #[test]
+    fn patch_versions_are_ignored() {
+        let request = ApiVersionRequest::new(vk::make_api_version(0, 1, 3, 100), vk::make_api_version(0, 1, 3, 200));
+        assert_eq!(request.effective_minimum(), vk::API_VERSION_1_3);
+        assert_eq!(request.negotiate(vk::make_api_version(0, 1, 3, 50)).unwrap(), vk::API_VERSION_1_3);
+    }
+}
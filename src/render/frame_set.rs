@@ -1,11 +1,44 @@
+use crate::errors::FrameSetError;
 use std::error::Error;
-use std::mem::MaybeUninit;
 use std::ops::{Index, IndexMut};
 
-pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
+/// The most frames a window can have in flight.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+/// How many frames the CPU can record ahead of the GPU, between 1 and [`MAX_FRAMES_IN_FLIGHT`].
+///
+/// More frames keep the GPU busier at the cost of latency. Defaults to 2.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FramesInFlight(usize);
+
+impl FramesInFlight {
+    pub const ONE: Self = Self(1);
+    pub const TWO: Self = Self(2);
+    pub const THREE: Self = Self(3);
+
+    /// Returns `None` unless `count` is between 1 and [`MAX_FRAMES_IN_FLIGHT`].
+    pub const fn new(count: usize) -> Option<Self> {
+        if count >= 1 && count <= MAX_FRAMES_IN_FLIGHT {
+            Some(Self(count))
+        } else {
+            None
+        }
+    }
+
+    pub const fn get(self) -> usize {
+        self.0
+    }
+}
+
+impl Default for FramesInFlight {
+    fn default() -> Self {
+        Self::TWO
+    }
+}
 
-#[repr(transparent)]
-pub struct FrameSet<T>([T; MAX_FRAMES_IN_FLIGHT]);
+/// One value per frame in flight, indexed by the current frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameSet<T>(Box<[T]>);
 
 impl<T> FrameSet<T> {
     pub fn iter(&self) -> impl Iterator<Item = &T> {
@@ -16,9 +49,26 @@ impl<T> FrameSet<T> {
         self.0.iter_mut()
     }
 
+    pub fn frames_in_flight(&self) -> FramesInFlight {
+        FramesInFlight(self.0.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Never `true` for sets built through [`FramesInFlight`], which guarantees 1 to [`MAX_FRAMES_IN_FLIGHT`] frames.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn create_factory<F: FnMut(usize) -> T>(frames_in_flight: FramesInFlight, f: F) -> Self {
+        Self((0..frames_in_flight.get()).map(f).collect())
+    }
 
-    pub fn create_factory<F: FnMut(usize) -> T>(f: F) -> Self {
-        Self(std::array::from_fn(f))
+    /// Applies `f` to every frame.
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> FrameSet<U> {
+        FrameSet(self.0.into_iter().map(f).collect())
     }
 }
 
@@ -38,41 +88,80 @@ impl<T> IndexMut<usize> for FrameSet<T> {
 
 impl<T> IntoIterator for FrameSet<T> {
     type Item = T;
-    type IntoIter = std::array::IntoIter<T, MAX_FRAMES_IN_FLIGHT>;
+    type IntoIter = std::vec::IntoIter<T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.0.into_vec().into_iter()
     }
 }
 
-impl<T,E: Error> FrameSet<Result<T, E>> {
+impl<T, E: Error> FrameSet<Result<T, E>> {
+    /// Returns the first error, or the set of values if every frame succeeded.
     pub fn promote_errors(self) -> Result<FrameSet<T>, E> {
-        unsafe {
-            let mut frame_set_uninit: [MaybeUninit<T>; MAX_FRAMES_IN_FLIGHT] = MaybeUninit::uninit().assume_init();
+        Ok(FrameSet(self.0.into_iter().collect::<Result<_, E>>()?))
+    }
+}
+
+impl<T> From<FrameSet<T>> for Vec<T> {
+    fn from(val: FrameSet<T>) -> Self {
+        val.0.into_vec()
+    }
+}
 
-            for (i, elem) in self.0.into_iter().enumerate() {
-                frame_set_uninit[i].write(elem?);
-            }
+impl<T> TryFrom<Vec<T>> for FrameSet<T> {
+    type Error = FrameSetError;
 
-            Ok(FrameSet(std::mem::transmute_copy::<_, [T; MAX_FRAMES_IN_FLIGHT]>(&frame_set_uninit)))
+    /// Fails unless the vector has between 1 and [`MAX_FRAMES_IN_FLIGHT`] values, one per frame.
+    fn try_from(value: Vec<T>) -> Result<Self, Self::Error> {
+        match FramesInFlight::new(value.len()) {
+            Some(_) => Ok(Self(value.into_boxed_slice())),
+            None => Err(FrameSetError::InvalidFrameCount(value.len())),
         }
     }
 }
 
-impl<T> Into<[T; MAX_FRAMES_IN_FLIGHT]> for FrameSet<T> {
-    fn into(self) -> [T; MAX_FRAMES_IN_FLIGHT] {
-        self.0
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_in_flight_range() {
+        assert_eq!(FramesInFlight::new(0), None);
+        assert_eq!(FramesInFlight::new(1), Some(FramesInFlight::ONE));
+        assert_eq!(FramesInFlight::new(3), Some(FramesInFlight::THREE));
+        assert_eq!(FramesInFlight::new(MAX_FRAMES_IN_FLIGHT + 1), None);
     }
-}
 
-impl<T> From<[T; MAX_FRAMES_IN_FLIGHT]> for FrameSet<T> {
-    fn from(value: [T; MAX_FRAMES_IN_FLIGHT]) -> Self {
-        Self(value)
+    #[test]
+    fn sets_follow_the_frame_count() {
+        let set = FrameSet::create_factory(FramesInFlight::THREE, |i| i * 10);
+        assert_eq!(set.len(), 3);
+        assert_eq!(set[2], 20);
+        assert_eq!(set.frames_in_flight(), FramesInFlight::THREE);
+        assert_eq!(Vec::from(set.map(|value| value + 1)), vec![1, 11, 21]);
     }
-}
 
-impl<T> From<Vec<T>> for FrameSet<T> where for <'a> &'a[T]: TryInto<[T; MAX_FRAMES_IN_FLIGHT]> {
-    fn from(value: Vec<T>) -> Self {
-        Self(value.as_slice()[..MAX_FRAMES_IN_FLIGHT].try_into().ok().unwrap())
+    #[test]
+    fn vectors_of_invalid_length_are_rejected() {
+        assert!(matches!(
+            FrameSet::<u32>::try_from(vec![]),
+            Err(FrameSetError::InvalidFrameCount(0))
+        ));
+        assert!(matches!(
+            FrameSet::try_from(vec![0; 4]),
+            Err(FrameSetError::InvalidFrameCount(4))
+        ));
+        assert_eq!(FrameSet::try_from(vec![1, 2]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn promote_errors_returns_the_first_error() {
+        let set = FrameSet::create_factory(FramesInFlight::TWO, |i| {
+            if i == 0 { Ok(i) } else { Err(std::fmt::Error) }
+        });
+        assert!(set.promote_errors().is_err());
+
+        let set = FrameSet::create_factory(FramesInFlight::ONE, Ok::<_, std::fmt::Error>);
+        assert_eq!(Vec::from(set.promote_errors().unwrap()), vec![0]);
     }
 }
@@ -0,0 +1,1366 @@
+use crate::errors::RenderGraphError;
+use crate::render::barrier::{Access, BufferBarrier, ImageBarrier, full_subresource_range};
+use crate::render::context::queues::{QueueLabel, QueueLabels, QueueRef};
+use crate::render::graph::{
+    BufferDesc, ImageDesc, PassNode, RenderGraph, ResourceId, ResourceOrigin, ResourceUse,
+};
+use crate::render::image_state::ImageUsage;
+use ash::vk;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt::{Display, Formatter};
+
+/// A barrier on a graph resource, turned into an [`ImageBarrier`] or a [`BufferBarrier`] once the physical resource is
+/// known.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GraphBarrier {
+    pub resource: ResourceId,
+    pub src: Access,
+    pub dst: Access,
+    /// Always [`vk::ImageLayout::UNDEFINED`] for buffers.
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+    pub src_queue_family: u32,
+    pub dst_queue_family: u32,
+}
+
+impl GraphBarrier {
+    fn new(
+        resource: ResourceId,
+        src: Access,
+        dst: Access,
+        old: vk::ImageLayout,
+        new: vk::ImageLayout,
+    ) -> Self {
+        Self {
+            resource,
+            src,
+            dst,
+            old_layout: old,
+            new_layout: new,
+            src_queue_family: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family: vk::QUEUE_FAMILY_IGNORED,
+        }
+    }
+
+    fn queue_family_transfer(mut self, src: u32, dst: u32) -> Self {
+        self.src_queue_family = src;
+        self.dst_queue_family = dst;
+        self
+    }
+
+    pub fn is_queue_family_transfer(&self) -> bool {
+        self.src_queue_family != self.dst_queue_family
+    }
+
+    pub fn image_barrier(
+        &self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> ImageBarrier {
+        ImageBarrier::new(image, self.old_layout, self.new_layout)
+            .src(self.src)
+            .dst(self.dst)
+            .queue_family_transfer(self.src_queue_family, self.dst_queue_family)
+            .subresource_range(full_subresource_range(aspect_mask))
+    }
+
+    pub fn buffer_barrier(&self, buffer: vk::Buffer) -> BufferBarrier {
+        BufferBarrier::new(buffer, self.src, self.dst)
+            .queue_family_transfer(self.src_queue_family, self.dst_queue_family)
+    }
+}
+
+/// An edge of the compiled graph: the pass at position `pass` of the schedule has to finish its use of `resource` first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Dependency {
+    pub pass: usize,
+    pub resource: ResourceId,
+    /// Whether the two passes run on different queues, in which case a timeline semaphore orders them.
+    pub cross_queue: bool,
+}
+
+/// A pass of the schedule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompiledPass {
+    /// The index of the pass in declaration order.
+    pub pass: usize,
+    pub name: String,
+    pub label: QueueLabel,
+    pub queue: QueueRef,
+    /// The index of the [`Batch`] submitting the pass.
+    pub batch: usize,
+    pub dependencies: Vec<Dependency>,
+    /// Recorded before the pass: layout transitions, hazards and the acquire half of ownership transfers.
+    pub barriers: Vec<GraphBarrier>,
+    /// Recorded after the pass: the release half of ownership transfers and the transitions of exported resources.
+    pub release_barriers: Vec<GraphBarrier>,
+}
+
+/// A timeline semaphore wait of a [`Batch`] on a batch of another queue.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BatchWait {
+    pub queue: QueueRef,
+    /// The value signaled by the awaited batch, relative to the start of the graph execution.
+    pub value: u64,
+    pub stages: vk::PipelineStageFlags2,
+}
+
+/// Consecutive passes of one queue submitted together. The batch waits for its dependencies on other queues before its
+/// first pass and signals the timeline semaphore of its queue once its last pass is done.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Batch {
+    pub queue: QueueRef,
+    /// Positions of the passes in the schedule.
+    pub passes: Vec<usize>,
+    /// The timeline value signaled by the batch, relative to the start of the graph execution: the batches of a queue
+    /// signal `1`, `2`, ...
+    pub value: u64,
+    pub waits: Vec<BatchWait>,
+}
+
+/// The description of a physical resource shared by transient resources with disjoint lifetimes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlotDesc {
+    Image(ImageDesc),
+    Buffer(BufferDesc),
+}
+
+/// A physical resource to create for the transient resources aliased onto it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransientSlot {
+    pub desc: SlotDesc,
+    /// The transient resources using the slot, in schedule order.
+    pub resources: Vec<ResourceId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CompiledResource {
+    pub(crate) name: String,
+    pub(crate) image: bool,
+    pub(crate) aspect_mask: vk::ImageAspectFlags,
+    pub(crate) slot: Option<usize>,
+    /// Whether a pass of the schedule uses the resource.
+    pub(crate) used: bool,
+}
+
+/// The result of [`RenderGraph::compile`]: the passes in execution order, grouped into per-queue batches, with every
+/// barrier and semaphore wait between them and the physical slots of the transient resources.
+///
+/// Compiling never touches Vulkan, so the schedule can be inspected and compared as plain data, printed through
+/// [`Display`] or exported with [`CompiledGraph::to_dot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompiledGraph {
+    pub(crate) passes: Vec<CompiledPass>,
+    pub(crate) batches: Vec<Batch>,
+    pub(crate) slots: Vec<TransientSlot>,
+    pub(crate) culled: Vec<usize>,
+    pub(crate) queues: Vec<(QueueLabel, QueueRef)>,
+    pub(crate) resources: Vec<CompiledResource>,
+    pub(crate) declared_passes: usize,
+}
+
+impl CompiledGraph {
+    pub fn passes(&self) -> &[CompiledPass] {
+        &self.passes
+    }
+
+    /// The batches in submission order.
+    pub fn batches(&self) -> &[Batch] {
+        &self.batches
+    }
+
+    pub fn slots(&self) -> &[TransientSlot] {
+        &self.slots
+    }
+
+    /// The declaration indices of the passes removed because nothing depends on them.
+    pub fn culled_passes(&self) -> &[usize] {
+        &self.culled
+    }
+
+    /// The queue a label used by the graph resolved to.
+    pub fn queue(&self, label: QueueLabel) -> Option<QueueRef> {
+        self.queues
+            .iter()
+            .find(|(queue_label, _)| *queue_label == label)
+            .map(|(_, queue)| *queue)
+    }
+
+    /// The slot a transient resource is aliased onto, `None` for imported and unused resources.
+    pub fn slot_of(&self, resource: ResourceId) -> Option<usize> {
+        self.resources
+            .get(resource.0)
+            .and_then(|resource| resource.slot)
+    }
+
+    pub fn resource_name(&self, resource: ResourceId) -> &str {
+        self.resources
+            .get(resource.0)
+            .map_or("?", |resource| resource.name.as_str())
+    }
+}
+
+/// The tracked state of a physical resource: an imported resource or a transient slot.
+#[derive(Copy, Clone, Debug)]
+struct PhysicalState {
+    layout: vk::ImageLayout,
+    access: Access,
+    writes: bool,
+    queue: Option<QueueRef>,
+    family: Option<u32>,
+    /// The position of the last pass using the resource.
+    last_pass: Option<usize>,
+    occupant: Option<ResourceId>,
+    /// Whether the contents can be thrown away, which skips ownership transfers.
+    discarded: bool,
+}
+
+impl PhysicalState {
+    /// The source scope of a barrier after the tracked accesses, reads only need an execution dependency.
+    fn src_access(&self) -> Access {
+        Access::new(
+            self.access.stages,
+            if self.writes {
+                self.access.access
+            } else {
+                vk::AccessFlags2::NONE
+            },
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Hazards {
+    last_writer: Option<usize>,
+    readers: Vec<(usize, vk::ImageLayout)>,
+}
+
+impl RenderGraph<'_> {
+    /// Compiles the declared passes against the queues negotiated for the device.
+    ///
+    /// Dependencies follow declaration order: reads depend on the last write of the resource, writes on the last write
+    /// and every read since, and reads in a different layout than earlier reads on those reads. Passes are then ordered
+    /// by the longest chain of passes depending on them, so work feeding long chains (typically on other queues) starts
+    /// first.
+    ///
+    /// Transient resources whose lifetimes don't overlap in the schedule share a [`TransientSlot`]: images need equal
+    /// descriptions, buffers the same usage. Resources are tracked as a whole (every mip level and array layer) and move
+    /// between queues one at a time, so a resource read on two queues is handed off from one to the other.
+    pub fn compile(&self, queue_labels: &QueueLabels) -> Result<CompiledGraph, RenderGraphError> {
+        let queues = self
+            .passes
+            .iter()
+            .map(|pass| resolve_queue(pass, queue_labels))
+            .collect::<Result<Vec<QueueRef>, RenderGraphError>>()?;
+
+        let uses = self
+            .passes
+            .iter()
+            .map(|pass| self.merge_uses(pass))
+            .collect::<Result<Vec<Vec<ResourceUse>>, RenderGraphError>>()?;
+
+        let (dependencies, producers) = self.hazards(&uses)?;
+        let live = self.live_passes(&uses, &producers);
+        let order = order_passes(&live, &dependencies);
+
+        let mut positions = vec![None; self.passes.len()];
+        for (position, pass) in order.iter().enumerate() {
+            positions[*pass] = Some(position);
+        }
+
+        let (slots, resource_slots) = self.assign_slots(&order, &uses);
+
+        let mut compiled = order
+            .iter()
+            .map(|pass| CompiledPass {
+                pass: *pass,
+                name: self.passes[*pass].name.clone(),
+                label: self.passes[*pass].queue,
+                queue: queues[*pass],
+                batch: 0,
+                dependencies: dependencies[*pass]
+                    .iter()
+                    .filter_map(|(dependency, resource)| {
+                        positions[*dependency].map(|position| Dependency {
+                            pass: position,
+                            resource: *resource,
+                            cross_queue: queues[*dependency] != queues[*pass],
+                        })
+                    })
+                    .collect(),
+                barriers: vec![],
+                release_barriers: vec![],
+            })
+            .collect::<Vec<CompiledPass>>();
+
+        self.track_states(&mut compiled, &uses, &resource_slots, slots.len());
+        for pass in &mut compiled {
+            let mut seen = Vec::with_capacity(pass.dependencies.len());
+            pass.dependencies.retain(|dependency| {
+                let key = (dependency.pass, dependency.resource);
+                let new = !seen.contains(&key);
+                seen.push(key);
+                new
+            });
+        }
+
+        let batches = build_batches(&mut compiled, &uses);
+
+        let mut used = vec![false; self.resources.len()];
+        for pass in &order {
+            for resource_use in &uses[*pass] {
+                used[resource_use.resource.0] = true;
+            }
+        }
+
+        let mut labels = compiled
+            .iter()
+            .map(|pass| (pass.label, pass.queue))
+            .collect::<Vec<(QueueLabel, QueueRef)>>();
+        labels.sort_by_key(|(label, _)| format!("{:?}", label));
+        labels.dedup();
+
+        Ok(CompiledGraph {
+            passes: compiled,
+            batches,
+            slots,
+            culled: (0..self.passes.len()).filter(|pass| !live[*pass]).collect(),
+            queues: labels,
+            resources: self
+                .resources
+                .iter()
+                .enumerate()
+                .map(|(index, resource)| CompiledResource {
+                    name: resource.name.clone(),
+                    image: resource.image,
+                    aspect_mask: resource.aspect_mask,
+                    slot: resource_slots[index],
+                    used: used[index],
+                })
+                .collect(),
+            declared_passes: self.passes.len(),
+        })
+    }
+
+    /// Validates the uses of a pass and merges the uses of the same resource.
+    fn merge_uses(&self, pass: &PassNode) -> Result<Vec<ResourceUse>, RenderGraphError> {
+        let mut merged: Vec<ResourceUse> = vec![];
+
+        for resource_use in &pass.uses {
+            let Some(resource) = self.resources.get(resource_use.resource.0) else {
+                return Err(RenderGraphError::UnknownResource {
+                    pass: pass.name.clone(),
+                    resource: resource_use.resource.0,
+                });
+            };
+
+            match merged
+                .iter_mut()
+                .find(|existing| existing.resource == resource_use.resource)
+            {
+                Some(existing) if existing.layout != resource_use.layout => {
+                    return Err(RenderGraphError::ConflictingLayouts {
+                        pass: pass.name.clone(),
+                        resource: resource.name.clone(),
+                        first: existing.layout,
+                        second: resource_use.layout,
+                    });
+                }
+                Some(existing) => {
+                    existing.access.stages |= resource_use.access.stages;
+                    existing.access.access |= resource_use.access.access;
+                    existing.read |= resource_use.read;
+                    existing.write |= resource_use.write;
+                }
+                None => merged.push(*resource_use),
+            }
+        }
+
+        Ok(merged)
+    }
+
+    /// The dependencies of every pass in declaration order, and the producers of what each pass reads.
+    #[allow(clippy::type_complexity)]
+    fn hazards(
+        &self,
+        uses: &[Vec<ResourceUse>],
+    ) -> Result<(Vec<Vec<(usize, ResourceId)>>, Vec<Vec<usize>>), RenderGraphError> {
+        let mut hazards = vec![Hazards::default(); self.resources.len()];
+        let mut dependencies = vec![vec![]; self.passes.len()];
+        let mut producers = vec![vec![]; self.passes.len()];
+
+        for (pass, pass_uses) in uses.iter().enumerate() {
+            for resource_use in pass_uses {
+                let resource = resource_use.resource;
+                let state = &mut hazards[resource.0];
+
+                if resource_use.read {
+                    match state.last_writer {
+                        Some(writer) => {
+                            dependencies[pass].push((writer, resource));
+                            producers[pass].push(writer);
+                        }
+                        None if self.resources[resource.0].is_transient() => {
+                            return Err(RenderGraphError::UninitializedRead {
+                                pass: self.passes[pass].name.clone(),
+                                resource: self.resources[resource.0].name.clone(),
+                            });
+                        }
+                        None => {}
+                    }
+                }
+
+                if resource_use.write {
+                    if let Some(writer) = state.last_writer
+                        && !resource_use.read
+                    {
+                        dependencies[pass].push((writer, resource));
+                    }
+                    dependencies[pass].extend(
+                        state
+                            .readers
+                            .iter()
+                            .filter(|(reader, _)| *reader != pass)
+                            .map(|(reader, _)| (*reader, resource)),
+                    );
+                    state.last_writer = Some(pass);
+                    state.readers.clear();
+                } else {
+                    dependencies[pass].extend(
+                        state
+                            .readers
+                            .iter()
+                            .filter(|(reader, layout)| {
+                                *reader != pass && *layout != resource_use.layout
+                            })
+                            .map(|(reader, _)| (*reader, resource)),
+                    );
+                    state.readers.push((pass, resource_use.layout));
+                }
+            }
+        }
+
+        for pass_dependencies in &mut dependencies {
+            pass_dependencies.sort();
+            pass_dependencies.dedup();
+        }
+
+        Ok((dependencies, producers))
+    }
+
+    /// Passes with side effects or writing an imported resource, and every pass producing something they read.
+    fn live_passes(&self, uses: &[Vec<ResourceUse>], producers: &[Vec<usize>]) -> Vec<bool> {
+        let mut live = vec![false; self.passes.len()];
+        let mut stack = (0..self.passes.len())
+            .filter(|pass| {
+                self.passes[*pass].side_effects
+                    || uses[*pass].iter().any(|resource_use| {
+                        resource_use.write
+                            && !self.resources[resource_use.resource.0].is_transient()
+                    })
+            })
+            .collect::<Vec<usize>>();
+
+        while let Some(pass) = stack.pop() {
+            if live[pass] {
+                continue;
+            }
+            live[pass] = true;
+            stack.extend(producers[pass].iter().copied());
+        }
+
+        live
+    }
+
+    /// Aliases transient resources with disjoint lifetimes onto shared slots.
+    fn assign_slots(
+        &self,
+        order: &[usize],
+        uses: &[Vec<ResourceUse>],
+    ) -> (Vec<TransientSlot>, Vec<Option<usize>>) {
+        let mut lifetimes: BTreeMap<ResourceId, (usize, usize)> = BTreeMap::new();
+        for (position, pass) in order.iter().enumerate() {
+            for resource_use in &uses[*pass] {
+                if self.resources[resource_use.resource.0].is_transient() {
+                    lifetimes
+                        .entry(resource_use.resource)
+                        .and_modify(|(_, last)| *last = position)
+                        .or_insert((position, position));
+                }
+            }
+        }
+
+        let mut transients = lifetimes
+            .into_iter()
+            .collect::<Vec<(ResourceId, (usize, usize))>>();
+        transients.sort_by_key(|(resource, (first, _))| (*first, *resource));
+
+        let mut slots: Vec<TransientSlot> = vec![];
+        let mut slot_ends: Vec<usize> = vec![];
+        let mut resource_slots = vec![None; self.resources.len()];
+
+        for (resource, (first, last)) in transients {
+            let desc = match self.resources[resource.0].origin {
+                ResourceOrigin::TransientImage(desc) => SlotDesc::Image(desc),
+                ResourceOrigin::TransientBuffer(desc) => SlotDesc::Buffer(desc),
+                ResourceOrigin::Imported { .. } => {
+                    unreachable!("only transient resources get slots")
+                }
+            };
+
+            let reusable = slots.iter().enumerate().position(|(index, slot)| {
+                slot_ends[index] < first
+                    && match (slot.desc, desc) {
+                        (SlotDesc::Image(slot), SlotDesc::Image(image)) => slot == image,
+                        (SlotDesc::Buffer(slot), SlotDesc::Buffer(buffer)) => {
+                            slot.usage == buffer.usage
+                        }
+                        _ => false,
+                    }
+            });
+
+            let index = match reusable {
+                Some(index) => {
+                    if let (SlotDesc::Buffer(slot), SlotDesc::Buffer(buffer)) =
+                        (&mut slots[index].desc, desc)
+                    {
+                        slot.size = slot.size.max(buffer.size);
+                    }
+                    slots[index].resources.push(resource);
+                    slot_ends[index] = last;
+                    index
+                }
+                None => {
+                    slots.push(TransientSlot {
+                        desc,
+                        resources: vec![resource],
+                    });
+                    slot_ends.push(last);
+                    slots.len() - 1
+                }
+            };
+
+            resource_slots[resource.0] = Some(index);
+        }
+
+        (slots, resource_slots)
+    }
+
+    /// Walks the schedule, recording the barriers every use needs and the dependencies queue handoffs and aliasing add.
+    fn track_states(
+        &self,
+        compiled: &mut [CompiledPass],
+        uses: &[Vec<ResourceUse>],
+        resource_slots: &[Option<usize>],
+        slot_count: usize,
+    ) {
+        let physical = |resource: ResourceId| match resource_slots[resource.0] {
+            Some(slot) => self.resources.len() + slot,
+            None => resource.0,
+        };
+
+        let mut states = self
+            .resources
+            .iter()
+            .enumerate()
+            .map(|(index, resource)| match resource.origin {
+                ResourceOrigin::Imported { initial, .. } => PhysicalState {
+                    layout: initial.layout,
+                    access: initial.access,
+                    writes: ImageUsage::new(initial.layout, initial.access).writes(),
+                    queue: None,
+                    family: initial.queue_family,
+                    last_pass: None,
+                    occupant: Some(ResourceId(index)),
+                    discarded: resource.image && initial.layout == vk::ImageLayout::UNDEFINED,
+                },
+                _ => PhysicalState::discarded(),
+            })
+            .chain((0..slot_count).map(|_| PhysicalState::discarded()))
+            .collect::<Vec<PhysicalState>>();
+
+        for position in 0..compiled.len() {
+            let queue = compiled[position].queue;
+            let family = queue.family;
+
+            for resource_use in &uses[compiled[position].pass] {
+                let resource = resource_use.resource;
+                let node = &self.resources[resource.0];
+                let state = &mut states[physical(resource)];
+
+                if state.occupant != Some(resource) {
+                    // a new resource takes over the slot, after the previous one is done with it
+                    state.occupant = Some(resource);
+                    state.layout = vk::ImageLayout::UNDEFINED;
+                    state.discarded = true;
+                    if let Some(last) = state.last_pass {
+                        compiled[position].dependencies.push(Dependency {
+                            pass: last,
+                            resource,
+                            cross_queue: compiled[last].queue != queue,
+                        });
+                    }
+                }
+
+                let transfer_ownership = !node.concurrent
+                    && !state.discarded
+                    && state.family.is_some_and(|previous| previous != family);
+
+                match (state.queue, state.last_pass) {
+                    (Some(previous_queue), Some(last)) if previous_queue != queue => {
+                        compiled[position].dependencies.push(Dependency {
+                            pass: last,
+                            resource,
+                            cross_queue: true,
+                        });
+
+                        if transfer_ownership {
+                            let previous_family = state.family.unwrap();
+                            compiled[last].release_barriers.push(
+                                GraphBarrier::new(
+                                    resource,
+                                    state.src_access(),
+                                    Access::NONE,
+                                    state.layout,
+                                    resource_use.layout,
+                                )
+                                .queue_family_transfer(previous_family, family),
+                            );
+                            compiled[position].barriers.push(
+                                GraphBarrier::new(
+                                    resource,
+                                    Access::NONE,
+                                    resource_use.access,
+                                    state.layout,
+                                    resource_use.layout,
+                                )
+                                .queue_family_transfer(previous_family, family),
+                            );
+                        } else if state.layout != resource_use.layout {
+                            // the semaphore wait covers the previous accesses, the barrier chains onto it
+                            compiled[position].barriers.push(GraphBarrier::new(
+                                resource,
+                                Access::new(resource_use.access.stages, vk::AccessFlags2::NONE),
+                                resource_use.access,
+                                state.layout,
+                                resource_use.layout,
+                            ));
+                        }
+
+                        state.access = resource_use.access;
+                        state.writes = resource_use.write;
+                    }
+                    _ => {
+                        if transfer_ownership {
+                            // imported from another queue family, the previous owner released it
+                            compiled[position].barriers.push(
+                                GraphBarrier::new(
+                                    resource,
+                                    Access::NONE,
+                                    resource_use.access,
+                                    state.layout,
+                                    resource_use.layout,
+                                )
+                                .queue_family_transfer(state.family.unwrap(), family),
+                            );
+                            state.access = resource_use.access;
+                            state.writes = resource_use.write;
+                        } else if state.layout == resource_use.layout
+                            && !resource_use.write
+                            && !state.writes
+                        {
+                            state.access.stages |= resource_use.access.stages;
+                            state.access.access |= resource_use.access.access;
+                        } else {
+                            if state.access != Access::NONE || state.layout != resource_use.layout {
+                                compiled[position].barriers.push(GraphBarrier::new(
+                                    resource,
+                                    state.src_access(),
+                                    resource_use.access,
+                                    state.layout,
+                                    resource_use.layout,
+                                ));
+                            }
+                            state.access = resource_use.access;
+                            state.writes = resource_use.write;
+                        }
+                    }
+                }
+
+                state.layout = resource_use.layout;
+                state.queue = Some(queue);
+                state.family = (!node.concurrent).then_some(family);
+                state.last_pass = Some(position);
+                state.discarded = false;
+            }
+        }
+
+        for (index, resource) in self.resources.iter().enumerate() {
+            let ResourceOrigin::Imported {
+                exported: Some((usage, target_family)),
+                ..
+            } = resource.origin
+            else {
+                continue;
+            };
+            let state = states[index];
+            let Some(last) = state.last_pass else {
+                continue;
+            };
+
+            let release = !resource.concurrent
+                && target_family.is_some_and(|target| Some(target) != state.family);
+
+            if release {
+                compiled[last].release_barriers.push(
+                    GraphBarrier::new(
+                        ResourceId(index),
+                        state.src_access(),
+                        Access::NONE,
+                        state.layout,
+                        usage.layout,
+                    )
+                    .queue_family_transfer(state.family.unwrap(), target_family.unwrap()),
+                );
+            } else if state.layout != usage.layout || (state.writes && usage.access != Access::NONE)
+            {
+                compiled[last].release_barriers.push(GraphBarrier::new(
+                    ResourceId(index),
+                    state.src_access(),
+                    usage.access,
+                    state.layout,
+                    usage.layout,
+                ));
+            }
+        }
+    }
+}
+
+impl PhysicalState {
+    fn discarded() -> Self {
+        Self {
+            layout: vk::ImageLayout::UNDEFINED,
+            access: Access::NONE,
+            writes: false,
+            queue: None,
+            family: None,
+            last_pass: None,
+            occupant: None,
+            discarded: true,
+        }
+    }
+}
+
+fn resolve_queue(
+    pass: &PassNode,
+    queue_labels: &QueueLabels,
+) -> Result<QueueRef, RenderGraphError> {
+    if !matches!(
+        pass.queue,
+        QueueLabel::Graphics | QueueLabel::Compute | QueueLabel::Transfer
+    ) {
+        return Err(RenderGraphError::UnsupportedQueue {
+            pass: pass.name.clone(),
+            label: pass.queue,
+        });
+    }
+
+    queue_labels
+        .get(&pass.queue)
+        .and_then(|refs| refs.first())
+        .copied()
+        .ok_or_else(|| RenderGraphError::MissingQueue {
+            pass: pass.name.clone(),
+            label: pass.queue,
+        })
+}
+
+/// Topologically sorts the live passes, longest chain of dependents first, then declaration order.
+fn order_passes(live: &[bool], dependencies: &[Vec<(usize, ResourceId)>]) -> Vec<usize> {
+    let count = live.len();
+    let mut dependents = vec![vec![]; count];
+    let mut remaining = vec![0usize; count];
+
+    for pass in (0..count).filter(|pass| live[*pass]) {
+        let mut predecessors = dependencies[pass]
+            .iter()
+            .map(|(dependency, _)| *dependency)
+            .filter(|dependency| live[*dependency])
+            .collect::<Vec<usize>>();
+        predecessors.sort();
+        predecessors.dedup();
+
+        remaining[pass] = predecessors.len();
+        for predecessor in predecessors {
+            dependents[predecessor].push(pass);
+        }
+    }
+
+    // dependencies always point to earlier declarations, so heights can be computed back to front
+    let mut heights = vec![0usize; count];
+    for pass in (0..count).rev().filter(|pass| live[*pass]) {
+        heights[pass] = 1 + dependents[pass]
+            .iter()
+            .map(|dependent| heights[*dependent])
+            .max()
+            .unwrap_or(0);
+    }
+
+    let mut ready = (0..count)
+        .filter(|pass| live[*pass] && remaining[*pass] == 0)
+        .map(|pass| (heights[pass], Reverse(pass)))
+        .collect::<BinaryHeap<(usize, Reverse<usize>)>>();
+
+    let mut order = Vec::with_capacity(count);
+    while let Some((_, Reverse(pass))) = ready.pop() {
+        order.push(pass);
+        for dependent in &dependents[pass] {
+            remaining[*dependent] -= 1;
+            if remaining[*dependent] == 0 {
+                ready.push((heights[*dependent], Reverse(*dependent)));
+            }
+        }
+    }
+
+    order
+}
+
+/// Splits the schedule into per-queue batches: a pass waiting on another queue starts a new batch, and a pass another
+/// queue waits on ends its batch so the wait is as short as possible.
+fn build_batches(compiled: &mut [CompiledPass], uses: &[Vec<ResourceUse>]) -> Vec<Batch> {
+    let mut signals = vec![false; compiled.len()];
+    for pass in compiled.iter() {
+        for dependency in pass.dependencies.iter().filter(|d| d.cross_queue) {
+            signals[dependency.pass] = true;
+        }
+    }
+
+    let mut batches: Vec<Batch> = vec![];
+    let mut open: BTreeMap<(u32, u32), usize> = BTreeMap::new();
+    let mut values: BTreeMap<(u32, u32), u64> = BTreeMap::new();
+
+    for position in 0..compiled.len() {
+        let queue = compiled[position].queue;
+        let key = (queue.family, queue.index);
+
+        let mut waits: Vec<BatchWait> = vec![];
+        for dependency in compiled[position]
+            .dependencies
+            .iter()
+            .filter(|d| d.cross_queue)
+        {
+            let producer = &batches[compiled[dependency.pass].batch];
+            let first_use = uses[compiled[position].pass]
+                .iter()
+                .filter(|resource_use| resource_use.resource == dependency.resource)
+                .fold(Access::NONE, |first_use, resource_use| Access {
+                    stages: first_use.stages | resource_use.access.stages,
+                    access: first_use.access | resource_use.access.access,
+                });
+            let stages = first_use.wait_scope().stages;
+
+            match waits.iter_mut().find(|wait| wait.queue == producer.queue) {
+                Some(wait) => {
+                    wait.value = wait.value.max(producer.value);
+                    wait.stages |= stages;
+                }
+                None => waits.push(BatchWait {
+                    queue: producer.queue,
+                    value: producer.value,
+                    stages,
+                }),
+            }
+        }
+
+        let batch = match open.get(&key) {
+            Some(batch) if waits.is_empty() => *batch,
+            _ => {
+                let value = values.entry(key).or_insert(0);
+                *value += 1;
+                batches.push(Batch {
+                    queue,
+                    passes: vec![],
+                    value: *value,
+                    waits,
+                });
+                batches.len() - 1
+            }
+        };
+
+        batches[batch].passes.push(position);
+        compiled[position].batch = batch;
+
+        if signals[position] {
+            open.remove(&key);
+        } else {
+            open.insert(key, batch);
+        }
+    }
+
+    batches
+}
+
+impl Display for CompiledGraph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, batch) in self.batches.iter().enumerate() {
+            writeln!(
+                f,
+                "batch {} on queue {}/{} signals {}",
+                index, batch.queue.family, batch.queue.index, batch.value
+            )?;
+            for wait in &batch.waits {
+                writeln!(
+                    f,
+                    "  wait queue {}/{} >= {} at {:?}",
+                    wait.queue.family, wait.queue.index, wait.value, wait.stages
+                )?;
+            }
+
+            for position in &batch.passes {
+                let pass = &self.passes[*position];
+                writeln!(f, "  pass {} {:?} ({:?})", position, pass.name, pass.label)?;
+                for barrier in &pass.barriers {
+                    writeln!(f, "    before {}", self.format_barrier(barrier))?;
+                }
+                for barrier in &pass.release_barriers {
+                    writeln!(f, "    after {}", self.format_barrier(barrier))?;
+                }
+            }
+        }
+
+        for (index, slot) in self.slots.iter().enumerate() {
+            let names = slot
+                .resources
+                .iter()
+                .map(|resource| self.resource_name(*resource))
+                .collect::<Vec<&str>>();
+            writeln!(f, "slot {}: {}", index, names.join(", "))?;
+        }
+
+        if !self.culled.is_empty() {
+            writeln!(f, "culled: {:?}", self.culled)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CompiledGraph {
+    pub(crate) fn format_barrier(&self, barrier: &GraphBarrier) -> String {
+        let mut text = format!(
+            "{} {} -> {}",
+            self.resource_name(barrier.resource),
+            format_access(barrier.src),
+            format_access(barrier.dst)
+        );
+
+        if barrier.old_layout != barrier.new_layout {
+            text += &format!(" {:?} -> {:?}", barrier.old_layout, barrier.new_layout);
+        }
+
+        if barrier.is_queue_family_transfer() {
+            text += &format!(
+                " family {} -> {}",
+                barrier.src_queue_family, barrier.dst_queue_family
+            );
+        }
+
+        text
+    }
+}
+
+fn format_access(access: Access) -> String {
+    if access == Access::NONE {
+        "NONE".to_string()
+    } else {
+        format!("{:?}/{:?}", access.stages, access.access)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::graph::{GraphImage, RenderGraph};
+    use crate::render::image_state::ImageState;
+
+    const GRAPHICS: QueueRef = QueueRef {
+        family: 0,
+        index: 0,
+    };
+    const COMPUTE: QueueRef = QueueRef {
+        family: 2,
+        index: 0,
+    };
+    const TRANSFER: QueueRef = QueueRef {
+        family: 1,
+        index: 0,
+    };
+
+    /// Every label on the same queue, like a single-queue device.
+    fn single_queue() -> QueueLabels {
+        [
+            QueueLabel::Graphics,
+            QueueLabel::Compute,
+            QueueLabel::Transfer,
+        ]
+        .into_iter()
+        .map(|label| (label, vec![GRAPHICS]))
+        .collect()
+    }
+
+    /// Graphics, async compute and transfer on three different families.
+    fn discrete() -> QueueLabels {
+        [
+            (QueueLabel::Graphics, vec![GRAPHICS]),
+            (QueueLabel::Compute, vec![COMPUTE]),
+            (QueueLabel::Transfer, vec![TRANSFER]),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn color_target(graph: &mut RenderGraph, name: &str) -> GraphImage {
+        graph.create_image(
+            name,
+            ImageDesc::new_2d(
+                vk::Format::R8G8B8A8_UNORM,
+                vk::Extent2D {
+                    width: 64,
+                    height: 64,
+                },
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                vk::ImageAspectFlags::COLOR,
+            ),
+        )
+    }
+
+    fn backbuffer(graph: &mut RenderGraph) -> GraphImage {
+        let image = graph.import_image(
+            "backbuffer",
+            vk::ImageAspectFlags::COLOR,
+            ImageState::UNDEFINED,
+            vk::SharingMode::EXCLUSIVE,
+        );
+        graph.export_image(image, ImageUsage::PRESENT, None);
+        image
+    }
+
+    fn names(compiled: &CompiledGraph) -> Vec<&str> {
+        compiled
+            .passes()
+            .iter()
+            .map(|pass| pass.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn unused_passes_are_culled() {
+        let mut graph = RenderGraph::new();
+        let unused = color_target(&mut graph, "unused");
+        let output = backbuffer(&mut graph);
+        graph
+            .add_pass("unused", QueueLabel::Graphics)
+            .write_image(unused, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("main", QueueLabel::Graphics)
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+        graph.add_pass("debug", QueueLabel::Graphics).side_effects();
+
+        let compiled = graph.compile(&single_queue()).unwrap();
+
+        assert_eq!(names(&compiled), vec!["main", "debug"]);
+        assert_eq!(compiled.culled_passes(), &[0]);
+        assert!(compiled.slots().is_empty());
+    }
+
+    #[test]
+    fn reading_an_unwritten_transient_fails() {
+        let mut graph = RenderGraph::new();
+        let image = color_target(&mut graph, "image");
+        graph
+            .add_pass("read", QueueLabel::Graphics)
+            .read_image(image, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .side_effects();
+
+        assert_eq!(
+            graph.compile(&single_queue()),
+            Err(RenderGraphError::UninitializedRead {
+                pass: "read".to_string(),
+                resource: "image".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn conflicting_layouts_in_a_pass_fail() {
+        let mut graph = RenderGraph::new();
+        let image = color_target(&mut graph, "image");
+        graph
+            .add_pass("write", QueueLabel::Graphics)
+            .write_image(image, ImageUsage::COLOR_ATTACHMENT)
+            .write_image(image, ImageUsage::TRANSFER_DST);
+
+        assert!(matches!(
+            graph.compile(&single_queue()),
+            Err(RenderGraphError::ConflictingLayouts { .. })
+        ));
+    }
+
+    #[test]
+    fn unsupported_and_missing_queues_fail() {
+        let mut graph = RenderGraph::new();
+        graph.add_pass("present", QueueLabel::Presentation);
+        assert!(matches!(
+            graph.compile(&single_queue()),
+            Err(RenderGraphError::UnsupportedQueue { .. })
+        ));
+
+        let mut graph = RenderGraph::new();
+        graph.add_pass("compute", QueueLabel::Compute);
+        let labels: QueueLabels = [(QueueLabel::Graphics, vec![GRAPHICS])]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            graph.compile(&labels),
+            Err(RenderGraphError::MissingQueue {
+                pass: "compute".to_string(),
+                label: QueueLabel::Compute,
+            })
+        );
+    }
+
+    #[test]
+    fn single_queue_schedule_snapshot() {
+        let mut graph = RenderGraph::new();
+        let scene = color_target(&mut graph, "scene");
+        let output = backbuffer(&mut graph);
+        graph
+            .add_pass("scene", QueueLabel::Graphics)
+            .write_image(scene, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("tonemap", QueueLabel::Graphics)
+            .read_image(scene, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+
+        let compiled = graph.compile(&single_queue()).unwrap();
+
+        assert_eq!(
+            compiled.to_string(),
+            "\
+batch 0 on queue 0/0 signals 1
+  pass 0 \"scene\" (Graphics)
+    before scene NONE -> COLOR_ATTACHMENT_OUTPUT/COLOR_ATTACHMENT_WRITE UNDEFINED -> COLOR_ATTACHMENT_OPTIMAL
+  pass 1 \"tonemap\" (Graphics)
+    before scene COLOR_ATTACHMENT_OUTPUT/COLOR_ATTACHMENT_WRITE -> FRAGMENT_SHADER/SHADER_SAMPLED_READ COLOR_ATTACHMENT_OPTIMAL -> SHADER_READ_ONLY_OPTIMAL
+    before backbuffer NONE -> COLOR_ATTACHMENT_OUTPUT/COLOR_ATTACHMENT_WRITE UNDEFINED -> COLOR_ATTACHMENT_OPTIMAL
+    after backbuffer COLOR_ATTACHMENT_OUTPUT/COLOR_ATTACHMENT_WRITE -> NONE COLOR_ATTACHMENT_OPTIMAL -> PRESENT_SRC_KHR
+slot 0: scene
+"
+        );
+    }
+
+    #[test]
+    fn consecutive_reads_need_one_barrier() {
+        let mut graph = RenderGraph::new();
+        let shadow = color_target(&mut graph, "shadow");
+        let output = backbuffer(&mut graph);
+        graph
+            .add_pass("shadow", QueueLabel::Graphics)
+            .write_image(shadow, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("lighting", QueueLabel::Graphics)
+            .read_image(shadow, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .side_effects();
+        graph
+            .add_pass("composite", QueueLabel::Graphics)
+            .read_image(shadow, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+
+        let compiled = graph.compile(&single_queue()).unwrap();
+
+        let shadow_barriers = compiled
+            .passes()
+            .iter()
+            .flat_map(|pass| pass.barriers.iter())
+            .filter(|barrier| barrier.resource == shadow.id())
+            .count();
+        assert_eq!(shadow_barriers, 2);
+    }
+
+    #[test]
+    fn transients_with_disjoint_lifetimes_are_aliased() {
+        let mut graph = RenderGraph::new();
+        let a = color_target(&mut graph, "a");
+        let b = color_target(&mut graph, "b");
+        let c = color_target(&mut graph, "c");
+        let output = backbuffer(&mut graph);
+        graph
+            .add_pass("a", QueueLabel::Graphics)
+            .write_image(a, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("b", QueueLabel::Graphics)
+            .read_image(a, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .write_image(b, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("c", QueueLabel::Graphics)
+            .read_image(b, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .write_image(c, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("output", QueueLabel::Graphics)
+            .read_image(c, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+
+        let compiled = graph.compile(&single_queue()).unwrap();
+
+        // a ends before c starts, b overlaps both
+        assert_eq!(compiled.slots().len(), 2);
+        assert_eq!(compiled.slots()[0].resources, vec![a.id(), c.id()]);
+        assert_eq!(compiled.slot_of(b.id()), Some(1));
+        assert_eq!(compiled.slot_of(output.id()), None);
+
+        // c starts from UNDEFINED after a's sampled reads
+        let c_barrier = compiled.passes()[2]
+            .barriers
+            .iter()
+            .find(|barrier| barrier.resource == c.id())
+            .unwrap();
+        assert_eq!(c_barrier.old_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(
+            c_barrier.src.stages,
+            vk::PipelineStageFlags2::FRAGMENT_SHADER
+        );
+        assert_eq!(c_barrier.src.access, vk::AccessFlags2::NONE);
+    }
+
+    #[test]
+    fn async_compute_is_linked_by_timeline_semaphores() {
+        let mut graph = RenderGraph::new();
+        let particles = graph.create_buffer(
+            "particles",
+            BufferDesc {
+                size: 1024,
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER,
+            },
+        );
+        let output = backbuffer(&mut graph);
+        graph
+            .add_pass("simulate", QueueLabel::Compute)
+            .write_buffer(particles, Access::COMPUTE_SHADER_WRITE);
+        graph
+            .add_pass("draw", QueueLabel::Graphics)
+            .read_buffer(
+                particles,
+                Access::new(
+                    vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
+                    vk::AccessFlags2::VERTEX_ATTRIBUTE_READ,
+                ),
+            )
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+
+        let compiled = graph.compile(&discrete()).unwrap();
+
+        assert_eq!(compiled.batches().len(), 2);
+        assert_eq!(compiled.batches()[0].queue, COMPUTE);
+        assert_eq!(compiled.batches()[1].queue, GRAPHICS);
+        assert_eq!(
+            compiled.batches()[1].waits,
+            vec![BatchWait {
+                queue: COMPUTE,
+                value: 1,
+                stages: vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
+            }]
+        );
+
+        // the buffer moves from the compute family to the graphics family
+        let release = compiled.passes()[0].release_barriers[0];
+        let acquire = compiled.passes()[1].barriers[0];
+        assert_eq!(release.resource, particles.id());
+        assert_eq!((release.src_queue_family, release.dst_queue_family), (2, 0));
+        assert_eq!(release.src, Access::COMPUTE_SHADER_WRITE);
+        assert_eq!((acquire.src_queue_family, acquire.dst_queue_family), (2, 0));
+        assert_eq!(acquire.dst.access, vk::AccessFlags2::VERTEX_ATTRIBUTE_READ);
+
+        // the same graph on a single queue needs neither semaphores nor ownership transfers
+        let compiled = graph.compile(&single_queue()).unwrap();
+        assert_eq!(compiled.batches().len(), 1);
+        assert!(
+            compiled
+                .passes()
+                .iter()
+                .flat_map(|pass| pass.barriers.iter().chain(&pass.release_barriers))
+                .all(|barrier| !barrier.is_queue_family_transfer())
+        );
+    }
+
+    #[test]
+    fn longer_chains_are_scheduled_first() {
+        let mut graph = RenderGraph::new();
+        let output = backbuffer(&mut graph);
+        let upload = graph.create_buffer(
+            "upload",
+            BufferDesc {
+                size: 256,
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+            },
+        );
+        let culled = graph.create_buffer(
+            "culled",
+            BufferDesc {
+                size: 256,
+                usage: vk::BufferUsageFlags::INDIRECT_BUFFER,
+            },
+        );
+        graph
+            .add_pass("ui", QueueLabel::Graphics)
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+        graph
+            .add_pass("upload", QueueLabel::Transfer)
+            .write_buffer(upload, Access::TRANSFER_WRITE);
+        graph
+            .add_pass("cull", QueueLabel::Compute)
+            .read_buffer(upload, Access::COMPUTE_SHADER_READ)
+            .write_buffer(culled, Access::COMPUTE_SHADER_WRITE);
+        graph
+            .add_pass("draw", QueueLabel::Graphics)
+            .read_buffer(
+                culled,
+                Access::new(
+                    vk::PipelineStageFlags2::DRAW_INDIRECT,
+                    vk::AccessFlags2::INDIRECT_COMMAND_READ,
+                ),
+            )
+            .modify_image(output, ImageUsage::COLOR_ATTACHMENT);
+
+        let compiled = graph.compile(&discrete()).unwrap();
+
+        assert_eq!(names(&compiled), vec!["upload", "ui", "cull", "draw"]);
+
+        // ui can run alongside the upload and the culling, draw waits for both queues it depends on
+        let draw_batch = &compiled.batches()[compiled.passes()[3].batch];
+        assert_eq!(draw_batch.waits.len(), 1);
+        assert_eq!(draw_batch.waits[0].queue, COMPUTE);
+        assert_ne!(compiled.passes()[2].batch, compiled.passes()[3].batch);
+    }
+
+    #[test]
+    fn exported_images_are_released_to_their_queue_family() {
+        let mut graph = RenderGraph::new();
+        let output = graph.import_image(
+            "backbuffer",
+            vk::ImageAspectFlags::COLOR,
+            ImageState::UNDEFINED,
+            vk::SharingMode::EXCLUSIVE,
+        );
+        graph.export_image(output, ImageUsage::PRESENT, Some(3));
+        graph
+            .add_pass("main", QueueLabel::Graphics)
+            .write_image(output, ImageUsage::COLOR_ATTACHMENT);
+
+        let compiled = graph.compile(&single_queue()).unwrap();
+        let release = compiled.passes()[0].release_barriers[0];
+
+        assert_eq!(release.new_layout, vk::ImageLayout::PRESENT_SRC_KHR);
+        assert_eq!((release.src_queue_family, release.dst_queue_family), (0, 3));
+    }
+}
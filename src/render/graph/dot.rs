@@ -0,0 +1,167 @@
+use crate::render::graph::compile::CompiledGraph;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+impl CompiledGraph {
+    /// Exports the schedule as a Graphviz DOT digraph: one cluster per queue, one node per pass labeled with its batch,
+    /// and one edge per dependency labeled with the resources involved. Edges between queues (timeline semaphore waits)
+    /// are dashed, barriers and ownership transfers are listed in the node tooltips.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let _ = self.write_dot(&mut dot);
+        dot
+    }
+
+    fn write_dot(&self, dot: &mut String) -> std::fmt::Result {
+        writeln!(dot, "digraph render_graph {{")?;
+        writeln!(dot, "    rankdir=LR;")?;
+        writeln!(dot, "    node [shape=box];")?;
+
+        let mut queues: BTreeMap<(u32, u32), Vec<usize>> = BTreeMap::new();
+        for (position, pass) in self.passes.iter().enumerate() {
+            queues
+                .entry((pass.queue.family, pass.queue.index))
+                .or_default()
+                .push(position);
+        }
+
+        for ((family, index), positions) in &queues {
+            let labels = self
+                .queues
+                .iter()
+                .filter(|(_, queue)| queue.family == *family && queue.index == *index)
+                .map(|(label, _)| format!("{:?}", label))
+                .collect::<Vec<String>>();
+
+            writeln!(dot, "    subgraph cluster_queue_{}_{} {{", family, index)?;
+            writeln!(
+                dot,
+                "        label=\"{} (family {}, queue {})\";",
+                escape(&labels.join(", ")),
+                family,
+                index
+            )?;
+
+            for position in positions {
+                let pass = &self.passes[*position];
+                let tooltip = pass
+                    .barriers
+                    .iter()
+                    .map(|barrier| format!("before {}", self.format_barrier(barrier)))
+                    .chain(
+                        pass.release_barriers
+                            .iter()
+                            .map(|barrier| format!("after {}", self.format_barrier(barrier))),
+                    )
+                    .collect::<Vec<String>>()
+                    .join("\\n");
+
+                writeln!(
+                    dot,
+                    "        pass_{} [label=\"{}\\nbatch {}\", tooltip=\"{}\"];",
+                    position,
+                    escape(&pass.name),
+                    pass.batch,
+                    escape(&tooltip).replace("\\\\n", "\\n")
+                )?;
+            }
+
+            writeln!(dot, "    }}")?;
+        }
+
+        for (position, pass) in self.passes.iter().enumerate() {
+            let mut edges: BTreeMap<usize, (Vec<&str>, bool)> = BTreeMap::new();
+            for dependency in &pass.dependencies {
+                let (resources, cross_queue) = edges.entry(dependency.pass).or_default();
+                let name = self.resource_name(dependency.resource);
+                if !resources.contains(&name) {
+                    resources.push(name);
+                }
+                *cross_queue |= dependency.cross_queue;
+            }
+
+            for (dependency, (resources, cross_queue)) in edges {
+                writeln!(
+                    dot,
+                    "    pass_{} -> pass_{} [label=\"{}\"{}];",
+                    dependency,
+                    position,
+                    escape(&resources.join(", ")),
+                    if cross_queue { ", style=dashed" } else { "" }
+                )?;
+            }
+        }
+
+        writeln!(dot, "}}")
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::barrier::Access;
+    use crate::render::context::queues::{QueueLabel, QueueLabels, QueueRef};
+    use crate::render::graph::{BufferDesc, RenderGraph};
+    use ash::vk;
+
+    #[test]
+    fn dot_snapshot() {
+        let mut graph = RenderGraph::new();
+        let particles = graph.create_buffer(
+            "particles",
+            BufferDesc {
+                size: 1024,
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+            },
+        );
+        graph
+            .add_pass("simulate", QueueLabel::Compute)
+            .write_buffer(particles, Access::COMPUTE_SHADER_WRITE);
+        graph
+            .add_pass("draw \"particles\"", QueueLabel::Graphics)
+            .read_buffer(particles, Access::COMPUTE_SHADER_READ)
+            .side_effects();
+
+        let labels: QueueLabels = [
+            (
+                QueueLabel::Graphics,
+                vec![QueueRef {
+                    family: 0,
+                    index: 0,
+                }],
+            ),
+            (
+                QueueLabel::Compute,
+                vec![QueueRef {
+                    family: 1,
+                    index: 0,
+                }],
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let dot = graph.compile(&labels).unwrap().to_dot();
+
+        assert_eq!(
+            dot,
+            r#"digraph render_graph {
+    rankdir=LR;
+    node [shape=box];
+    subgraph cluster_queue_0_0 {
+        label="Graphics (family 0, queue 0)";
+        pass_1 [label="draw \"particles\"\nbatch 1", tooltip="before particles NONE -> COMPUTE_SHADER/SHADER_STORAGE_READ family 1 -> 0"];
+    }
+    subgraph cluster_queue_1_0 {
+        label="Compute (family 1, queue 0)";
+        pass_0 [label="simulate\nbatch 0", tooltip="after particles COMPUTE_SHADER/SHADER_STORAGE_WRITE -> NONE family 1 -> 0"];
+    }
+    pass_0 -> pass_1 [label="particles", style=dashed];
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use crate::errors::GraphExecuteError;
+use crate::render::barrier::{BufferBarrier, ImageBarrier};
+use crate::render::command_recorder::{
+    AutoSubmitInfo, CommandBufferExt, CommandBufferSyncInfo, CommandRecorder, SemaphoreInfo,
+};
+use crate::render::context::VulkanContext;
+use crate::render::context::command_pool::CommandBufferRecycler;
+use crate::render::context::device::LazyQueue;
+use crate::render::context::queues::{QueueLabel, QueueRef};
+use crate::render::context::submission::SubmissionHandle;
+use crate::render::graph::compile::{CompiledGraph, GraphBarrier};
+use crate::render::graph::{GraphResources, RenderGraph, ResourceId};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Semaphores, fence and external synchronization of a graph execution, such as the swapchain semaphores.
+#[derive(Clone, Debug, Default)]
+pub struct GraphSubmitInfo {
+    waits: Vec<(QueueLabel, SemaphoreInfo)>,
+    signals: Vec<(QueueLabel, SemaphoreInfo)>,
+    fence: Option<vk::Fence>,
+}
+
+impl GraphSubmitInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits on `semaphore` before the first batch running on the queue `label` resolved to.
+    pub fn wait(mut self, label: QueueLabel, semaphore: SemaphoreInfo) -> Self {
+        self.waits.push((label, semaphore));
+        self
+    }
+
+    /// Signals `semaphore` once the last batch running on the queue `label` resolved to completes.
+    pub fn signal(mut self, label: QueueLabel, semaphore: SemaphoreInfo) -> Self {
+        self.signals.push((label, semaphore));
+        self
+    }
+
+    /// The fence signaled once every batch of the execution completed, on every queue. Graphs without batches never
+    /// signal it.
+    pub fn fence(mut self, fence: vk::Fence) -> Self {
+        self.fence = Some(fence);
+        self
+    }
+}
+
+/// The submissions of a graph execution, the last one on each queue it used.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphSubmission {
+    submissions: Vec<SubmissionHandle>,
+}
+
+impl GraphSubmission {
+    /// The last submission on each queue, complete once every batch of the execution on that queue completed.
+    pub fn submissions(&self) -> &[SubmissionHandle] {
+        &self.submissions
+    }
+
+    /// The last submission on `queue`.
+    pub fn submission(&self, queue: QueueRef) -> Option<SubmissionHandle> {
+        self.submissions
+            .iter()
+            .find(|submission| submission.queue() == queue)
+            .copied()
+    }
+
+    /// Blocks until every batch of the execution completed.
+    pub fn wait(&self, vulkan: &VulkanContext) -> VkResult<()> {
+        SubmissionHandle::wait_all(vulkan, &self.submissions)
+    }
+}
+
+/// Records and submits [`CompiledGraph`]s. Each batch is a submission to its queue, waiting on the timeline values
+/// of the submissions it depends on.
+///
+/// Command buffers are allocated per execution and freed once their submission completed, so executions can be kept
+/// in flight without waiting.
+pub struct GraphExecutor {
+    vulkan: Arc<VulkanContext>,
+    command_buffers: CommandBufferRecycler,
+}
+
+impl GraphExecutor {
+    pub fn new(vulkan: Arc<VulkanContext>) -> Self {
+        Self {
+            command_buffers: CommandBufferRecycler::new(vulkan.clone()),
+            vulkan,
+        }
+    }
+
+    /// Records every batch of `compiled` with the passes of `graph` and submits them in order.
+    ///
+    /// `compiled` must come from `graph`, and every resource the schedule uses needs a physical resource in `resources`.
+    /// When a pass fails to record, the batches submitted so far (including the one of the failing pass) stay submitted
+    /// so external semaphores are consumed, and the remaining batches are skipped.
+    pub fn execute(
+        &mut self,
+        graph: &mut RenderGraph,
+        compiled: &CompiledGraph,
+        resources: &GraphResources,
+        submit_info: &GraphSubmitInfo,
+    ) -> Result<GraphSubmission, GraphExecuteError> {
+        if compiled.declared_passes != graph.passes.len() {
+            return Err(GraphExecuteError::GraphMismatch);
+        }
+
+        for resource in compiled
+            .resources
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.used)
+        {
+            let (index, resource) = resource;
+            let bound = if resource.image {
+                resources.raw_image(ResourceId(index)).is_some()
+            } else {
+                resources.raw_buffer(ResourceId(index)).is_some()
+            };
+
+            if !bound {
+                return Err(GraphExecuteError::UnboundResource(resource.name.clone()));
+            }
+        }
+
+        let external_batch = |label: QueueLabel, last: bool| {
+            let queue = compiled.queue(label);
+            let mut batches = compiled
+                .batches
+                .iter()
+                .enumerate()
+                .filter(|(_, batch)| Some(batch.queue) == queue)
+                .map(|(index, _)| index);
+            if last {
+                batches.next_back()
+            } else {
+                batches.next()
+            }
+            .ok_or(GraphExecuteError::NoBatchOnQueue(label))
+        };
+
+        let waits = submit_info
+            .waits
+            .iter()
+            .map(|(label, semaphore)| Ok((external_batch(*label, false)?, *semaphore)))
+            .collect::<Result<Vec<(usize, SemaphoreInfo)>, GraphExecuteError>>()?;
+        let signals = submit_info
+            .signals
+            .iter()
+            .map(|(label, semaphore)| Ok((external_batch(*label, true)?, *semaphore)))
+            .collect::<Result<Vec<(usize, SemaphoreInfo)>, GraphExecuteError>>()?;
+
+        self.command_buffers.reclaim()?;
+
+        let mut submitted: HashMap<(QueueRef, u64), SubmissionHandle> = HashMap::new();
+        let mut submission = GraphSubmission::default();
+
+        for (index, batch) in compiled.batches.iter().enumerate() {
+            let mut sync_info = CommandBufferSyncInfo::new();
+            for wait in &batch.waits {
+                // batches only wait on earlier batches, which are submitted by now
+                sync_info =
+                    sync_info.wait_submission(submitted[&(wait.queue, wait.value)], wait.stages);
+            }
+            for (_, semaphore) in waits.iter().filter(|(batch, _)| *batch == index) {
+                sync_info = sync_info.wait(*semaphore);
+            }
+            for (_, semaphore) in signals.iter().filter(|(batch, _)| *batch == index) {
+                sync_info = sync_info.signal(*semaphore);
+            }
+
+            let command_buffer = self.command_buffers.allocate(batch.queue.family)?;
+
+            let mut recorder = command_buffer.begin_auto_submit(
+                self.vulkan.clone(),
+                true,
+                AutoSubmitInfo::new(LazyQueue::Ref(batch.queue), sync_info),
+            )?;
+
+            let recorded = record_batch(&mut recorder, graph, compiled, resources, &batch.passes);
+
+            // the batch is submitted even when a pass failed, the external waits it carries are consumed either way
+            if let Some(batch_submission) = recorder.finish()? {
+                self.command_buffers
+                    .track(batch.queue.family, command_buffer, batch_submission);
+                submitted.insert((batch.queue, batch.value), batch_submission);
+
+                match submission
+                    .submissions
+                    .iter_mut()
+                    .find(|s| s.queue() == batch.queue)
+                {
+                    Some(last) => *last = batch_submission,
+                    None => submission.submissions.push(batch_submission),
+                }
+            }
+
+            recorded?;
+        }
+
+        // batches on other queues can still run once the last batch completes, so the fence gets its own submission
+        // waiting on every queue the execution used
+        if let Some(fence) = submit_info.fence
+            && let Some(last) = compiled.batches.last()
+        {
+            let sync_info = submission
+                .submissions
+                .iter()
+                .fold(CommandBufferSyncInfo::new(), |sync_info, handle| {
+                    sync_info.wait_submission(*handle, vk::PipelineStageFlags2::ALL_COMMANDS)
+                })
+                .fence(fence);
+            AutoSubmitInfo::new(LazyQueue::Ref(last.queue), sync_info).submit(&[], &self.vulkan)?;
+        }
+
+        Ok(submission)
+    }
+}
+
+fn record_batch(
+    recorder: &mut CommandRecorder,
+    graph: &mut RenderGraph,
+    compiled: &CompiledGraph,
+    resources: &GraphResources,
+    passes: &[usize],
+) -> Result<(), GraphExecuteError> {
+    for position in passes {
+        let pass = &compiled.passes[*position];
+
+        record_barriers(recorder, compiled, resources, &pass.barriers);
+
+        if let Some(callback) = graph.passes[pass.pass].callback.as_mut() {
+            callback(recorder, resources).map_err(|source| GraphExecuteError::Pass {
+                pass: pass.name.clone(),
+                source,
+            })?;
+        }
+
+        record_barriers(recorder, compiled, resources, &pass.release_barriers);
+    }
+
+    Ok(())
+}
+
+fn record_barriers(
+    recorder: &mut CommandRecorder,
+    compiled: &CompiledGraph,
+    resources: &GraphResources,
+    barriers: &[GraphBarrier],
+) {
+    if barriers.is_empty() {
+        return;
+    }
+
+    let mut image_barriers: Vec<ImageBarrier> = vec![];
+    let mut buffer_barriers: Vec<BufferBarrier> = vec![];
+
+    for barrier in barriers {
+        let resource = &compiled.resources[barrier.resource.0];
+        if resource.image {
+            if let Some(image) = resources.raw_image(barrier.resource) {
+                image_barriers.push(barrier.image_barrier(image, resource.aspect_mask));
+            }
+        } else if let Some(buffer) = resources.raw_buffer(barrier.resource) {
+            buffer_barriers.push(barrier.buffer_barrier(buffer));
+        }
+    }
+
+    recorder.pipeline_barrier(&[], &buffer_barriers, &image_barriers);
+}
@@ -0,0 +1,507 @@
+pub mod compile;
+pub mod dot;
+pub mod executor;
+
+use crate::render::barrier::Access;
+use crate::render::command_recorder::CommandRecorder;
+use crate::render::context::queues::QueueLabel;
+use crate::render::image_state::{ImageState, ImageUsage};
+use ash::vk;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+
+pub use compile::{
+    Batch, BatchWait, CompiledGraph, CompiledPass, Dependency, GraphBarrier, SlotDesc,
+    TransientSlot,
+};
+pub use executor::{GraphExecutor, GraphSubmission, GraphSubmitInfo};
+
+/// A resource declared in a [`RenderGraph`], the index of its declaration.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceId(pub usize);
+
+/// An image declared in a [`RenderGraph`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GraphImage(ResourceId);
+
+/// A buffer declared in a [`RenderGraph`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GraphBuffer(ResourceId);
+
+impl GraphImage {
+    pub fn id(&self) -> ResourceId {
+        self.0
+    }
+}
+
+impl GraphBuffer {
+    pub fn id(&self) -> ResourceId {
+        self.0
+    }
+}
+
+/// The description of a transient image, transient images with equal descriptions and disjoint lifetimes share the
+/// same physical image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageDesc {
+    pub format: vk::Format,
+    pub extent: vk::Extent3D,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub samples: vk::SampleCountFlags,
+    pub usage: vk::ImageUsageFlags,
+    pub aspect_mask: vk::ImageAspectFlags,
+}
+
+impl ImageDesc {
+    /// A single-sampled 2D image with one mip level and one array layer.
+    pub fn new_2d(
+        format: vk::Format,
+        extent: vk::Extent2D,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Self {
+        Self {
+            format,
+            extent: extent.into(),
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            usage,
+            aspect_mask,
+        }
+    }
+}
+
+/// The description of a transient buffer, a transient buffer can reuse the physical buffer of a larger one with the
+/// same usage once its lifetime ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BufferDesc {
+    pub size: vk::DeviceSize,
+    pub usage: vk::BufferUsageFlags,
+}
+
+/// Where a resource comes from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceOrigin {
+    /// Created for the graph, its contents are undefined before its first write and discarded after its last use.
+    TransientImage(ImageDesc),
+    TransientBuffer(BufferDesc),
+    /// Owned outside of the graph, such as a swapchain image or a persistent buffer.
+    Imported {
+        initial: ImageState,
+        /// The usage and owning queue family to leave the resource in, see [`RenderGraph::export_image`].
+        exported: Option<(ImageUsage, Option<u32>)>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ResourceNode {
+    pub(crate) name: String,
+    pub(crate) image: bool,
+    pub(crate) aspect_mask: vk::ImageAspectFlags,
+    pub(crate) concurrent: bool,
+    pub(crate) origin: ResourceOrigin,
+}
+
+impl ResourceNode {
+    pub(crate) fn is_transient(&self) -> bool {
+        !matches!(self.origin, ResourceOrigin::Imported { .. })
+    }
+}
+
+/// How a pass uses a resource. Buffers always use [`vk::ImageLayout::UNDEFINED`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceUse {
+    pub resource: ResourceId,
+    pub layout: vk::ImageLayout,
+    pub access: Access,
+    /// Whether the pass needs the previous contents of the resource.
+    pub read: bool,
+    /// Whether the pass modifies the resource.
+    pub write: bool,
+}
+
+/// Records the commands of a pass, the resources give access to the physical images and buffers.
+pub type PassCallback<'a> =
+    Box<dyn FnMut(&mut CommandRecorder, &GraphResources) -> anyhow::Result<()> + 'a>;
+
+pub(crate) struct PassNode<'a> {
+    pub(crate) name: String,
+    pub(crate) queue: QueueLabel,
+    pub(crate) uses: Vec<ResourceUse>,
+    pub(crate) side_effects: bool,
+    pub(crate) callback: Option<PassCallback<'a>>,
+}
+
+/// A frame graph: passes declare the resources they read and write, and [`RenderGraph::compile`] turns the declarations
+/// into a schedule across the [`QueueLabel::Graphics`], [`QueueLabel::Compute`] and [`QueueLabel::Transfer`] queues,
+/// with the barriers, queue family ownership transfers and timeline semaphore waits in between.
+///
+/// Declaration order defines the meaning of the graph: a read sees the last write declared before it. The compiled
+/// schedule may run passes in a different order as long as it respects those dependencies, and passes that contribute
+/// nothing to an imported resource or a pass with side effects are culled.
+///
+/// # Examples
+///
+/// ```
+/// use ash::vk;
+/// use neuron_engine::render::context::queues::{QueueLabel, QueueLabels, QueueRef};
+/// use neuron_engine::render::graph::{ImageDesc, RenderGraph};
+/// use neuron_engine::render::image_state::{ImageState, ImageUsage};
+///
+/// let mut graph = RenderGraph::new();
+/// let hdr = graph.create_image(
+///     "hdr",
+///     ImageDesc::new_2d(
+///         vk::Format::R16G16B16A16_SFLOAT,
+///         vk::Extent2D { width: 1280, height: 720 },
+///         vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+///         vk::ImageAspectFlags::COLOR,
+///     ),
+/// );
+/// let backbuffer = graph.import_image(
+///     "backbuffer",
+///     vk::ImageAspectFlags::COLOR,
+///     ImageState::UNDEFINED,
+///     vk::SharingMode::EXCLUSIVE,
+/// );
+/// graph.export_image(backbuffer, ImageUsage::PRESENT, None);
+///
+/// graph
+///     .add_pass("scene", QueueLabel::Graphics)
+///     .write_image(hdr, ImageUsage::COLOR_ATTACHMENT);
+/// graph
+///     .add_pass("tonemap", QueueLabel::Graphics)
+///     .read_image(hdr, ImageUsage::FRAGMENT_SHADER_SAMPLED)
+///     .write_image(backbuffer, ImageUsage::COLOR_ATTACHMENT);
+///
+/// let queue = QueueRef { family: 0, index: 0 };
+/// let labels: QueueLabels = [QueueLabel::Graphics, QueueLabel::Compute, QueueLabel::Transfer]
+///     .into_iter()
+///     .map(|label| (label, vec![queue]))
+///     .collect();
+///
+/// let compiled = graph.compile(&labels).unwrap();
+/// assert_eq!(compiled.passes().len(), 2);
+/// assert_eq!(compiled.batches().len(), 1);
+/// ```
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    pub(crate) resources: Vec<ResourceNode>,
+    pub(crate) passes: Vec<PassNode<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_resource(&mut self, node: ResourceNode) -> ResourceId {
+        self.resources.push(node);
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn create_image(&mut self, name: impl Into<String>, desc: ImageDesc) -> GraphImage {
+        GraphImage(self.add_resource(ResourceNode {
+            name: name.into(),
+            image: true,
+            aspect_mask: desc.aspect_mask,
+            concurrent: false,
+            origin: ResourceOrigin::TransientImage(desc),
+        }))
+    }
+
+    pub fn create_buffer(&mut self, name: impl Into<String>, desc: BufferDesc) -> GraphBuffer {
+        GraphBuffer(self.add_resource(ResourceNode {
+            name: name.into(),
+            image: false,
+            aspect_mask: vk::ImageAspectFlags::empty(),
+            concurrent: false,
+            origin: ResourceOrigin::TransientBuffer(desc),
+        }))
+    }
+
+    /// Imports an image owned outside of the graph, in `initial` state.
+    ///
+    /// When `initial` names a queue family other than the one of the first pass using the image, the graph records the
+    /// acquire half of the ownership transfer, the release half is up to the previous owner.
+    pub fn import_image(
+        &mut self,
+        name: impl Into<String>,
+        aspect_mask: vk::ImageAspectFlags,
+        initial: ImageState,
+        sharing_mode: vk::SharingMode,
+    ) -> GraphImage {
+        GraphImage(self.add_resource(ResourceNode {
+            name: name.into(),
+            image: true,
+            aspect_mask,
+            concurrent: sharing_mode == vk::SharingMode::CONCURRENT,
+            origin: ResourceOrigin::Imported {
+                initial,
+                exported: None,
+            },
+        }))
+    }
+
+    /// Imports a buffer owned outside of the graph, last accessed with `initial_access` on `queue_family`.
+    pub fn import_buffer(
+        &mut self,
+        name: impl Into<String>,
+        initial_access: Access,
+        queue_family: Option<u32>,
+        sharing_mode: vk::SharingMode,
+    ) -> GraphBuffer {
+        GraphBuffer(self.add_resource(ResourceNode {
+            name: name.into(),
+            image: false,
+            aspect_mask: vk::ImageAspectFlags::empty(),
+            concurrent: sharing_mode == vk::SharingMode::CONCURRENT,
+            origin: ResourceOrigin::Imported {
+                initial: ImageState {
+                    layout: vk::ImageLayout::UNDEFINED,
+                    access: initial_access,
+                    queue_family,
+                },
+                exported: None,
+            },
+        }))
+    }
+
+    /// Leaves an imported image in `usage` once the graph is done with it, releasing it to `queue_family` when it
+    /// differs from the family of the last pass using the image.
+    ///
+    /// Does nothing for transient images.
+    pub fn export_image(
+        &mut self,
+        image: GraphImage,
+        usage: ImageUsage,
+        queue_family: Option<u32>,
+    ) {
+        self.export(image.0, usage, queue_family);
+    }
+
+    /// Like [`RenderGraph::export_image`] for buffers.
+    pub fn export_buffer(
+        &mut self,
+        buffer: GraphBuffer,
+        access: Access,
+        queue_family: Option<u32>,
+    ) {
+        self.export(
+            buffer.0,
+            ImageUsage::new(vk::ImageLayout::UNDEFINED, access),
+            queue_family,
+        );
+    }
+
+    fn export(&mut self, resource: ResourceId, usage: ImageUsage, queue_family: Option<u32>) {
+        if let Some(ResourceOrigin::Imported { exported, .. }) = self
+            .resources
+            .get_mut(resource.0)
+            .map(|resource| &mut resource.origin)
+        {
+            *exported = Some((usage, queue_family));
+        }
+    }
+
+    /// Adds a pass running on the queue `queue` resolves to, which must be [`QueueLabel::Graphics`],
+    /// [`QueueLabel::Compute`] or [`QueueLabel::Transfer`].
+    pub fn add_pass(&mut self, name: impl Into<String>, queue: QueueLabel) -> PassBuilder<'_, 'a> {
+        self.passes.push(PassNode {
+            name: name.into(),
+            queue,
+            uses: vec![],
+            side_effects: false,
+            callback: None,
+        });
+
+        PassBuilder {
+            pass: self.passes.last_mut().unwrap(),
+        }
+    }
+
+    pub fn resource_name(&self, resource: ResourceId) -> Option<&str> {
+        self.resources
+            .get(resource.0)
+            .map(|resource| resource.name.as_str())
+    }
+
+    pub fn resource_origin(&self, resource: ResourceId) -> Option<&ResourceOrigin> {
+        self.resources
+            .get(resource.0)
+            .map(|resource| &resource.origin)
+    }
+
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+}
+
+impl Debug for RenderGraph<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenderGraph")
+            .field("resources", &self.resources)
+            .field(
+                "passes",
+                &self
+                    .passes
+                    .iter()
+                    .map(|pass| (&pass.name, pass.queue, &pass.uses))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Declares the resources of a pass, see [`RenderGraph::add_pass`].
+pub struct PassBuilder<'g, 'a> {
+    pass: &'g mut PassNode<'a>,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    fn add_use(&mut self, resource: ResourceId, usage: ImageUsage, read: bool, write: bool) {
+        self.pass.uses.push(ResourceUse {
+            resource,
+            layout: usage.layout,
+            access: usage.access,
+            read,
+            write,
+        });
+    }
+
+    pub fn read_image(mut self, image: GraphImage, usage: ImageUsage) -> Self {
+        self.add_use(image.0, usage, true, false);
+        self
+    }
+
+    /// Overwrites the image, its previous contents are not needed.
+    pub fn write_image(mut self, image: GraphImage, usage: ImageUsage) -> Self {
+        self.add_use(image.0, usage, false, true);
+        self
+    }
+
+    /// Reads and writes the image, such as blending onto an attachment or a storage image updated in place.
+    pub fn modify_image(mut self, image: GraphImage, usage: ImageUsage) -> Self {
+        self.add_use(image.0, usage, true, true);
+        self
+    }
+
+    pub fn read_buffer(mut self, buffer: GraphBuffer, access: Access) -> Self {
+        self.add_use(
+            buffer.0,
+            ImageUsage::new(vk::ImageLayout::UNDEFINED, access),
+            true,
+            false,
+        );
+        self
+    }
+
+    /// Overwrites the buffer, its previous contents are not needed.
+    pub fn write_buffer(mut self, buffer: GraphBuffer, access: Access) -> Self {
+        self.add_use(
+            buffer.0,
+            ImageUsage::new(vk::ImageLayout::UNDEFINED, access),
+            false,
+            true,
+        );
+        self
+    }
+
+    pub fn modify_buffer(mut self, buffer: GraphBuffer, access: Access) -> Self {
+        self.add_use(
+            buffer.0,
+            ImageUsage::new(vk::ImageLayout::UNDEFINED, access),
+            true,
+            true,
+        );
+        self
+    }
+
+    /// Keeps the pass even if nothing reads what it writes, for passes with effects the graph can't see.
+    pub fn side_effects(self) -> Self {
+        self.pass.side_effects = true;
+        self
+    }
+
+    /// Sets the function recording the commands of the pass. Passes without one only emit their barriers.
+    pub fn execute<F>(self, callback: F) -> Self
+    where
+        F: FnMut(&mut CommandRecorder, &GraphResources) -> anyhow::Result<()> + 'a,
+    {
+        self.pass.callback = Some(Box::new(callback));
+        self
+    }
+}
+
+/// The physical images and buffers bound to the resources of a graph for an execution.
+#[derive(Clone, Debug, Default)]
+pub struct GraphResources {
+    images: HashMap<ResourceId, vk::Image>,
+    buffers: HashMap<ResourceId, vk::Buffer>,
+}
+
+impl GraphResources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind_image(&mut self, image: GraphImage, handle: vk::Image) -> &mut Self {
+        self.images.insert(image.0, handle);
+        self
+    }
+
+    pub fn bind_buffer(&mut self, buffer: GraphBuffer, handle: vk::Buffer) -> &mut Self {
+        self.buffers.insert(buffer.0, handle);
+        self
+    }
+
+    /// Binds `handle` to every transient image aliased onto `slot`, see [`CompiledGraph::slots`].
+    pub fn bind_image_slot(
+        &mut self,
+        compiled: &CompiledGraph,
+        slot: usize,
+        handle: vk::Image,
+    ) -> &mut Self {
+        if let Some(slot) = compiled.slots().get(slot) {
+            for resource in &slot.resources {
+                self.images.insert(*resource, handle);
+            }
+        }
+        self
+    }
+
+    /// Binds `handle` to every transient buffer aliased onto `slot`, see [`CompiledGraph::slots`].
+    pub fn bind_buffer_slot(
+        &mut self,
+        compiled: &CompiledGraph,
+        slot: usize,
+        handle: vk::Buffer,
+    ) -> &mut Self {
+        if let Some(slot) = compiled.slots().get(slot) {
+            for resource in &slot.resources {
+                self.buffers.insert(*resource, handle);
+            }
+        }
+        self
+    }
+
+    /// The physical image bound to `image`, or a null handle.
+    pub fn image(&self, image: GraphImage) -> vk::Image {
+        self.images.get(&image.0).copied().unwrap_or_default()
+    }
+
+    /// The physical buffer bound to `buffer`, or a null handle.
+    pub fn buffer(&self, buffer: GraphBuffer) -> vk::Buffer {
+        self.buffers.get(&buffer.0).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn raw_image(&self, resource: ResourceId) -> Option<vk::Image> {
+        self.images.get(&resource).copied()
+    }
+
+    pub(crate) fn raw_buffer(&self, resource: ResourceId) -> Option<vk::Buffer> {
+        self.buffers.get(&resource).copied()
+    }
+}
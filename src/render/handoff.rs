@@ -0,0 +1,347 @@
+use crate::errors::HandoffError;
+use crate::render::barrier::{Access, BufferBarrier, ImageBarrier};
+use crate::render::context::device::{Device, LazyQueue};
+use crate::render::context::queues::QueueRef;
+use crate::render::memory::{Buffer, Image};
+use ash::vk;
+
+/// Hands resources over from one queue to another, producing the barriers each side records.
+///
+/// Every resource is given as the barrier between its last use on the source queue and its first use on the
+/// destination queue, the queue family indices of the barrier are ignored. Depending on the queues and the sharing
+/// mode of the resource, the handoff is:
+///
+/// - an ownership transfer, for exclusive resources changing queue family: the release half is recorded on the source
+///   queue, the acquire half (which also performs the layout transition) on the destination queue, after a semaphore
+///   wait on the release;
+/// - a plain barrier on the destination queue, for concurrent resources or queues of the same family, the semaphore
+///   between the queues making the source writes available;
+/// - a plain barrier on the source queue when both queues are the same, with an empty acquire.
+///
+/// [`CommandRecorder::release`](crate::render::command_recorder::CommandRecorder::release) and
+/// [`CommandRecorder::acquire`](crate::render::command_recorder::CommandRecorder::acquire) record each half.
+///
+/// ```
+/// use ash::vk;
+/// use neuron_engine::render::barrier::{Access, ImageBarrier};
+/// use neuron_engine::render::context::queues::QueueRef;
+/// use neuron_engine::render::handoff::OwnershipHandoff;
+///
+/// let graphics = QueueRef { family: 0, index: 0 };
+/// let present = QueueRef { family: 2, index: 0 };
+/// let image = vk::Image::null();
+///
+/// let handoff = OwnershipHandoff::between(graphics, present).image(
+///     ImageBarrier::new(
+///         image,
+///         vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+///         vk::ImageLayout::PRESENT_SRC_KHR,
+///     )
+///     .src(Access::COLOR_ATTACHMENT_WRITE),
+///     vk::SharingMode::EXCLUSIVE,
+/// );
+///
+/// assert!(handoff.transfers_ownership());
+/// assert_eq!(handoff.release_barriers().1[0].dst_queue_family, 2);
+/// assert_eq!(handoff.acquire_barriers().1[0].src_queue_family, 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct OwnershipHandoff {
+    src: QueueRef,
+    dst: QueueRef,
+    buffers: Vec<(BufferBarrier, vk::SharingMode)>,
+    images: Vec<(ImageBarrier, vk::SharingMode)>,
+}
+
+impl OwnershipHandoff {
+    /// A handoff from `src` to `dst`, resolved on `device`.
+    pub fn new(
+        device: &Device,
+        src: impl Into<LazyQueue>,
+        dst: impl Into<LazyQueue>,
+    ) -> Result<Self, HandoffError> {
+        let resolve = |queue: LazyQueue| {
+            device
+                .get_lazy_queue(queue)
+                .map(|queue| queue.queue_ref())
+                .ok_or(HandoffError::QueueNotFound(queue))
+        };
+
+        Ok(Self::between(resolve(src.into())?, resolve(dst.into())?))
+    }
+
+    /// A handoff between two resolved queues.
+    pub fn between(src: QueueRef, dst: QueueRef) -> Self {
+        Self {
+            src,
+            dst,
+            buffers: vec![],
+            images: vec![],
+        }
+    }
+
+    pub fn src(&self) -> QueueRef {
+        self.src
+    }
+
+    pub fn dst(&self) -> QueueRef {
+        self.dst
+    }
+
+    /// Adds a buffer range, `barrier` goes from its last use on the source queue to its first use on the destination.
+    pub fn buffer(mut self, barrier: BufferBarrier, sharing_mode: vk::SharingMode) -> Self {
+        self.buffers.push((barrier, sharing_mode));
+        self
+    }
+
+    /// Adds an image, `barrier` goes from its last use on the source queue to its first use on the destination.
+    pub fn image(mut self, barrier: ImageBarrier, sharing_mode: vk::SharingMode) -> Self {
+        self.images.push((barrier, sharing_mode));
+        self
+    }
+
+    /// Adds the whole `buffer`.
+    pub fn whole_buffer(self, buffer: &Buffer, src: Access, dst: Access) -> Self {
+        let sharing_mode = buffer.sharing_mode();
+        self.buffer(BufferBarrier::new(buffer.handle(), src, dst), sharing_mode)
+    }
+
+    /// Adds every subresource of `image`.
+    pub fn whole_image(
+        self,
+        image: &Image,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        src: Access,
+        dst: Access,
+    ) -> Self {
+        let sharing_mode = image.sharing_mode();
+        self.image(
+            ImageBarrier::new(image.handle(), old_layout, new_layout)
+                .src(src)
+                .dst(dst)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: image.aspect_mask(),
+                    base_mip_level: 0,
+                    level_count: image.mip_levels(),
+                    base_array_layer: 0,
+                    layer_count: image.array_layers(),
+                }),
+            sharing_mode,
+        )
+    }
+
+    pub fn is_same_queue(&self) -> bool {
+        self.src == self.dst
+    }
+
+    fn transfers(&self, sharing_mode: vk::SharingMode) -> bool {
+        sharing_mode == vk::SharingMode::EXCLUSIVE && self.src.family != self.dst.family
+    }
+
+    /// Whether any resource changes queue family ownership, in which case the source queue has to record the release
+    /// barriers.
+    pub fn transfers_ownership(&self) -> bool {
+        self.buffers
+            .iter()
+            .map(|(_, sharing_mode)| *sharing_mode)
+            .chain(self.images.iter().map(|(_, sharing_mode)| *sharing_mode))
+            .any(|sharing_mode| self.transfers(sharing_mode))
+    }
+
+    /// The barriers to record on the source queue after the last use of the resources.
+    pub fn release_barriers(&self) -> (Vec<BufferBarrier>, Vec<ImageBarrier>) {
+        let buffers = self
+            .buffers
+            .iter()
+            .filter_map(|(barrier, sharing_mode)| {
+                if self.transfers(*sharing_mode) {
+                    Some(BufferBarrier {
+                        dst: Access::NONE,
+                        ..barrier.queue_family_transfer(self.src.family, self.dst.family)
+                    })
+                } else {
+                    self.is_same_queue().then_some(unowned_buffer(barrier))
+                }
+            })
+            .collect();
+
+        let images = self
+            .images
+            .iter()
+            .filter_map(|(barrier, sharing_mode)| {
+                if self.transfers(*sharing_mode) {
+                    Some(
+                        barrier
+                            .queue_family_transfer(self.src.family, self.dst.family)
+                            .dst(Access::NONE),
+                    )
+                } else {
+                    self.is_same_queue().then_some(unowned_image(barrier))
+                }
+            })
+            .collect();
+
+        (buffers, images)
+    }
+
+    /// The barriers to record on the destination queue before the first use of the resources, once the submission
+    /// waited on the source queue's work.
+    pub fn acquire_barriers(&self) -> (Vec<BufferBarrier>, Vec<ImageBarrier>) {
+        if self.is_same_queue() {
+            return (vec![], vec![]);
+        }
+
+        let buffers = self
+            .buffers
+            .iter()
+            .map(|(barrier, sharing_mode)| {
+                let barrier = if self.transfers(*sharing_mode) {
+                    barrier.queue_family_transfer(self.src.family, self.dst.family)
+                } else {
+                    unowned_buffer(barrier)
+                };
+
+                BufferBarrier {
+                    src: barrier.dst.wait_scope(),
+                    ..barrier
+                }
+            })
+            .collect();
+
+        let images = self
+            .images
+            .iter()
+            .map(|(barrier, sharing_mode)| {
+                let barrier = if self.transfers(*sharing_mode) {
+                    barrier.queue_family_transfer(self.src.family, self.dst.family)
+                } else {
+                    unowned_image(barrier)
+                };
+
+                barrier.src(barrier.dst.wait_scope())
+            })
+            .collect();
+
+        (buffers, images)
+    }
+}
+
+fn unowned_buffer(barrier: &BufferBarrier) -> BufferBarrier {
+    barrier.queue_family_transfer(vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+}
+
+fn unowned_image(barrier: &ImageBarrier) -> ImageBarrier {
+    barrier.queue_family_transfer(vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    const GRAPHICS: QueueRef = QueueRef {
+        family: 0,
+        index: 0,
+    };
+    const GRAPHICS_EXTRA: QueueRef = QueueRef {
+        family: 0,
+        index: 1,
+    };
+    const TRANSFER: QueueRef = QueueRef {
+        family: 1,
+        index: 0,
+    };
+
+    fn upload_barrier() -> ImageBarrier {
+        ImageBarrier::new(
+            vk::Image::from_raw(1),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+        .src(Access::TRANSFER_WRITE)
+        .dst(Access::FRAGMENT_SHADER_READ)
+    }
+
+    #[test]
+    fn exclusive_resources_transfer_between_families() {
+        let handoff = OwnershipHandoff::between(TRANSFER, GRAPHICS)
+            .image(upload_barrier(), vk::SharingMode::EXCLUSIVE)
+            .buffer(
+                BufferBarrier::new(
+                    vk::Buffer::from_raw(2),
+                    Access::TRANSFER_WRITE,
+                    Access::COMPUTE_SHADER_READ,
+                ),
+                vk::SharingMode::EXCLUSIVE,
+            );
+        assert!(handoff.transfers_ownership());
+
+        let (release_buffers, release_images) = handoff.release_barriers();
+        let (acquire_buffers, acquire_images) = handoff.acquire_barriers();
+
+        let release = release_images[0];
+        let acquire = acquire_images[0];
+        assert_eq!((release.src_queue_family, release.dst_queue_family), (1, 0));
+        assert_eq!((acquire.src_queue_family, acquire.dst_queue_family), (1, 0));
+        assert_eq!(release.src, Access::TRANSFER_WRITE);
+        assert_eq!(release.dst, Access::NONE);
+        assert_eq!(
+            acquire.src,
+            Access::new(
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::NONE
+            )
+        );
+        assert_eq!(acquire.dst, Access::FRAGMENT_SHADER_READ);
+        // both halves carry the same layout transition
+        assert_eq!(
+            (release.old_layout, release.new_layout),
+            (acquire.old_layout, acquire.new_layout)
+        );
+
+        assert_eq!(release_buffers[0].dst, Access::NONE);
+        assert_eq!(acquire_buffers[0].dst, Access::COMPUTE_SHADER_READ);
+        assert_eq!(acquire_buffers[0].dst_queue_family, 0);
+    }
+
+    #[test]
+    fn concurrent_and_same_family_handoffs_skip_the_transfer() {
+        for handoff in [
+            OwnershipHandoff::between(TRANSFER, GRAPHICS)
+                .image(upload_barrier(), vk::SharingMode::CONCURRENT),
+            OwnershipHandoff::between(GRAPHICS_EXTRA, GRAPHICS)
+                .image(upload_barrier(), vk::SharingMode::EXCLUSIVE),
+        ] {
+            assert!(!handoff.transfers_ownership());
+
+            let (_, release_images) = handoff.release_barriers();
+            let (_, acquire_images) = handoff.acquire_barriers();
+            assert!(release_images.is_empty());
+
+            let acquire = acquire_images[0];
+            assert_eq!(acquire.src_queue_family, vk::QUEUE_FAMILY_IGNORED);
+            assert_eq!(acquire.dst_queue_family, vk::QUEUE_FAMILY_IGNORED);
+            assert_eq!(
+                acquire.new_layout,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            );
+            assert_eq!(acquire.dst, Access::FRAGMENT_SHADER_READ);
+        }
+    }
+
+    #[test]
+    fn same_queue_handoffs_are_a_single_barrier() {
+        let handoff = OwnershipHandoff::between(GRAPHICS, GRAPHICS)
+            .image(upload_barrier(), vk::SharingMode::EXCLUSIVE);
+
+        let (_, release_images) = handoff.release_barriers();
+        let (acquire_buffers, acquire_images) = handoff.acquire_barriers();
+        assert!(acquire_buffers.is_empty() && acquire_images.is_empty());
+
+        let release = release_images[0];
+        assert_eq!(release.src, Access::TRANSFER_WRITE);
+        assert_eq!(release.dst, Access::FRAGMENT_SHADER_READ);
+        assert_eq!(release.src_queue_family, vk::QUEUE_FAMILY_IGNORED);
+    }
+}
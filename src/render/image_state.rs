@@ -0,0 +1,585 @@
+use crate::errors::ImageStateError;
+use crate::render::barrier::{Access, ImageBarrier};
+use ash::vk;
+use std::collections::HashMap;
+
+/// How an image is used next: the layout it must be in and the stages and accesses using it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageUsage {
+    pub layout: vk::ImageLayout,
+    pub access: Access,
+}
+
+impl ImageUsage {
+    pub const TRANSFER_SRC: Self =
+        Self::new(vk::ImageLayout::TRANSFER_SRC_OPTIMAL, Access::TRANSFER_READ);
+    pub const TRANSFER_DST: Self = Self::new(
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        Access::TRANSFER_WRITE,
+    );
+    pub const COLOR_ATTACHMENT: Self = Self::new(
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        Access::COLOR_ATTACHMENT_WRITE,
+    );
+    pub const DEPTH_STENCIL_ATTACHMENT: Self = Self::new(
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+    );
+    pub const FRAGMENT_SHADER_SAMPLED: Self = Self::new(
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        Access::FRAGMENT_SHADER_READ,
+    );
+    pub const COMPUTE_STORAGE_READ: Self =
+        Self::new(vk::ImageLayout::GENERAL, Access::COMPUTE_SHADER_READ);
+    pub const COMPUTE_STORAGE_WRITE: Self =
+        Self::new(vk::ImageLayout::GENERAL, Access::COMPUTE_SHADER_WRITE);
+    /// Handing the image to the presentation engine, the present semaphore takes care of the execution dependency.
+    pub const PRESENT: Self = Self::new(vk::ImageLayout::PRESENT_SRC_KHR, Access::NONE);
+
+    pub const fn new(layout: vk::ImageLayout, access: Access) -> Self {
+        Self { layout, access }
+    }
+
+    pub(crate) fn writes(&self) -> bool {
+        const WRITES: vk::AccessFlags2 = vk::AccessFlags2::from_raw(
+            vk::AccessFlags2::SHADER_WRITE.as_raw()
+                | vk::AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+                | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+                | vk::AccessFlags2::TRANSFER_WRITE.as_raw()
+                | vk::AccessFlags2::HOST_WRITE.as_raw()
+                | vk::AccessFlags2::MEMORY_WRITE.as_raw(),
+        );
+
+        self.access.access.intersects(WRITES)
+    }
+}
+
+/// The last known state of an image subresource.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageState {
+    pub layout: vk::ImageLayout,
+    /// Every stage and access since the last barrier, reads accumulate until the next write or layout change.
+    pub access: Access,
+    /// The queue family owning the subresource, `None` for concurrent images or images no queue has used yet.
+    pub queue_family: Option<u32>,
+}
+
+impl ImageState {
+    /// An image no queue has used yet, its contents are discardable.
+    pub const UNDEFINED: Self = Self {
+        layout: vk::ImageLayout::UNDEFINED,
+        access: Access::NONE,
+        queue_family: None,
+    };
+}
+
+#[derive(Clone, Debug)]
+struct TrackedImage {
+    aspect_mask: vk::ImageAspectFlags,
+    mip_levels: u32,
+    array_layers: u32,
+    concurrent: bool,
+    /// Indexed by `mip * array_layers + layer`.
+    states: Vec<ImageState>,
+}
+
+impl TrackedImage {
+    fn state_mut(&mut self, mip: u32, layer: u32) -> &mut ImageState {
+        &mut self.states[(mip * self.array_layers + layer) as usize]
+    }
+}
+
+/// Remembers the layout, access and owning queue family of every image subresource, and turns declared usages into
+/// the sync2 barriers needed to reach them.
+///
+/// The tracker only sees what it is told: usages recorded into command buffers that are then never submitted, or
+/// submitted in a different order, make it diverge from the GPU.
+#[derive(Clone, Debug, Default)]
+pub struct ImageStateTracker {
+    images: HashMap<vk::Image, TrackedImage>,
+}
+
+impl ImageStateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking `image`, every subresource starts in `initial_layout`.
+    ///
+    /// Concurrent images never need queue family ownership transfers.
+    pub fn register(
+        &mut self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        mip_levels: u32,
+        array_layers: u32,
+        initial_layout: vk::ImageLayout,
+        sharing_mode: vk::SharingMode,
+    ) {
+        self.images.insert(
+            image,
+            TrackedImage {
+                aspect_mask,
+                mip_levels,
+                array_layers,
+                concurrent: sharing_mode == vk::SharingMode::CONCURRENT,
+                states: vec![
+                    ImageState {
+                        layout: initial_layout,
+                        access: Access::NONE,
+                        queue_family: None,
+                    };
+                    (mip_levels * array_layers) as usize
+                ],
+            },
+        );
+    }
+
+    pub fn forget(&mut self, image: vk::Image) {
+        self.images.remove(&image);
+    }
+
+    pub fn is_tracked(&self, image: vk::Image) -> bool {
+        self.images.contains_key(&image)
+    }
+
+    /// The state of a single subresource.
+    pub fn state(&self, image: vk::Image, mip_level: u32, array_layer: u32) -> Option<ImageState> {
+        let tracked = self.images.get(&image)?;
+        (mip_level < tracked.mip_levels && array_layer < tracked.array_layers)
+            .then(|| tracked.states[(mip_level * tracked.array_layers + array_layer) as usize])
+    }
+
+    /// Resets a freshly acquired swapchain image to `UNDEFINED`, registering it on first use.
+    ///
+    /// `wait_stage` is the stage the image available semaphore is waited on, the first barrier waits on it so the
+    /// layout transition happens after the presentation engine released the image.
+    pub fn acquire_swapchain_image(
+        &mut self,
+        image: vk::Image,
+        wait_stage: vk::PipelineStageFlags2,
+    ) {
+        if !self.is_tracked(image) {
+            self.register(
+                image,
+                vk::ImageAspectFlags::COLOR,
+                1,
+                1,
+                vk::ImageLayout::UNDEFINED,
+                vk::SharingMode::EXCLUSIVE,
+            );
+        }
+
+        self.discard(image, Access::new(wait_stage, vk::AccessFlags2::NONE));
+    }
+
+    /// Marks the contents of every subresource of `image` as discardable, the next transition starts from `UNDEFINED`
+    /// and waits on `last_access`.
+    pub fn discard(&mut self, image: vk::Image, last_access: Access) {
+        if let Some(tracked) = self.images.get_mut(&image) {
+            tracked.states.fill(ImageState {
+                layout: vk::ImageLayout::UNDEFINED,
+                access: last_access,
+                queue_family: None,
+            });
+        }
+    }
+
+    /// Declares the next use of `subresource_range` (every subresource when `None`) on `queue_family`, returns the
+    /// barriers to record before it.
+    ///
+    /// Subresources sharing a previous state are merged into one barrier, consecutive reads in the same layout need no
+    /// barrier at all. When the owning queue family changes, the barrier carries the ownership transfer and has to be
+    /// recorded on both queues: as the release on the previous one and as the acquire on `queue_family`. Images in
+    /// `UNDEFINED` skip the transfer since their contents are discarded anyway.
+    ///
+    /// Fails for images that were never registered, the tracker can't know what state they are in.
+    pub fn transition(
+        &mut self,
+        image: vk::Image,
+        subresource_range: Option<vk::ImageSubresourceRange>,
+        usage: ImageUsage,
+        queue_family: u32,
+    ) -> Result<Vec<ImageBarrier>, ImageStateError> {
+        let Some(tracked) = self.images.get_mut(&image) else {
+            return Err(ImageStateError::UntrackedImage(image));
+        };
+
+        let range = subresource_range.unwrap_or(vk::ImageSubresourceRange {
+            aspect_mask: tracked.aspect_mask,
+            base_mip_level: 0,
+            level_count: tracked.mip_levels,
+            base_array_layer: 0,
+            layer_count: tracked.array_layers,
+        });
+        let mips = clamp_range(range.base_mip_level, range.level_count, tracked.mip_levels);
+        let layers = clamp_range(
+            range.base_array_layer,
+            range.layer_count,
+            tracked.array_layers,
+        );
+        let concurrent = tracked.concurrent;
+
+        // runs of layers within a mip level that share a previous state and need a barrier
+        let mut runs: Vec<(u32, u32, u32, ImageState)> = vec![];
+        for mip in mips.clone() {
+            for layer in layers.clone() {
+                let state = tracked.state_mut(mip, layer);
+                let previous = *state;
+
+                // an exclusive image read on another family still needs its ownership transfer
+                if previous.layout == usage.layout
+                    && !usage.writes()
+                    && !ImageUsage::new(previous.layout, previous.access).writes()
+                    && (concurrent
+                        || previous
+                            .queue_family
+                            .is_none_or(|family| family == queue_family))
+                {
+                    state.access.stages |= usage.access.stages;
+                    state.access.access |= usage.access.access;
+                    if !concurrent {
+                        state.queue_family = Some(queue_family);
+                    }
+                    continue;
+                }
+
+                *state = ImageState {
+                    layout: usage.layout,
+                    access: usage.access,
+                    queue_family: (!concurrent).then_some(queue_family),
+                };
+
+                match runs.last_mut() {
+                    Some((run_mip, base, count, run_state))
+                        if *run_mip == mip && *base + *count == layer && *run_state == previous =>
+                    {
+                        *count += 1
+                    }
+                    _ => runs.push((mip, layer, 1, previous)),
+                }
+            }
+        }
+
+        // merge identical layer runs of consecutive mip levels
+        let mut merged: Vec<(u32, u32, u32, u32, ImageState)> = vec![];
+        for (mip, base_layer, layer_count, previous) in runs {
+            match merged.last_mut() {
+                Some((base_mip, mip_count, run_base, run_count, run_state))
+                    if *base_mip + *mip_count == mip
+                        && *run_base == base_layer
+                        && *run_count == layer_count
+                        && *run_state == previous =>
+                {
+                    *mip_count += 1
+                }
+                _ => merged.push((mip, 1, base_layer, layer_count, previous)),
+            }
+        }
+
+        Ok(merged
+            .into_iter()
+            .map(|(base_mip, mip_count, base_layer, layer_count, previous)| {
+                let writes = ImageUsage::new(previous.layout, previous.access).writes();
+                let mut barrier = ImageBarrier::new(image, previous.layout, usage.layout)
+                    .src(Access::new(
+                        previous.access.stages,
+                        // reads only need an execution dependency
+                        if writes {
+                            previous.access.access
+                        } else {
+                            vk::AccessFlags2::NONE
+                        },
+                    ))
+                    .dst(usage.access)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: range.aspect_mask,
+                        base_mip_level: base_mip,
+                        level_count: mip_count,
+                        base_array_layer: base_layer,
+                        layer_count,
+                    });
+
+                if let Some(previous_family) = previous.queue_family
+                    && previous_family != queue_family
+                    && previous.layout != vk::ImageLayout::UNDEFINED
+                {
+                    barrier = barrier.queue_family_transfer(previous_family, queue_family);
+                }
+
+                barrier
+            })
+            .collect())
+    }
+}
+
+fn clamp_range(base: u32, count: u32, total: u32) -> std::ops::Range<u32> {
+    // VK_REMAINING_MIP_LEVELS and VK_REMAINING_ARRAY_LAYERS are both ~0
+    let end = if count == vk::REMAINING_MIP_LEVELS {
+        total
+    } else {
+        base.saturating_add(count).min(total)
+    };
+
+    base.min(total)..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    const GRAPHICS: u32 = 0;
+    const COMPUTE: u32 = 1;
+
+    fn tracker_with(image: vk::Image, mip_levels: u32, array_layers: u32) -> ImageStateTracker {
+        let mut tracker = ImageStateTracker::new();
+        tracker.register(
+            image,
+            vk::ImageAspectFlags::COLOR,
+            mip_levels,
+            array_layers,
+            vk::ImageLayout::UNDEFINED,
+            vk::SharingMode::EXCLUSIVE,
+        );
+        tracker
+    }
+
+    fn range(
+        base_mip_level: u32,
+        level_count: u32,
+        base_array_layer: u32,
+        layer_count: u32,
+    ) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level,
+            level_count,
+            base_array_layer,
+            layer_count,
+        }
+    }
+
+    /// The previous layout and the mip and layer ranges of a barrier.
+    fn subresources(barrier: &ImageBarrier) -> (vk::ImageLayout, u32, u32, u32, u32) {
+        let range = barrier.subresource_range;
+        (
+            barrier.old_layout,
+            range.base_mip_level,
+            range.level_count,
+            range.base_array_layer,
+            range.layer_count,
+        )
+    }
+
+    #[test]
+    fn unregistered_images_are_rejected() {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = tracker_with(image, 1, 1);
+        let other = vk::Image::from_raw(2);
+
+        assert!(matches!(
+            tracker.transition(other, None, ImageUsage::TRANSFER_DST, GRAPHICS),
+            Err(ImageStateError::UntrackedImage(untracked)) if untracked == other
+        ));
+
+        tracker.forget(image);
+        assert!(
+            tracker
+                .transition(image, None, ImageUsage::TRANSFER_DST, GRAPHICS)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn acquired_swapchain_images_start_undefined() {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = ImageStateTracker::new();
+        let wait_stage = vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT;
+
+        tracker.acquire_swapchain_image(image, wait_stage);
+        let barriers = tracker
+            .transition(image, None, ImageUsage::COLOR_ATTACHMENT, GRAPHICS)
+            .unwrap();
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(barriers[0].old_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(
+            barriers[0].new_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(
+            barriers[0].src,
+            Access::new(wait_stage, vk::AccessFlags2::NONE)
+        );
+        assert_eq!(barriers[0].src_queue_family, vk::QUEUE_FAMILY_IGNORED);
+
+        tracker
+            .transition(image, None, ImageUsage::PRESENT, GRAPHICS)
+            .unwrap();
+
+        // the next acquire discards the presented contents again
+        tracker.acquire_swapchain_image(image, wait_stage);
+        assert_eq!(
+            tracker.state(image, 0, 0),
+            Some(ImageState {
+                layout: vk::ImageLayout::UNDEFINED,
+                access: Access::new(wait_stage, vk::AccessFlags2::NONE),
+                queue_family: None,
+            })
+        );
+    }
+
+    #[test]
+    fn consecutive_reads_merge_without_barriers() {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = tracker_with(image, 1, 1);
+
+        tracker
+            .transition(image, None, ImageUsage::TRANSFER_DST, GRAPHICS)
+            .unwrap();
+        assert_eq!(
+            tracker
+                .transition(image, None, ImageUsage::FRAGMENT_SHADER_SAMPLED, GRAPHICS)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let compute_sampled = ImageUsage::new(
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            Access::COMPUTE_SHADER_READ,
+        );
+        assert!(
+            tracker
+                .transition(image, None, compute_sampled, GRAPHICS)
+                .unwrap()
+                .is_empty()
+        );
+
+        // the write waits on every read since the last barrier, without making them available
+        let barriers = tracker
+            .transition(image, None, ImageUsage::TRANSFER_DST, GRAPHICS)
+            .unwrap();
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(
+            barriers[0].src,
+            Access::new(
+                Access::FRAGMENT_SHADER_READ.stages | Access::COMPUTE_SHADER_READ.stages,
+                vk::AccessFlags2::NONE,
+            )
+        );
+    }
+
+    #[test]
+    fn matching_subresources_share_a_barrier() {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = tracker_with(image, 4, 6);
+
+        let barriers = tracker
+            .transition(image, None, ImageUsage::TRANSFER_DST, GRAPHICS)
+            .unwrap();
+        assert_eq!(
+            barriers.iter().map(subresources).collect::<Vec<_>>(),
+            vec![(vk::ImageLayout::UNDEFINED, 0, 4, 0, 6)]
+        );
+
+        // mips 1..3 and layers 2..4 of mip 0 diverge, splitting the next full transition around them
+        tracker
+            .transition(
+                image,
+                Some(range(1, 2, 0, 6)),
+                ImageUsage::TRANSFER_SRC,
+                GRAPHICS,
+            )
+            .unwrap();
+        tracker
+            .transition(
+                image,
+                Some(range(0, 1, 2, 2)),
+                ImageUsage::TRANSFER_SRC,
+                GRAPHICS,
+            )
+            .unwrap();
+        let barriers = tracker
+            .transition(image, None, ImageUsage::FRAGMENT_SHADER_SAMPLED, GRAPHICS)
+            .unwrap();
+        assert_eq!(
+            barriers.iter().map(subresources).collect::<Vec<_>>(),
+            vec![
+                (vk::ImageLayout::TRANSFER_DST_OPTIMAL, 0, 1, 0, 2),
+                (vk::ImageLayout::TRANSFER_SRC_OPTIMAL, 0, 1, 2, 2),
+                (vk::ImageLayout::TRANSFER_DST_OPTIMAL, 0, 1, 4, 2),
+                (vk::ImageLayout::TRANSFER_SRC_OPTIMAL, 1, 2, 0, 6),
+                (vk::ImageLayout::TRANSFER_DST_OPTIMAL, 3, 1, 0, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn family_changes_emit_ownership_transfers() {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = tracker_with(image, 1, 1);
+
+        // nothing to transfer while the contents are undefined
+        let barriers = tracker
+            .transition(image, None, ImageUsage::TRANSFER_DST, GRAPHICS)
+            .unwrap();
+        assert_eq!(barriers[0].src_queue_family, vk::QUEUE_FAMILY_IGNORED);
+
+        let barriers = tracker
+            .transition(image, None, ImageUsage::COMPUTE_STORAGE_READ, COMPUTE)
+            .unwrap();
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(
+            (barriers[0].src_queue_family, barriers[0].dst_queue_family),
+            (GRAPHICS, COMPUTE)
+        );
+
+        // a read in the same layout still moves the image back to its new family
+        let graphics_read = ImageUsage::new(vk::ImageLayout::GENERAL, Access::FRAGMENT_SHADER_READ);
+        let barriers = tracker
+            .transition(image, None, graphics_read, GRAPHICS)
+            .unwrap();
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(barriers[0].old_layout, vk::ImageLayout::GENERAL);
+        assert_eq!(
+            (barriers[0].src_queue_family, barriers[0].dst_queue_family),
+            (COMPUTE, GRAPHICS)
+        );
+        assert_eq!(
+            tracker.state(image, 0, 0).unwrap().queue_family,
+            Some(GRAPHICS)
+        );
+    }
+
+    #[test]
+    fn concurrent_images_never_transfer() {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = ImageStateTracker::new();
+        tracker.register(
+            image,
+            vk::ImageAspectFlags::COLOR,
+            1,
+            1,
+            vk::ImageLayout::UNDEFINED,
+            vk::SharingMode::CONCURRENT,
+        );
+
+        tracker
+            .transition(image, None, ImageUsage::TRANSFER_DST, GRAPHICS)
+            .unwrap();
+        let barriers = tracker
+            .transition(image, None, ImageUsage::COMPUTE_STORAGE_READ, COMPUTE)
+            .unwrap();
+        assert_eq!(barriers[0].src_queue_family, vk::QUEUE_FAMILY_IGNORED);
+
+        let graphics_read = ImageUsage::new(vk::ImageLayout::GENERAL, Access::FRAGMENT_SHADER_READ);
+        assert!(
+            tracker
+                .transition(image, None, graphics_read, GRAPHICS)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
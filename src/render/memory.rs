@@ -0,0 +1,894 @@
+use crate::app::feature_request::DeviceFeature;
+use crate::errors::MemoryError;
+use crate::render::context::VulkanContext;
+use crate::render::context::deletion_queue::LastUse;
+use crate::render::context::device::Device;
+use crate::render::context::instance::Instance;
+use crate::render::context::physical_device::PhysicalDeviceInfo;
+use ash::prelude::VkResult;
+use ash::vk;
+use gpu_allocator::vulkan::{
+    Allocation, AllocationCreateDesc, AllocationScheme, Allocator, AllocatorCreateDesc,
+};
+use gpu_allocator::{AllocationSizes, AllocatorDebugSettings, MemoryLocation};
+use log::{debug, error};
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Where the memory of a resource lives, which decides how the host can access it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MemoryUsage {
+    /// Device local memory the host can't access, for everything the GPU reads and writes on its own.
+    #[default]
+    GpuOnly,
+    /// Host visible memory, persistently mapped, for data the host writes and the GPU reads (staging buffers, per-frame
+    /// uniforms).
+    Upload,
+    /// Host visible and cached memory, persistently mapped, for data the GPU writes and the host reads back.
+    Readback,
+}
+
+impl MemoryUsage {
+    fn location(&self) -> MemoryLocation {
+        match self {
+            MemoryUsage::GpuOnly => MemoryLocation::GpuOnly,
+            MemoryUsage::Upload => MemoryLocation::CpuToGpu,
+            MemoryUsage::Readback => MemoryLocation::GpuToCpu,
+        }
+    }
+
+    pub fn is_host_visible(&self) -> bool {
+        !matches!(self, MemoryUsage::GpuOnly)
+    }
+}
+
+/// A snapshot of the allocator, see [`MemoryAllocator::statistics`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStatistics {
+    pub allocation_count: usize,
+    /// The number of `VkDeviceMemory` blocks the allocations are sub-allocated from.
+    pub block_count: usize,
+    /// The bytes used by live allocations.
+    pub allocated_bytes: u64,
+    /// The bytes reserved by the blocks, including the unused space between allocations.
+    pub reserved_bytes: u64,
+}
+
+/// The device memory allocator, owned by the [`VulkanContext`] and built on `gpu-allocator`.
+///
+/// Dropped [`Buffer`]s and [`Image`]s go through the
+/// [`DeletionQueue`](crate::render::context::deletion_queue::DeletionQueue): resources marked with
+/// [`Buffer::mark_used`] are destroyed once their timeline values are reached, unmarked ones once the work submitted
+/// before they were dropped is complete (see
+/// [`DeletionQueue::frame_key`](crate::render::context::deletion_queue::DeletionQueue::frame_key)).
+pub struct MemoryAllocator {
+    device: ash::Device,
+    allocator: Mutex<Allocator>,
+    non_coherent_atom_size: vk::DeviceSize,
+}
+
+impl MemoryAllocator {
+    pub(crate) fn new(
+        instance: &Instance,
+        device: &Device,
+        physical_device: &PhysicalDeviceInfo,
+    ) -> anyhow::Result<Self> {
+        let allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: instance.instance().clone(),
+            device: device.device().clone(),
+            physical_device: physical_device.handle(),
+            debug_settings: AllocatorDebugSettings {
+                log_memory_information: cfg!(debug_assertions),
+                log_leaks_on_shutdown: cfg!(debug_assertions),
+                ..AllocatorDebugSettings::default()
+            },
+            buffer_device_address: device.has_feature(DeviceFeature::BufferDeviceAddress),
+            allocation_sizes: AllocationSizes::default(),
+        })?;
+
+        debug!("[memory] Created device memory allocator");
+
+        Ok(Self {
+            device: device.device().clone(),
+            allocator: Mutex::new(allocator),
+            non_coherent_atom_size: physical_device.limits().non_coherent_atom_size.max(1),
+        })
+    }
+
+    fn allocator(&self) -> MutexGuard<'_, Allocator> {
+        // a panic while allocating leaves the allocator usable, gpu-allocator updates its state last
+        self.allocator.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn allocate(
+        &self,
+        name: &str,
+        mut requirements: vk::MemoryRequirements,
+        usage: MemoryUsage,
+        linear: bool,
+    ) -> Result<Allocation, MemoryError> {
+        if usage.is_host_visible() {
+            // keeps flushed and invalidated ranges inside the allocation on non-coherent memory
+            requirements.alignment = requirements.alignment.max(self.non_coherent_atom_size);
+            requirements.size = align_up(requirements.size, self.non_coherent_atom_size);
+        }
+
+        Ok(self.allocator().allocate(&AllocationCreateDesc {
+            name,
+            requirements,
+            location: usage.location(),
+            linear,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        })?)
+    }
+
+    fn free(&self, allocation: Allocation) {
+        if let Err(e) = self.allocator().free(allocation) {
+            error!("[memory] Failed to free allocation: {}", e);
+        }
+    }
+
+    pub fn statistics(&self) -> MemoryStatistics {
+        let report = self.allocator().generate_report();
+        MemoryStatistics {
+            allocation_count: report.allocations.len(),
+            block_count: report.blocks.len(),
+            allocated_bytes: report.total_allocated_bytes,
+            reserved_bytes: report.total_reserved_bytes,
+        }
+    }
+
+    pub fn non_coherent_atom_size(&self) -> vk::DeviceSize {
+        self.non_coherent_atom_size
+    }
+
+    /// Flushes host writes to `allocation` when its memory is not host coherent, `offset` and `size` are relative to
+    /// the allocation.
+    fn flush(&self, allocation: &Allocation, offset: u64, size: u64) -> VkResult<()> {
+        match self.mapped_range(allocation, offset, size) {
+            Some(range) => unsafe { self.device.flush_mapped_memory_ranges(&[range]) },
+            None => Ok(()),
+        }
+    }
+
+    /// Makes device writes to `allocation` visible to the host when its memory is not host coherent.
+    fn invalidate(&self, allocation: &Allocation, offset: u64, size: u64) -> VkResult<()> {
+        match self.mapped_range(allocation, offset, size) {
+            Some(range) => unsafe { self.device.invalidate_mapped_memory_ranges(&[range]) },
+            None => Ok(()),
+        }
+    }
+
+    fn mapped_range(
+        &self,
+        allocation: &Allocation,
+        offset: u64,
+        size: u64,
+    ) -> Option<vk::MappedMemoryRange<'static>> {
+        if allocation
+            .memory_properties()
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            return None;
+        }
+
+        // allocations of host visible memory are aligned to the atom size, see MemoryAllocator::allocate
+        let start = align_down(allocation.offset() + offset, self.non_coherent_atom_size);
+        let end = align_up(
+            allocation.offset() + offset + size,
+            self.non_coherent_atom_size,
+        )
+        .min(allocation.offset() + allocation.size());
+
+        Some(
+            vk::MappedMemoryRange::default()
+                .memory(unsafe { allocation.memory() })
+                .offset(start)
+                .size(end - start),
+        )
+    }
+}
+
+impl Debug for MemoryAllocator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryAllocator")
+            .field("statistics", &self.statistics())
+            .finish()
+    }
+}
+
+pub(crate) fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn align_down(value: u64, alignment: u64) -> u64 {
+    value / alignment * alignment
+}
+
+/// The aspects of a format: depth and/or stencil for depth formats, color for everything else.
+pub fn format_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// The size in bytes and the extent in texels of a texel block of `format` as copied from a buffer, `None` for
+/// formats the engine doesn't know the layout of. Depth/stencil formats report their depth aspect.
+pub fn format_texel_block(format: vk::Format) -> Option<(u64, vk::Extent2D)> {
+    use vk::Format as F;
+
+    // (bytes, block width and height)
+    let (size, block) = match format {
+        F::R4G4_UNORM_PACK8
+        | F::R8_UNORM
+        | F::R8_SNORM
+        | F::R8_USCALED
+        | F::R8_SSCALED
+        | F::R8_UINT
+        | F::R8_SINT
+        | F::R8_SRGB
+        | F::S8_UINT => (1, 1),
+        F::R4G4B4A4_UNORM_PACK16
+        | F::B4G4R4A4_UNORM_PACK16
+        | F::R5G6B5_UNORM_PACK16
+        | F::B5G6R5_UNORM_PACK16
+        | F::R5G5B5A1_UNORM_PACK16
+        | F::B5G5R5A1_UNORM_PACK16
+        | F::A1R5G5B5_UNORM_PACK16
+        | F::R8G8_UNORM
+        | F::R8G8_SNORM
+        | F::R8G8_USCALED
+        | F::R8G8_SSCALED
+        | F::R8G8_UINT
+        | F::R8G8_SINT
+        | F::R8G8_SRGB
+        | F::R16_UNORM
+        | F::R16_SNORM
+        | F::R16_USCALED
+        | F::R16_SSCALED
+        | F::R16_UINT
+        | F::R16_SINT
+        | F::R16_SFLOAT
+        | F::D16_UNORM
+        | F::D16_UNORM_S8_UINT => (2, 1),
+        F::R8G8B8_UNORM
+        | F::R8G8B8_SNORM
+        | F::R8G8B8_USCALED
+        | F::R8G8B8_SSCALED
+        | F::R8G8B8_UINT
+        | F::R8G8B8_SINT
+        | F::R8G8B8_SRGB
+        | F::B8G8R8_UNORM
+        | F::B8G8R8_SNORM
+        | F::B8G8R8_USCALED
+        | F::B8G8R8_SSCALED
+        | F::B8G8R8_UINT
+        | F::B8G8R8_SINT
+        | F::B8G8R8_SRGB => (3, 1),
+        F::R8G8B8A8_UNORM
+        | F::R8G8B8A8_SNORM
+        | F::R8G8B8A8_USCALED
+        | F::R8G8B8A8_SSCALED
+        | F::R8G8B8A8_UINT
+        | F::R8G8B8A8_SINT
+        | F::R8G8B8A8_SRGB
+        | F::B8G8R8A8_UNORM
+        | F::B8G8R8A8_SNORM
+        | F::B8G8R8A8_USCALED
+        | F::B8G8R8A8_SSCALED
+        | F::B8G8R8A8_UINT
+        | F::B8G8R8A8_SINT
+        | F::B8G8R8A8_SRGB
+        | F::A8B8G8R8_UNORM_PACK32
+        | F::A8B8G8R8_SNORM_PACK32
+        | F::A8B8G8R8_USCALED_PACK32
+        | F::A8B8G8R8_SSCALED_PACK32
+        | F::A8B8G8R8_UINT_PACK32
+        | F::A8B8G8R8_SINT_PACK32
+        | F::A8B8G8R8_SRGB_PACK32
+        | F::A2R10G10B10_UNORM_PACK32
+        | F::A2R10G10B10_SNORM_PACK32
+        | F::A2R10G10B10_USCALED_PACK32
+        | F::A2R10G10B10_SSCALED_PACK32
+        | F::A2R10G10B10_UINT_PACK32
+        | F::A2R10G10B10_SINT_PACK32
+        | F::A2B10G10R10_UNORM_PACK32
+        | F::A2B10G10R10_SNORM_PACK32
+        | F::A2B10G10R10_USCALED_PACK32
+        | F::A2B10G10R10_SSCALED_PACK32
+        | F::A2B10G10R10_UINT_PACK32
+        | F::A2B10G10R10_SINT_PACK32
+        | F::R16G16_UNORM
+        | F::R16G16_SNORM
+        | F::R16G16_USCALED
+        | F::R16G16_SSCALED
+        | F::R16G16_UINT
+        | F::R16G16_SINT
+        | F::R16G16_SFLOAT
+        | F::R32_UINT
+        | F::R32_SINT
+        | F::R32_SFLOAT
+        | F::B10G11R11_UFLOAT_PACK32
+        | F::E5B9G9R9_UFLOAT_PACK32
+        | F::X8_D24_UNORM_PACK32
+        | F::D32_SFLOAT
+        | F::D24_UNORM_S8_UINT
+        | F::D32_SFLOAT_S8_UINT => (4, 1),
+        F::R16G16B16_UNORM
+        | F::R16G16B16_SNORM
+        | F::R16G16B16_USCALED
+        | F::R16G16B16_SSCALED
+        | F::R16G16B16_UINT
+        | F::R16G16B16_SINT
+        | F::R16G16B16_SFLOAT => (6, 1),
+        F::R16G16B16A16_UNORM
+        | F::R16G16B16A16_SNORM
+        | F::R16G16B16A16_USCALED
+        | F::R16G16B16A16_SSCALED
+        | F::R16G16B16A16_UINT
+        | F::R16G16B16A16_SINT
+        | F::R16G16B16A16_SFLOAT
+        | F::R32G32_UINT
+        | F::R32G32_SINT
+        | F::R32G32_SFLOAT
+        | F::R64_UINT
+        | F::R64_SINT
+        | F::R64_SFLOAT => (8, 1),
+        F::R32G32B32_UINT | F::R32G32B32_SINT | F::R32G32B32_SFLOAT => (12, 1),
+        F::R32G32B32A32_UINT
+        | F::R32G32B32A32_SINT
+        | F::R32G32B32A32_SFLOAT
+        | F::R64G64_UINT
+        | F::R64G64_SINT
+        | F::R64G64_SFLOAT => (16, 1),
+        F::R64G64B64_UINT | F::R64G64B64_SINT | F::R64G64B64_SFLOAT => (24, 1),
+        F::R64G64B64A64_UINT | F::R64G64B64A64_SINT | F::R64G64B64A64_SFLOAT => (32, 1),
+        F::BC1_RGB_UNORM_BLOCK
+        | F::BC1_RGB_SRGB_BLOCK
+        | F::BC1_RGBA_UNORM_BLOCK
+        | F::BC1_RGBA_SRGB_BLOCK
+        | F::BC4_UNORM_BLOCK
+        | F::BC4_SNORM_BLOCK
+        | F::ETC2_R8G8B8_UNORM_BLOCK
+        | F::ETC2_R8G8B8_SRGB_BLOCK
+        | F::ETC2_R8G8B8A1_UNORM_BLOCK
+        | F::ETC2_R8G8B8A1_SRGB_BLOCK
+        | F::EAC_R11_UNORM_BLOCK
+        | F::EAC_R11_SNORM_BLOCK => (8, 4),
+        F::BC2_UNORM_BLOCK
+        | F::BC2_SRGB_BLOCK
+        | F::BC3_UNORM_BLOCK
+        | F::BC3_SRGB_BLOCK
+        | F::BC5_UNORM_BLOCK
+        | F::BC5_SNORM_BLOCK
+        | F::BC6H_UFLOAT_BLOCK
+        | F::BC6H_SFLOAT_BLOCK
+        | F::BC7_UNORM_BLOCK
+        | F::BC7_SRGB_BLOCK
+        | F::ETC2_R8G8B8A8_UNORM_BLOCK
+        | F::ETC2_R8G8B8A8_SRGB_BLOCK
+        | F::EAC_R11G11_UNORM_BLOCK
+        | F::EAC_R11G11_SNORM_BLOCK
+        | F::ASTC_4X4_UNORM_BLOCK
+        | F::ASTC_4X4_SRGB_BLOCK => (16, 4),
+        _ => return None,
+    };
+
+    Some((
+        size,
+        vk::Extent2D {
+            width: block,
+            height: block,
+        },
+    ))
+}
+
+/// How to create a [`Buffer`].
+#[derive(Clone, Debug)]
+pub struct BufferInfo {
+    pub name: String,
+    pub size: vk::DeviceSize,
+    pub usage: vk::BufferUsageFlags,
+    pub memory: MemoryUsage,
+    /// The queue families sharing the buffer concurrently, exclusive sharing when empty.
+    pub concurrent_queue_families: Vec<u32>,
+}
+
+impl BufferInfo {
+    pub fn new(size: vk::DeviceSize, usage: vk::BufferUsageFlags, memory: MemoryUsage) -> Self {
+        Self {
+            name: String::new(),
+            size,
+            usage,
+            memory,
+            concurrent_queue_families: vec![],
+        }
+    }
+
+    /// The name of the allocation, shown in leak reports.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn concurrent(mut self, queue_families: &[u32]) -> Self {
+        self.concurrent_queue_families = queue_families.to_vec();
+        self
+    }
+}
+
+/// A buffer and its memory. Host visible buffers are persistently mapped.
+///
+/// Dropping the buffer defers its destruction until the GPU is done with it, see [`Buffer::mark_used`].
+pub struct Buffer {
+    vulkan: Arc<VulkanContext>,
+    handle: vk::Buffer,
+    allocation: Option<Allocation>,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    memory: MemoryUsage,
+    sharing_mode: vk::SharingMode,
+    last_use: LastUse,
+}
+
+impl Buffer {
+    pub fn new(vulkan: Arc<VulkanContext>, info: &BufferInfo) -> Result<Self, MemoryError> {
+        let create_info = vk::BufferCreateInfo::default()
+            .size(info.size)
+            .usage(info.usage);
+        let sharing_mode = if info.concurrent_queue_families.is_empty() {
+            vk::SharingMode::EXCLUSIVE
+        } else {
+            vk::SharingMode::CONCURRENT
+        };
+        let create_info = create_info
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(&info.concurrent_queue_families);
+
+        let device = vulkan.device();
+        let handle = unsafe { device.create_buffer(&create_info, None) }?;
+        let requirements = unsafe { device.get_buffer_memory_requirements(handle) };
+
+        let allocation = match vulkan
+            .memory()
+            .allocate(&info.name, requirements, info.memory, true)
+        {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_buffer(handle, None) };
+                return Err(e);
+            }
+        };
+
+        if let Err(e) =
+            unsafe { device.bind_buffer_memory(handle, allocation.memory(), allocation.offset()) }
+        {
+            vulkan.memory().free(allocation);
+            unsafe { device.destroy_buffer(handle, None) };
+            return Err(e.into());
+        }
+
+        Ok(Self {
+            vulkan,
+            handle,
+            allocation: Some(allocation),
+            size: info.size,
+            usage: info.usage,
+            memory: info.memory,
+            sharing_mode,
+            last_use: LastUse::default(),
+        })
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.handle
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory
+    }
+
+    pub fn sharing_mode(&self) -> vk::SharingMode {
+        self.sharing_mode
+    }
+
+    /// The GPU address of the buffer, fails unless [`DeviceFeature::BufferDeviceAddress`] is enabled and the buffer
+    /// has [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`] usage.
+    pub fn device_address(&self) -> Result<vk::DeviceAddress, MemoryError> {
+        self.vulkan.device().require_feature(DeviceFeature::BufferDeviceAddress)?;
+        if !self.usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            return Err(MemoryError::MissingUsage(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS));
+        }
+
+        Ok(unsafe {
+            self.vulkan
+                .device()
+                .get_buffer_device_address(&vk::BufferDeviceAddressInfo::default().buffer(self.handle))
+        })
+    }
+
+    /// The mapped memory of the buffer, `None` for [`MemoryUsage::GpuOnly`] buffers. Reads may need
+    /// [`Buffer::invalidate`] first.
+    pub fn mapped_slice(&self) -> Option<&[u8]> {
+        self.allocation
+            .as_ref()
+            .and_then(Allocation::mapped_slice)
+            .map(|slice| &slice[..self.size as usize])
+    }
+
+    /// The mapped memory of the buffer, `None` for [`MemoryUsage::GpuOnly`] buffers. Writes may need
+    /// [`Buffer::flush`] afterwards.
+    pub fn mapped_slice_mut(&mut self) -> Option<&mut [u8]> {
+        let size = self.size as usize;
+        self.allocation
+            .as_mut()
+            .and_then(Allocation::mapped_slice_mut)
+            .map(|slice| &mut slice[..size])
+    }
+
+    fn check_range(&self, offset: vk::DeviceSize, size: usize) -> Result<(), MemoryError> {
+        if offset.saturating_add(size as u64) > self.size {
+            Err(MemoryError::OutOfBounds {
+                offset,
+                size: size as u64,
+                capacity: self.size,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Copies `data` into the mapped buffer at `offset` and flushes it if the memory is not host coherent.
+    pub fn write(&mut self, offset: vk::DeviceSize, data: &[u8]) -> Result<(), MemoryError> {
+        self.check_range(offset, data.len())?;
+        let mapped = self.mapped_slice_mut().ok_or(MemoryError::NotMapped)?;
+        mapped[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+        self.flush(offset, data.len() as u64)
+    }
+
+    /// Like [`Buffer::write`] for plain data types.
+    pub fn write_pod<T: bytemuck::Pod>(
+        &mut self,
+        offset: vk::DeviceSize,
+        data: &[T],
+    ) -> Result<(), MemoryError> {
+        self.write(offset, bytemuck::cast_slice(data))
+    }
+
+    /// Invalidates the mapped buffer if the memory is not host coherent and copies `out.len()` bytes at `offset`.
+    pub fn read(&self, offset: vk::DeviceSize, out: &mut [u8]) -> Result<(), MemoryError> {
+        self.check_range(offset, out.len())?;
+        self.invalidate(offset, out.len() as u64)?;
+        let mapped = self.mapped_slice().ok_or(MemoryError::NotMapped)?;
+        out.copy_from_slice(&mapped[offset as usize..offset as usize + out.len()]);
+        Ok(())
+    }
+
+    /// Makes host writes to the range visible to the device, a no-op on host coherent memory.
+    pub fn flush(&self, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<(), MemoryError> {
+        match &self.allocation {
+            Some(allocation) => Ok(self.vulkan.memory().flush(allocation, offset, size)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes device writes to the range visible to the host, a no-op on host coherent memory.
+    pub fn invalidate(
+        &self,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<(), MemoryError> {
+        match &self.allocation {
+            Some(allocation) => Ok(self.vulkan.memory().invalidate(allocation, offset, size)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Records that the GPU uses the buffer until `semaphore` reaches `value`. Once dropped, the buffer is destroyed
+    /// after every recorded value is reached instead of after the current frame.
+    pub fn mark_used(&self, semaphore: vk::Semaphore, value: u64) {
+        self.last_use.mark(semaphore, value);
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if let Some(allocation) = self.allocation.take() {
+            let buffer = self.handle;
+            let deletion_queue = self.vulkan.deletion_queue();
+            deletion_queue.defer(self.last_use.take_key(deletion_queue), move |vulkan| {
+                unsafe { vulkan.device().destroy_buffer(buffer, None) };
+                vulkan.memory().free(allocation);
+            });
+        }
+    }
+}
+
+impl Debug for Buffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Buffer")
+            .field("handle", &self.handle)
+            .field("size", &self.size)
+            .field("usage", &self.usage)
+            .field("memory", &self.memory)
+            .finish()
+    }
+}
+
+/// How to create an [`Image`].
+#[derive(Clone, Debug)]
+pub struct ImageInfo {
+    pub name: String,
+    pub image_type: vk::ImageType,
+    pub format: vk::Format,
+    pub extent: vk::Extent3D,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub samples: vk::SampleCountFlags,
+    pub tiling: vk::ImageTiling,
+    pub usage: vk::ImageUsageFlags,
+    pub flags: vk::ImageCreateFlags,
+    pub memory: MemoryUsage,
+    /// The queue families sharing the image concurrently, exclusive sharing when empty.
+    pub concurrent_queue_families: Vec<u32>,
+}
+
+impl ImageInfo {
+    /// A single-sampled, optimally tiled 2D image with one mip level and one array layer in GPU-only memory.
+    pub fn new_2d(format: vk::Format, extent: vk::Extent2D, usage: vk::ImageUsageFlags) -> Self {
+        Self {
+            name: String::new(),
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            extent: extent.into(),
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage,
+            flags: vk::ImageCreateFlags::empty(),
+            memory: MemoryUsage::GpuOnly,
+            concurrent_queue_families: vec![],
+        }
+    }
+
+    /// The name of the allocation, shown in leak reports.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    pub fn array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn memory(mut self, memory: MemoryUsage) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn concurrent(mut self, queue_families: &[u32]) -> Self {
+        self.concurrent_queue_families = queue_families.to_vec();
+        self
+    }
+}
+
+/// An image and its memory, created in [`vk::ImageLayout::UNDEFINED`].
+///
+/// Dropping the image defers its destruction until the GPU is done with it, see [`Image::mark_used`].
+pub struct Image {
+    vulkan: Arc<VulkanContext>,
+    handle: vk::Image,
+    allocation: Option<Allocation>,
+    format: vk::Format,
+    extent: vk::Extent3D,
+    mip_levels: u32,
+    array_layers: u32,
+    usage: vk::ImageUsageFlags,
+    sharing_mode: vk::SharingMode,
+    last_use: LastUse,
+}
+
+impl Image {
+    pub fn new(vulkan: Arc<VulkanContext>, info: &ImageInfo) -> Result<Self, MemoryError> {
+        let create_info = vk::ImageCreateInfo::default()
+            .flags(info.flags)
+            .image_type(info.image_type)
+            .format(info.format)
+            .extent(info.extent)
+            .mip_levels(info.mip_levels)
+            .array_layers(info.array_layers)
+            .samples(info.samples)
+            .tiling(info.tiling)
+            .usage(info.usage)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let sharing_mode = if info.concurrent_queue_families.is_empty() {
+            vk::SharingMode::EXCLUSIVE
+        } else {
+            vk::SharingMode::CONCURRENT
+        };
+        let create_info = create_info
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(&info.concurrent_queue_families);
+
+        let device = vulkan.device();
+        let handle = unsafe { device.create_image(&create_info, None) }?;
+        let requirements = unsafe { device.get_image_memory_requirements(handle) };
+
+        let allocation = match vulkan.memory().allocate(
+            &info.name,
+            requirements,
+            info.memory,
+            info.tiling == vk::ImageTiling::LINEAR,
+        ) {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_image(handle, None) };
+                return Err(e);
+            }
+        };
+
+        if let Err(e) =
+            unsafe { device.bind_image_memory(handle, allocation.memory(), allocation.offset()) }
+        {
+            vulkan.memory().free(allocation);
+            unsafe { device.destroy_image(handle, None) };
+            return Err(e.into());
+        }
+
+        Ok(Self {
+            vulkan,
+            handle,
+            allocation: Some(allocation),
+            format: info.format,
+            extent: info.extent,
+            mip_levels: info.mip_levels,
+            array_layers: info.array_layers,
+            usage: info.usage,
+            sharing_mode,
+            last_use: LastUse::default(),
+        })
+    }
+
+    pub fn handle(&self) -> vk::Image {
+        self.handle
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn extent(&self) -> vk::Extent3D {
+        self.extent
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    pub fn array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+
+    pub fn sharing_mode(&self) -> vk::SharingMode {
+        self.sharing_mode
+    }
+
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        format_aspect_mask(self.format)
+    }
+
+    /// Records that the GPU uses the image until `semaphore` reaches `value`. Once dropped, the image is destroyed
+    /// after every recorded value is reached instead of after the current frame.
+    pub fn mark_used(&self, semaphore: vk::Semaphore, value: u64) {
+        self.last_use.mark(semaphore, value);
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        if let Some(allocation) = self.allocation.take() {
+            let image = self.handle;
+            let deletion_queue = self.vulkan.deletion_queue();
+            deletion_queue.defer(self.last_use.take_key(deletion_queue), move |vulkan| {
+                unsafe { vulkan.device().destroy_image(image, None) };
+                vulkan.memory().free(allocation);
+            });
+        }
+    }
+}
+
+impl Debug for Image {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("handle", &self.handle)
+            .field("format", &self.format)
+            .field("extent", &self.extent)
+            .field("mip_levels", &self.mip_levels)
+            .field("array_layers", &self.array_layers)
+            .field("usage", &self.usage)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atom_alignment() {
+        assert_eq!(align_up(0, 64), 0);
+        assert_eq!(align_up(1, 64), 64);
+        assert_eq!(align_up(64, 64), 64);
+        assert_eq!(align_up(65, 64), 128);
+        assert_eq!(align_down(127, 64), 64);
+        assert_eq!(align_down(128, 64), 128);
+    }
+
+    #[test]
+    fn aspect_masks() {
+        assert_eq!(
+            format_aspect_mask(vk::Format::R8G8B8A8_UNORM),
+            vk::ImageAspectFlags::COLOR
+        );
+        assert_eq!(
+            format_aspect_mask(vk::Format::D32_SFLOAT),
+            vk::ImageAspectFlags::DEPTH
+        );
+        assert_eq!(
+            format_aspect_mask(vk::Format::D24_UNORM_S8_UINT),
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        );
+    }
+
+    #[test]
+    fn texel_blocks() {
+        let texel = vk::Extent2D {
+            width: 1,
+            height: 1,
+        };
+        let block = vk::Extent2D {
+            width: 4,
+            height: 4,
+        };
+
+        assert_eq!(format_texel_block(vk::Format::R8G8B8A8_SRGB), Some((4, texel)));
+        assert_eq!(format_texel_block(vk::Format::R32G32B32_SFLOAT), Some((12, texel)));
+        assert_eq!(format_texel_block(vk::Format::D24_UNORM_S8_UINT), Some((4, texel)));
+        assert_eq!(format_texel_block(vk::Format::BC1_RGB_UNORM_BLOCK), Some((8, block)));
+        assert_eq!(format_texel_block(vk::Format::BC7_SRGB_BLOCK), Some((16, block)));
+        assert_eq!(format_texel_block(vk::Format::UNDEFINED), None);
+    }
+}
@@ -1,4 +1,10 @@
+pub mod barrier;
 pub mod context;
 pub mod frame_set;
+pub mod graph;
+pub mod handoff;
+pub mod image_state;
+pub mod memory;
+pub mod upload;
 pub mod window;
-mod command_recorder;
+pub mod command_recorder;
@@ -0,0 +1,677 @@
+use crate::errors::{MemoryError, UploadError};
+use crate::render::barrier::{Access, BufferBarrier, ImageBarrier};
+use crate::render::command_recorder::{
+    AutoSubmitInfo, CommandBufferExt, CommandBufferSyncInfo, CommandRecorder,
+};
+use crate::render::context::VulkanContext;
+use crate::render::context::command_pool::CommandBufferRecycler;
+use crate::render::context::device::LazyQueue;
+use crate::render::context::queue::Queue;
+use crate::render::context::queues::{QueueLabel, QueueRef};
+use crate::render::context::submission::SubmissionHandle;
+use crate::render::image_state::ImageUsage;
+use crate::render::memory::{Buffer, BufferInfo, Image, MemoryUsage, align_up, format_texel_block};
+use ash::prelude::VkResult;
+use ash::vk;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The size of the staging buffers uploads are packed into, larger uploads get a dedicated staging buffer.
+pub const DEFAULT_STAGING_CHUNK_SIZE: vk::DeviceSize = 16 * 1024 * 1024;
+
+/// Staging offsets are aligned to this, and to the texel size of image uploads.
+const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+/// The submissions of an upload batch: the copies on the upload queue and the ownership acquisitions on the consuming
+/// queues.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UploadHandle {
+    submissions: Vec<SubmissionHandle>,
+}
+
+impl UploadHandle {
+    pub fn submissions(&self) -> &[SubmissionHandle] {
+        &self.submissions
+    }
+
+    /// The submission a consumer on `queue` waits on before using the uploaded resources: the ownership acquisition
+    /// on that queue if there is one, the copies otherwise. `None` for an empty batch.
+    pub fn submission_for(&self, queue: QueueRef) -> Option<SubmissionHandle> {
+        self.submissions
+            .iter()
+            .find(|submission| submission.queue() == queue)
+            .or(self.submissions.first())
+            .copied()
+    }
+
+    /// Returns whether every submission of the batch completed, without blocking.
+    pub fn is_complete(&self, vulkan: &VulkanContext) -> VkResult<bool> {
+        for submission in &self.submissions {
+            if !submission.is_complete(vulkan)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Blocks until every submission of the batch completed.
+    pub fn wait(&self, vulkan: &VulkanContext) -> VkResult<()> {
+        SubmissionHandle::wait_all(vulkan, &self.submissions)
+    }
+}
+
+struct StagingChunk {
+    buffer: Buffer,
+    cursor: vk::DeviceSize,
+    /// The batch using the chunk, the chunk is only written again once it completed.
+    in_flight: Option<SubmissionHandle>,
+    /// Created for a single upload larger than the chunk size, dropped once its batch completed.
+    dedicated: bool,
+}
+
+/// Where an upload is consumed after the copy.
+#[derive(Copy, Clone, Debug)]
+struct Consumer {
+    queue: QueueRef,
+    access: Access,
+    /// Whether the resource is exclusive to a queue family, which needs an ownership transfer between families.
+    exclusive: bool,
+}
+
+enum PendingCopy {
+    Buffer {
+        staging: vk::Buffer,
+        region: vk::BufferCopy,
+        dst: vk::Buffer,
+        consumer: Consumer,
+    },
+    Image {
+        staging: vk::Buffer,
+        region: vk::BufferImageCopy,
+        dst: vk::Image,
+        range: vk::ImageSubresourceRange,
+        layout: vk::ImageLayout,
+        consumer: Consumer,
+    },
+}
+
+/// Copies host data into buffers and images through pooled staging memory.
+///
+/// Uploads are batched until [`UploadManager::flush`], which submits every copy on the
+/// [`QueueLabel::Transfer`] queue, or on the graphics queue on devices without a dedicated transfer family. Exclusive
+/// resources consumed by another queue family are released after the copy and acquired on the consuming queue, the
+/// returned [`UploadHandle`] completes once they are ready to use there.
+///
+/// Destination resources must stay alive until the batch completes, for example by marking them used with the
+/// submissions of the handle.
+pub struct UploadManager {
+    vulkan: Arc<VulkanContext>,
+    queue: Arc<Queue>,
+    chunk_size: vk::DeviceSize,
+    chunks: Vec<StagingChunk>,
+    command_buffers: CommandBufferRecycler,
+    copies: Vec<PendingCopy>,
+}
+
+impl UploadManager {
+    pub fn new(vulkan: Arc<VulkanContext>) -> Result<Self, UploadError> {
+        Self::with_chunk_size(vulkan, DEFAULT_STAGING_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(
+        vulkan: Arc<VulkanContext>,
+        chunk_size: vk::DeviceSize,
+    ) -> Result<Self, UploadError> {
+        let device = vulkan.device();
+        let queue = device
+            .get_labeled_queue(QueueLabel::Transfer)
+            .or_else(|| device.get_labeled_queue(QueueLabel::Graphics))
+            .ok_or(UploadError::MissingQueue(QueueLabel::Transfer))?;
+
+        debug!(
+            "[upload] Uploading on queue family {}, queue {}",
+            queue.family(),
+            queue.index()
+        );
+
+        Ok(Self {
+            command_buffers: CommandBufferRecycler::new(vulkan.clone()),
+            vulkan,
+            queue,
+            chunk_size,
+            chunks: vec![],
+            copies: vec![],
+        })
+    }
+
+    /// The queue the copies are submitted on.
+    pub fn queue(&self) -> QueueRef {
+        self.queue.queue_ref()
+    }
+
+    /// The number of copies waiting for [`UploadManager::flush`].
+    pub fn pending_copies(&self) -> usize {
+        self.copies.len()
+    }
+
+    /// The bytes of staging memory currently allocated.
+    pub fn staging_size(&self) -> vk::DeviceSize {
+        self.chunks.iter().map(|chunk| chunk.buffer.size()).sum()
+    }
+
+    fn consumer(
+        &self,
+        label: QueueLabel,
+        access: Access,
+        exclusive: bool,
+    ) -> Result<Consumer, UploadError> {
+        let queue = self
+            .vulkan
+            .device()
+            .get_labeled_queue_ref(label)
+            .ok_or(UploadError::MissingQueue(label))?;
+
+        Ok(Consumer {
+            queue,
+            access,
+            exclusive,
+        })
+    }
+
+    /// Copies `data` into staging memory, returns the staging buffer and the offset of the data.
+    fn stage(
+        &mut self,
+        data: &[u8],
+        alignment: vk::DeviceSize,
+    ) -> Result<(vk::Buffer, vk::DeviceSize), UploadError> {
+        let size = data.len() as vk::DeviceSize;
+
+        let fits = |chunk: &StagingChunk| {
+            !chunk.dedicated
+                && chunk.in_flight.is_none()
+                && align_up(chunk.cursor, alignment) + size <= chunk.buffer.size()
+        };
+
+        let mut index = self.chunks.iter().position(fits);
+        if index.is_none() {
+            self.reclaim()?;
+            index = self.chunks.iter().position(fits);
+        }
+
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let dedicated = size > self.chunk_size;
+                let buffer = Buffer::new(
+                    self.vulkan.clone(),
+                    &BufferInfo::new(
+                        if dedicated { size } else { self.chunk_size },
+                        vk::BufferUsageFlags::TRANSFER_SRC,
+                        MemoryUsage::Upload,
+                    )
+                    .name("upload staging"),
+                )?;
+
+                self.chunks.push(StagingChunk {
+                    buffer,
+                    cursor: 0,
+                    in_flight: None,
+                    dedicated,
+                });
+                self.chunks.len() - 1
+            }
+        };
+
+        let chunk = &mut self.chunks[index];
+        let offset = align_up(chunk.cursor, alignment);
+        chunk.buffer.write(offset, data)?;
+        chunk.cursor = offset + size;
+
+        Ok((chunk.buffer.handle(), offset))
+    }
+
+    /// Queues a copy of `data` into `dst` at `offset`, consumed with `access` on the `consumer` queue.
+    pub fn upload_buffer(
+        &mut self,
+        dst: &Buffer,
+        offset: vk::DeviceSize,
+        data: &[u8],
+        consumer: QueueLabel,
+        access: Access,
+    ) -> Result<(), UploadError> {
+        if offset.saturating_add(data.len() as u64) > dst.size() {
+            return Err(MemoryError::OutOfBounds {
+                offset,
+                size: data.len() as u64,
+                capacity: dst.size(),
+            }
+            .into());
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let consumer = self.consumer(
+            consumer,
+            access,
+            dst.sharing_mode() == vk::SharingMode::EXCLUSIVE,
+        )?;
+        let (staging, staging_offset) = self.stage(data, STAGING_ALIGNMENT)?;
+
+        self.copies.push(PendingCopy::Buffer {
+            staging,
+            region: vk::BufferCopy {
+                src_offset: staging_offset,
+                dst_offset: offset,
+                size: data.len() as u64,
+            },
+            dst: dst.handle(),
+            consumer,
+        });
+
+        Ok(())
+    }
+
+    /// Like [`UploadManager::upload_buffer`] for plain data types.
+    pub fn upload_buffer_pod<T: bytemuck::Pod>(
+        &mut self,
+        dst: &Buffer,
+        offset: vk::DeviceSize,
+        data: &[T],
+        consumer: QueueLabel,
+        access: Access,
+    ) -> Result<(), UploadError> {
+        self.upload_buffer(dst, offset, bytemuck::cast_slice(data), consumer, access)
+    }
+
+    /// Queues a copy of `data` into every array layer of the mip level `mip_level` of `dst`, after which the image is
+    /// in `usage` on the `consumer` queue. `data` is tightly packed, layer after layer, and the previous contents of
+    /// the mip level are discarded.
+    ///
+    /// `data` must cover the whole mip level, and a mip level can only be uploaded once per batch.
+    pub fn upload_image(
+        &mut self,
+        dst: &Image,
+        mip_level: u32,
+        data: &[u8],
+        consumer: QueueLabel,
+        usage: ImageUsage,
+    ) -> Result<(), UploadError> {
+        if mip_level >= dst.mip_levels() {
+            return Err(UploadError::InvalidMipLevel {
+                mip_level,
+                mip_levels: dst.mip_levels(),
+            });
+        }
+
+        let extent = dst.extent();
+        let extent = vk::Extent3D {
+            width: (extent.width >> mip_level).max(1),
+            height: (extent.height >> mip_level).max(1),
+            depth: (extent.depth >> mip_level).max(1),
+        };
+
+        let Some((block_size, expected)) =
+            image_data_size(dst.format(), extent, dst.array_layers())
+        else {
+            return Err(UploadError::UnsupportedFormat(dst.format()));
+        };
+        if data.len() as u64 != expected {
+            return Err(UploadError::InvalidDataSize {
+                expected,
+                actual: data.len() as u64,
+            });
+        }
+
+        // the release barrier of the first upload expects the mip level in TRANSFER_DST_OPTIMAL
+        if self.copies.iter().any(|copy| {
+            matches!(copy, PendingCopy::Image { dst: image, range, .. }
+                if *image == dst.handle() && range.base_mip_level == mip_level)
+        }) {
+            return Err(UploadError::DuplicateImageUpload { mip_level });
+        }
+
+        let consumer = self.consumer(
+            consumer,
+            usage.access,
+            dst.sharing_mode() == vk::SharingMode::EXCLUSIVE,
+        )?;
+        let (staging, staging_offset) = self.stage(data, texel_alignment(block_size))?;
+
+        let aspect_mask = dst.aspect_mask();
+        // buffer copies address a single aspect, stencil data of depth/stencil formats needs a separate copy
+        let copy_aspect = if aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            vk::ImageAspectFlags::DEPTH
+        } else {
+            aspect_mask
+        };
+
+        self.copies.push(PendingCopy::Image {
+            staging,
+            region: vk::BufferImageCopy {
+                buffer_offset: staging_offset,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: copy_aspect,
+                    mip_level,
+                    base_array_layer: 0,
+                    layer_count: dst.array_layers(),
+                },
+                image_offset: vk::Offset3D::default(),
+                image_extent: extent,
+            },
+            dst: dst.handle(),
+            range: vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: mip_level,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: dst.array_layers(),
+            },
+            layout: usage.layout,
+            consumer,
+        });
+
+        Ok(())
+    }
+
+    /// Submits the queued copies and the ownership transfers to their consumers.
+    pub fn flush(&mut self) -> Result<UploadHandle, UploadError> {
+        if self.copies.is_empty() {
+            return Ok(UploadHandle::default());
+        }
+
+        self.reclaim()?;
+
+        let family = self.queue.family();
+        let copies = std::mem::take(&mut self.copies);
+
+        // ownership transfers, grouped by the consuming queue
+        let mut acquires: HashMap<QueueRef, (Vec<BufferBarrier>, Vec<ImageBarrier>)> =
+            HashMap::new();
+        let mut buffer_releases = vec![];
+        let mut image_releases = vec![];
+        let mut image_transitions = vec![];
+
+        for copy in &copies {
+            match copy {
+                PendingCopy::Buffer {
+                    region,
+                    dst,
+                    consumer,
+                    ..
+                } => {
+                    let barrier = BufferBarrier::new(*dst, Access::TRANSFER_WRITE, Access::NONE)
+                        .range(region.dst_offset, region.size);
+
+                    if consumer.queue.family == family {
+                        buffer_releases.push(BufferBarrier {
+                            dst: consumer.access,
+                            ..barrier
+                        });
+                    } else if consumer.exclusive {
+                        let release = barrier.queue_family_transfer(family, consumer.queue.family);
+                        buffer_releases.push(release);
+                        acquires
+                            .entry(consumer.queue)
+                            .or_default()
+                            .0
+                            .push(BufferBarrier {
+                                src: consumer.access.wait_scope(),
+                                dst: consumer.access,
+                                ..release
+                            });
+                    } else {
+                        // concurrent resources only need the consumer to wait on the copies
+                        buffer_releases.push(barrier);
+                    }
+                }
+                PendingCopy::Image {
+                    dst,
+                    range,
+                    layout,
+                    consumer,
+                    ..
+                } => {
+                    image_transitions.push(
+                        ImageBarrier::new(
+                            *dst,
+                            vk::ImageLayout::UNDEFINED,
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        )
+                        .dst(Access::TRANSFER_WRITE)
+                        .subresource_range(*range),
+                    );
+
+                    let barrier =
+                        ImageBarrier::new(*dst, vk::ImageLayout::TRANSFER_DST_OPTIMAL, *layout)
+                            .src(Access::TRANSFER_WRITE)
+                            .subresource_range(*range);
+
+                    if consumer.queue.family == family {
+                        image_releases.push(barrier.dst(consumer.access));
+                    } else if consumer.exclusive {
+                        let release = barrier.queue_family_transfer(family, consumer.queue.family);
+                        image_releases.push(release);
+                        acquires.entry(consumer.queue).or_default().1.push(
+                            release
+                                .src(consumer.access.wait_scope())
+                                .dst(consumer.access),
+                        );
+                    } else {
+                        image_releases.push(barrier);
+                    }
+                }
+            }
+        }
+
+        let command_buffer = self.command_buffers.allocate(family)?;
+        let mut recorder = command_buffer.begin_auto_submit(
+            self.vulkan.clone(),
+            true,
+            AutoSubmitInfo::new(
+                LazyQueue::Ref(self.queue.queue_ref()),
+                CommandBufferSyncInfo::new(),
+            ),
+        )?;
+
+        record_copies(
+            &mut recorder,
+            &copies,
+            &image_transitions,
+            &buffer_releases,
+            &image_releases,
+        );
+
+        let Some(copy_submission) = recorder.finish()? else {
+            unreachable!("auto submit recorders return their submission");
+        };
+        self.command_buffers
+            .track(family, command_buffer, copy_submission);
+
+        for chunk in self
+            .chunks
+            .iter_mut()
+            .filter(|chunk| chunk.cursor > 0 && chunk.in_flight.is_none())
+        {
+            chunk.in_flight = Some(copy_submission);
+            // a chunk dropped with the manager is only destroyed once the copies reading it completed
+            chunk
+                .buffer
+                .mark_used(copy_submission.semaphore(), copy_submission.value());
+        }
+
+        let mut handle = UploadHandle {
+            submissions: vec![copy_submission],
+        };
+
+        let mut acquires = acquires.into_iter().collect::<Vec<_>>();
+        acquires.sort_by_key(|(queue, _)| (queue.family, queue.index));
+
+        for (queue, (buffer_barriers, image_barriers)) in acquires {
+            let stages = buffer_barriers
+                .iter()
+                .map(|barrier| barrier.src.stages)
+                .chain(image_barriers.iter().map(|barrier| barrier.src.stages))
+                .fold(vk::PipelineStageFlags2::NONE, |stages, s| stages | s);
+
+            let command_buffer = self.command_buffers.allocate(queue.family)?;
+            let mut recorder = command_buffer.begin_auto_submit(
+                self.vulkan.clone(),
+                true,
+                AutoSubmitInfo::new(
+                    LazyQueue::Ref(queue),
+                    // the acquire barriers start at the stages the wait blocks, which orders them after the release
+                    CommandBufferSyncInfo::new().wait_submission(copy_submission, stages),
+                ),
+            )?;
+            recorder.pipeline_barrier(&[], &buffer_barriers, &image_barriers);
+
+            let Some(acquire_submission) = recorder.finish()? else {
+                unreachable!("auto submit recorders return their submission");
+            };
+            self.command_buffers
+                .track(queue.family, command_buffer, acquire_submission);
+            handle.submissions.push(acquire_submission);
+        }
+
+        Ok(handle)
+    }
+
+    /// Recycles the staging chunks and frees the command buffers of completed batches.
+    fn reclaim(&mut self) -> VkResult<()> {
+        self.command_buffers.reclaim()?;
+
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for mut chunk in self.chunks.drain(..) {
+            if let Some(submission) = chunk.in_flight {
+                if !submission.is_complete(&self.vulkan)? {
+                    chunks.push(chunk);
+                    continue;
+                }
+
+                if chunk.dedicated {
+                    continue;
+                }
+
+                chunk.in_flight = None;
+                chunk.cursor = 0;
+            }
+            chunks.push(chunk);
+        }
+        self.chunks = chunks;
+
+        Ok(())
+    }
+}
+
+fn record_copies(
+    recorder: &mut CommandRecorder,
+    copies: &[PendingCopy],
+    image_transitions: &[ImageBarrier],
+    buffer_releases: &[BufferBarrier],
+    image_releases: &[ImageBarrier],
+) {
+    if !image_transitions.is_empty() {
+        recorder.pipeline_barrier(&[], &[], image_transitions);
+    }
+
+    for copy in copies {
+        match copy {
+            PendingCopy::Buffer {
+                staging,
+                region,
+                dst,
+                ..
+            } => {
+                recorder.copy_buffer(*staging, *dst, &[*region]);
+            }
+            PendingCopy::Image {
+                staging,
+                region,
+                dst,
+                ..
+            } => {
+                recorder.copy_buffer_to_image(
+                    *staging,
+                    *dst,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[*region],
+                );
+            }
+        }
+    }
+
+    recorder.pipeline_barrier(&[], buffer_releases, image_releases);
+}
+
+/// The texel block size of `format` and the bytes of tightly packed data covering `extent` in `array_layers` layers,
+/// `None` for formats of unknown layout.
+fn image_data_size(
+    format: vk::Format,
+    extent: vk::Extent3D,
+    array_layers: u32,
+) -> Option<(u64, u64)> {
+    let (block_size, block) = format_texel_block(format)?;
+    let blocks = extent.width.div_ceil(block.width) as u64
+        * extent.height.div_ceil(block.height) as u64
+        * extent.depth as u64
+        * array_layers as u64;
+    Some((block_size, blocks * block_size))
+}
+
+/// The staging alignment of an image upload: buffer offsets of image copies must be a multiple of the texel block
+/// size.
+fn texel_alignment(block_size: u64) -> u64 {
+    STAGING_ALIGNMENT / gcd(STAGING_ALIGNMENT, block_size) * block_size
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texel_alignments() {
+        // R8
+        assert_eq!(texel_alignment(1), 16);
+        // RGBA8
+        assert_eq!(texel_alignment(4), 16);
+        // RGBA32F
+        assert_eq!(texel_alignment(16), 16);
+        // RGB32F
+        assert_eq!(texel_alignment(12), 48);
+        // RGB16
+        assert_eq!(texel_alignment(6), 48);
+        // BC1
+        assert_eq!(texel_alignment(8), 16);
+    }
+
+    #[test]
+    fn image_data_sizes() {
+        let extent = vk::Extent3D {
+            width: 30,
+            height: 10,
+            depth: 1,
+        };
+
+        assert_eq!(
+            image_data_size(vk::Format::R8G8B8A8_UNORM, extent, 6),
+            Some((4, 30 * 10 * 6 * 4))
+        );
+        // partial blocks at the edges are stored whole
+        assert_eq!(
+            image_data_size(vk::Format::BC7_UNORM_BLOCK, extent, 1),
+            Some((16, 8 * 3 * 16))
+        );
+        assert_eq!(image_data_size(vk::Format::UNDEFINED, extent, 1), None);
+    }
+}
@@ -1,10 +1,13 @@
-use crate::errors::CreateWindowError;
+use crate::errors::{CreateWindowError, MemoryError};
+use crate::render::context::deletion_queue::FrameSource;
 use crate::render::context::queues::{QueueLabel, QueueRef};
-use crate::render::frame_set::{FrameSet, MAX_FRAMES_IN_FLIGHT};
+use crate::render::frame_set::{FrameSet, FramesInFlight};
+use crate::render::memory::{Image, ImageInfo, format_aspect_mask};
 use crate::{Engine, VulkanContext};
 use ash::prelude::VkResult;
 use ash::vk;
-use log::{trace, warn};
+use log::{error, trace, warn};
+use std::any::Any;
 use std::sync::Arc;
 use winit::window::Window;
 
@@ -13,36 +16,239 @@ pub struct WindowData {
     vulkan_context: Arc<VulkanContext>,
     surface: vk::SurfaceKHR,
     swapchain: vk::SwapchainKHR,
+    /// Swapchains replaced by a reconfigure and the deletion queue frame they were retired in. Frames in flight may
+    /// still render to or present their images, they are destroyed once this window completed that frame, and always
+    /// before the surface.
+    retired_swapchains: Vec<(u64, vk::SwapchainKHR)>,
+    swapchain_preferences: SwapchainPreferences,
+    /// Set when the swapchain no longer matches the window or its preferences.
+    needs_reconfigure: bool,
+    /// The new extent of the swapchain, until the application was notified of it.
+    resized: Option<vk::Extent2D>,
     swapchain_configuration: SwapchainConfiguration,
     swapchain_resources: SwapchainResources,
     swapchain_sync_resources: SwapchainSyncResources,
+    image_data_factory: Option<ImageDataFactory>,
     current_frame: usize,
+    frame_source: FrameSource,
+    /// The deletion queue frame each frame in flight was last submitted in.
+    submitted_frames: FrameSet<u64>,
+}
+
+/// What a window would like its swapchain to be, every preference falls back on something the surface supports.
+///
+/// Candidates are ranked, the first one the surface supports is selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapchainPreferences {
+    present_modes: Vec<vk::PresentModeKHR>,
+    surface_formats: Vec<vk::SurfaceFormatKHR>,
+    image_count: Option<u32>,
+    extra_usage: vk::ImageUsageFlags,
+    composite_alpha: Vec<vk::CompositeAlphaFlagsKHR>,
+    depth_formats: Vec<vk::Format>,
+    samples: vk::SampleCountFlags,
+}
+
+impl Default for SwapchainPreferences {
+    /// MAILBOX then FIFO, `B8G8R8A8_SRGB` with the sRGB color space, one image over the minimum and an opaque surface.
+    fn default() -> Self {
+        Self {
+            present_modes: vec![vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+            surface_formats: vec![vk::SurfaceFormatKHR {
+                format: vk::Format::B8G8R8A8_SRGB,
+                color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            }],
+            image_count: None,
+            extra_usage: vk::ImageUsageFlags::empty(),
+            composite_alpha: vec![vk::CompositeAlphaFlagsKHR::OPAQUE],
+            depth_formats: vec![],
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+}
+
+impl SwapchainPreferences {
+    /// The ranked present modes, FIFO is used when none is supported.
+    pub fn present_modes(mut self, present_modes: &[vk::PresentModeKHR]) -> Self {
+        self.present_modes = present_modes.to_vec();
+        self
+    }
+
+    /// The ranked format and color space pairs, the first surface format is used when none is supported.
+    pub fn surface_formats(mut self, surface_formats: &[vk::SurfaceFormatKHR]) -> Self {
+        self.surface_formats = surface_formats.to_vec();
+        self
+    }
+
+    /// The desired number of swapchain images, clamped to what the surface supports. Defaults to one over the minimum.
+    pub fn image_count(mut self, image_count: u32) -> Self {
+        self.image_count = Some(image_count);
+        self
+    }
+
+    /// Usage on top of `COLOR_ATTACHMENT | TRANSFER_DST`, such as `STORAGE`. Unsupported flags are dropped.
+    pub fn extra_usage(mut self, extra_usage: vk::ImageUsageFlags) -> Self {
+        self.extra_usage = extra_usage;
+        self
+    }
+
+    /// The ranked composite alpha modes, such as `PRE_MULTIPLIED` for transparent windows. The first supported mode
+    /// is used when none is supported.
+    pub fn composite_alpha(mut self, composite_alpha: &[vk::CompositeAlphaFlagsKHR]) -> Self {
+        self.composite_alpha = composite_alpha.to_vec();
+        self
+    }
+
+    /// The ranked formats of a depth attachment created with the swapchain, none is created when empty or when no
+    /// format supports depth attachments.
+    pub fn depth_formats(mut self, depth_formats: &[vk::Format]) -> Self {
+        self.depth_formats = depth_formats.to_vec();
+        self
+    }
+
+    /// The sample count of the attachments, a multisampled color attachment is created with the swapchain above one
+    /// sample. Lowered to the highest count the device supports.
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn select_present_mode(&self, available: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+        self.present_modes
+            .iter()
+            .copied()
+            .find(|mode| available.contains(mode))
+            .unwrap_or(vk::PresentModeKHR::FIFO)
+    }
+
+    /// Returns `None` when the surface reports no format at all.
+    pub fn select_surface_format(&self, available: &[vk::SurfaceFormatKHR]) -> Option<vk::SurfaceFormatKHR> {
+        self.surface_formats
+            .iter()
+            .copied()
+            .find(|format| available.contains(format))
+            .or(available.first().copied())
+    }
+
+    pub fn select_image_count(&self, capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+        let image_count = self
+            .image_count
+            .unwrap_or(capabilities.min_image_count + 1)
+            .max(capabilities.min_image_count);
+
+        // a maximum of 0 means there is no limit
+        if capabilities.max_image_count > 0 {
+            image_count.min(capabilities.max_image_count)
+        } else {
+            image_count
+        }
+    }
+
+    pub fn select_usage(&self, supported: vk::ImageUsageFlags) -> vk::ImageUsageFlags {
+        let unsupported = self.extra_usage & !supported;
+        if !unsupported.is_empty() {
+            warn!("[swapchain/configuration] Unsupported swapchain usage: {:?}", unsupported);
+        }
+
+        vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | (self.extra_usage & supported)
+    }
+
+    /// The first depth format `supported` accepts.
+    pub fn select_depth_format(&self, supported: impl Fn(vk::Format) -> bool) -> Option<vk::Format> {
+        self.depth_formats.iter().copied().find(|format| supported(*format))
+    }
+
+    pub fn select_samples(&self, supported: vk::SampleCountFlags) -> vk::SampleCountFlags {
+        // the highest requested count, then every lower count until one is supported
+        let mut samples = match self.samples.as_raw() {
+            0 => 1,
+            raw => 1 << raw.ilog2(),
+        };
+        while samples > 1 && !supported.contains(vk::SampleCountFlags::from_raw(samples)) {
+            samples >>= 1;
+        }
+
+        vk::SampleCountFlags::from_raw(samples)
+    }
+
+    pub fn select_composite_alpha(&self, supported: vk::CompositeAlphaFlagsKHR) -> vk::CompositeAlphaFlagsKHR {
+        self.composite_alpha
+            .iter()
+            .copied()
+            .find(|alpha| supported.contains(*alpha))
+            .unwrap_or_else(|| {
+                [
+                    vk::CompositeAlphaFlagsKHR::OPAQUE,
+                    vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+                    vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+                    vk::CompositeAlphaFlagsKHR::INHERIT,
+                ]
+                .into_iter()
+                .find(|alpha| supported.contains(*alpha))
+                .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            })
+    }
 }
 
 pub struct SwapchainConfiguration {
     format: vk::Format,
     color_space: vk::ColorSpaceKHR,
     extent: vk::Extent2D,
+    present_mode: vk::PresentModeKHR,
+    image_count: u32,
+    usage: vk::ImageUsageFlags,
+    composite_alpha: vk::CompositeAlphaFlagsKHR,
+    depth_format: Option<vk::Format>,
+    samples: vk::SampleCountFlags,
 }
 
+/// Everything created along with a swapchain, destroyed once the frames using it completed.
 pub struct SwapchainResources {
+    vulkan: Arc<VulkanContext>,
     images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+    /// Signaled by the rendering of each image and waited on by its presentation. Presenting does not tell when the
+    /// wait is done, so the semaphores belong to the images instead of the frames in flight.
+    present_semaphores: Vec<vk::Semaphore>,
+    depth: Option<SwapchainAttachment>,
+    msaa_color: Option<SwapchainAttachment>,
+    image_data: Vec<Box<dyn Any>>,
+}
+
+/// An attachment shared by every image of a swapchain, such as the depth buffer.
+pub struct SwapchainAttachment {
+    image: Image,
+    view: vk::ImageView,
 }
 
+/// A swapchain image and its resources, given to the factory of [`WindowData::set_image_data`].
+pub struct SwapchainImage<'a> {
+    pub index: usize,
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    pub configuration: &'a SwapchainConfiguration,
+    pub resources: &'a SwapchainResources,
+}
+
+type ImageDataFactory =
+    Box<dyn FnMut(&Arc<VulkanContext>, &SwapchainImage) -> VkResult<Box<dyn Any>>>;
+
 pub struct SwapchainSyncResources {
     image_available: FrameSet<vk::Semaphore>,
-    render_finished: FrameSet<vk::Semaphore>,
     in_flight_fences: FrameSet<vk::Fence>,
 }
 
 pub struct AcquiredImage {
     image: vk::Image,
+    image_view: vk::ImageView,
     image_index: u32,
     image_available_semaphore: vk::Semaphore,
     render_finished_semaphore: vk::Semaphore,
     in_flight_fence: vk::Fence,
     current_frame: usize,
-    present_queue_family: u32,
+    present_queue: QueueRef,
 }
 
 impl AcquiredImage {
@@ -50,6 +256,10 @@ impl AcquiredImage {
         self.image
     }
 
+    pub fn image_view(&self) -> vk::ImageView {
+        self.image_view
+    }
+
     pub fn image_index(&self) -> u32 {
         self.image_index
     }
@@ -58,6 +268,7 @@ impl AcquiredImage {
         self.image_available_semaphore
     }
 
+    /// The semaphore the presentation waits on, it belongs to the image rather than the frame in flight.
     pub fn render_finished_semaphore(&self) -> vk::Semaphore {
         self.render_finished_semaphore
     }
@@ -71,7 +282,12 @@ impl AcquiredImage {
     }
 
     pub fn present_queue_family(&self) -> u32 {
-        self.present_queue_family
+        self.present_queue.family
+    }
+
+    /// The queue the image is presented on, the destination of the handoff after rendering.
+    pub fn present_queue(&self) -> QueueRef {
+        self.present_queue
     }
 }
 
@@ -87,29 +303,223 @@ impl SwapchainConfiguration {
     pub fn extent(&self) -> vk::Extent2D {
         self.extent
     }
+
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        self.present_mode
+    }
+
+    /// The number of images the swapchain was created with.
+    pub fn image_count(&self) -> u32 {
+        self.image_count
+    }
+
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+
+    pub fn composite_alpha(&self) -> vk::CompositeAlphaFlagsKHR {
+        self.composite_alpha
+    }
+
+    /// The format of the depth attachment, if there is one.
+    pub fn depth_format(&self) -> Option<vk::Format> {
+        self.depth_format
+    }
+
+    /// The sample count of the depth and multisampled color attachments.
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
 }
 
 impl SwapchainResources {
+    fn new(
+        vulkan: Arc<VulkanContext>,
+        swapchain: vk::SwapchainKHR,
+        configuration: &SwapchainConfiguration,
+    ) -> VkResult<Self> {
+        let images = unsafe {
+            vulkan
+                .device()
+                .loader()
+                .swapchain()
+                .get_swapchain_images(swapchain)
+        }?;
+
+        // filled in step by step, so that dropping it on failure releases what was created
+        let mut resources = Self {
+            vulkan: vulkan.clone(),
+            images: vec![],
+            image_views: vec![],
+            present_semaphores: vec![],
+            depth: None,
+            msaa_color: None,
+            image_data: vec![],
+        };
+
+        for image in &images {
+            resources.image_views.push(create_view(
+                &vulkan,
+                *image,
+                configuration.format,
+            )?);
+            resources.present_semaphores.push(vulkan.create_semaphore()?);
+        }
+        resources.images = images;
+
+        let extent = configuration.extent;
+        if let Some(depth_format) = configuration.depth_format {
+            resources.depth = Some(SwapchainAttachment::new(
+                vulkan.clone(),
+                &ImageInfo::new_2d(
+                    depth_format,
+                    extent,
+                    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                )
+                .samples(configuration.samples)
+                .name("swapchain depth"),
+            )?);
+        }
+
+        if configuration.samples != vk::SampleCountFlags::TYPE_1 {
+            resources.msaa_color = Some(SwapchainAttachment::new(
+                vulkan.clone(),
+                &ImageInfo::new_2d(
+                    configuration.format,
+                    extent,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT
+                        | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                )
+                .samples(configuration.samples)
+                .name("swapchain msaa color"),
+            )?);
+        }
+
+        Ok(resources)
+    }
+
     pub fn images(&self) -> &Vec<vk::Image> {
         &self.images
     }
+
+    /// A color view of every image, indexed like [`SwapchainResources::images`].
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+
+    pub fn present_semaphores(&self) -> &[vk::Semaphore] {
+        &self.present_semaphores
+    }
+
+    /// The depth attachment, requested through [`SwapchainPreferences::depth_formats`].
+    pub fn depth(&self) -> Option<&SwapchainAttachment> {
+        self.depth.as_ref()
+    }
+
+    /// The multisampled color attachment to resolve into the swapchain images, requested through
+    /// [`SwapchainPreferences::samples`].
+    pub fn msaa_color(&self) -> Option<&SwapchainAttachment> {
+        self.msaa_color.as_ref()
+    }
+}
+
+impl Drop for SwapchainResources {
+    fn drop(&mut self) {
+        let image_views = self
+            .image_views
+            .drain(..)
+            .chain(self.depth.iter().map(|depth| depth.view))
+            .chain(self.msaa_color.iter().map(|msaa_color| msaa_color.view))
+            .collect::<Vec<vk::ImageView>>();
+        let present_semaphores = std::mem::take(&mut self.present_semaphores);
+
+        // frames in flight may still render to or present the images
+        self.vulkan
+            .deletion_queue()
+            .defer_frame(move |vulkan| unsafe {
+                for image_view in image_views {
+                    vulkan.device().destroy_image_view(image_view, None);
+                }
+                for semaphore in present_semaphores {
+                    vulkan.device().destroy_semaphore(semaphore, None);
+                }
+            });
+    }
+}
+
+impl SwapchainAttachment {
+    fn new(vulkan: Arc<VulkanContext>, info: &ImageInfo) -> VkResult<Self> {
+        let image = Image::new(vulkan.clone(), info).map_err(attachment_error)?;
+        let view = create_view(&vulkan, image.handle(), image.format())?;
+        Ok(Self { image, view })
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+}
+
+fn create_view(
+    vulkan: &VulkanContext,
+    image: vk::Image,
+    format: vk::Format,
+) -> VkResult<vk::ImageView> {
+    unsafe {
+        vulkan.device().create_image_view(
+            &vk::ImageViewCreateInfo::default()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: format_aspect_mask(format),
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                }),
+            None,
+        )
+    }
+}
+
+fn attachment_error(error: MemoryError) -> vk::Result {
+    match error {
+        MemoryError::VulkanError(result) => result,
+        error => {
+            error!("[window/swapchain] Failed to allocate an attachment: {}", error);
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY
+        }
+    }
 }
 
 impl SwapchainSyncResources {
-    pub fn new(engine: &Engine) -> VkResult<Self> {
+    pub fn new(vulkan: &VulkanContext, frames_in_flight: FramesInFlight) -> VkResult<Self> {
         Ok(Self {
-            image_available: engine.vulkan().create_semaphores()?,
-            render_finished: engine.vulkan().create_semaphores()?,
-            in_flight_fences: engine.vulkan().create_fences_signaled()?,
+            image_available: vulkan.create_semaphores(frames_in_flight)?,
+            in_flight_fences: vulkan.create_fences_signaled(frames_in_flight)?,
         })
     }
 
-    pub fn image_available(&self) -> &FrameSet<vk::Semaphore> {
-        &self.image_available
+    /// Destroys the semaphores and fences, every frame using them must have completed.
+    unsafe fn destroy(&self, vulkan: &VulkanContext) {
+        for semaphore in self.image_available.iter() {
+            unsafe { vulkan.device().destroy_semaphore(*semaphore, None) };
+        }
+        for fence in self.in_flight_fences.iter() {
+            unsafe { vulkan.device().destroy_fence(*fence, None) };
+        }
+    }
+
+    pub fn frames_in_flight(&self) -> FramesInFlight {
+        self.in_flight_fences.frames_in_flight()
     }
 
-    pub fn render_finished(&self) -> &FrameSet<vk::Semaphore> {
-        &self.render_finished
+    pub fn image_available(&self) -> &FrameSet<vk::Semaphore> {
+        &self.image_available
     }
 
     pub fn in_flight_fences(&self) -> &FrameSet<vk::Fence> {
@@ -118,38 +528,143 @@ impl SwapchainSyncResources {
 }
 
 impl WindowData {
-    pub(crate) fn new(engine: &mut Engine, window: Window) -> Result<Self, CreateWindowError> {
+    pub(crate) fn new(
+        engine: &mut Engine,
+        window: Window,
+        swapchain_preferences: SwapchainPreferences,
+    ) -> Result<Self, CreateWindowError> {
         let surface = engine.vulkan().create_surface(&window)?;
 
-        let (swapchain, swapchain_configuration, swapchain_resources) =
-            Self::setup_swapchain(engine.vulkan(), &window, surface.clone(), None)?;
+        let (swapchain, swapchain_configuration, swapchain_resources) = Self::setup_swapchain(
+            engine.vulkan(),
+            &window,
+            surface,
+            &swapchain_preferences,
+            None,
+        )?;
 
-        let swapchain_sync_resources = SwapchainSyncResources::new(engine)?;
+        let frames_in_flight = engine.frames_in_flight();
+        let swapchain_sync_resources =
+            SwapchainSyncResources::new(&engine.vulkan(), frames_in_flight)?;
 
         Ok(Self {
             window,
             vulkan_context: engine.vulkan(),
             surface,
             swapchain,
+            retired_swapchains: vec![],
+            swapchain_preferences,
+            needs_reconfigure: false,
+            resized: None,
             swapchain_configuration,
             swapchain_resources,
             swapchain_sync_resources,
+            image_data_factory: None,
             current_frame: 0,
+            frame_source: engine.vulkan().deletion_queue().register_frame_source(),
+            submitted_frames: FrameSet::create_factory(frames_in_flight, |_| 0),
         })
     }
 
-    #[allow(dead_code)]
+    /// Recreates the swapchain, retiring the current one.
     pub(crate) fn reconfigure_swapchain(&mut self) -> VkResult<()> {
-        (
-            self.swapchain,
-            self.swapchain_configuration,
-            self.swapchain_resources,
-        ) = Self::setup_swapchain(
+        // creating a swapchain retires the old one even when the creation fails, so it is never used again
+        let old_swapchain = std::mem::replace(&mut self.swapchain, vk::SwapchainKHR::null());
+        if old_swapchain != vk::SwapchainKHR::null() {
+            let frame = self.vulkan_context.deletion_queue().current_frame();
+            self.retired_swapchains.push((frame, old_swapchain));
+        }
+
+        let (swapchain, swapchain_configuration, swapchain_resources) = Self::setup_swapchain(
             self.vulkan_context.clone(),
             &self.window,
             self.surface,
-            Some(self.swapchain),
-        )?;
+            &self.swapchain_preferences,
+            (old_swapchain != vk::SwapchainKHR::null()).then_some(old_swapchain),
+        )
+        .inspect_err(|_| self.needs_reconfigure = true)?;
+
+        if swapchain_configuration.extent != self.swapchain_configuration.extent {
+            self.resized = Some(swapchain_configuration.extent);
+        }
+
+        self.swapchain = swapchain;
+        self.swapchain_configuration = swapchain_configuration;
+        self.swapchain_resources = swapchain_resources;
+        self.needs_reconfigure = false;
+
+        self.build_image_data()
+    }
+
+    /// Recreates the swapchain before the next frame, such as after the window was resized.
+    pub fn request_reconfigure(&mut self) {
+        self.needs_reconfigure = true;
+    }
+
+    /// Whether the window has no area to render to, rendering is paused until it grows again.
+    pub fn is_minimized(&self) -> bool {
+        let size = self.window.inner_size();
+        size.width == 0 || size.height == 0
+    }
+
+    /// Recreates the swapchain if it was invalidated, returns whether the window can be rendered to.
+    pub(crate) fn update_swapchain(&mut self) -> VkResult<bool> {
+        if self.is_minimized() {
+            return Ok(false);
+        }
+
+        if self.needs_reconfigure || self.swapchain == vk::SwapchainKHR::null() {
+            trace!("[window/swapchain] Reconfiguring swapchain");
+            match self.reconfigure_swapchain() {
+                Ok(()) => {}
+                // the surface has no area even though the window has
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Takes the extent the swapchain was resized to since the last call.
+    pub(crate) fn take_resized(&mut self) -> Option<vk::Extent2D> {
+        self.resized.take()
+    }
+
+    /// Reports the frames whose in-flight fences are signaled to the deletion queue, and destroys the retired
+    /// swapchains no frame in flight uses anymore.
+    pub(crate) fn complete_frames(&mut self) -> VkResult<()> {
+        let deletion_queue = self.vulkan_context.deletion_queue();
+        let mut completed = deletion_queue.current_frame() - 1;
+
+        for (fence, submitted) in self
+            .swapchain_sync_resources
+            .in_flight_fences
+            .iter()
+            .zip(self.submitted_frames.iter())
+        {
+            if !unsafe { self.vulkan_context.device().get_fence_status(*fence) }? {
+                completed = completed.min(submitted.saturating_sub(1));
+            }
+        }
+
+        deletion_queue.complete_frame(self.frame_source, completed);
+
+        let vulkan = &self.vulkan_context;
+        self.retired_swapchains.retain(|(frame, swapchain)| {
+            if *frame > completed {
+                return true;
+            }
+
+            unsafe {
+                vulkan
+                    .device()
+                    .loader()
+                    .swapchain()
+                    .destroy_swapchain(*swapchain, None)
+            };
+            false
+        });
 
         Ok(())
     }
@@ -158,25 +673,19 @@ impl WindowData {
         vulkan: Arc<VulkanContext>,
         window: &Window,
         surface: vk::SurfaceKHR,
+        preferences: &SwapchainPreferences,
         old_swapchain: Option<vk::SwapchainKHR>,
     ) -> VkResult<(vk::SwapchainKHR, SwapchainConfiguration, SwapchainResources)> {
-        vulkan.device().wait_idle()?;
-
         let present_modes = vulkan.query_present_modes(surface)?;
         let surface_formats = vulkan.query_surface_formats(surface)?;
         let surface_capabilities = vulkan.query_surface_capabilities(surface)?;
 
-        // TODO: Swapchain configuration requests
-
         trace!("[swapchain/configuration] Available present modes:");
         present_modes
             .iter()
             .for_each(|m| trace!("[swapchain/configuration/#] - {:?}", m));
 
-        let present_mode = present_modes
-            .into_iter()
-            .find(|m| m == &vk::PresentModeKHR::MAILBOX)
-            .unwrap_or(vk::PresentModeKHR::FIFO);
+        let present_mode = preferences.select_present_mode(&present_modes);
 
         trace!(
             "[swapchain/configuration] Selected present mode: {:?}",
@@ -191,39 +700,61 @@ impl WindowData {
             )
         });
 
-        let surface_format = surface_formats
-            .iter()
-            .cloned()
-            .filter(|f| f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
-            .find(|f| f.format == vk::Format::B8G8R8A8_SRGB)
-            .unwrap_or(surface_formats[0]);
+        let surface_format = preferences
+            .select_surface_format(&surface_formats)
+            .ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)?;
 
         trace!(
             "[swapchain/configuration] Selected surface format: {:?}",
             surface_format
         );
 
-        let min_image_count = if surface_capabilities.max_image_count > 0 {
-            surface_capabilities
-                .max_image_count
-                .min(surface_capabilities.min_image_count + 1)
-        } else {
-            surface_capabilities.min_image_count + 1
-        };
+        let min_image_count = preferences.select_image_count(&surface_capabilities);
 
         trace!(
             "[swapchain/configuration] Selected swapchain min image count: {:?}",
             min_image_count
         );
 
+        let usage = preferences.select_usage(surface_capabilities.supported_usage_flags);
+        let composite_alpha =
+            preferences.select_composite_alpha(surface_capabilities.supported_composite_alpha);
+
+        trace!(
+            "[swapchain/configuration] Selected usage: {:?}, composite alpha: {:?}",
+            usage, composite_alpha
+        );
+
+        let depth_format = preferences.select_depth_format(|format| {
+            unsafe {
+                vulkan
+                    .instance()
+                    .get_physical_device_format_properties(vulkan.physical_device(), format)
+            }
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        });
+
+        let limits = vulkan.physical_device_info().limits();
+        let samples = preferences.select_samples(if depth_format.is_some() {
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+        } else {
+            limits.framebuffer_color_sample_counts
+        });
+
+        trace!(
+            "[swapchain/configuration] Selected depth format: {:?}, samples: {:?}",
+            depth_format, samples
+        );
+
         let extent = if surface_capabilities.current_extent.width == u32::MAX {
             vk::Extent2D {
                 width: window.inner_size().width.clamp(
-                    surface_capabilities.max_image_extent.width,
+                    surface_capabilities.min_image_extent.width,
                     surface_capabilities.max_image_extent.width,
                 ),
                 height: window.inner_size().height.clamp(
-                    surface_capabilities.max_image_extent.height,
+                    surface_capabilities.min_image_extent.height,
                     surface_capabilities.max_image_extent.height,
                 ),
             }
@@ -236,6 +767,11 @@ impl WindowData {
             extent
         );
 
+        // minimized windows can report a zero extent, which no swapchain can have
+        if extent.width == 0 || extent.height == 0 {
+            return Err(vk::Result::ERROR_OUT_OF_DATE_KHR);
+        }
+
         let swapchain = unsafe {
             vulkan.device().loader().swapchain().create_swapchain(
                 &vk::SwapchainCreateInfoKHR::default()
@@ -244,40 +780,49 @@ impl WindowData {
                     .min_image_count(min_image_count)
                     .image_format(surface_format.format)
                     .image_color_space(surface_format.color_space)
-                    .image_usage(
-                        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST,
-                    )
+                    .image_usage(usage)
                     .image_array_layers(1)
                     .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
                     .image_extent(extent)
                     .clipped(true)
                     .pre_transform(surface_capabilities.current_transform)
-                    .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                    .composite_alpha(composite_alpha)
                     .old_swapchain(old_swapchain.unwrap_or(vk::SwapchainKHR::null())),
                 None,
             )
         }?;
 
-        let images = unsafe {
-            vulkan
-                .device()
-                .loader()
-                .swapchain()
-                .get_swapchain_images(swapchain)
-        }?;
-
-        trace!(
-            "[window/swapchain] Created swapchain with {:?} images.",
-            images.len()
-        );
-
-        let cfg = SwapchainConfiguration {
+        let mut cfg = SwapchainConfiguration {
             format: surface_format.format,
             color_space: surface_format.color_space,
             extent,
+            present_mode,
+            image_count: 0,
+            usage,
+            composite_alpha,
+            depth_format,
+            samples,
         };
 
-        let res = SwapchainResources { images };
+        let res = match SwapchainResources::new(vulkan.clone(), swapchain, &cfg) {
+            Ok(res) => res,
+            Err(e) => {
+                unsafe {
+                    vulkan
+                        .device()
+                        .loader()
+                        .swapchain()
+                        .destroy_swapchain(swapchain, None)
+                };
+                return Err(e);
+            }
+        };
+        cfg.image_count = res.images.len() as u32;
+
+        trace!(
+            "[window/swapchain] Created swapchain with {:?} images.",
+            cfg.image_count
+        );
 
         Ok((swapchain, cfg, res))
     }
@@ -294,6 +839,18 @@ impl WindowData {
         self.swapchain
     }
 
+    pub fn swapchain_preferences(&self) -> &SwapchainPreferences {
+        &self.swapchain_preferences
+    }
+
+    /// Replaces the swapchain preferences, the swapchain is recreated with them before the next frame.
+    pub fn set_swapchain_preferences(&mut self, preferences: SwapchainPreferences) {
+        if preferences != self.swapchain_preferences {
+            self.swapchain_preferences = preferences;
+            self.needs_reconfigure = true;
+        }
+    }
+
     pub fn swapchain_configuration(&self) -> &SwapchainConfiguration {
         &self.swapchain_configuration
     }
@@ -306,7 +863,95 @@ impl WindowData {
         &self.swapchain_sync_resources
     }
 
-    fn acquire_image(&self, prqf: u32) -> VkResult<(AcquiredImage, bool)> {
+    pub fn frames_in_flight(&self) -> FramesInFlight {
+        self.swapchain_sync_resources.frames_in_flight()
+    }
+
+    /// Changes how many frames can be in flight, after waiting for the current ones. Per-frame sets recorded into
+    /// this window's frames, such as command buffers, have to be recreated with the new count.
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: FramesInFlight) -> VkResult<()> {
+        if frames_in_flight == self.frames_in_flight() {
+            return Ok(());
+        }
+
+        let in_flight_fences = self
+            .swapchain_sync_resources
+            .in_flight_fences
+            .iter()
+            .copied()
+            .collect::<Vec<vk::Fence>>();
+        self.vulkan_context.wait_for_fences(&in_flight_fences)?;
+
+        let sync_resources = SwapchainSyncResources::new(&self.vulkan_context, frames_in_flight)?;
+        let old_sync_resources = std::mem::replace(&mut self.swapchain_sync_resources, sync_resources);
+        unsafe { old_sync_resources.destroy(&self.vulkan_context) };
+
+        // every frame completed, the deletion queue can consider them all done
+        self.submitted_frames = FrameSet::create_factory(frames_in_flight, |_| 0);
+        self.current_frame = 0;
+
+        trace!("[window] Frames in flight set to {}", frames_in_flight.get());
+        Ok(())
+    }
+
+    /// Attaches data to every swapchain image, such as framebuffers or descriptor sets. `factory` builds it for each
+    /// image now and again whenever the swapchain is recreated, the previous data is dropped while frames may still
+    /// be in flight, so it should defer the destruction of its Vulkan objects.
+    pub fn set_image_data<T, F>(&mut self, mut factory: F) -> VkResult<()>
+    where
+        T: 'static,
+        F: FnMut(&Arc<VulkanContext>, &SwapchainImage) -> VkResult<T> + 'static,
+    {
+        self.image_data_factory = Some(Box::new(move |vulkan, image| {
+            factory(vulkan, image).map(|data| Box::new(data) as Box<dyn Any>)
+        }));
+        self.build_image_data()
+    }
+
+    /// Drops the data attached to the swapchain images and stops rebuilding it.
+    pub fn clear_image_data(&mut self) {
+        self.image_data_factory = None;
+        self.swapchain_resources.image_data.clear();
+    }
+
+    /// The data attached to the image at `image_index`, `None` if there is none or it is not a `T`.
+    pub fn image_data<T: 'static>(&self, image_index: u32) -> Option<&T> {
+        self.swapchain_resources
+            .image_data
+            .get(image_index as usize)?
+            .downcast_ref()
+    }
+
+    fn build_image_data(&mut self) -> VkResult<()> {
+        let Some(factory) = self.image_data_factory.as_mut() else {
+            return Ok(());
+        };
+
+        let resources = &self.swapchain_resources;
+        let image_data = resources
+            .images
+            .iter()
+            .zip(&resources.image_views)
+            .enumerate()
+            .map(|(index, (image, view))| {
+                factory(
+                    &self.vulkan_context,
+                    &SwapchainImage {
+                        index,
+                        image: *image,
+                        view: *view,
+                        configuration: &self.swapchain_configuration,
+                        resources,
+                    },
+                )
+            })
+            .collect::<VkResult<Vec<Box<dyn Any>>>>()?;
+
+        self.swapchain_resources.image_data = image_data;
+        Ok(())
+    }
+
+    fn acquire_image(&self, prqref: QueueRef) -> VkResult<(AcquiredImage, bool)> {
         let in_flight_fence = self.swapchain_sync_resources.in_flight_fences[self.current_frame];
         self.vulkan_context.wait_for_fence(in_flight_fence)?;
 
@@ -325,7 +970,8 @@ impl WindowData {
                 )
         }?;
 
-        let image = self.swapchain_resources.images()[image_index as usize];
+        let image = self.swapchain_resources.images[image_index as usize];
+        let image_view = self.swapchain_resources.image_views[image_index as usize];
 
         self.vulkan_context
             .reset_fence(self.swapchain_sync_resources.in_flight_fences[self.current_frame])?;
@@ -333,13 +979,14 @@ impl WindowData {
         Ok((
             AcquiredImage {
                 image,
-                image_index: image_index.clone(),
+                image_view,
+                image_index,
                 current_frame: self.current_frame,
                 image_available_semaphore,
-                render_finished_semaphore: self.swapchain_sync_resources.render_finished
-                    [self.current_frame],
+                render_finished_semaphore: self.swapchain_resources.present_semaphores
+                    [image_index as usize],
                 in_flight_fence,
-                present_queue_family: prqf,
+                present_queue: prqref,
             },
             suboptimal,
         ))
@@ -347,51 +994,56 @@ impl WindowData {
 
     fn present_image(&mut self, image: AcquiredImage, prqref: QueueRef) -> VkResult<bool> {
         // TODO: turn this expect into an error
-        let suboptimal = unsafe {
-            self.vulkan_context
-                .device()
-                .loader()
-                .swapchain()
-                .queue_present(
-                    self.vulkan_context
-                        .device()
-                        .get_queue(prqref)
-                        .expect("No presentation queue"),
-                    &vk::PresentInfoKHR::default()
-                        .swapchains(&[self.swapchain])
-                        .wait_semaphores(&[image.render_finished_semaphore])
-                        .image_indices(&[image.image_index]),
-                )
-        }?;
-
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
-
-
-        Ok(suboptimal)
+        let result = self
+            .vulkan_context
+            .device()
+            .get_queue(prqref)
+            .expect("No presentation queue")
+            .present(
+                &vk::PresentInfoKHR::default()
+                    .swapchains(&[self.swapchain])
+                    .wait_semaphores(&[image.render_finished_semaphore])
+                    .image_indices(&[image.image_index]),
+            );
+
+        // an out of date presentation still waits on the semaphore, the frame was submitted either way
+        self.current_frame = (self.current_frame + 1) % self.frames_in_flight().get();
+
+        result
     }
 
     fn render_frame_inner<F: FnOnce(&Self, &AcquiredImage) -> VkResult<()>>(&mut self, f: F) -> VkResult<bool> {
         let prqref = self.vulkan_context.device().get_labeled_queue_ref(QueueLabel::Presentation).expect("No presentation queue");
 
-        let (acquired_image, suboptimal) = self.acquire_image(prqref.family)?;
+        let (acquired_image, suboptimal) = self.acquire_image(prqref)?;
 
         f(self, &acquired_image)?;
+        self.submitted_frames[self.current_frame] =
+            self.vulkan_context.deletion_queue().current_frame();
 
-        self.present_image(acquired_image, prqref)?;
+        let present_suboptimal = self.present_image(acquired_image, prqref)?;
 
-        Ok(suboptimal)
+        Ok(suboptimal || present_suboptimal)
     }
 
+    /// Renders a frame with `f`, unless the window is minimized. A swapchain found suboptimal or out of date is
+    /// recreated before the next frame.
     pub fn render_frame<F: FnOnce(&Self, &AcquiredImage) -> VkResult<()>>(&mut self, f: F) -> VkResult<()> {
+        if !self.update_swapchain()? {
+            return Ok(());
+        }
+
         match self.render_frame_inner(f) {
             Ok(false) => Ok(()),
             Ok(true) | Err(vk::Result::SUBOPTIMAL_KHR) => {
                 warn!("Swapchain configuration suboptimal");
-                self.reconfigure_swapchain()
+                self.needs_reconfigure = true;
+                Ok(())
             },
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
                 warn!("Swapchain configuration out of date");
-                self.reconfigure_swapchain()
+                self.needs_reconfigure = true;
+                Ok(())
             }
             Err(e) => Err(e),
         }
@@ -400,12 +1052,29 @@ impl WindowData {
 
 impl Drop for WindowData {
     fn drop(&mut self) {
+        self.vulkan_context
+            .deletion_queue()
+            .unregister_frame_source(self.frame_source);
+
+        let in_flight_fences = self
+            .swapchain_sync_resources
+            .in_flight_fences
+            .iter()
+            .copied()
+            .collect::<Vec<vk::Fence>>();
+        if let Err(e) = self.vulkan_context.wait_for_fences(&in_flight_fences) {
+            warn!("[window] Failed to wait for the frames in flight: {}", e);
+        }
+
         unsafe {
-            self.vulkan_context
-                .device()
-                .loader()
-                .swapchain()
-                .destroy_swapchain(self.swapchain, None);
+            self.swapchain_sync_resources.destroy(&self.vulkan_context);
+
+            // every swapchain created for the surface has to be destroyed before it
+            let swapchain_loader = self.vulkan_context.device().loader().swapchain();
+            for (_, swapchain) in self.retired_swapchains.drain(..) {
+                swapchain_loader.destroy_swapchain(swapchain, None);
+            }
+            swapchain_loader.destroy_swapchain(self.swapchain, None);
 
             self.vulkan_context
                 .instance()
@@ -415,3 +1084,111 @@ impl Drop for WindowData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNORM: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+        format: vk::Format::B8G8R8A8_UNORM,
+        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    };
+    const HDR: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+        format: vk::Format::A2B10G10R10_UNORM_PACK32,
+        color_space: vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    };
+
+    #[test]
+    fn ranked_candidates_fall_back_on_supported_values() {
+        let preferences = SwapchainPreferences::default()
+            .present_modes(&[vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX])
+            .surface_formats(&[HDR, UNORM]);
+
+        assert_eq!(
+            preferences.select_present_mode(&[vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX]),
+            vk::PresentModeKHR::MAILBOX
+        );
+        assert_eq!(
+            preferences.select_present_mode(&[vk::PresentModeKHR::FIFO_RELAXED]),
+            vk::PresentModeKHR::FIFO
+        );
+
+        assert_eq!(preferences.select_surface_format(&[UNORM, HDR]), Some(HDR));
+        let srgb = SwapchainPreferences::default().surface_formats[0];
+        assert_eq!(preferences.select_surface_format(&[srgb]), Some(srgb));
+        assert_eq!(preferences.select_surface_format(&[]), None);
+    }
+
+    #[test]
+    fn image_count_is_clamped() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 4,
+            ..Default::default()
+        };
+
+        assert_eq!(SwapchainPreferences::default().select_image_count(&capabilities), 3);
+        assert_eq!(SwapchainPreferences::default().image_count(8).select_image_count(&capabilities), 4);
+        assert_eq!(SwapchainPreferences::default().image_count(1).select_image_count(&capabilities), 2);
+
+        let unbounded = vk::SurfaceCapabilitiesKHR {
+            max_image_count: 0,
+            ..capabilities
+        };
+        assert_eq!(SwapchainPreferences::default().image_count(8).select_image_count(&unbounded), 8);
+    }
+
+    #[test]
+    fn usage_and_composite_alpha_respect_support() {
+        let preferences = SwapchainPreferences::default()
+            .extra_usage(vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED)
+            .composite_alpha(&[vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED]);
+
+        let usage = preferences.select_usage(
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::STORAGE,
+        );
+        assert!(usage.contains(vk::ImageUsageFlags::STORAGE));
+        assert!(!usage.contains(vk::ImageUsageFlags::SAMPLED));
+
+        assert_eq!(
+            preferences.select_composite_alpha(
+                vk::CompositeAlphaFlagsKHR::OPAQUE | vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED
+            ),
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED
+        );
+        assert_eq!(
+            preferences.select_composite_alpha(vk::CompositeAlphaFlagsKHR::INHERIT),
+            vk::CompositeAlphaFlagsKHR::INHERIT
+        );
+    }
+
+    #[test]
+    fn attachments_fall_back_on_supported_formats_and_sample_counts() {
+        let preferences = SwapchainPreferences::default()
+            .depth_formats(&[vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D32_SFLOAT])
+            .samples(vk::SampleCountFlags::TYPE_8);
+
+        assert_eq!(
+            preferences.select_depth_format(|format| format == vk::Format::D32_SFLOAT),
+            Some(vk::Format::D32_SFLOAT)
+        );
+        assert_eq!(SwapchainPreferences::default().select_depth_format(|_| true), None);
+
+        assert_eq!(
+            preferences.select_samples(vk::SampleCountFlags::TYPE_1 | vk::SampleCountFlags::TYPE_4),
+            vk::SampleCountFlags::TYPE_4
+        );
+        assert_eq!(
+            preferences.select_samples(vk::SampleCountFlags::TYPE_1),
+            vk::SampleCountFlags::TYPE_1
+        );
+        assert_eq!(
+            SwapchainPreferences::default()
+                .samples(vk::SampleCountFlags::TYPE_2 | vk::SampleCountFlags::TYPE_4)
+                .select_samples(vk::SampleCountFlags::TYPE_1 | vk::SampleCountFlags::TYPE_4),
+            vk::SampleCountFlags::TYPE_4
+        );
+    }
+}
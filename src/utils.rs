@@ -1,2 +1 @@
-#[macro_use]
-use neuron_procmacro::sealed;
+pub use neuron_procmacro::{extend_type, sealed};